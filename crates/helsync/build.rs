@@ -16,6 +16,16 @@ const COMMANDS: &[&str] = &[
     "create_tag",
     "create_tag_bind",
     "remove_tag_bind",
+    "start_sync",
+    "pause_sync",
+    "stop_sync",
+    "sync_status",
+    "get_drive",
+    "register_drive",
+    "connect_drive",
+    "list_remote_folders",
+    "set_remote_root",
+    "disconnect_drive",
 ];
 
 fn main() {
@@ -1,6 +1,7 @@
-use crate::database::{Database, Drive, App};
+use crate::database::{Database, Drive, DriveTokenStore, App};
 use super::errors::*;
-use helsync::oauth2;
+use helsync::oauth2::{self, TokenStore};
+use helsync::sync::{Matcher, SyncRules};
 
 use clap::Parser;
 use anyhow::Result;
@@ -75,6 +76,15 @@ impl DescribeOpt {
             println!(" - Is Expired: {}", token.is_expired());
         }
 
+        let rules = super::utils::load_rules(db, &self.name).await?;
+        if !rules.is_empty() {
+            println!("\nSync Rules:");
+            for rule in rules.rules() {
+                let (kind, matcher, value) = rule.to_columns();
+                println!(" - {kind} {matcher} {value}");
+            }
+        }
+
         Ok(())
     }
 }
@@ -113,24 +123,81 @@ pub struct CreateOpt {
     /// App Registration.
     #[arg(long, short)]
     pub app: String,
+
+    /// Only sync the remote folder with this ID (may be repeated).
+    #[arg(long = "include-folder", value_name = "ID")]
+    pub include_folders: Vec<String>,
+
+    /// Don't sync the remote folder with this ID (may be repeated).
+    #[arg(long = "exclude-folder", value_name = "ID")]
+    pub exclude_folders: Vec<String>,
+
+    /// Only sync files whose path matches this glob, e.g.
+    /// "journal/**" (may be repeated).
+    #[arg(long = "include-path", value_name = "GLOB")]
+    pub include_paths: Vec<String>,
+
+    /// Don't sync files whose path matches this glob, e.g.
+    /// "**/*.tmp" (may be repeated).
+    #[arg(long = "exclude-path", value_name = "GLOB")]
+    pub exclude_paths: Vec<String>,
+
+    /// Don't sync files larger than this many bytes.
+    #[arg(long, value_name = "BYTES")]
+    pub max_size: Option<u64>,
 }
 
 impl CreateOpt {
     pub async fn run(&self, db: &Database) -> Result<()> {
         super::utils::is_valid_name(&self.name)?;
-        let mut conn = db.acquire().await?;
+        let mut tx = db.pool().begin().await?;
         sqlx::query("INSERT INTO Drive (name, path, app) VALUES (?, ?, ?)")
             .bind(&self.name)
             .bind(&self.path)
             .bind(&self.app)
-            .execute(&mut *conn).await
+            .execute(&mut *tx).await
             .map_err(handle_unique_violation_err)?;
 
+        for rule in self.rules().rules() {
+            let (kind, matcher, value) = rule.to_columns();
+            sqlx::query("INSERT INTO DriveRule (drive, kind, matcher, value) VALUES (?, ?, ?, ?)")
+                .bind(&self.name)
+                .bind(kind)
+                .bind(matcher)
+                .bind(value)
+                .execute(&mut *tx).await?;
+        }
+
+        tx.commit().await?;
         println!("drive \"{}\" successfully created", self.name);
         Ok(())
     }
 }
 
+impl CreateOpt {
+
+    /// The sync rules given by the command's flags.
+    fn rules(&self) -> SyncRules {
+        let mut rules = SyncRules::default();
+        for id in &self.include_folders {
+            rules = rules.include(Matcher::Folder(id.clone()));
+        }
+        for id in &self.exclude_folders {
+            rules = rules.exclude(Matcher::Folder(id.clone()));
+        }
+        for glob in &self.include_paths {
+            rules = rules.include(Matcher::Path(glob.clone()));
+        }
+        for glob in &self.exclude_paths {
+            rules = rules.exclude(Matcher::Path(glob.clone()));
+        }
+        if let Some(size) = self.max_size {
+            rules = rules.exclude(Matcher::LargerThan(size));
+        }
+        rules
+    }
+}
+
 /// Remove a drive.
 #[derive(Parser, Debug)]
 pub struct RemoveOpt {
@@ -177,18 +244,12 @@ impl ConnectOpt {
             .map_err(handle_not_found_err)?;
 
         let app_config: oauth2::Config = app.clone().into();
+        let store = DriveTokenStore::new(db, &self.name);
         if let Some(token) = drive.token {
             let mut token = token.clone();
             let res = token.refresh_if_expired(&app_config).await;
             if let Ok(_) = res {
-                sqlx::query("UPDATE Drive SET access_token=?, refresh_token=?, created_at=?, expires_in=? WHERE name=?")
-                    .bind(&token.access_token)
-                    .bind(&token.refresh_token)
-                    .bind(token.created_at)
-                    .bind(token.expires_in)
-                    .bind(&self.name)
-                    .execute(&mut *conn).await?;
-
+                store.save(&token).await?;
                 println!("drive \"{}\" successfully authenticated", self.name);
                 return Ok(());
             }
@@ -197,13 +258,7 @@ impl ConnectOpt {
         let token = oauth2::Grant::from_server(app.port, &app_config).await?
             .to_token().await?;
 
-        sqlx::query("UPDATE Drive SET access_token=?, refresh_token=?, created_at=?, expires_in=? WHERE name=?")
-            .bind(&token.access_token)
-            .bind(&token.refresh_token)
-            .bind(token.created_at)
-            .bind(token.expires_in)
-            .bind(&self.name)
-            .execute(&mut *conn).await?;
+        store.save(&token).await?;
 
         println!("\ndrive \"{}\" successfully authenticated", self.name);
         Ok(())
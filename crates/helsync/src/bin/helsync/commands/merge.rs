@@ -1,6 +1,17 @@
-use crate::database::Database;
+use crate::database::{CloudProvider, Database, DriveTokenStore};
+use super::status::{list_changes, print_changes};
+use super::utils::{load_drive, load_rules, load_sync, open_filesystem};
+
+use helsync::cloud::{dropbox, googledrive, onedrive};
+use helsync::core::{Delta, FileSystem};
+use helsync::local::Client;
+use helsync::oauth2::Config;
+use helsync::sync::{ActionKind, Resolution, SyncRules, DEFAULT_CONCURRENCY};
+
 use anyhow::Result;
 use clap::Parser;
+use console::style;
+use std::sync::Arc;
 
 /// Group of commands for merging sync changes.
 #[derive(Parser, Debug)]
@@ -11,10 +22,137 @@ pub struct MergeOpt {
 
     /// Path to local drive data.
     pub path: String,
+
+    /// Show pending changes without merging them.
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Compare every local and remote file, even if the drive has
+    /// been merged before.
+    #[arg(long)]
+    pub full: bool,
+
+    /// Maximum number of changes to merge at once.
+    #[arg(long, short = 'j', default_value_t = DEFAULT_CONCURRENCY)]
+    pub concurrency: usize,
 }
 
 impl MergeOpt {
-    pub async fn run (&self, _: &Database) -> Result<()> {
-        unimplemented!();
+    pub async fn run (&self, db: &Database) -> Result<()> {
+        std::fs::create_dir_all(&self.path)?;
+        let path = std::fs::canonicalize(&self.path)?;
+        let local = open_filesystem(&path).await?;
+        let (drive, app, token) = load_drive(db, &self.name).await?;
+        let rules = load_rules(db, &self.name).await?;
+        if !self.dry_run {
+            local.set_sync_rules(&rules).await?;
+        }
+
+        let config: Config = app.clone().into();
+        let store = Arc::new(DriveTokenStore::new(db, &self.name));
+
+        match app.provider {
+            CloudProvider::OneDrive => {
+                let remote = Arc::new(onedrive::Client::new(&token, &config)
+                    .with_token_store(store)
+                    .with_upload_store(local.clone()));
+                self.merge(local, remote, &drive.path, rules).await
+            },
+            CloudProvider::GoogleDrive => {
+                let remote = Arc::new(googledrive::Client::new(&token, &config)
+                    .with_token_store(store)
+                    .with_upload_store(local.clone()));
+                self.merge(local, remote, &drive.path, rules).await
+            },
+            CloudProvider::Dropbox => {
+                let remote = Arc::new(dropbox::Client::new(&token, &config)
+                    .with_token_store(store));
+                self.merge(local, remote, &drive.path, rules).await
+            },
+        }
     }
+
+    async fn merge<R>(&self, local: Arc<Client>, remote: Arc<R>, remote_path: &str, rules: SyncRules) -> Result<()>
+    where R: FileSystem<Error = helsync::core::Error> + Delta {
+        let mut sync = load_sync(local.clone(), remote.clone(), remote_path, !self.dry_run).await?;
+        sync.set_rules(rules);
+        sync.set_concurrency(self.concurrency);
+        let is_full = self.full || !sync.has_synced();
+
+        if self.dry_run {
+            let changes = list_changes(&mut sync, &local, remote.as_ref(), is_full).await?;
+            println!("Pending changes for drive \"{}\"", self.name);
+            print_changes(&changes);
+
+            let conflicts = changes.iter()
+                .filter(|change| change.action == ActionKind::Conflict)
+                .count();
+
+            if conflicts > 0 {
+                return Err(anyhow::anyhow!("{conflicts} conflicting change(s) pending"));
+            }
+
+            return Ok(());
+        }
+
+        let report = if is_full {
+            println!("performing a full merge of drive \"{}\"...", self.name);
+            let report = sync.sync_full().await?;
+            for (i, reconciled) in report.iter().enumerate() {
+                let progress = style(format!("[{}/{}]", i + 1, report.len())).bold().cyan();
+                println!("{progress} {} ({})", reconciled.name, describe(&reconciled.resolution));
+            }
+
+            report
+        } else {
+            println!("merging changes of drive \"{}\"...", self.name);
+            sync.sync_changes_with(|i, total, reconciled| {
+                if let Some(reconciled) = reconciled {
+                    let progress = style(format!("[{i}/{total}]")).bold().cyan();
+                    println!("{progress} {} ({})", reconciled.name, describe(&reconciled.resolution));
+                }
+            }).await?
+        };
+
+        let failures = report.iter()
+            .filter(|reconciled| matches!(reconciled.resolution, Resolution::Failed { .. } | Resolution::Skipped))
+            .count();
+
+        if failures > 0 {
+            return Err(anyhow::anyhow!("failed to merge {failures} of {} change(s)", report.len()));
+        }
+
+        let conflicts = report.iter()
+            .filter(|reconciled| is_conflicted(&reconciled.resolution))
+            .count();
+
+        if conflicts > 0 {
+            return Err(anyhow::anyhow!("merged {} change(s) with {conflicts} conflict(s)", report.len()));
+        }
+
+        println!("drive \"{}\" successfully merged {} change(s)", self.name, report.len());
+        Ok(())
+    }
+}
+
+/// Describes how a change was reconciled.
+fn describe(resolution: &Resolution) -> String {
+    match resolution {
+        Resolution::Local => "kept local version".to_string(),
+        Resolution::Remote => "kept remote version".to_string(),
+        Resolution::Conflict { copy_name, .. } =>
+            format!("conflict, local version saved to \"{copy_name}\""),
+        Resolution::Merged { is_conflicted: false } => "merged".to_string(),
+        Resolution::Merged { is_conflicted: true } => "merged with conflicts".to_string(),
+        Resolution::Failed { error } => format!("failed: {error}"),
+        Resolution::Skipped => "skipped, a parent failed".to_string(),
+    }
+}
+
+/// Whether a change was reconciled with conflicts that require the
+/// user's attention.
+fn is_conflicted(resolution: &Resolution) -> bool {
+    matches!(resolution,
+             Resolution::Conflict { .. } |
+             Resolution::Merged { is_conflicted: true })
 }
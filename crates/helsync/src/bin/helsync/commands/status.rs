@@ -1,16 +1,257 @@
-use crate::database::Database;
+use crate::database::{CloudProvider, Database, DriveTokenStore};
+use super::utils::{find_filesystem, load_drive, load_rules, load_sync, open_filesystem};
+
+use helsync::cloud::{dropbox, googledrive, onedrive};
+use helsync::core::{Delta, FileSystem};
+use helsync::local::Client;
+use helsync::oauth2::Config;
+use helsync::sync::{ActionKind, Sync, SyncRules};
+
 use anyhow::Result;
 use clap::Parser;
+use console::style;
+use serde::Serialize;
+use std::sync::Arc;
 
 /// Group of commands for inspecting sync changes.
 #[derive(Parser, Debug)]
 pub struct StatusOpt {
-    /// Name of drive to merge to.
+    /// Name of drive to inspect.
     pub name: String,
+
+    /// Print changes as JSON.
+    #[arg(long)]
+    pub json: bool,
 }
 
 impl StatusOpt {
-    pub async fn run (&self, _: &Database) -> Result<()> {
-        unimplemented!();
+    pub async fn run (&self, db: &Database) -> Result<()> {
+        let path = find_filesystem(db, &self.name).await?;
+        let local = open_filesystem(&path).await?;
+        let (drive, app, token) = load_drive(db, &self.name).await?;
+        let rules = load_rules(db, &self.name).await?;
+        let config: Config = app.clone().into();
+        let store = Arc::new(DriveTokenStore::new(db, &self.name));
+
+        let changes = match app.provider {
+            CloudProvider::OneDrive => {
+                let remote = Arc::new(onedrive::Client::new(&token, &config)
+                    .with_token_store(store));
+                self.status(local, remote, &drive.path, rules).await?
+            },
+            CloudProvider::GoogleDrive => {
+                let remote = Arc::new(googledrive::Client::new(&token, &config)
+                    .with_token_store(store));
+                self.status(local, remote, &drive.path, rules).await?
+            },
+            CloudProvider::Dropbox => {
+                let remote = Arc::new(dropbox::Client::new(&token, &config)
+                    .with_token_store(store));
+                self.status(local, remote, &drive.path, rules).await?
+            },
+        };
+
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&changes)?);
+            return Ok(());
+        }
+
+        println!("On drive \"{}\"", self.name);
+        print_changes(&changes);
+        Ok(())
+    }
+
+    async fn status<R>(&self, local: Arc<Client>, remote: Arc<R>, remote_path: &str, rules: SyncRules) -> Result<Vec<Change>>
+    where R: FileSystem<Error = helsync::core::Error> + Delta {
+        // The rules are only applied in memory, since listing changes
+        // must not modify the drive.
+        let mut sync = load_sync(local.clone(), remote.clone(), remote_path, false).await?;
+        sync.set_rules(rules);
+        let is_full = !sync.has_synced();
+        list_changes(&mut sync, &local, remote.as_ref(), is_full).await
+    }
+}
+
+/// Prints a git-status-like summary of pending changes.
+pub(super) fn print_changes(changes: &[Change]) {
+    if changes.is_empty() {
+        println!("nothing to merge, local and remote are up to date");
+        return;
+    }
+
+    for (side, header) in [
+        (Side::Local, "Local changes:"),
+        (Side::Remote, "Remote changes:"),
+        (Side::Both, "Conflicting changes:"),
+    ] {
+        let changes: Vec<&Change> = changes.iter()
+            .filter(|change| change.side == side)
+            .collect();
+
+        if changes.is_empty() {
+            continue;
+        }
+
+        println!("\n{}", style(header).bold());
+        for change in changes {
+            let kind = format!("{}:", change.kind);
+            let name = match &change.old_name {
+                Some(old_name) => format!("{old_name} -> {}", change.name),
+                None => change.name.clone(),
+            };
+
+            let line = format!("  {kind:<10} {name}");
+            match side {
+                Side::Local => println!("{}", style(line).green()),
+                Side::Remote => println!("{}", style(line).yellow()),
+                Side::Both => println!("{}", style(line).red()),
+            }
+        }
+    }
+}
+
+/// The filesystem(s) in which a [Change] was made.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) enum Side {
+    Local,
+    Remote,
+    Both,
+}
+
+/// The kind of a pending [Change].
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) enum ChangeKind {
+    Added,
+    Modified,
+    Renamed,
+    Moved,
+    Deleted,
+    Conflict,
+}
+
+impl std::fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChangeKind::Added => write!(f, "added"),
+            ChangeKind::Modified => write!(f, "modified"),
+            ChangeKind::Renamed => write!(f, "renamed"),
+            ChangeKind::Moved => write!(f, "moved"),
+            ChangeKind::Deleted => write!(f, "deleted"),
+            ChangeKind::Conflict => write!(f, "conflict"),
+        }
+    }
+}
+
+/// A change that has not yet been merged.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct Change {
+    pub side: Side,
+    pub kind: ChangeKind,
+
+    /// The action that a merge would perform.
+    pub action: ActionKind,
+
+    /// ID of the local file, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    local_id: Option<i64>,
+
+    /// ID of the remote file, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    remote_id: Option<String>,
+
+    name: String,
+
+    /// The file's name before it was renamed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    old_name: Option<String>,
+}
+
+/// Lists the pending changes between the local and remote
+/// filesystems, without applying them or advancing the sync delta
+/// tokens.
+///
+/// Changes are planned by [Sync::plan_full] if `is_full` is set, and
+/// by [Sync::plan_changes] otherwise.
+pub(super) async fn list_changes<R>(sync: &mut Sync<R>, local: &Client, remote: &R, is_full: bool) -> Result<Vec<Change>>
+where R: FileSystem<Error = helsync::core::Error> + Delta {
+    let actions = match is_full {
+        true => sync.plan_full().await?,
+        false => {
+            sync.resolve_remote_root().await?;
+            sync.plan_changes().await?
+        },
+    };
+
+    let mut changes = Vec::new();
+    for action in actions {
+        let (side, kind) = match action.kind {
+            ActionKind::CreateRemote => (Side::Local, ChangeKind::Added),
+            ActionKind::CreateLocal => (Side::Remote, ChangeKind::Added),
+            ActionKind::Upload => (Side::Local, ChangeKind::Modified),
+            ActionKind::Download => (Side::Remote, ChangeKind::Modified),
+            ActionKind::Move => (Side::Remote, ChangeKind::Moved),
+            ActionKind::DeleteRemote => (Side::Local, ChangeKind::Deleted),
+            ActionKind::DeleteLocal => (Side::Remote, ChangeKind::Deleted),
+            ActionKind::Conflict => (Side::Both, ChangeKind::Conflict),
+        };
+
+        // The name and (local) parent of the file on the side that
+        // hasn't changed yet, and the parent it is changed to.
+        let (previous, parent) = match (action.kind, &action.local, &action.remote) {
+            (ActionKind::Upload, Some(local_file), _) => {
+                let remote_file = match &local_file.remote_id {
+                    Some(remote_id) => remote.get_file(remote_id).await.ok()
+                        .map(Into::<helsync::core::File>::into),
+                    None => None,
+                };
+
+                let previous = match remote_file {
+                    Some(file) => Some((file.name, local_parent(local, file.parent_id.as_deref()).await?)),
+                    None => None,
+                };
+
+                (previous, local_file.parent)
+            },
+            (ActionKind::Download | ActionKind::Move, _, Some(remote_file)) => {
+                let previous = local.get_remote_file(&remote_file.id).await?
+                    .map(|file| (file.name, file.parent));
+
+                (previous, local_parent(local, remote_file.parent_id.as_deref()).await?)
+            },
+            _ => (None, None),
+        };
+
+        let name = action.name().to_string();
+        let (kind, old_name) = match previous {
+            Some((old_name, _)) if old_name != name => (ChangeKind::Renamed, Some(old_name)),
+            Some((_, old_parent)) if old_parent != parent => (ChangeKind::Moved, None),
+            _ => (kind, None),
+        };
+
+        changes.push(Change {
+            side,
+            kind,
+            action: action.kind,
+            local_id: action.local.as_ref().map(|file| file.id),
+            remote_id: action.remote.as_ref().map(|file| file.id.clone())
+                .or_else(|| action.local.as_ref().and_then(|file| file.remote_id.clone())),
+            old_name,
+            name,
+        });
+    }
+
+    changes.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(changes)
+}
+
+/// Finds the ID of the local folder that is synced to the remote
+/// folder `remote_id`, or `None` for the root folder.
+async fn local_parent(local: &Client, remote_id: Option<&str>) -> Result<Option<i64>> {
+    match remote_id {
+        Some(remote_id) => Ok(local.get_remote_file(remote_id).await?.map(|file| file.id)),
+        None => Ok(None),
     }
 }
@@ -1,3 +1,14 @@
+use crate::database::{App, Database, Drive, Filesystem};
+use super::errors::handle_not_found_err;
+use helsync::local::{self, Client};
+use helsync::core::{Delta, File, FileSystem};
+use helsync::oauth2::Token;
+use helsync::sync::{Rule, Sync, SyncRules};
+
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
 /// Ensures that an object (i.e. drive or profile) is appropriately
 /// named. Must be between 1-20 chars, start with a letter, and be
 /// alphanumeric.
@@ -10,3 +21,115 @@ pub fn is_valid_name(name: &str) -> anyhow::Result<()> {
         .then(|| ())
         .ok_or(anyhow::anyhow!("bad name: use pattern [a-zA-Z][a-zA-Z0-9]"))
 }
+
+/// Name of the SQLite database that stores a filesystem's local
+/// drive data, relative to the filesystem's path.
+pub const FS_DATABASE: &str = ".helsync.sqlite";
+
+/// Opens the local [Client] of the filesystem at `path`, creating
+/// and migrating its database if necessary.
+pub async fn open_filesystem(path: &Path) -> Result<Arc<Client>> {
+    let db_path = path.join(FS_DATABASE);
+    let db_path = db_path.to_str()
+        .ok_or(anyhow::anyhow!("invalid path"))?;
+
+    let db = ::database::Database::new(&::database::Config {
+        max_connections: 5,
+        local_path: db_path.to_string(),
+        migrations: local::migrations(),
+    }).await?;
+
+    Ok(Arc::new(Client::new(Arc::new(db))))
+}
+
+/// Fetches the path of the filesystem bound to the drive `name`.
+pub async fn find_filesystem(db: &Database, name: &str) -> Result<PathBuf> {
+    let mut conn = db.acquire().await?;
+    let fs: Filesystem = sqlx::query_as("SELECT * FROM Filesystem WHERE drive=?")
+        .bind(name)
+        .fetch_one(&mut *conn).await
+        .map_err(|_| anyhow::anyhow!("drive \"{name}\" has no filesystem"))?;
+
+    Ok(PathBuf::from(fs.path))
+}
+
+/// Loads the drive `name` along with its app and OAuth2 token.
+///
+/// The token may have expired. Clients refresh it when needed, and
+/// save the refreshed token through a
+/// [DriveTokenStore](crate::database::DriveTokenStore).
+pub async fn load_drive(db: &Database, name: &str) -> Result<(Drive, App, Token)> {
+    let mut conn = db.acquire().await?;
+    let drive: Drive = sqlx::query_as("SELECT * FROM Drive WHERE name=?")
+        .bind(name)
+        .fetch_one(&mut *conn).await
+        .map_err(handle_not_found_err)?;
+
+    let app: App = sqlx::query_as("SELECT * FROM App WHERE name=?")
+        .bind(&drive.app)
+        .fetch_one(&mut *conn).await
+        .map_err(handle_not_found_err)?;
+
+    let token = drive.token.clone()
+        .ok_or(anyhow::anyhow!("drive \"{name}\" is not connected"))?;
+
+    Ok((drive, app, token))
+}
+
+/// Loads the selective sync rules of the drive `name`.
+pub async fn load_rules(db: &Database, name: &str) -> Result<SyncRules> {
+    let mut conn = db.acquire().await?;
+    let rows: Vec<(String, String, String)> = sqlx::query_as("SELECT kind,
+    matcher, value FROM DriveRule WHERE drive=? ORDER BY id")
+        .bind(name)
+        .fetch_all(&mut *conn).await?;
+
+    let rules = rows.iter()
+        .map(|(kind, matcher, value)| Rule::from_columns(kind, matcher, value)
+             .ok_or(anyhow::anyhow!("invalid sync rule: {kind} {matcher} {value}")))
+        .collect::<Result<Vec<Rule>>>()?;
+
+    Ok(rules.into())
+}
+
+/// Resolves a slash-separated folder `path` in a remote filesystem
+/// to the folder's ID. Returns `None` for the remote's root folder.
+///
+/// If `create` is true, then missing folders are created. Otherwise,
+/// an error is returned.
+pub async fn resolve_remote_path<R>(remote: &R, path: &str, create: bool) -> Result<Option<String>>
+where R: FileSystem<Error = helsync::core::Error> {
+    let mut parent_id: Option<String> = None;
+    for name in path.split('/').filter(|name| !name.is_empty()) {
+        let folder = remote.list_files(parent_id.as_deref()).await?
+            .into_iter()
+            .map(Into::<File>::into)
+            .find(|file| file.is_folder && !file.is_deleted && file.name == name);
+
+        let folder = match folder {
+            Some(folder) => folder,
+            None if create => remote.create_folder(parent_id.as_deref(), name).await?.into(),
+            None => return Err(anyhow::anyhow!("remote folder \"{path}\" not found")),
+        };
+
+        parent_id = Some(folder.id);
+    }
+
+    Ok(parent_id)
+}
+
+/// Loads the [Sync] between a drive's local and remote filesystems.
+///
+/// If the drive has never been synced, then its remote root is
+/// resolved from the drive's folder `path`, as by
+/// [resolve_remote_path].
+pub async fn load_sync<R>(local: Arc<Client>, remote: Arc<R>, path: &str, create: bool) -> Result<Sync<R>>
+where R: FileSystem<Error = helsync::core::Error> + Delta {
+    let mut sync = Sync::load(local, remote.clone()).await?;
+    if sync.remote_root().is_none() {
+        let remote_root = resolve_remote_path(remote.as_ref(), path, create).await?;
+        sync.set_remote_root(remote_root.as_deref());
+    }
+
+    Ok(sync)
+}
@@ -47,6 +47,11 @@ impl Database {
         Ok(Self(pool))
     }
 
+    /// Returns a handle to the connection pool.
+    pub fn pool(&self) -> sqlx::pool::Pool<Sqlite> {
+        self.0.clone()
+    }
+
     /// Acquire an SQLite database connection from the pool.
     pub async fn acquire(&self) -> Result<PoolConnection<Sqlite>> {
         self.0.acquire().await
@@ -74,6 +79,8 @@ impl std::fmt::Display for BindKind {
 /// CloudProvider enumerates the supported cloud APIs.
 #[derive(Debug, Clone, ValueEnum, sqlx::Type)]
 pub enum CloudProvider {
+    #[value(name = "Dropbox")]
+    Dropbox,
     #[value(name = "GoogleDrive")]
     GoogleDrive,
     #[value(name = "OneDrive")]
@@ -83,6 +90,7 @@ pub enum CloudProvider {
 impl std::fmt::Display for CloudProvider {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            CloudProvider::Dropbox => write!(f, "Dropbox"),
             CloudProvider::GoogleDrive => write!(f, "GoogleDrive"),
             CloudProvider::OneDrive => write!(f, "OneDrive"),
         }
@@ -125,4 +133,16 @@ CREATE TABLE IF NOT EXISTS Filesystem (
     ON UPDATE CASCADE
     ON DELETE CASCADE
 );
+
+CREATE TABLE IF NOT EXISTS DriveRule (
+  id      INTEGER     PRIMARY KEY,
+  drive   VARCHAR(20) NOT NULL,
+  kind    VARCHAR(10) NOT NULL,
+  matcher VARCHAR(10) NOT NULL,
+  value   TEXT        NOT NULL,
+
+  FOREIGN KEY (drive) REFERENCES Drive(name)
+    ON UPDATE CASCADE
+    ON DELETE CASCADE
+);
 ";
@@ -1,7 +1,9 @@
+use super::Database;
 use console::style;
-use helsync::oauth2::Token;
+use helsync::oauth2::{StoreFuture, Token, TokenStore};
 
-use sqlx::sqlite::SqliteRow;
+use sqlx::pool::Pool;
+use sqlx::sqlite::{Sqlite, SqliteRow};
 use sqlx::FromRow;
 use sqlx::Row;
 
@@ -47,3 +49,41 @@ impl std::fmt::Display for Drive {
         )
     }
 }
+
+/// Stores the OAuth2 token of a drive in the CLI's database.
+pub struct DriveTokenStore {
+    pool: Pool<Sqlite>,
+    name: String,
+}
+
+impl DriveTokenStore {
+    pub fn new(db: &Database, name: &str) -> Self {
+        Self { pool: db.pool(), name: name.to_string() }
+    }
+}
+
+impl TokenStore for DriveTokenStore {
+    fn load(&self) -> StoreFuture<'_, Option<Token>> {
+        Box::pin(async move {
+            let drive: Option<Drive> = sqlx::query_as("SELECT * FROM Drive WHERE name=?")
+                .bind(&self.name)
+                .fetch_optional(&self.pool).await?;
+
+            Ok(drive.and_then(|drive| drive.token))
+        })
+    }
+
+    fn save<'a>(&'a self, token: &'a Token) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            sqlx::query("UPDATE Drive SET access_token=?, refresh_token=?, created_at=?, expires_in=? WHERE name=?")
+                .bind(&token.access_token)
+                .bind(&token.refresh_token)
+                .bind(token.created_at)
+                .bind(token.expires_in)
+                .bind(&self.name)
+                .execute(&self.pool).await?;
+
+            Ok(())
+        })
+    }
+}
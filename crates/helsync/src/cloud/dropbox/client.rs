@@ -0,0 +1,754 @@
+use crate::core::{RetryPolicy, Result, Error, FileSystem, Delta, Page};
+use crate::oauth2::{Config, Token, TokenStore};
+use super::error::DropboxError;
+use super::file::{Metadata, Tag};
+
+use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
+use serde_json::{from_value, Value};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+/// Dropbox API endpoint for RPC routes (metadata operations).
+pub const API_ENDPOINT: &str = "https://api.dropboxapi.com/2";
+
+/// Dropbox API endpoint for content routes (uploads and downloads).
+pub const CONTENT_ENDPOINT: &str = "https://content.dropboxapi.com/2";
+
+/// Maximum size of files uploaded in a single request. Larger
+/// files are uploaded in chunks using an upload session.
+const SMALL_FILE_LIMIT: usize = 8 * 1024 * 1024;
+
+/// Size of the chunks appended to an upload session.
+const CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+/// Maximum number of entries requested per page of a listing.
+const PAGE_SIZE: usize = 1000;
+
+/// Implements a Dropbox API client.
+pub struct Client {
+    client: crate::core::Client,
+    req: Arc<reqwest::Client>,
+    api_endpoint: String,
+    content_endpoint: String,
+
+    /// Maps the lowercased paths of known entries to their IDs, for
+    /// resolving parents and deleted entries.
+    ids: Mutex<HashMap<String, String>>,
+}
+
+impl Client {
+
+    /// Instantiate new Dropbox client.
+    pub fn new(token: &Token, config: &Config) -> Self {
+        Self {
+            client: crate::core::Client::new(token, config),
+            req: Arc::new(reqwest::Client::new()),
+            api_endpoint: API_ENDPOINT.to_string(),
+            content_endpoint: CONTENT_ENDPOINT.to_string(),
+            ids: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Overrides the RPC endpoint (defaults to [API_ENDPOINT]), e.g.
+    /// to target a local test server.
+    pub fn with_api_endpoint(mut self, endpoint: &str) -> Self {
+        self.api_endpoint = endpoint.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Overrides the content endpoint (defaults to
+    /// [CONTENT_ENDPOINT]).
+    pub fn with_content_endpoint(mut self, endpoint: &str) -> Self {
+        self.content_endpoint = endpoint.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Overrides the [RetryPolicy] used for API requests.
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.client = self.client.with_retry_policy(policy);
+        self
+    }
+
+    /// Saves the client's token in `store` every time it is
+    /// refreshed.
+    pub fn with_token_store(mut self, store: Arc<dyn TokenStore>) -> Self {
+        self.client = self.client.with_token_store(store);
+        self
+    }
+
+    /// Calls an RPC route, which takes and returns JSON.
+    async fn rpc(&self, route: &str, body: &Value) -> Result<Value> {
+        let url = format!("{}/{route}", self.api_endpoint);
+        let req = self.req.clone().post(&url)
+            .header(AUTHORIZATION, self.client.authorization().await?)
+            .json(body);
+
+        let res = self.client.execute_with_retry(req).await?
+            .error_for_status()?;
+
+        Ok(res.json().await?)
+    }
+
+    /// Calls an upload route, which takes its arguments in the
+    /// `Dropbox-API-Arg` header and the content in the body.
+    async fn upload(&self, route: &str, arg: &Value, buf: &[u8]) -> Result<Value> {
+        let url = format!("{}/{route}", self.content_endpoint);
+        let req = self.req.clone().post(&url)
+            .header(AUTHORIZATION, self.client.authorization().await?)
+            .header("Dropbox-API-Arg", api_arg(arg))
+            .header(CONTENT_TYPE, "application/octet-stream")
+            .body(buf.to_vec());
+
+        let res = self.client.execute_with_retry(req).await?
+            .error_for_status()?;
+
+        // Appending to an upload session returns no content.
+        let bytes = res.bytes().await?;
+        match bytes.is_empty() {
+            true => Ok(Value::Null),
+            false => Ok(serde_json::from_slice(&bytes)?),
+        }
+    }
+
+    /// Fetches the metadata at `path`, which is either a path or an
+    /// `id:` identifier, without resolving its parent.
+    async fn metadata(&self, path: &str) -> Result<Metadata> {
+        let json = self.rpc("files/get_metadata", &serde_json::json!({
+            "path": path,
+        })).await?;
+
+        let metadata: Metadata = from_value(json)?;
+        self.remember(&metadata);
+        Ok(metadata)
+    }
+
+    /// Gets the path of the folder with `id`, or the root folder's
+    /// (empty) path if `id` is `None`.
+    async fn folder_path(&self, id: Option<&str>) -> Result<String> {
+        match id {
+            Some(id) => Ok(self.metadata(id).await?.path_display.unwrap_or_default()),
+            None => Ok(String::new()),
+        }
+    }
+
+    /// Records the ID of an entry's path.
+    fn remember(&self, metadata: &Metadata) {
+        if let (Tag::File | Tag::Folder, Some(path)) = (metadata.tag, &metadata.path_lower) {
+            self.ids.lock().unwrap().insert(path.clone(), metadata.id.clone());
+        }
+    }
+
+    /// Sets the ID of an entry's parent, looking it up if the
+    /// parent hasn't been seen before.
+    async fn resolve_parent(&self, mut metadata: Metadata) -> Result<Metadata> {
+        self.remember(&metadata);
+        let Some(parent_path) = metadata.parent_path() else {
+            return Ok(metadata);
+        };
+
+        let known_id = self.ids.lock().unwrap().get(parent_path).cloned();
+        metadata.parent_id = match known_id {
+            Some(id) => Some(id),
+            None => Some(self.metadata(parent_path).await?.id),
+        };
+
+        Ok(metadata)
+    }
+
+    /// Sets the ID of a deleted entry, which Dropbox doesn't report.
+    /// Returns `None` if the ID can't be determined.
+    ///
+    /// Entries seen by this client are resolved from memory. Deleted
+    /// files are otherwise resolved from their revision history,
+    /// which retains their ID. Deleted folders have no revisions.
+    async fn resolve_deleted(&self, mut metadata: Metadata) -> Result<Option<Metadata>> {
+        let Some(path) = metadata.path_lower.clone() else {
+            return Ok(None);
+        };
+
+        let known_id = self.ids.lock().unwrap().remove(&path);
+        if let Some(id) = known_id {
+            metadata.id = id;
+            return Ok(Some(metadata));
+        }
+
+        let res = self.rpc("files/list_revisions", &serde_json::json!({
+            "path": path,
+            "mode": "path",
+            "limit": 1,
+        })).await;
+
+        let json = match res {
+            Ok(json) => json,
+
+            // The path is a folder, or never held a file.
+            Err(Error::Client(err)) if err.status == Some(409) => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        match json["entries"][0]["id"].as_str() {
+            Some(id) => {
+                metadata.id = id.to_string();
+                Ok(Some(metadata))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Resolves the IDs of a page of listed entries, in order, so
+    /// that paths which are deleted and re-created within the page
+    /// map to the right IDs.
+    async fn resolve_entries(&self, entries: Vec<Metadata>) -> Result<Vec<Metadata>> {
+        let mut resolved = Vec::with_capacity(entries.len());
+        for entry in entries {
+            match entry.tag {
+                Tag::Deleted => resolved.extend(self.resolve_deleted(entry).await?),
+                Tag::File | Tag::Folder => resolved.push(self.resolve_parent(entry).await?),
+            }
+        }
+
+        Ok(resolved)
+    }
+
+    /// Moves or copies a file using one of the relocation routes.
+    async fn relocate(&self, route: &str, source_id: &str, parent_id: Option<&str>, name: Option<&str>) -> Result<Metadata> {
+        let name = match name {
+            Some(name) => name.to_string(),
+            None => self.metadata(source_id).await?.name,
+        };
+
+        let parent_path = self.folder_path(parent_id).await?;
+        let json = self.rpc(route, &serde_json::json!({
+            "from_path": source_id,
+            "to_path": format!("{parent_path}/{name}"),
+            "autorename": false,
+        })).await?;
+
+        let metadata: Metadata = from_value(json["metadata"].clone())?;
+        self.resolve_parent(metadata).await
+    }
+
+    /// Upload small files (<= 8MB) in a single request.
+    async fn upload_small_file(&self, id: &str, buf: &[u8]) -> Result<Metadata> {
+        let json = self.upload("files/upload", &serde_json::json!({
+            "path": id,
+            "mode": "overwrite",
+            "autorename": false,
+            "mute": true,
+        }), buf).await?;
+
+        let metadata: Metadata = from_value(tagged(json, "file"))?;
+        self.resolve_parent(metadata).await
+    }
+
+    /// Upload large files (> 8MB) in chunks using an upload session.
+    async fn upload_large_file(&self, id: &str, buf: &[u8]) -> Result<Metadata> {
+        let mut chunks = buf.chunks(CHUNK_SIZE);
+        let first = chunks.next().unwrap_or_default();
+        let json = self.upload("files/upload_session/start", &serde_json::json!({
+            "close": false,
+        }), first).await?;
+
+        let session_id = json["session_id"].as_str()
+            .ok_or(DropboxError {
+                error_summary: "upload_session/start: no session_id in response".to_string(),
+            })?
+            .to_string();
+
+        // The last chunk is uploaded along with the commit.
+        let mut offset = first.len();
+        let last = chunks.next_back().unwrap_or_default();
+        for chunk in chunks {
+            self.upload("files/upload_session/append_v2", &serde_json::json!({
+                "cursor": {"session_id": session_id, "offset": offset},
+                "close": false,
+            }), chunk).await?;
+
+            offset += chunk.len();
+        }
+
+        let json = self.upload("files/upload_session/finish", &serde_json::json!({
+            "cursor": {"session_id": session_id, "offset": offset},
+            "commit": {
+                "path": id,
+                "mode": "overwrite",
+                "autorename": false,
+                "mute": true,
+            },
+        }), last).await?;
+
+        let metadata: Metadata = from_value(tagged(json, "file"))?;
+        self.resolve_parent(metadata).await
+    }
+
+    /// Fetches pages of a listing until the cursor is exhausted,
+    /// returning the entries and the final cursor.
+    async fn list_all(&self, mut json: Value) -> Result<(Vec<Metadata>, String)> {
+        let mut entries: Vec<Metadata> = Vec::new();
+        loop {
+            let page: Vec<Metadata> = from_value(json["entries"].clone())?;
+            entries.append(&mut self.resolve_entries(page).await?);
+
+            let cursor = json["cursor"].as_str().unwrap_or_default().to_string();
+            if !json["has_more"].as_bool().unwrap_or(false) {
+                return Ok((entries, cursor));
+            }
+
+            json = self.rpc("files/list_folder/continue", &serde_json::json!({
+                "cursor": cursor,
+            })).await?;
+        }
+    }
+}
+
+impl FileSystem for Client {
+    type File = Metadata;
+    type Error = Error;
+
+    /// Retrieve the [Metadata] of a file or folder by id.
+    ///
+    /// API Reference: [get_metadata](https://www.dropbox.com/developers/documentation/http/documentation#files-get_metadata)
+    async fn get_file(&self, id: &str) -> Result<Metadata> {
+        let metadata = self.metadata(id).await?;
+        self.resolve_parent(metadata).await
+    }
+
+    /// Copy a file or folder.
+    ///
+    /// Copies the file with id `source_id` to the parent
+    /// `parent_id`. If `parent_id` is `None`, the file is copied to
+    /// the root folder. Optionally specifying a `name` will rename
+    /// the copied item.
+    ///
+    /// API Reference: [copy_v2](https://www.dropbox.com/developers/documentation/http/documentation#files-copy)
+    async fn copy_file(&self, source_id: &str, parent_id: Option<&str>, name: Option<&str>) -> Result<Metadata> {
+        self.relocate("files/copy_v2", source_id, parent_id, name).await
+    }
+
+    /// Move a file or folder to a new folder.
+    ///
+    /// Setting `parent_id` to `None` moves the file to the root
+    /// folder. Specifying a `name` renames the file.
+    ///
+    /// API Reference: [move_v2](https://www.dropbox.com/developers/documentation/http/documentation#files-move)
+    async fn move_file(&self, source_id: &str, parent_id: Option<&str>, name: Option<&str>) -> Result<Metadata> {
+        self.relocate("files/move_v2", source_id, parent_id, name).await
+    }
+
+    /// Delete a file or folder, along with its contents.
+    ///
+    /// API Reference: [delete_v2](https://www.dropbox.com/developers/documentation/http/documentation#files-delete)
+    async fn remove_file(&self, id: &str) -> Result<()> {
+        self.rpc("files/delete_v2", &serde_json::json!({
+            "path": id,
+        })).await?;
+
+        Ok(())
+    }
+
+    /// Create a new folder.
+    ///
+    /// If `parent_id` is `None`, the folder is created in the root
+    /// folder.
+    ///
+    /// API Reference: [create_folder_v2](https://www.dropbox.com/developers/documentation/http/documentation#files-create_folder)
+    async fn create_folder(&self, parent_id: Option<&str>, name: &str) -> Result<Metadata> {
+        let parent_path = self.folder_path(parent_id).await?;
+        let json = self.rpc("files/create_folder_v2", &serde_json::json!({
+            "path": format!("{parent_path}/{name}"),
+            "autorename": false,
+        })).await?;
+
+        let metadata: Metadata = from_value(tagged(json["metadata"].clone(), "folder"))?;
+        self.resolve_parent(metadata).await
+    }
+
+    /// Create a new, empty file.
+    ///
+    /// Dropbox has no route for creating files without content, so
+    /// an empty file is uploaded instead, replacing any file with
+    /// the same name.
+    ///
+    /// API Reference: [upload](https://www.dropbox.com/developers/documentation/http/documentation#files-upload)
+    async fn create_file(&self, parent_id: Option<&str>, name: &str) -> Result<Metadata> {
+        let parent_path = self.folder_path(parent_id).await?;
+        self.upload_small_file(&format!("{parent_path}/{name}"), &[]).await
+    }
+
+    /// List the children of a folder.
+    ///
+    /// Set `id` to `None` to retrieve the children of the root
+    /// folder. Every page of children is fetched; see
+    /// [list_files_paged](FileSystem::list_files_paged) to process
+    /// large folders page by page.
+    ///
+    /// API Reference: [list_folder](https://www.dropbox.com/developers/documentation/http/documentation#files-list_folder)
+    async fn list_files(&self, id: Option<&str>) -> Result<Vec<Metadata>> {
+        let mut items: Vec<Metadata> = Vec::new();
+        let mut pages = self.list_files_paged(id);
+        while let Some(mut page) = pages.next().await? {
+            items.append(&mut page);
+        }
+
+        Ok(items)
+    }
+
+    /// List a single page of children of a folder. The page token
+    /// is the listing's cursor.
+    ///
+    /// API Reference: [list_folder/continue](https://www.dropbox.com/developers/documentation/http/documentation#files-list_folder-continue)
+    async fn list_files_page(&self, id: Option<&str>, page_token: Option<&str>) -> Result<Page<Metadata>> {
+        let json = match page_token {
+            Some(cursor) => self.rpc("files/list_folder/continue", &serde_json::json!({
+                "cursor": cursor,
+            })).await?,
+            None => self.rpc("files/list_folder", &serde_json::json!({
+                "path": id.unwrap_or_default(),
+                "limit": PAGE_SIZE,
+            })).await?,
+        };
+
+        let mut items: Vec<Metadata> = from_value(json["entries"].clone())?;
+        for item in items.iter_mut() {
+            item.parent_id = id.map(|id| id.to_string());
+            self.remember(item);
+        }
+
+        let cursor = match json["has_more"].as_bool().unwrap_or(false) {
+            true => json["cursor"].as_str().map(|cursor| cursor.to_string()),
+            false => None,
+        };
+
+        Ok((items, cursor))
+    }
+
+    /// Upload or replace the contents of a file.
+    ///
+    /// API Reference: [upload_session](https://www.dropbox.com/developers/documentation/http/documentation#files-upload_session-start)
+    async fn write_to_file(&self, id: &str, buf: &[u8]) -> Result<Metadata> {
+        if buf.len() <= SMALL_FILE_LIMIT {
+            self.upload_small_file(id, buf).await
+        } else {
+            self.upload_large_file(id, buf).await
+        }
+    }
+
+    /// Download the contents of a file.
+    ///
+    /// API Reference: [download](https://www.dropbox.com/developers/documentation/http/documentation#files-download)
+    async fn read_from_file(&self, id: &str) -> Result<Vec<u8>> {
+        // Download routes reject requests with a binary content type.
+        let url = format!("{}/files/download", self.content_endpoint);
+        let req = self.req.clone().post(&url)
+            .header(AUTHORIZATION, self.client.authorization().await?)
+            .header("Dropbox-API-Arg", api_arg(&serde_json::json!({"path": id})));
+
+        let res = self.client.execute_with_retry(req).await?
+            .error_for_status()?;
+
+        let bytes = res.bytes().await?;
+        Ok(bytes.to_vec())
+    }
+}
+
+impl Delta for Client {
+    type File = Metadata;
+
+    /// Track changes for a Dropbox.
+    ///
+    /// `cursor` is a cursor returned by a previous call. If it is
+    /// `None`, every file is listed along with a cursor to the
+    /// current state.
+    ///
+    /// Dropbox doesn't report the IDs of deleted entries. Deleted
+    /// folders that this client hasn't seen before are omitted.
+    ///
+    /// API Reference: [list_folder/continue](https://www.dropbox.com/developers/documentation/http/documentation#files-list_folder-continue)
+    async fn list_deltas(&self, cursor: Option<&str>) -> Result<(Vec<Metadata>, String)> {
+        let json = match cursor {
+            Some(cursor) => self.rpc("files/list_folder/continue", &serde_json::json!({
+                "cursor": cursor,
+            })).await?,
+            None => self.rpc("files/list_folder", &serde_json::json!({
+                "path": "",
+                "recursive": true,
+                "limit": PAGE_SIZE,
+            })).await?,
+        };
+
+        // Moves are reported as a deletion at the old path followed
+        // by the entry at its new path, so only the latest entry of
+        // each ID is kept.
+        let (entries, cursor) = self.list_all(json).await?;
+        let mut seen = HashSet::new();
+        let mut deltas: Vec<Metadata> = entries.into_iter().rev()
+            .filter(|entry| seen.insert(entry.id.clone()))
+            .collect();
+
+        deltas.reverse();
+        Ok((deltas, cursor))
+    }
+}
+
+/// Encodes arguments for the `Dropbox-API-Arg` header. HTTP headers
+/// must be ASCII, so other characters are escaped.
+fn api_arg(arg: &Value) -> String {
+    let mut encoded = String::new();
+    for c in arg.to_string().chars() {
+        if c.is_ascii() && !c.is_ascii_control() {
+            encoded.push(c);
+            continue;
+        }
+
+        let mut units = [0; 2];
+        for unit in c.encode_utf16(&mut units) {
+            encoded.push_str(&format!("\\u{unit:04x}"));
+        }
+    }
+
+    encoded
+}
+
+/// Tags metadata returned by routes that only return one kind of
+/// entry, and so omit the `.tag` field.
+fn tagged(mut json: Value, tag: &str) -> Value {
+    if json.get(".tag").is_none() {
+        json[".tag"] = Value::String(tag.to_string());
+    }
+
+    json
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::cloud::fake::FakeDropbox;
+
+    #[tokio::test]
+    async fn test_get_file() {
+        let fake = FakeDropbox::new().await;
+        let client = fake.client();
+        let parent = client.create_folder(None, "helsync-parent")
+            .await.unwrap();
+
+        let file = client.create_file(Some(&parent.id), "helsync-get-file.txt")
+            .await.unwrap();
+
+        // Parents are resolved from paths by a fresh client.
+        let res = fake.client().get_file(&file.id).await.unwrap();
+        assert!(res.id == file.id);
+        assert!(res.tag == Tag::File);
+        assert!(res.parent_id == Some(parent.id));
+    }
+
+    #[tokio::test]
+    async fn test_copy_file() {
+        let fake = FakeDropbox::new().await;
+        let client = fake.client();
+        let file = client.create_file(None, "helsync-copy-file.txt")
+            .await.unwrap();
+
+        let copied = client.copy_file(&file.id, None, Some("new-name.txt"))
+            .await.unwrap();
+
+        assert!(copied.id != file.id);
+        assert!(copied.name == "new-name.txt");
+
+        let copied_verify = client.get_file(&copied.id).await.unwrap();
+        assert!(copied_verify.name == "new-name.txt");
+        assert!(copied_verify.parent_id.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_move_file() {
+        let fake = FakeDropbox::new().await;
+        let client = fake.client();
+        let child = client.create_file(None, "helsync-child-file.txt")
+            .await.unwrap();
+
+        let parent = client.create_folder(None, "helsync-parent")
+            .await.unwrap();
+
+        let moved = client.move_file(&child.id, Some(&parent.id), None)
+            .await.unwrap();
+
+        assert!(moved.id == child.id);
+        assert!(moved.name == "helsync-child-file.txt");
+        assert!(moved.parent_id == Some(parent.id));
+
+        // Moving without a parent moves the file to the root.
+        let moved = client.move_file(&child.id, None, Some("renamed.txt"))
+            .await.unwrap();
+
+        assert!(moved.name == "renamed.txt");
+        assert!(moved.parent_id.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_create_folder() {
+        let fake = FakeDropbox::new().await;
+        let client = fake.client();
+        let parent = client.create_folder(None, "helsync-test-folder")
+            .await.unwrap();
+
+        assert!(parent.name == "helsync-test-folder");
+        assert!(parent.tag == Tag::Folder);
+
+        let child = client.create_folder(Some(&parent.id), "helsync-child")
+            .await.unwrap();
+
+        assert!(child.parent_id == Some(parent.id.clone()));
+        client.remove_file(&parent.id).await.unwrap();
+        assert!(client.get_file(&child.id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_list_files_paged() {
+        let fake = FakeDropbox::new().await;
+        let client = fake.client();
+        fake.set_page_size(2);
+
+        let parent = client.create_folder(None, "helsync-test-paged")
+            .await.unwrap();
+
+        for i in 0..5 {
+            client.create_file(Some(&parent.id), &format!("helsync-page-{i}.txt"))
+                .await.unwrap();
+        }
+
+        let (page, cursor) = client.list_files_page(Some(&parent.id), None)
+            .await.unwrap();
+
+        assert!(page.len() == 2);
+        assert!(cursor.is_some());
+
+        let files = client.list_files(Some(&parent.id)).await.unwrap();
+        assert!(files.len() == 5);
+        assert!(files.iter().all(|f| f.parent_id == Some(parent.id.clone())));
+
+        let files = client.list_files(None).await.unwrap();
+        assert!(files.len() == 1 && files[0].id == parent.id);
+    }
+
+    #[tokio::test]
+    async fn test_read_write_small() {
+        let fake = FakeDropbox::new().await;
+        let client = fake.client();
+
+        // Non-ASCII names are escaped in the Dropbox-API-Arg header.
+        let file = client.create_file(None, "helsync-wríte-small.txt")
+            .await.unwrap();
+
+        let buf = "Hello, World!".as_bytes();
+        let file = client.write_to_file(&file.id, buf)
+            .await.unwrap();
+
+        assert!(file.name == "helsync-wríte-small.txt");
+        assert!(file.size == Some(buf.len() as u64));
+
+        let v = client.read_from_file(&file.id).await.unwrap();
+        let string = String::from_utf8(v).unwrap();
+        assert!(string == "Hello, World!");
+    }
+
+    #[tokio::test]
+    async fn test_read_write_large() {
+        let fake = FakeDropbox::new().await;
+        let client = fake.client();
+        let file = client.create_file(None, "helsync-write-large.txt")
+            .await.unwrap();
+
+        // Uploaded in three chunks of an upload session.
+        let buf: Vec<u8> = (0..SMALL_FILE_LIMIT + 1).map(|i| i as u8).collect();
+        let file = client.write_to_file(&file.id, &buf)
+            .await.unwrap();
+
+        assert!(file.name == "helsync-write-large.txt");
+        assert!(client.read_from_file(&file.id).await.unwrap() == buf);
+    }
+
+    #[tokio::test]
+    async fn test_list_deltas() {
+        let fake = FakeDropbox::new().await;
+        let client = fake.client();
+        let folder = client.create_folder(None, "helsync-folder")
+            .await.unwrap();
+
+        // The first call enumerates every file.
+        let (changes, cursor) = client.list_deltas(None).await.unwrap();
+        assert!(changes.len() == 1 && changes[0].id == folder.id);
+
+        // Make a change.
+        let file = client.create_file(Some(&folder.id), "helsync-track-changes.txt")
+            .await.unwrap();
+
+        let (changes, cursor) = client.list_deltas(Some(&cursor)).await.unwrap();
+        assert!(changes.len() == 1);
+        assert!(changes[0].id == file.id);
+        assert!(changes[0].parent_id == Some(folder.id.clone()));
+
+        // Moves are reported once, at the new path.
+        client.move_file(&file.id, None, None).await.unwrap();
+        let (changes, cursor) = client.list_deltas(Some(&cursor)).await.unwrap();
+        assert!(changes.len() == 1);
+        assert!(changes[0].id == file.id && changes[0].parent_id.is_none());
+
+        client.remove_file(&file.id).await.unwrap();
+        let (changes, _) = client.list_deltas(Some(&cursor)).await.unwrap();
+        assert!(changes.len() == 1);
+        assert!(changes[0].id == file.id && changes[0].tag == Tag::Deleted);
+    }
+
+    #[tokio::test]
+    async fn test_list_deltas_deleted() {
+        let fake = FakeDropbox::new().await;
+        let client = fake.client();
+        fake.set_page_size(2);
+
+        let folder = client.create_folder(None, "helsync-folder")
+            .await.unwrap();
+
+        let file = client.create_file(None, "helsync-deleted.txt")
+            .await.unwrap();
+
+        let (_, cursor) = client.list_deltas(None).await.unwrap();
+        client.remove_file(&file.id).await.unwrap();
+        client.remove_file(&folder.id).await.unwrap();
+
+        // A client that hasn't seen the deleted files resolves files
+        // from their revisions, but can't resolve folders.
+        let (changes, _) = fake.client().list_deltas(Some(&cursor)).await.unwrap();
+        assert!(changes.len() == 1);
+        assert!(changes[0].id == file.id && changes[0].tag == Tag::Deleted);
+
+        let (changes, _) = client.list_deltas(Some(&cursor)).await.unwrap();
+        assert!(changes.len() == 2);
+        assert!(changes[1].id == folder.id && changes[1].tag == Tag::Deleted);
+    }
+
+    #[tokio::test]
+    async fn test_get_missing_file() {
+        let fake = FakeDropbox::new().await;
+        let client = fake.client();
+        assert!(client.get_file("id:missing").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_refresh_token() {
+        let fake = FakeDropbox::new().await;
+        let mut token = fake.token();
+        token.expires_in = 0;
+
+        // An expired token is refreshed against the token endpoint.
+        fake.revoke_token();
+        let client = Client::new(&token, &fake.config())
+            .with_api_endpoint(&format!("{}/2", fake.url()))
+            .with_content_endpoint(&format!("{}/content/2", fake.url()));
+
+        let folder = client.create_folder(None, "helsync-refresh")
+            .await.unwrap();
+
+        assert!(folder.name == "helsync-refresh");
+    }
+}
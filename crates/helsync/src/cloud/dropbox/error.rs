@@ -0,0 +1,15 @@
+use serde::{Serialize, Deserialize};
+
+/// Dropbox API error response.
+#[derive(Debug, Clone, Serialize, Deserialize, thiserror::Error)]
+pub struct DropboxError {
+
+    /// A summary of the error, e.g. `path/not_found/`.
+    pub error_summary: String,
+}
+
+impl std::fmt::Display for DropboxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", serde_json::to_string(self).unwrap())
+    }
+}
@@ -0,0 +1,103 @@
+use crate::core::File;
+use serde::{Serialize, Deserialize};
+use chrono::DateTime;
+
+/// Metadata for a file, folder or deleted entry in Dropbox.
+///
+/// Dropbox returns metadata as a union tagged by `.tag`. Deleted
+/// entries only carry a name and path; the client fills in their
+/// `id` when it is known.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Metadata {
+
+    /// Whether the entry is a file, folder or deleted entry.
+    #[serde(rename = ".tag")]
+    pub tag: Tag,
+
+    /// A unique identifier for the file, e.g. `id:a4ayc_80_OEAAAAAAAAAXw`.
+    #[serde(default)]
+    pub id: String,
+
+    /// The last component of the path (including extension).
+    pub name: String,
+
+    /// The lowercased full path in the user's Dropbox, starting with
+    /// a slash.
+    pub path_lower: Option<String>,
+
+    /// The cased path to be used for display purposes only.
+    pub path_display: Option<String>,
+
+    /// For files, the modification time set by the desktop client
+    /// when the file was added to Dropbox.
+    pub client_modified: Option<String>,
+
+    /// For files, the last time the file was modified on Dropbox.
+    pub server_modified: Option<String>,
+
+    /// For files, a unique identifier for the current revision.
+    pub rev: Option<String>,
+
+    /// For files, the file size in bytes.
+    pub size: Option<u64>,
+
+    /// For files, a hash of the file content. Note that this is the
+    /// [Dropbox content hash](https://www.dropbox.com/developers/reference/content-hash),
+    /// not a plain SHA256 hash.
+    pub content_hash: Option<String>,
+
+    /// ID of the entry's parent folder, or `None` if the parent is
+    /// the root folder. Resolved by the client, since Dropbox
+    /// doesn't report parents.
+    #[serde(skip)]
+    pub parent_id: Option<String>,
+}
+
+/// The type of a [Metadata] entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Tag {
+    File,
+    Folder,
+    Deleted,
+}
+
+impl Metadata {
+
+    /// The lowercased path of the entry's parent folder, or `None`
+    /// if the entry is in the root folder.
+    pub fn parent_path(&self) -> Option<&str> {
+        let (parent, _) = self.path_lower.as_deref()?.rsplit_once('/')?;
+        match parent.is_empty() {
+            true => None,
+            false => Some(parent),
+        }
+    }
+}
+
+impl From<Metadata> for File {
+    fn from(metadata: Metadata) -> Self {
+        let modified_at = metadata.server_modified.as_deref()
+            .and_then(|ts| DateTime::parse_from_rfc3339(ts).ok())
+            .map(|dt| dt.timestamp())
+            .unwrap_or(0);
+
+        let created_at = metadata.client_modified.as_deref()
+            .and_then(|ts| DateTime::parse_from_rfc3339(ts).ok())
+            .map(|dt| dt.timestamp())
+            .unwrap_or(modified_at);
+
+        File {
+            id: metadata.id,
+            name: metadata.name,
+            modified_at,
+            created_at,
+            parent_id: metadata.parent_id,
+            is_folder: metadata.tag == Tag::Folder,
+            is_deleted: metadata.tag == Tag::Deleted,
+            sha1: None,
+            sha256: None,
+            size: metadata.size,
+        }
+    }
+}
@@ -0,0 +1,78 @@
+//! Dropbox [FileSystem](crate::core::FileSystem).
+//!
+//! Official API Reference: [Dropbox HTTP API](https://www.dropbox.com/developers/documentation/http/documentation)
+//!
+//! Dropbox addresses files by path, but every file and folder also
+//! has a stable `id:` identifier which survives moves and renames.
+//! The client identifies files by their `id:` identifiers and
+//! translates them to paths where the API requires them.
+//!
+//! # Examples
+//! ## Listing all Files
+//! ```no_run
+//! use helsync::cloud::dropbox;
+//! use helsync::core::FileSystem;
+//! use helsync::oauth2;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let app_config = oauth2::Config::dropbox(
+//!         "client-id", "http://localhost:6969"
+//!     );
+//!
+//!     let token =
+//!         oauth2::Token::from_refresh_token("example", &app_config)
+//!         .await.unwrap();
+//!
+//!     let client = dropbox::Client::new(&token, &app_config);
+//!
+//!     // Passing None selects the root directory. Alternatively,
+//!     // pass a parent id: e.g. Some("id:parent-id").
+//!     let files = client.list_files(None).await.unwrap();
+//!
+//!     for file in files {
+//!         // Perform some file-wise operation...
+//!     }
+//! }
+//! ```
+//! ## Tracking Changes
+//! ```no_run
+//! use helsync::cloud::dropbox;
+//! use helsync::core::{Delta, FileSystem};
+//! use helsync::oauth2;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let app_config = oauth2::Config::dropbox(
+//!         "client-id", "http://localhost:6969"
+//!     );
+//!
+//!     let token =
+//!         oauth2::Token::from_refresh_token("example", &app_config)
+//!         .await.unwrap();
+//!
+//!     let client = dropbox::Client::new(&token, &app_config);
+//!
+//!     // Enumerate every file, retrieving a cursor.
+//!     let (_, cursor) = client.list_deltas(None).await.unwrap();
+//!
+//!     // Perform some change (e.g. create a file).
+//!     let file = client.create_file(None, "my-new-file.txt")
+//!         .await.unwrap();
+//!
+//!     // Fetch the change using the previously retrieved cursor.
+//!     let (changes, _) = client.list_deltas(Some(&cursor)).await.unwrap();
+//!
+//!     let find = changes.iter().find(|f| f.id == file.id);
+//!     assert!(find.is_some());
+//! }
+//! ```
+
+mod client;
+pub use client::*;
+
+mod error;
+pub use error::*;
+
+mod file;
+pub use file::*;
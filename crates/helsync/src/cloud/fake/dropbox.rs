@@ -0,0 +1,532 @@
+use super::{Api, FakeServer, Request, Response, now};
+use crate::cloud::dropbox::Client;
+use crate::oauth2::Config;
+
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+
+/// A fake Dropbox API server hosting a single Dropbox.
+pub(crate) type FakeDropbox = FakeServer<Dropbox>;
+
+impl FakeDropbox {
+
+    /// Starts a fake server hosting an empty Dropbox.
+    pub async fn new() -> Self {
+        Self::start(Dropbox::default()).await
+    }
+
+    /// An app [Config] that refreshes tokens against the fake.
+    pub fn config(&self) -> Config {
+        let mut config = Config::dropbox("client-id", "http://localhost:6969");
+        config.token_endpoint = self.token_endpoint();
+        config
+    }
+
+    /// A client authorized to use the fake.
+    pub fn client(&self) -> Client {
+        Client::new(&self.token(), &self.config())
+            .with_api_endpoint(&format!("{}/2", self.url()))
+            .with_content_endpoint(&format!("{}/content/2", self.url()))
+    }
+
+    /// Limits the number of entries returned per page of a listing.
+    pub fn set_page_size(&self, page_size: usize) {
+        self.with_api(|api| api.page_size = page_size);
+    }
+}
+
+struct Entry {
+    name: String,
+    parent_id: Option<String>,
+    is_folder: bool,
+    content: Vec<u8>,
+    rev: usize,
+    modified_at: String,
+}
+
+/// A listing in progress: the entries of the initial listing that
+/// haven't been returned yet, followed by the changes logged after
+/// `position`.
+#[derive(Clone)]
+struct Cursor {
+    path: String,
+    recursive: bool,
+    pending: Vec<Value>,
+    position: usize,
+}
+
+/// The emulated state of a Dropbox. Entries have `id:` identifiers
+/// and the root folder has the empty path.
+pub(crate) struct Dropbox {
+    entries: BTreeMap<String, Entry>,
+    log: Vec<Value>,
+    cursors: Vec<Cursor>,
+    revisions: HashMap<String, String>,
+    sessions: HashMap<String, Vec<u8>>,
+    next_id: usize,
+    page_size: usize,
+}
+
+impl Default for Dropbox {
+    fn default() -> Self {
+        Self {
+            entries: BTreeMap::new(),
+            log: Vec::new(),
+            cursors: Vec::new(),
+            revisions: HashMap::new(),
+            sessions: HashMap::new(),
+            next_id: 0,
+            page_size: 200,
+        }
+    }
+}
+
+impl Api for Dropbox {
+    fn handle(&mut self, req: &Request, _base: &str) -> Response {
+        let segments: Vec<&str> = req.path.trim_matches('/').split('/').collect();
+        match (req.method.as_str(), segments.as_slice()) {
+            ("POST", ["2", "files", route @ ..]) => self.rpc(&route.join("/"), &req.json()),
+            ("POST", ["content", "2", "files", route @ ..]) => {
+                let arg: Value = req.header("dropbox-api-arg")
+                    .and_then(|arg| serde_json::from_str(arg).ok())
+                    .unwrap_or(Value::Null);
+
+                self.content(&route.join("/"), &arg, req)
+            }
+            _ => Self::error(400, &format!("unsupported route {} {}", req.method, req.path)),
+        }
+    }
+
+    fn error(status: u16, message: &str) -> Response {
+        Response::json(status, &json!({"error_summary": message, "error": {}}))
+    }
+}
+
+impl Dropbox {
+
+    fn rpc(&mut self, route: &str, body: &Value) -> Response {
+        match route {
+            "get_metadata" => self.get_metadata(body),
+            "list_folder" => self.list_folder(body),
+            "list_folder/continue" => self.list_folder_continue(body),
+            "list_revisions" => self.list_revisions(body),
+            "create_folder_v2" => self.create_folder(body),
+            "delete_v2" => self.delete(body),
+            "copy_v2" => self.relocate(body, false),
+            "move_v2" => self.relocate(body, true),
+            _ => Self::error(400, &format!("unsupported route {route}")),
+        }
+    }
+
+    fn content(&mut self, route: &str, arg: &Value, req: &Request) -> Response {
+        match route {
+            "download" => self.download(arg, req),
+            "upload" => self.upload(arg, req.body.clone()),
+            "upload_session/start" => self.start_session(req.body.clone()),
+            "upload_session/append_v2" => self.append_session(arg, &req.body),
+            "upload_session/finish" => self.finish_session(arg, &req.body),
+            _ => Self::error(400, &format!("unsupported route {route}")),
+        }
+    }
+
+    /// Formats the display path of an entry.
+    fn path(&self, id: &str) -> String {
+        let entry = &self.entries[id];
+        match &entry.parent_id {
+            Some(parent_id) => format!("{}/{}", self.path(parent_id), entry.name),
+            None => format!("/{}", entry.name),
+        }
+    }
+
+    /// Resolves a path or `id:` identifier to `Some(id)`, or to
+    /// `Some(None)` for the root folder.
+    fn resolve(&self, path: &str) -> Option<Option<String>> {
+        if path.is_empty() {
+            return Some(None);
+        }
+
+        if path.starts_with("id:") {
+            return self.entries.contains_key(path).then(|| Some(path.to_string()));
+        }
+
+        let path = path.to_lowercase();
+        self.entries.keys()
+            .find(|id| self.path(id).to_lowercase() == path)
+            .map(|id| Some(id.clone()))
+    }
+
+    /// Splits a destination path into its existing parent folder and
+    /// the new entry's name.
+    fn resolve_destination(&self, path: &str) -> Option<(Option<String>, String)> {
+        let (parent_path, name) = path.rsplit_once('/')?;
+        let parent_id = self.resolve(parent_path)?;
+        let is_folder = parent_id.as_ref()
+            .map_or(true, |id| self.entries[id].is_folder);
+
+        (is_folder && !name.is_empty()).then(|| (parent_id, name.to_string()))
+    }
+
+    fn children(&self, parent_id: Option<&str>) -> Vec<String> {
+        self.entries.iter()
+            .filter(|(_, entry)| entry.parent_id.as_deref() == parent_id)
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+
+    /// Lists the descendants of a folder, parents first.
+    fn descendants(&self, parent_id: Option<&str>) -> Vec<String> {
+        let mut ids = Vec::new();
+        for id in self.children(parent_id) {
+            ids.push(id.clone());
+            ids.append(&mut self.descendants(Some(&id)));
+        }
+
+        ids
+    }
+
+    fn to_json(&self, id: &str) -> Value {
+        let entry = &self.entries[id];
+        let path = self.path(id);
+        if entry.is_folder {
+            return json!({
+                ".tag": "folder",
+                "id": id,
+                "name": entry.name,
+                "path_lower": path.to_lowercase(),
+                "path_display": path,
+            });
+        }
+
+        json!({
+            ".tag": "file",
+            "id": id,
+            "name": entry.name,
+            "path_lower": path.to_lowercase(),
+            "path_display": path,
+            "client_modified": entry.modified_at,
+            "server_modified": entry.modified_at,
+            "rev": format!("{:09x}", entry.rev),
+            "size": entry.content.len(),
+        })
+    }
+
+    /// Formats metadata for routes that only return one kind of
+    /// entry, which omit the `.tag` field.
+    fn to_untagged_json(&self, id: &str) -> Value {
+        let mut json = self.to_json(id);
+        json.as_object_mut().unwrap().remove(".tag");
+        json
+    }
+
+    fn insert(&mut self, parent_id: Option<String>, name: &str, is_folder: bool, content: Vec<u8>) -> String {
+        self.next_id += 1;
+        let id = format!("id:entry{:04}", self.next_id);
+        self.entries.insert(id.clone(), Entry {
+            name: name.to_string(),
+            parent_id,
+            is_folder,
+            content,
+            rev: self.next_id,
+            modified_at: now(),
+        });
+
+        self.log.push(self.to_json(&id));
+        id
+    }
+
+    /// Logs the deletion of an entry and its descendants, in the
+    /// order Dropbox reports them: children first.
+    fn log_removal(&mut self, id: &str) {
+        for child_id in self.children(Some(id)) {
+            self.log_removal(&child_id);
+        }
+
+        let path = self.path(id);
+        let entry = &self.entries[id];
+        let deleted = json!({
+            ".tag": "deleted",
+            "name": entry.name,
+            "path_lower": path.to_lowercase(),
+            "path_display": path,
+        });
+
+        if !entry.is_folder {
+            self.revisions.insert(path.to_lowercase(), id.to_string());
+        }
+
+        self.log.push(deleted);
+    }
+
+    /// Deletes an entry and its descendants.
+    fn remove(&mut self, id: &str) {
+        self.log_removal(id);
+        for child_id in self.descendants(Some(id)) {
+            self.entries.remove(&child_id);
+        }
+
+        self.entries.remove(id);
+    }
+
+    /// Copies an entry and its descendants, returning the ID of the
+    /// copy.
+    fn duplicate(&mut self, id: &str, parent_id: Option<String>, name: &str) -> String {
+        let (is_folder, content) = {
+            let entry = &self.entries[id];
+            (entry.is_folder, entry.content.clone())
+        };
+
+        let copy_id = self.insert(parent_id, name, is_folder, content);
+        for child_id in self.children(Some(id)) {
+            let child_name = self.entries[&child_id].name.clone();
+            self.duplicate(&child_id, Some(copy_id.clone()), &child_name);
+        }
+
+        copy_id
+    }
+
+    fn get_metadata(&self, body: &Value) -> Response {
+        match self.resolve(body["path"].as_str().unwrap_or_default()) {
+            Some(Some(id)) => Response::json(200, &self.to_json(&id)),
+            _ => Self::error(409, "path/not_found/"),
+        }
+    }
+
+    fn list_folder(&mut self, body: &Value) -> Response {
+        let path = body["path"].as_str().unwrap_or_default();
+        let parent_id = match self.resolve(path) {
+            Some(None) => None,
+            Some(Some(id)) if self.entries[&id].is_folder => Some(id),
+            Some(Some(_)) => return Self::error(409, "path/not_folder/"),
+            None => return Self::error(409, "path/not_found/"),
+        };
+
+        let recursive = body["recursive"].as_bool().unwrap_or(false);
+        let ids = match recursive {
+            true => self.descendants(parent_id.as_deref()),
+            false => self.children(parent_id.as_deref()),
+        };
+
+        let cursor = Cursor {
+            path: parent_id.map(|id| self.path(&id).to_lowercase()).unwrap_or_default(),
+            recursive,
+            pending: ids.iter().map(|id| self.to_json(id)).collect(),
+            position: self.log.len(),
+        };
+
+        let limit = body["limit"].as_u64().unwrap_or(u64::MAX) as usize;
+        self.page(cursor, limit)
+    }
+
+    fn list_folder_continue(&mut self, body: &Value) -> Response {
+        let cursor = body["cursor"].as_str()
+            .and_then(|cursor| cursor.parse::<usize>().ok())
+            .and_then(|cursor| self.cursors.get(cursor));
+
+        match cursor {
+            Some(cursor) => self.page(cursor.clone(), usize::MAX),
+            None => Self::error(409, "reset/"),
+        }
+    }
+
+    /// Returns the next page of a listing along with a cursor to the
+    /// rest of it.
+    fn page(&mut self, mut cursor: Cursor, limit: usize) -> Response {
+        let limit = limit.min(self.page_size);
+        let mut entries: Vec<Value> = Vec::new();
+        if !cursor.pending.is_empty() {
+            let rest = cursor.pending.split_off(limit.min(cursor.pending.len()));
+            entries = std::mem::replace(&mut cursor.pending, rest);
+        } else {
+            while cursor.position < self.log.len() && entries.len() < limit {
+                let entry = &self.log[cursor.position];
+                let path = entry["path_lower"].as_str().unwrap_or_default();
+                let parent_path = path.rsplit_once('/').map(|(parent, _)| parent);
+                let is_match = match cursor.recursive {
+                    true => path.starts_with(&format!("{}/", cursor.path)),
+                    false => parent_path == Some(cursor.path.as_str()),
+                };
+
+                if is_match {
+                    entries.push(entry.clone());
+                }
+
+                cursor.position += 1;
+            }
+        }
+
+        let has_more = !cursor.pending.is_empty() || cursor.position < self.log.len();
+        self.cursors.push(cursor);
+        Response::json(200, &json!({
+            "entries": entries,
+            "cursor": (self.cursors.len() - 1).to_string(),
+            "has_more": has_more,
+        }))
+    }
+
+    fn list_revisions(&self, body: &Value) -> Response {
+        let path = body["path"].as_str().unwrap_or_default();
+        if let Some(Some(id)) = self.resolve(path) {
+            if !self.entries[&id].is_folder {
+                return Response::json(200, &json!({
+                    "is_deleted": false,
+                    "entries": [self.to_untagged_json(&id)],
+                }));
+            }
+        }
+
+        match self.revisions.get(&path.to_lowercase()) {
+            Some(id) => Response::json(200, &json!({
+                "is_deleted": true,
+                "entries": [{"id": id, "name": path.rsplit('/').next()}],
+            })),
+            None => Self::error(409, "path/not_file/"),
+        }
+    }
+
+    fn create_folder(&mut self, body: &Value) -> Response {
+        let path = body["path"].as_str().unwrap_or_default();
+        if self.resolve(path).is_some() {
+            return Self::error(409, "path/conflict/folder/");
+        }
+
+        let Some((parent_id, name)) = self.resolve_destination(path) else {
+            return Self::error(409, "path/not_found/");
+        };
+
+        let id = self.insert(parent_id, &name, true, Vec::new());
+        Response::json(200, &json!({"metadata": self.to_untagged_json(&id)}))
+    }
+
+    fn delete(&mut self, body: &Value) -> Response {
+        let Some(Some(id)) = self.resolve(body["path"].as_str().unwrap_or_default()) else {
+            return Self::error(409, "path_lookup/not_found/");
+        };
+
+        let json = self.to_json(&id);
+        self.remove(&id);
+        Response::json(200, &json!({"metadata": json}))
+    }
+
+    /// Copies or moves an entry.
+    fn relocate(&mut self, body: &Value, is_move: bool) -> Response {
+        let Some(Some(id)) = self.resolve(body["from_path"].as_str().unwrap_or_default()) else {
+            return Self::error(409, "from_lookup/not_found/");
+        };
+
+        let to_path = body["to_path"].as_str().unwrap_or_default();
+        let Some((parent_id, name)) = self.resolve_destination(to_path) else {
+            return Self::error(409, "to/not_found/");
+        };
+
+        // Renaming an entry to a different case of the same name is
+        // allowed, but otherwise the destination must be vacant.
+        match self.resolve(to_path) {
+            Some(Some(other_id)) if !is_move || other_id != id => {
+                return Self::error(409, "to/conflict/file/");
+            }
+            _ => (),
+        }
+
+        if !is_move {
+            let copy_id = self.duplicate(&id, parent_id, &name);
+            return Response::json(200, &json!({"metadata": self.to_json(&copy_id)}));
+        }
+
+        // Dropbox reports moves as deletions at the old path.
+        self.log_removal(&id);
+        let entry = self.entries.get_mut(&id).unwrap();
+        entry.parent_id = parent_id;
+        entry.name = name;
+
+        self.log.push(self.to_json(&id));
+        for child_id in self.descendants(Some(&id)) {
+            self.log.push(self.to_json(&child_id));
+        }
+
+        Response::json(200, &json!({"metadata": self.to_json(&id)}))
+    }
+
+    fn download(&self, arg: &Value, req: &Request) -> Response {
+        if req.header("content-type").is_some_and(|ty| ty != "text/plain") {
+            return Self::error(400, "bad content type");
+        }
+
+        match self.resolve(arg["path"].as_str().unwrap_or_default()) {
+            Some(Some(id)) if !self.entries[&id].is_folder => {
+                let result = self.to_untagged_json(&id).to_string();
+                Response::bytes(200, self.entries[&id].content.clone())
+                    .header("Dropbox-API-Result", &result)
+            }
+            Some(Some(_)) => Self::error(409, "path/not_file/"),
+            _ => Self::error(409, "path/not_found/"),
+        }
+    }
+
+    /// Uploads a file, creating it if `path` doesn't exist.
+    fn upload(&mut self, arg: &Value, content: Vec<u8>) -> Response {
+        let path = arg["path"].as_str().unwrap_or_default();
+        let is_overwrite = arg["mode"].as_str() == Some("overwrite");
+        let id = match self.resolve(path) {
+            Some(Some(id)) if self.entries[&id].is_folder => {
+                return Self::error(409, "path/conflict/folder/");
+            }
+            Some(Some(_)) if !is_overwrite => {
+                return Self::error(409, "path/conflict/file/");
+            }
+            Some(Some(id)) => {
+                self.next_id += 1;
+                let entry = self.entries.get_mut(&id).unwrap();
+                entry.content = content;
+                entry.rev = self.next_id;
+                entry.modified_at = now();
+                self.log.push(self.to_json(&id));
+                id
+            }
+            Some(None) => return Self::error(409, "path/malformed_path/"),
+            None => match self.resolve_destination(path) {
+                Some((parent_id, name)) => self.insert(parent_id, &name, false, content),
+                None => return Self::error(409, "path/not_found/"),
+            },
+        };
+
+        Response::json(200, &self.to_untagged_json(&id))
+    }
+
+    fn start_session(&mut self, content: Vec<u8>) -> Response {
+        let session_id = format!("session-{}", self.sessions.len());
+        self.sessions.insert(session_id.clone(), content);
+        Response::json(200, &json!({"session_id": session_id}))
+    }
+
+    /// Appends to an upload session if `cursor` points to its end.
+    fn append(&mut self, cursor: &Value, content: &[u8]) -> Option<Response> {
+        let session_id = cursor["session_id"].as_str().unwrap_or_default();
+        let Some(buf) = self.sessions.get_mut(session_id) else {
+            return Some(Self::error(409, "lookup_failed/not_found/"));
+        };
+
+        if cursor["offset"].as_u64() != Some(buf.len() as u64) {
+            return Some(Self::error(409, "lookup_failed/incorrect_offset/"));
+        }
+
+        buf.extend_from_slice(content);
+        None
+    }
+
+    fn append_session(&mut self, arg: &Value, content: &[u8]) -> Response {
+        match self.append(&arg["cursor"], content) {
+            Some(err) => err,
+            None => Response::json(200, &Value::Null),
+        }
+    }
+
+    fn finish_session(&mut self, arg: &Value, content: &[u8]) -> Response {
+        if let Some(err) = self.append(&arg["cursor"], content) {
+            return err;
+        }
+
+        let session_id = arg["cursor"]["session_id"].as_str().unwrap_or_default();
+        let content = self.sessions.remove(session_id).unwrap_or_default();
+        self.upload(&arg["commit"], content)
+    }
+}
@@ -0,0 +1,481 @@
+use super::{Api, FakeServer, Request, Response, now, parse_delta_token, changed_since};
+use crate::cloud::googledrive::Client;
+use crate::oauth2::Config;
+
+use serde_json::{json, Value};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+
+/// MIME type of Google Drive folders.
+const FOLDER_MIME_TYPE: &str = "application/vnd.google-apps.folder";
+
+/// A fake Google Drive API server.
+pub(crate) type FakeGoogleDrive = FakeServer<Drive>;
+
+impl FakeGoogleDrive {
+
+    /// Starts a fake server hosting an empty drive.
+    pub async fn new() -> Self {
+        Self::start(Drive::default()).await
+    }
+
+    /// An app [Config] that refreshes tokens against the fake.
+    pub fn config(&self) -> Config {
+        let mut config = Config::googledrive("client-id", "client-secret", "http://localhost:6969");
+        config.token_endpoint = self.token_endpoint();
+        config
+    }
+
+    /// A client authorized to use the fake.
+    pub fn client(&self) -> Client {
+        Client::new(&self.token(), &self.config())
+            .with_api_endpoint(&format!("{}/drive/v3", self.url()))
+            .with_upload_endpoint(&format!("{}/upload/drive/v3", self.url()))
+    }
+
+    /// Limits the number of files returned per page of a listing or
+    /// change list.
+    pub fn set_page_size(&self, page_size: usize) {
+        self.with_api(|api| api.page_size = page_size);
+    }
+
+    /// Makes resumable uploads fail once they have received `limit`
+    /// bytes, as if the connection dropped.
+    pub fn set_upload_limit(&self, limit: Option<usize>) {
+        self.with_api(|api| api.upload_limit = limit);
+    }
+}
+
+struct Entry {
+    name: String,
+    parent_id: String,
+    mime_type: String,
+    content: Vec<u8>,
+    version: u64,
+    created_at: String,
+    modified_at: String,
+}
+
+/// The emulated state of a Google Drive. The "My Drive" folder has
+/// the ID "root". Deletions are permanent.
+pub(crate) struct Drive {
+    files: BTreeMap<String, Entry>,
+    removed: HashMap<String, String>,
+    log: Vec<String>,
+    uploads: HashMap<String, Vec<u8>>,
+    next_id: usize,
+    page_size: usize,
+    upload_limit: Option<usize>,
+}
+
+impl Default for Drive {
+    fn default() -> Self {
+        Self {
+            files: BTreeMap::new(),
+            removed: HashMap::new(),
+            log: Vec::new(),
+            uploads: HashMap::new(),
+            next_id: 0,
+            page_size: 200,
+            upload_limit: None,
+        }
+    }
+}
+
+impl Api for Drive {
+    fn handle(&mut self, req: &Request, base: &str) -> Response {
+        let segments: Vec<&str> = req.path.trim_matches('/').split('/').collect();
+        match (req.method.as_str(), segments.as_slice()) {
+            ("GET", ["drive", "v3", "files"]) => self.list(req),
+            ("POST", ["drive", "v3", "files"]) => self.create(req),
+            ("GET", ["drive", "v3", "files", id]) => match req.query.get("alt") {
+                Some(alt) if alt == "media" => self.download(id),
+                _ => self.get(id),
+            },
+            ("PATCH", ["drive", "v3", "files", id]) => self.update(id, req),
+            ("DELETE", ["drive", "v3", "files", id]) => self.delete(id),
+            ("POST", ["drive", "v3", "files", id, "copy"]) => self.copy(id, req),
+            ("GET", ["drive", "v3", "changes", "startPageToken"]) =>
+                Response::json(200, &json!({"startPageToken": self.log.len().to_string()})),
+            ("GET", ["drive", "v3", "changes"]) => self.changes(req),
+            ("PATCH", ["upload", "drive", "v3", "files", id]) => match req.query.get("uploadType") {
+                Some(kind) if kind == "multipart" => self.upload_multipart(id, req),
+                Some(kind) if kind == "resumable" => self.create_session(id, base),
+                _ => Self::error(400, "unsupported upload type"),
+            },
+            ("PUT", ["upload", "drive", "v3", "files", id]) => self.upload_chunk(id, req),
+            _ => Self::error(400, &format!("unsupported route {} {}", req.method, req.path)),
+        }
+    }
+
+    fn error(status: u16, message: &str) -> Response {
+        Response::json(status, &json!({"error": {"code": status, "message": message}}))
+    }
+
+    fn requires_auth(&self, req: &Request) -> bool {
+        !req.query.contains_key("upload_id")
+    }
+}
+
+impl Drive {
+
+    fn is_folder(&self, id: &str) -> bool {
+        id == "root" || self.files.get(id).is_some_and(|file| file.mime_type == FOLDER_MIME_TYPE)
+    }
+
+    fn children(&self, parent_id: &str) -> Vec<String> {
+        self.files.iter()
+            .filter(|(_, file)| file.parent_id == parent_id)
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+
+    fn insert(&mut self, parent_id: &str, name: &str, mime_type: &str, content: Vec<u8>) -> String {
+        self.next_id += 1;
+        let id = format!("file-{:04}", self.next_id);
+        self.files.insert(id.clone(), Entry {
+            name: name.to_string(),
+            parent_id: parent_id.to_string(),
+            mime_type: mime_type.to_string(),
+            content,
+            version: 1,
+            created_at: now(),
+            modified_at: now(),
+        });
+
+        self.log.push(id.clone());
+        id
+    }
+
+    /// Marks a file as modified.
+    fn touch(&mut self, id: &str) {
+        if let Some(file) = self.files.get_mut(id) {
+            file.version += 1;
+            file.modified_at = now();
+            self.log.push(id.to_string());
+        }
+    }
+
+    /// Permanently deletes a file and its descendants.
+    fn remove(&mut self, id: &str) {
+        for child_id in self.children(id) {
+            self.remove(&child_id);
+        }
+
+        if self.files.remove(id).is_some() {
+            self.removed.insert(id.to_string(), now());
+            self.log.push(id.to_string());
+        }
+    }
+
+    fn to_json(&self, id: &str) -> Value {
+        let file = &self.files[id];
+        let mut json = json!({
+            "id": id,
+            "name": file.name,
+            "mimeType": file.mime_type,
+            "parents": [file.parent_id],
+            "createdTime": file.created_at,
+            "modifiedTime": file.modified_at,
+            "trashed": false,
+            "version": file.version.to_string(),
+        });
+
+        if file.mime_type != FOLDER_MIME_TYPE {
+            json["size"] = json!(file.content.len().to_string());
+            json["sha1Checksum"] = json!(format!("{:x}", Sha1::digest(&file.content)));
+            json["sha256Checksum"] = json!(format!("{:x}", Sha256::digest(&file.content)));
+        }
+
+        json
+    }
+
+    fn get(&self, id: &str) -> Response {
+        if id == "root" {
+            return Response::json(200, &json!({"id": "root", "name": "My Drive", "mimeType": FOLDER_MIME_TYPE}));
+        }
+
+        match self.files.get(id) {
+            Some(_) => Response::json(200, &self.to_json(id)),
+            None => Self::error(404, &format!("File not found: {id}.")),
+        }
+    }
+
+    /// Lists every file, or the children of a folder if the query is
+    /// `parents in '{id}'`.
+    fn list(&self, req: &Request) -> Response {
+        let ids = match req.query.get("q") {
+            Some(q) => match q.strip_prefix("parents in '").and_then(|q| q.strip_suffix('\'')) {
+                Some(parent_id) => self.children(parent_id),
+                None => return Self::error(400, &format!("unsupported query: {q}")),
+            },
+            None => self.files.keys().cloned().collect(),
+        };
+
+        let page_size = req.query.get("pageSize")
+            .and_then(|size| size.parse().ok())
+            .unwrap_or(self.page_size)
+            .min(self.page_size);
+
+        let offset = req.query.get("pageToken")
+            .and_then(|offset| offset.parse().ok())
+            .unwrap_or(0);
+
+        let files: Vec<Value> = ids.iter()
+            .skip(offset)
+            .take(page_size)
+            .map(|id| self.to_json(id))
+            .collect();
+
+        let mut json = json!({"kind": "drive#fileList", "files": files});
+        if offset + page_size < ids.len() {
+            json["nextPageToken"] = json!((offset + page_size).to_string());
+        }
+
+        Response::json(200, &json)
+    }
+
+    fn create(&mut self, req: &Request) -> Response {
+        let body = req.json();
+        let Some(name) = body["name"].as_str() else {
+            return Self::error(400, "missing name");
+        };
+
+        let parent_id = body["parents"][0].as_str().unwrap_or("root");
+        if !self.is_folder(parent_id) {
+            return Self::error(404, &format!("File not found: {parent_id}."));
+        }
+
+        let mime_type = body["mimeType"].as_str().unwrap_or("application/octet-stream");
+        let id = self.insert(parent_id, name, mime_type, Vec::new());
+        Response::json(200, &self.to_json(&id))
+    }
+
+    fn update(&mut self, id: &str, req: &Request) -> Response {
+        if !self.files.contains_key(id) {
+            return Self::error(404, &format!("File not found: {id}."));
+        }
+
+        if let Some(parent_id) = req.query.get("addParents") {
+            if !self.is_folder(parent_id) {
+                return Self::error(404, &format!("File not found: {parent_id}."));
+            }
+
+            // A folder can't be moved into itself or its descendants.
+            let mut ancestor_id = parent_id.clone();
+            while let Some(ancestor) = self.files.get(&ancestor_id) {
+                if ancestor_id == id {
+                    return Self::error(403, "cannot move a folder into itself");
+                }
+                ancestor_id = ancestor.parent_id.clone();
+            }
+
+            self.files.get_mut(id).unwrap().parent_id = parent_id.clone();
+        }
+
+        if let Some(name) = req.json()["name"].as_str() {
+            self.files.get_mut(id).unwrap().name = name.to_string();
+        }
+
+        self.touch(id);
+        Response::json(200, &self.to_json(id))
+    }
+
+    fn delete(&mut self, id: &str) -> Response {
+        if !self.files.contains_key(id) {
+            return Self::error(404, &format!("File not found: {id}."));
+        }
+
+        self.remove(id);
+        Response::empty(204)
+    }
+
+    fn copy(&mut self, id: &str, req: &Request) -> Response {
+        let Some(file) = self.files.get(id) else {
+            return Self::error(404, &format!("File not found: {id}."));
+        };
+
+        if file.mime_type == FOLDER_MIME_TYPE {
+            return Self::error(403, "folders cannot be copied");
+        }
+
+        let body = req.json();
+        let name = body["name"].as_str()
+            .map(|name| name.to_string())
+            .unwrap_or(format!("Copy of {}", file.name));
+
+        let parent_id = body["parents"][0].as_str()
+            .unwrap_or(&file.parent_id)
+            .to_string();
+
+        if !self.is_folder(&parent_id) {
+            return Self::error(404, &format!("File not found: {parent_id}."));
+        }
+
+        let (mime_type, content) = (file.mime_type.clone(), file.content.clone());
+        let copy_id = self.insert(&parent_id, &name, &mime_type, content);
+        Response::json(200, &self.to_json(&copy_id))
+    }
+
+    fn download(&self, id: &str) -> Response {
+        match self.files.get(id) {
+            Some(file) if file.mime_type != FOLDER_MIME_TYPE => Response::bytes(200, file.content.clone()),
+            Some(_) => Self::error(403, "folders have no content"),
+            None => Self::error(404, &format!("File not found: {id}.")),
+        }
+    }
+
+    fn write(&mut self, id: &str, content: Vec<u8>) -> Response {
+        match self.files.get_mut(id) {
+            Some(file) if file.mime_type != FOLDER_MIME_TYPE => file.content = content,
+            Some(_) => return Self::error(403, "folders have no content"),
+            None => return Self::error(404, &format!("File not found: {id}.")),
+        }
+
+        self.touch(id);
+        Response::json(200, &self.to_json(id))
+    }
+
+    /// Handles a `multipart/related` upload, made up of a metadata
+    /// part followed by a content part.
+    fn upload_multipart(&mut self, id: &str, req: &Request) -> Response {
+        let Some(boundary) = req.header("content-type")
+            .and_then(|content_type| content_type.split_once("boundary="))
+            .map(|(_, boundary)| format!("--{boundary}")) else {
+            return Self::error(400, "missing multipart boundary");
+        };
+
+        let parts = split(&req.body, boundary.as_bytes());
+        let (Some(metadata), Some(content)) = (parts.get(1), parts.get(2)) else {
+            return Self::error(400, "malformed multipart body");
+        };
+
+        let metadata: Value = serde_json::from_slice(part_body(metadata)).unwrap_or(Value::Null);
+        if let (Some(file), Some(name)) = (self.files.get_mut(id), metadata["name"].as_str()) {
+            file.name = name.to_string();
+        }
+
+        self.write(id, part_body(content).to_vec())
+    }
+
+    fn create_session(&mut self, id: &str, base: &str) -> Response {
+        if !self.files.contains_key(id) {
+            return Self::error(404, &format!("File not found: {id}."));
+        }
+
+        self.uploads.insert(id.to_string(), Vec::new());
+        Response::empty(200).header("Location",
+            &format!("{base}/upload/drive/v3/files/{id}?uploadType=resumable&upload_id={id}"))
+    }
+
+    fn upload_chunk(&mut self, id: &str, req: &Request) -> Response {
+        let Some(received) = self.uploads.get_mut(id) else {
+            return Self::error(404, "upload session not found");
+        };
+
+        // Queries the status of the upload.
+        if req.header("content-range").is_some_and(|range| range.starts_with("bytes */")) {
+            return match received.len() {
+                0 => Response::empty(308),
+                len => Response::empty(308).header("Range", &format!("bytes=0-{}", len - 1)),
+            };
+        }
+
+        let Some((start, end, total)) = req.content_range() else {
+            return Self::error(400, "missing Content-Range header");
+        };
+
+        if start != received.len() || end + 1 - start != req.body.len() || end >= total {
+            return Self::error(400, "unexpected Content-Range");
+        }
+
+        if self.upload_limit.is_some_and(|limit| end >= limit) {
+            return Self::error(500, "connection dropped");
+        }
+
+        received.extend_from_slice(&req.body);
+        if received.len() < total {
+            let range = format!("bytes=0-{}", received.len() - 1);
+            return Response::empty(308).header("Range", &range);
+        }
+
+        let content = self.uploads.remove(id).unwrap_or_default();
+        self.write(id, content)
+    }
+
+    /// Lists the files that changed after the position in the change
+    /// log given by the page token.
+    fn changes(&self, req: &Request) -> Response {
+        let Some((since, offset)) = req.query.get("pageToken")
+            .and_then(|token| parse_delta_token(token))
+            .filter(|(since, _)| *since <= self.log.len()) else {
+            return Self::error(400, "invalid page token");
+        };
+
+        let page_size = req.query.get("pageSize")
+            .and_then(|size| size.parse().ok())
+            .unwrap_or(self.page_size)
+            .min(self.page_size);
+
+        let ids = changed_since(&self.log, since);
+        let changes: Vec<Value> = ids.iter()
+            .skip(offset)
+            .take(page_size)
+            .map(|id| match self.files.get(id) {
+                Some(file) => json!({
+                    "kind": "drive#change",
+                    "removed": false,
+                    "fileId": id,
+                    "time": file.modified_at,
+                    "file": self.to_json(id),
+                }),
+                None => json!({
+                    "kind": "drive#change",
+                    "removed": true,
+                    "fileId": id,
+                    "time": self.removed.get(id).cloned().unwrap_or_else(now),
+                }),
+            })
+            .collect();
+
+        let mut json = json!({"kind": "drive#changeList", "changes": changes});
+        if offset + page_size < ids.len() {
+            json["nextPageToken"] = json!(format!("{since}.{}", offset + page_size));
+        } else {
+            json["newStartPageToken"] = json!(self.log.len().to_string());
+        }
+
+        Response::json(200, &json)
+    }
+}
+
+/// Splits `buf` on every occurrence of `delimiter`.
+fn split<'a>(buf: &'a [u8], delimiter: &[u8]) -> Vec<&'a [u8]> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+    while i + delimiter.len() <= buf.len() {
+        if &buf[i..i + delimiter.len()] == delimiter {
+            parts.push(&buf[start..i]);
+            i += delimiter.len();
+            start = i;
+        } else {
+            i += 1;
+        }
+    }
+
+    parts.push(&buf[start..]);
+    parts
+}
+
+/// Strips the headers and trailing line break of a multipart body
+/// part.
+fn part_body(part: &[u8]) -> &[u8] {
+    let body = part.windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .map(|i| &part[i + 4..])
+        .unwrap_or_default();
+
+    body.strip_suffix(b"\r\n").unwrap_or(body)
+}
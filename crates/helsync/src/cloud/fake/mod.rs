@@ -0,0 +1,416 @@
+//! Fake cloud API servers for testing the cloud clients without
+//! network access.
+//!
+//! Each fake emulates the subset of its provider's REST API used by
+//! the clients (items, children, uploads, deltas, errors), along with
+//! an OAuth2 token endpoint. State is kept in memory and is discarded
+//! when the server is dropped.
+mod dropbox;
+pub(crate) use dropbox::*;
+
+mod googledrive;
+pub(crate) use googledrive::*;
+
+mod onedrive;
+pub(crate) use onedrive::*;
+
+mod s3;
+pub(crate) use s3::*;
+
+mod webdav;
+pub(crate) use webdav::*;
+
+use crate::core::{UploadSession, UploadStore};
+use crate::oauth2::{StoreFuture, Token};
+
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufStream};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+/// A parsed HTTP request.
+pub(crate) struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: HashMap<String, String>,
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+
+    /// Gets the value of the header with the (lowercase) `name`.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(|value| value.as_str())
+    }
+
+    /// Parses the request body as JSON, or `Null` if it isn't JSON.
+    pub fn json(&self) -> Value {
+        serde_json::from_slice(&self.body).unwrap_or(Value::Null)
+    }
+
+    /// Parses a `Content-Range: bytes {start}-{end}/{total}` header.
+    pub fn content_range(&self) -> Option<(usize, usize, usize)> {
+        let range = self.header("content-range")?.strip_prefix("bytes ")?;
+        let (range, total) = range.split_once('/')?;
+        let (start, end) = range.split_once('-')?;
+        Some((start.parse().ok()?, end.parse().ok()?, total.parse().ok()?))
+    }
+}
+
+/// An HTTP response.
+pub(crate) struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+
+    /// A response without a body.
+    pub fn empty(status: u16) -> Self {
+        Self { status, headers: Vec::new(), body: Vec::new() }
+    }
+
+    /// A response with a JSON body.
+    pub fn json(status: u16, value: &Value) -> Self {
+        Self::empty(status)
+            .header("Content-Type", "application/json")
+            .body(value.to_string().into_bytes())
+    }
+
+    /// A response with a binary body.
+    pub fn bytes(status: u16, body: Vec<u8>) -> Self {
+        Self::empty(status)
+            .header("Content-Type", "application/octet-stream")
+            .body(body)
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    fn body(mut self, body: Vec<u8>) -> Self {
+        self.body = body;
+        self
+    }
+}
+
+/// The emulated REST API of a cloud provider.
+pub(crate) trait Api: Send + 'static {
+
+    /// Handles an authorized API request. `base` is the server's
+    /// URL, for building links that point back to the server.
+    fn handle(&mut self, req: &Request, base: &str) -> Response;
+
+    /// Formats an error response in the provider's error schema.
+    fn error(status: u16, message: &str) -> Response;
+
+    /// Whether a request must carry a valid access token. Upload
+    /// sessions and monitor URLs are pre-authenticated.
+    fn requires_auth(&self, _req: &Request) -> bool {
+        true
+    }
+}
+
+/// State shared by every fake: the issued tokens, injected
+/// failures and request counter.
+struct Common {
+    access_token: String,
+    refresh_token: String,
+    refreshes: usize,
+    failures: VecDeque<(u16, Option<u64>)>,
+    requests: usize,
+}
+
+impl Common {
+
+    /// Handles requests that don't reach the [Api]: token refreshes,
+    /// injected failures and unauthorized requests.
+    fn intercept<A: Api>(&mut self, api: &A, req: &Request) -> Option<Response> {
+        self.requests += 1;
+        if req.path == "/token" {
+            return Some(self.refresh(req));
+        }
+
+        if let Some((status, retry_after)) = self.failures.pop_front() {
+            let res = A::error(status, "injected failure");
+            return Some(match retry_after {
+                Some(secs) => res.header("Retry-After", &secs.to_string()),
+                None => res,
+            });
+        }
+
+        let bearer = format!("Bearer {}", self.access_token);
+        if api.requires_auth(req) && req.header("authorization") != Some(bearer.as_str()) {
+            return Some(A::error(401, "invalid or expired access token"));
+        }
+
+        None
+    }
+
+    /// Emulates the OAuth2 refresh token grant.
+    fn refresh(&mut self, req: &Request) -> Response {
+        let params: HashMap<String, String> = form_urlencoded::parse(&req.body)
+            .into_owned()
+            .collect();
+
+        let is_valid = params.get("grant_type").map(|s| s.as_str()) == Some("refresh_token") &&
+            params.get("refresh_token") == Some(&self.refresh_token);
+
+        if req.method != "POST" || !is_valid {
+            return Response::json(400, &serde_json::json!({
+                "error": "invalid_grant",
+                "error_description": "invalid refresh token",
+            }));
+        }
+
+        self.refreshes += 1;
+        self.access_token = format!("access-token-{}", self.refreshes);
+        Response::json(200, &serde_json::json!({
+            "token_type": "Bearer",
+            "access_token": self.access_token,
+            "expires_in": 3600,
+            "refresh_token": self.refresh_token,
+        }))
+    }
+}
+
+/// A fake cloud provider listening on a local port.
+///
+/// The server runs on the current tokio runtime and stops when the
+/// fake is dropped.
+pub(crate) struct FakeServer<A: Api> {
+    url: String,
+    state: Arc<Mutex<(Common, A)>>,
+    task: JoinHandle<()>,
+}
+
+impl<A: Api> FakeServer<A> {
+
+    /// Starts serving `api` on a random local port.
+    pub async fn start(api: A) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let common = Common {
+            access_token: "access-token-0".to_string(),
+            refresh_token: "refresh-token".to_string(),
+            refreshes: 0,
+            failures: VecDeque::new(),
+            requests: 0,
+        };
+
+        let state = Arc::new(Mutex::new((common, api)));
+        let task = tokio::spawn(serve(listener, url.clone(), state.clone()));
+        Self { url, state, task }
+    }
+
+    /// The server's base URL.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// The server's OAuth2 token endpoint.
+    pub fn token_endpoint(&self) -> String {
+        format!("{}/token", self.url)
+    }
+
+    /// A [Token] holding the currently valid access token.
+    pub fn token(&self) -> Token {
+        let state = self.state.lock().unwrap();
+        Token {
+            access_token: state.0.access_token.clone(),
+            refresh_token: state.0.refresh_token.clone(),
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs() as i64,
+            expires_in: 3600,
+        }
+    }
+
+    /// Fails the next `count` requests with `status`.
+    pub fn fail_next(&self, status: u16, count: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.0.failures.extend(std::iter::repeat((status, None)).take(count));
+    }
+
+    /// Throttles the next `count` requests with 429 responses that
+    /// ask to retry after `retry_after` seconds.
+    pub fn throttle_next(&self, count: usize, retry_after: u64) {
+        let mut state = self.state.lock().unwrap();
+        state.0.failures.extend(std::iter::repeat((429, Some(retry_after))).take(count));
+    }
+
+    /// Revokes the current access token, so that requests fail
+    /// until the token is refreshed.
+    pub fn revoke_token(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.0.access_token = "revoked".to_string();
+    }
+
+    /// The number of requests received so far.
+    pub fn requests(&self) -> usize {
+        self.state.lock().unwrap().0.requests
+    }
+
+    /// Runs `f` on the emulated API's state.
+    pub fn with_api<T>(&self, f: impl FnOnce(&mut A) -> T) -> T {
+        f(&mut self.state.lock().unwrap().1)
+    }
+}
+
+impl<A: Api> Drop for FakeServer<A> {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn serve<A: Api>(listener: TcpListener, url: String, state: Arc<Mutex<(Common, A)>>) {
+    while let Ok((stream, _)) = listener.accept().await {
+        tokio::spawn(serve_connection(stream, url.clone(), state.clone()));
+    }
+}
+
+/// Serves the requests of a keep-alive connection until the client
+/// closes it.
+async fn serve_connection<A: Api>(stream: TcpStream, url: String, state: Arc<Mutex<(Common, A)>>) {
+    let mut stream = BufStream::new(stream);
+    while let Ok(Some(req)) = read_request(&mut stream).await {
+        let res = {
+            let mut state = state.lock().unwrap();
+            let (common, api) = &mut *state;
+            match common.intercept(api, &req) {
+                Some(res) => res,
+                None => api.handle(&req, &url),
+            }
+        };
+
+        if write_response(&mut stream, res).await.is_err() {
+            return;
+        }
+    }
+}
+
+async fn read_request(stream: &mut BufStream<TcpStream>) -> std::io::Result<Option<Request>> {
+    let mut line = String::new();
+    if stream.read_line(&mut line).await? == 0 {
+        return Ok(None);
+    }
+
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let target = parts.next().unwrap_or_default();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let path = path.to_string();
+    let query = form_urlencoded::parse(query.as_bytes())
+        .into_owned()
+        .collect();
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        if stream.read_line(&mut line).await? == 0 {
+            return Ok(None);
+        }
+
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+        }
+    }
+
+    let length = headers.get("content-length")
+        .and_then(|length| length.parse().ok())
+        .unwrap_or(0);
+
+    let mut body = vec![0; length];
+    stream.read_exact(&mut body).await?;
+    Ok(Some(Request { method, path, query, headers, body }))
+}
+
+async fn write_response(stream: &mut BufStream<TcpStream>, res: Response) -> std::io::Result<()> {
+    // Responses to HEAD requests set the length of the omitted body.
+    let mut head = format!("HTTP/1.1 {} Fake\r\n", res.status);
+    if !res.headers.iter().any(|(name, _)| name.eq_ignore_ascii_case("content-length")) {
+        head.push_str(&format!("Content-Length: {}\r\n", res.body.len()));
+    }
+
+    for (name, value) in &res.headers {
+        head.push_str(&format!("{name}: {value}\r\n"));
+    }
+
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(&res.body).await?;
+    stream.flush().await
+}
+
+/// Formats the current time as an RFC 3339 timestamp.
+pub(crate) fn now() -> String {
+    chrono::Utc::now().to_rfc3339()
+}
+
+/// Parses a `{since}.{offset}` delta page token. A bare `{since}`
+/// token points to the first page.
+pub(crate) fn parse_delta_token(token: &str) -> Option<(usize, usize)> {
+    match token.split_once('.') {
+        Some((since, offset)) => Some((since.parse().ok()?, offset.parse().ok()?)),
+        None => Some((token.parse().ok()?, 0)),
+    }
+}
+
+/// Lists the IDs that changed after position `since` of a change
+/// log, keeping only the last change of each ID.
+pub(crate) fn changed_since(log: &[String], since: usize) -> Vec<String> {
+    let mut ids: Vec<String> = Vec::new();
+    for id in log.iter().skip(since) {
+        ids.retain(|other| other != id);
+        ids.push(id.clone());
+    }
+
+    ids
+}
+
+/// An [UploadStore] that keeps sessions in memory.
+#[derive(Default)]
+pub(crate) struct FakeUploadStore {
+    sessions: Mutex<HashMap<String, UploadSession>>,
+}
+
+impl FakeUploadStore {
+
+    /// Gets the stored session of the upload to `file_id`.
+    pub fn session(&self, file_id: &str) -> Option<UploadSession> {
+        self.sessions.lock().unwrap().get(file_id).cloned()
+    }
+}
+
+impl UploadStore for FakeUploadStore {
+    fn load<'a>(&'a self, file_id: &'a str) -> StoreFuture<'a, Option<UploadSession>> {
+        Box::pin(async move { Ok(self.session(file_id)) })
+    }
+
+    fn save<'a>(&'a self, session: &'a UploadSession) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            self.sessions.lock().unwrap()
+                .insert(session.file_id.clone(), session.clone());
+            Ok(())
+        })
+    }
+
+    fn remove<'a>(&'a self, file_id: &'a str) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            self.sessions.lock().unwrap().remove(file_id);
+            Ok(())
+        })
+    }
+}
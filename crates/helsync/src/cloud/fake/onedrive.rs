@@ -0,0 +1,499 @@
+use super::{Api, FakeServer, Request, Response, now, parse_delta_token, changed_since};
+use crate::cloud::onedrive::Client;
+use crate::oauth2::Config;
+
+use serde_json::{json, Value};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+
+/// A fake Microsoft Graph server hosting a single OneDrive.
+pub(crate) type FakeOneDrive = FakeServer<Graph>;
+
+impl FakeOneDrive {
+
+    /// Starts a fake server hosting an empty drive.
+    pub async fn new() -> Self {
+        Self::start(Graph::default()).await
+    }
+
+    /// An app [Config] that refreshes tokens against the fake.
+    pub fn config(&self) -> Config {
+        let mut config = Config::onedrive("client-id", "http://localhost:6969");
+        config.token_endpoint = self.token_endpoint();
+        config
+    }
+
+    /// A client authorized to use the fake.
+    pub fn client(&self) -> Client {
+        Client::new(&self.token(), &self.config())
+            .with_api_endpoint(&format!("{}/drive", self.url()))
+    }
+
+    /// Limits the number of items returned per page of a listing or
+    /// delta.
+    pub fn set_page_size(&self, page_size: usize) {
+        self.with_api(|api| api.page_size = page_size);
+    }
+
+    /// Makes upload sessions fail once they have received `limit`
+    /// bytes, as if the connection dropped.
+    pub fn set_upload_limit(&self, limit: Option<usize>) {
+        self.with_api(|api| api.upload_limit = limit);
+    }
+}
+
+struct Item {
+    name: String,
+    parent_id: String,
+    is_folder: bool,
+    is_deleted: bool,
+    content: Vec<u8>,
+    created_at: String,
+    modified_at: String,
+}
+
+/// The emulated state of a OneDrive. The root folder has the ID
+/// "root".
+pub(crate) struct Graph {
+    items: BTreeMap<String, Item>,
+    log: Vec<String>,
+    uploads: HashMap<String, Vec<u8>>,
+    polls: HashMap<String, usize>,
+    next_id: usize,
+    page_size: usize,
+    upload_limit: Option<usize>,
+}
+
+impl Default for Graph {
+    fn default() -> Self {
+        Self {
+            items: BTreeMap::new(),
+            log: Vec::new(),
+            uploads: HashMap::new(),
+            polls: HashMap::new(),
+            next_id: 0,
+            page_size: 200,
+            upload_limit: None,
+        }
+    }
+}
+
+impl Api for Graph {
+    fn handle(&mut self, req: &Request, base: &str) -> Response {
+        let segments: Vec<&str> = req.path.trim_matches('/').split('/').collect();
+        match (req.method.as_str(), segments.as_slice()) {
+            ("GET", ["monitor", id]) => self.monitor(id),
+            ("GET", ["upload", id]) => self.upload_status(id),
+            ("PUT", ["upload", id]) => self.upload_chunk(id, req),
+            ("GET", ["drive", "root"]) => Response::json(200, &json!({"id": "root", "name": "root", "root": {}, "folder": {"childCount": self.children("root").len()}})),
+            ("GET", ["drive", "root", "delta"]) => self.delta(req, base),
+            ("GET", ["drive", "root", "children"]) => self.list_children("root", req, base),
+            ("POST", ["drive", "root", "children"]) => self.create("root", req),
+            ("GET", ["drive", "items", id]) => self.get(id),
+            ("PATCH", ["drive", "items", id]) => self.update(id, req),
+            ("DELETE", ["drive", "items", id]) => self.delete(id),
+            ("GET", ["drive", "items", id, "children"]) => self.list_children(id, req, base),
+            ("POST", ["drive", "items", id, "children"]) => self.create(id, req),
+            ("POST", ["drive", "items", id, "copy"]) => self.copy(id, req, base),
+            ("GET", ["drive", "items", id, "content"]) => self.download(id),
+            ("PUT", ["drive", "items", id, "content"]) => self.upload(id, req.body.clone()),
+            ("POST", ["drive", "items", id, "createUploadSession"]) => self.create_session(id, base),
+            _ => Self::error(400, &format!("unsupported route {} {}", req.method, req.path)),
+        }
+    }
+
+    fn error(status: u16, message: &str) -> Response {
+        let code = match status {
+            400 => "invalidRequest",
+            401 => "unauthenticated",
+            404 => "itemNotFound",
+            409 => "nameAlreadyExists",
+            410 => "resyncRequired",
+            416 => "invalidRange",
+            429 => "activityLimitReached",
+            503 => "serviceNotAvailable",
+            _ => "generalException",
+        };
+
+        Response::json(status, &json!({"error": {"code": code, "message": message}}))
+    }
+
+    fn requires_auth(&self, req: &Request) -> bool {
+        !req.path.starts_with("/monitor/") && !req.path.starts_with("/upload/")
+    }
+}
+
+impl Graph {
+
+    /// Looks up an item that hasn't been deleted.
+    fn find(&self, id: &str) -> Option<&Item> {
+        self.items.get(id).filter(|item| !item.is_deleted)
+    }
+
+    fn is_folder(&self, id: &str) -> bool {
+        id == "root" || self.find(id).is_some_and(|item| item.is_folder)
+    }
+
+    /// Finds the ID of the item called `name` in a folder.
+    fn find_child(&self, parent_id: &str, name: &str) -> Option<String> {
+        self.items.iter()
+            .find(|(_, item)| !item.is_deleted && item.parent_id == parent_id && item.name == name)
+            .map(|(id, _)| id.clone())
+    }
+
+    fn children(&self, parent_id: &str) -> Vec<String> {
+        self.items.iter()
+            .filter(|(_, item)| !item.is_deleted && item.parent_id == parent_id)
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+
+    fn insert(&mut self, parent_id: &str, name: &str, is_folder: bool, content: Vec<u8>) -> String {
+        self.next_id += 1;
+        let id = format!("item-{:04}", self.next_id);
+        self.items.insert(id.clone(), Item {
+            name: name.to_string(),
+            parent_id: parent_id.to_string(),
+            is_folder,
+            is_deleted: false,
+            content,
+            created_at: now(),
+            modified_at: now(),
+        });
+
+        self.log.push(id.clone());
+        id
+    }
+
+    /// Deletes an item and its descendants.
+    fn remove(&mut self, id: &str) {
+        for child_id in self.children(id) {
+            self.remove(&child_id);
+        }
+
+        if let Some(item) = self.items.get_mut(id) {
+            item.is_deleted = true;
+            item.modified_at = now();
+            self.log.push(id.to_string());
+        }
+    }
+
+    /// Copies an item and its descendants, returning the ID of the
+    /// copy.
+    fn duplicate(&mut self, id: &str, parent_id: &str, name: &str) -> String {
+        let (is_folder, content) = match self.find(id) {
+            Some(item) => (item.is_folder, item.content.clone()),
+            None => return String::new(),
+        };
+
+        let copy_id = self.insert(parent_id, name, is_folder, content);
+        for child_id in self.children(id) {
+            let child_name = self.items[&child_id].name.clone();
+            self.duplicate(&child_id, &copy_id, &child_name);
+        }
+
+        copy_id
+    }
+
+    fn to_json(&self, id: &str) -> Value {
+        let item = &self.items[id];
+        let parent_name = match self.items.get(&item.parent_id) {
+            Some(parent) => parent.name.as_str(),
+            None => "root",
+        };
+
+        let mut json = json!({
+            "id": id,
+            "name": item.name,
+            "createdDateTime": item.created_at,
+            "lastModifiedDateTime": item.modified_at,
+            "size": item.content.len(),
+            "parentReference": {
+                "driveId": "fake-drive",
+                "driveType": "personal",
+                "id": item.parent_id,
+                "name": parent_name,
+            },
+        });
+
+        if item.is_folder {
+            json["folder"] = json!({"childCount": self.children(id).len()});
+        } else {
+            json["file"] = json!({
+                "mimeType": "application/octet-stream",
+                "hashes": {
+                    "sha1Hash": format!("{:X}", Sha1::digest(&item.content)),
+                    "sha256Hash": format!("{:X}", Sha256::digest(&item.content)),
+                },
+            });
+        }
+
+        if item.is_deleted {
+            json["deleted"] = json!({"state": "deleted"});
+        }
+
+        json
+    }
+
+    fn get(&self, id: &str) -> Response {
+        match self.find(id) {
+            Some(_) => Response::json(200, &self.to_json(id)),
+            None => Self::error(404, "item not found"),
+        }
+    }
+
+    fn list_children(&self, parent_id: &str, req: &Request, base: &str) -> Response {
+        if !self.is_folder(parent_id) {
+            return Self::error(404, "item not found");
+        }
+
+        let top = req.query.get("$top")
+            .and_then(|top| top.parse().ok())
+            .unwrap_or(self.page_size)
+            .min(self.page_size);
+
+        let offset = req.query.get("$skiptoken")
+            .and_then(|offset| offset.parse().ok())
+            .unwrap_or(0);
+
+        let children = self.children(parent_id);
+        let page: Vec<Value> = children.iter()
+            .skip(offset)
+            .take(top)
+            .map(|id| self.to_json(id))
+            .collect();
+
+        let mut json = json!({"value": page});
+        if offset + top < children.len() {
+            json["@odata.nextLink"] = json!(format!("{base}{}?$top={top}&$skiptoken={}", req.path, offset + top));
+        }
+
+        Response::json(200, &json)
+    }
+
+    fn create(&mut self, parent_id: &str, req: &Request) -> Response {
+        if !self.is_folder(parent_id) {
+            return Self::error(404, "parent not found");
+        }
+
+        let body = req.json();
+        let Some(name) = body["name"].as_str() else {
+            return Self::error(400, "missing name");
+        };
+
+        if let Some(existing_id) = self.find_child(parent_id, name) {
+            if body["@microsoft.graph.conflictBehavior"] != "replace" {
+                return Self::error(409, "an item with the same name already exists");
+            }
+            self.remove(&existing_id);
+        }
+
+        let id = self.insert(parent_id, name, body.get("folder").is_some(), Vec::new());
+        Response::json(201, &self.to_json(&id))
+    }
+
+    fn update(&mut self, id: &str, req: &Request) -> Response {
+        if self.find(id).is_none() {
+            return Self::error(404, "item not found");
+        }
+
+        let body = req.json();
+        let parent_id = body["parentReference"]["id"].as_str()
+            .unwrap_or(&self.items[id].parent_id)
+            .to_string();
+
+        let name = body["name"].as_str()
+            .unwrap_or(&self.items[id].name)
+            .to_string();
+
+        if !self.is_folder(&parent_id) {
+            return Self::error(400, "parent is not a folder");
+        }
+
+        // A folder can't be moved into itself or its descendants.
+        let mut ancestor_id = parent_id.clone();
+        while let Some(ancestor) = self.items.get(&ancestor_id) {
+            if ancestor_id == id {
+                return Self::error(400, "cannot move an item into itself");
+            }
+            ancestor_id = ancestor.parent_id.clone();
+        }
+
+        if self.find_child(&parent_id, &name).is_some_and(|other_id| other_id != id) {
+            return Self::error(409, "an item with the same name already exists");
+        }
+
+        let item = self.items.get_mut(id).unwrap();
+        item.parent_id = parent_id;
+        item.name = name;
+        item.modified_at = now();
+        self.log.push(id.to_string());
+        Response::json(200, &self.to_json(id))
+    }
+
+    fn delete(&mut self, id: &str) -> Response {
+        if self.find(id).is_none() {
+            return Self::error(404, "item not found");
+        }
+
+        self.remove(id);
+        Response::empty(204)
+    }
+
+    /// Copies happen immediately, but are reported through a monitor
+    /// URL, like the real API.
+    fn copy(&mut self, id: &str, req: &Request, base: &str) -> Response {
+        let Some(item) = self.find(id) else {
+            return Self::error(404, "item not found");
+        };
+
+        let body = req.json();
+        let parent_id = body["parentReference"]["id"].as_str()
+            .unwrap_or(&item.parent_id)
+            .to_string();
+
+        let name = body["name"].as_str()
+            .unwrap_or(&item.name)
+            .to_string();
+
+        if !self.is_folder(&parent_id) {
+            return Self::error(400, "parent is not a folder");
+        }
+
+        if self.find_child(&parent_id, &name).is_some() {
+            return Self::error(409, "an item with the same name already exists");
+        }
+
+        let copy_id = self.duplicate(id, &parent_id, &name);
+        Response::empty(202)
+            .header("Location", &format!("{base}/monitor/{copy_id}"))
+    }
+
+    /// Reports a copy as in progress the first time it's polled, and
+    /// as completed afterwards.
+    fn monitor(&mut self, id: &str) -> Response {
+        let polls = self.polls.entry(id.to_string()).or_default();
+        *polls += 1;
+
+        let (status, percentage) = match *polls {
+            1 => ("inProgress", 50.0),
+            _ => ("completed", 100.0),
+        };
+
+        Response::json(200, &json!({
+            "percentageComplete": percentage,
+            "resourceId": id,
+            "status": status,
+        }))
+    }
+
+    fn download(&self, id: &str) -> Response {
+        match self.find(id) {
+            Some(item) if !item.is_folder => Response::bytes(200, item.content.clone()),
+            Some(_) => Self::error(400, "folders have no content"),
+            None => Self::error(404, "item not found"),
+        }
+    }
+
+    fn upload(&mut self, id: &str, content: Vec<u8>) -> Response {
+        match self.items.get_mut(id) {
+            Some(item) if !item.is_deleted && !item.is_folder => {
+                item.content = content;
+                item.modified_at = now();
+            },
+            Some(item) if !item.is_deleted => return Self::error(400, "folders have no content"),
+            _ => return Self::error(404, "item not found"),
+        }
+
+        self.log.push(id.to_string());
+        Response::json(200, &self.to_json(id))
+    }
+
+    fn create_session(&mut self, id: &str, base: &str) -> Response {
+        if self.find(id).is_none() {
+            return Self::error(404, "item not found");
+        }
+
+        self.uploads.insert(id.to_string(), Vec::new());
+        Response::json(200, &json!({
+            "uploadUrl": format!("{base}/upload/{id}"),
+            "expirationDateTime": now(),
+        }))
+    }
+
+    fn upload_status(&self, id: &str) -> Response {
+        match self.uploads.get(id) {
+            Some(received) => Response::json(200, &json!({
+                "expirationDateTime": now(),
+                "nextExpectedRanges": [format!("{}-", received.len())],
+            })),
+            None => Self::error(404, "upload session not found"),
+        }
+    }
+
+    fn upload_chunk(&mut self, id: &str, req: &Request) -> Response {
+        let Some(received) = self.uploads.get_mut(id) else {
+            return Self::error(404, "upload session not found");
+        };
+
+        let Some((start, end, total)) = req.content_range() else {
+            return Self::error(400, "missing Content-Range header");
+        };
+
+        if start != received.len() || end + 1 - start != req.body.len() || end >= total {
+            return Self::error(416, "unexpected Content-Range");
+        }
+
+        if self.upload_limit.is_some_and(|limit| end >= limit) {
+            return Self::error(500, "connection dropped");
+        }
+
+        received.extend_from_slice(&req.body);
+        if received.len() < total {
+            return Response::json(202, &json!({
+                "expirationDateTime": now(),
+                "nextExpectedRanges": [format!("{}-", received.len())],
+            }));
+        }
+
+        let content = self.uploads.remove(id).unwrap_or_default();
+        let res = self.upload(id, content);
+        Response { status: 201, ..res }
+    }
+
+    /// Reports the items that changed after the position in the
+    /// change log given by the token. Without a token, every item in
+    /// the drive is enumerated.
+    fn delta(&self, req: &Request, base: &str) -> Response {
+        let (since, offset) = match req.query.get("token").map(|token| token.as_str()) {
+            Some("latest") => (self.log.len(), 0),
+            Some(token) => match parse_delta_token(token) {
+                Some(token) if token.0 <= self.log.len() => token,
+                _ => return Self::error(410, "invalid delta token"),
+            },
+            None => (0, 0),
+        };
+
+        let mut ids = changed_since(&self.log, since);
+        if since == 0 {
+            ids.retain(|id| !self.items[id].is_deleted);
+        }
+
+        let page: Vec<Value> = ids.iter()
+            .skip(offset)
+            .take(self.page_size)
+            .map(|id| self.to_json(id))
+            .collect();
+
+        let mut json = json!({"value": page});
+        if offset + self.page_size < ids.len() {
+            let token = format!("{since}.{}", offset + self.page_size);
+            json["@odata.nextLink"] = json!(format!("{base}/drive/root/delta?token={token}"));
+        } else {
+            json["@odata.deltaLink"] = json!(format!("{base}/drive/root/delta?token={}", self.log.len()));
+        }
+
+        Response::json(200, &json)
+    }
+}
@@ -0,0 +1,335 @@
+use super::{Api, FakeServer, Request, Response};
+use crate::cloud::s3::sigv4::{self, Credentials};
+use crate::cloud::s3::Client;
+
+use percent_encoding::percent_decode_str;
+use std::collections::{BTreeMap, HashMap};
+
+/// The name of the fake's only bucket.
+const BUCKET: &str = "helsync";
+
+/// The access key accepted by the fake.
+const ACCESS_KEY_ID: &str = "minioadmin";
+const SECRET_ACCESS_KEY: &str = "minioadmin-secret";
+
+/// Minimum size of every part of a multipart upload but the last.
+const MIN_PART_SIZE: usize = 5 * 1024 * 1024;
+
+/// A fake S3-compatible server (in the manner of MinIO) hosting a
+/// single bucket.
+pub(crate) type FakeS3 = FakeServer<S3>;
+
+impl FakeS3 {
+
+    /// Starts a fake S3 server with an empty bucket.
+    pub async fn new() -> Self {
+        Self::start(S3::default()).await
+    }
+
+    /// A client authorized to use the fake's bucket.
+    pub fn client(&self) -> Client {
+        Client::new(self.url(), BUCKET, ACCESS_KEY_ID, SECRET_ACCESS_KEY)
+    }
+
+    /// Sets the maximum number of keys returned per page of a
+    /// listing, regardless of what the client asks for.
+    pub fn set_page_size(&self, page_size: usize) {
+        self.with_api(|api| api.page_size = Some(page_size));
+    }
+
+    /// The keys of every object in the bucket.
+    pub fn keys(&self) -> Vec<String> {
+        self.with_api(|api| api.objects.keys().cloned().collect())
+    }
+
+    /// The number of multipart uploads that were started but neither
+    /// completed nor aborted.
+    pub fn pending_uploads(&self) -> usize {
+        self.with_api(|api| api.uploads.len())
+    }
+}
+
+struct Object {
+    content: Vec<u8>,
+    etag: String,
+    modified_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// The emulated state of an S3 bucket.
+#[derive(Default)]
+pub(crate) struct S3 {
+    objects: BTreeMap<String, Object>,
+    uploads: HashMap<String, BTreeMap<usize, Vec<u8>>>,
+    next_id: usize,
+    page_size: Option<usize>,
+}
+
+impl Api for S3 {
+    fn handle(&mut self, req: &Request, _base: &str) -> Response {
+        if let Err(res) = verify(req) {
+            return res;
+        }
+
+        let Some(key) = to_key(&req.path) else {
+            return Self::error(404, "no such bucket");
+        };
+
+        let query = |name: &str| req.query.get(name).map(|value| value.as_str());
+        match (req.method.as_str(), key.as_deref()) {
+            ("GET", None) if query("list-type") == Some("2") => self.list(req),
+            ("GET", Some(key)) => self.get(key),
+            ("HEAD", Some(key)) => self.head(key),
+            ("PUT", Some(_)) if query("uploadId").is_some() => self.upload_part(req),
+            ("PUT", Some(key)) if req.header("x-amz-copy-source").is_some() => self.copy(key, req),
+            ("PUT", Some(key)) => self.put(key, req.body.clone()),
+            ("POST", Some(_)) if query("uploads").is_some() => self.create_upload(),
+            ("POST", Some(key)) if query("uploadId").is_some() => self.complete_upload(key, req),
+            ("DELETE", Some(_)) if query("uploadId").is_some() => {
+                self.uploads.remove(query("uploadId").unwrap_or_default());
+                Response::empty(204)
+            },
+            ("DELETE", Some(key)) => {
+                self.objects.remove(key);
+                Response::empty(204)
+            },
+            _ => Self::error(405, "method not allowed"),
+        }
+    }
+
+    fn error(status: u16, message: &str) -> Response {
+        let code = match status {
+            400 => "InvalidRequest",
+            403 => "SignatureDoesNotMatch",
+            404 => "NoSuchKey",
+            405 => "MethodNotAllowed",
+            503 => "SlowDown",
+            _ => "InternalError",
+        };
+
+        Response::bytes(status, format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+            <Error><Code>{code}</Code><Message>{message}</Message></Error>").into_bytes())
+            .header("Content-Type", "application/xml")
+    }
+
+    /// Requests are signed with the access key, which the fake
+    /// verifies itself.
+    fn requires_auth(&self, _req: &Request) -> bool {
+        false
+    }
+}
+
+impl S3 {
+
+    fn next_etag(&mut self) -> String {
+        self.next_id += 1;
+        format!("\"{:032x}\"", self.next_id)
+    }
+
+    fn put(&mut self, key: &str, content: Vec<u8>) -> Response {
+        let etag = self.next_etag();
+        self.objects.insert(key.to_string(), Object {
+            content,
+            etag: etag.clone(),
+            modified_at: chrono::Utc::now(),
+        });
+
+        Response::empty(200).header("ETag", &etag)
+    }
+
+    fn get(&self, key: &str) -> Response {
+        match self.objects.get(key) {
+            Some(object) => Response::bytes(200, object.content.clone())
+                .header("ETag", &object.etag),
+            None => Self::error(404, "the specified key does not exist"),
+        }
+    }
+
+    /// Responds with the object's headers. The response to a HEAD
+    /// request has no body, not even for errors.
+    fn head(&self, key: &str) -> Response {
+        match self.objects.get(key) {
+            Some(object) => Response::empty(200)
+                .header("ETag", &object.etag)
+                .header("Content-Length", &object.content.len().to_string())
+                .header("Last-Modified", &object.modified_at.to_rfc2822()),
+            None => Response::empty(404),
+        }
+    }
+
+    fn copy(&mut self, key: &str, req: &Request) -> Response {
+        let source = req.header("x-amz-copy-source").and_then(to_key).flatten();
+        let Some(content) = source.and_then(|source| self.objects.get(&source))
+            .map(|object| object.content.clone()) else {
+            return Self::error(404, "the specified key does not exist");
+        };
+
+        self.put(key, content);
+        let object = &self.objects[key];
+        Response::bytes(200, format!("<CopyObjectResult><ETag>{}</ETag>\
+            <LastModified>{}</LastModified></CopyObjectResult>", escape(&object.etag),
+            object.modified_at.to_rfc3339()).into_bytes())
+    }
+
+    fn create_upload(&mut self) -> Response {
+        self.next_id += 1;
+        let upload_id = format!("upload-{}", self.next_id);
+        self.uploads.insert(upload_id.clone(), BTreeMap::new());
+        Response::bytes(200, format!("<InitiateMultipartUploadResult>\
+            <Bucket>{BUCKET}</Bucket><UploadId>{upload_id}</UploadId>\
+            </InitiateMultipartUploadResult>").into_bytes())
+    }
+
+    fn upload_part(&mut self, req: &Request) -> Response {
+        let number = req.query.get("partNumber").and_then(|number| number.parse().ok());
+        let upload_id = req.query.get("uploadId").cloned().unwrap_or_default();
+        let (Some(number), Some(parts)) = (number, self.uploads.get_mut(&upload_id)) else {
+            return Self::error(404, "the specified upload does not exist");
+        };
+
+        parts.insert(number, req.body.clone());
+        Response::empty(200).header("ETag", &format!("\"part-{number}\""))
+    }
+
+    fn complete_upload(&mut self, key: &str, req: &Request) -> Response {
+        let upload_id = req.query.get("uploadId").cloned().unwrap_or_default();
+        let Some(parts) = self.uploads.remove(&upload_id) else {
+            return Self::error(404, "the specified upload does not exist");
+        };
+
+        let body = String::from_utf8_lossy(&req.body);
+        let is_listed = parts.keys().all(|number| {
+            body.contains(&format!("<PartNumber>{number}</PartNumber><ETag>&quot;part-{number}&quot;</ETag>"))
+        });
+
+        let is_too_small = parts.values().rev().skip(1).any(|part| part.len() < MIN_PART_SIZE);
+        if !is_listed || is_too_small {
+            return Self::error(400, "invalid or too small parts");
+        }
+
+        self.put(key, parts.into_values().flatten().collect());
+        Response::bytes(200, format!("<CompleteMultipartUploadResult>\
+            <Key>{}</Key></CompleteMultipartUploadResult>", escape(key)).into_bytes())
+    }
+
+    /// Emulates `ListObjectsV2`, using the last listed key as the
+    /// continuation token.
+    fn list(&self, req: &Request) -> Response {
+        let prefix = req.query.get("prefix").cloned().unwrap_or_default();
+        let delimiter = req.query.get("delimiter").is_some_and(|delimiter| delimiter == "/");
+        let start = req.query.get("continuation-token");
+        let max_keys = req.query.get("max-keys")
+            .and_then(|max_keys| max_keys.parse().ok())
+            .unwrap_or(1000)
+            .min(self.page_size.unwrap_or(usize::MAX));
+
+        let mut contents = String::new();
+        let mut common_prefixes: Vec<String> = Vec::new();
+        let mut count = 0;
+        let mut last_key = None;
+        let mut is_truncated = false;
+        let keys = self.objects.iter()
+            .filter(|(key, _)| key.starts_with(&prefix))
+            .filter(|(key, _)| start.map_or(true, |start| *key > start));
+
+        for (key, object) in keys {
+            let common_prefix = match delimiter {
+                true => key[prefix.len()..].find('/')
+                    .map(|i| key[..prefix.len() + i + 1].to_string()),
+                false => None,
+            };
+
+            // Keys below a listed common prefix are skipped.
+            if common_prefix.is_some() && common_prefix.as_ref() == common_prefixes.last() {
+                last_key = Some(key.clone());
+                continue;
+            }
+
+            if count == max_keys {
+                is_truncated = true;
+                break;
+            }
+
+            count += 1;
+            last_key = Some(key.clone());
+            match common_prefix {
+                Some(common_prefix) => common_prefixes.push(common_prefix),
+                None => contents.push_str(&format!("<Contents><Key>{}</Key>\
+                    <LastModified>{}</LastModified><ETag>{}</ETag><Size>{}</Size>\
+                    </Contents>", escape(key), object.modified_at.to_rfc3339(),
+                    escape(&object.etag), object.content.len())),
+            }
+        }
+
+        let common_prefixes: String = common_prefixes.iter()
+            .map(|prefix| format!("<CommonPrefixes><Prefix>{}</Prefix></CommonPrefixes>", escape(prefix)))
+            .collect();
+
+        let next = match (is_truncated, last_key) {
+            (true, Some(key)) => format!("<NextContinuationToken>{}</NextContinuationToken>", escape(&key)),
+            _ => String::new(),
+        };
+
+        Response::bytes(200, format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+            <ListBucketResult xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\">\
+            <Name>{BUCKET}</Name><Prefix>{}</Prefix><KeyCount>{count}</KeyCount>\
+            <IsTruncated>{is_truncated}</IsTruncated>{next}{contents}{common_prefixes}\
+            </ListBucketResult>", escape(&prefix)).into_bytes())
+            .header("Content-Type", "application/xml")
+    }
+}
+
+/// Checks a request's Signature Version 4 `Authorization` header.
+fn verify(req: &Request) -> Result<(), Response> {
+    let invalid = || S3::error(403, "the request signature does not match");
+    let authorization = req.header("authorization").ok_or_else(invalid)?;
+    let signed_headers = authorization.split(", ")
+        .find_map(|part| part.strip_prefix("SignedHeaders="))
+        .ok_or_else(invalid)?;
+
+    let headers: BTreeMap<String, String> = signed_headers.split(';')
+        .map(|name| (name.to_string(), req.header(name).unwrap_or_default().to_string()))
+        .collect();
+
+    let payload_hash = req.header("x-amz-content-sha256").unwrap_or_default();
+    if payload_hash != sigv4::sha256_hex(&req.body) {
+        return Err(S3::error(400, "the payload hash does not match"));
+    }
+
+    let query: Vec<(&str, &str)> = req.query.iter()
+        .map(|(key, value)| (key.as_str(), value.as_str()))
+        .collect();
+
+    let credentials = Credentials {
+        access_key_id: ACCESS_KEY_ID.to_string(),
+        secret_access_key: SECRET_ACCESS_KEY.to_string(),
+        region: "us-east-1".to_string(),
+    };
+
+    let expected = sigv4::authorization(&credentials, &req.method, &req.path,
+        &sigv4::canonical_query(&query), &headers, payload_hash);
+
+    match authorization == expected {
+        true => Ok(()),
+        false => Err(invalid()),
+    }
+}
+
+/// Converts a request path to an object key, or `None` if the path
+/// addresses the bucket itself. Returns `None` (the outer option) if
+/// the path is outside of the bucket.
+fn to_key(path: &str) -> Option<Option<String>> {
+    let path = percent_decode_str(path).decode_utf8_lossy();
+    let rest = path.strip_prefix('/')?.strip_prefix(BUCKET)?;
+    match rest.strip_prefix('/') {
+        Some(key) if !key.is_empty() => Some(Some(key.to_string())),
+        _ if rest.is_empty() || rest == "/" => Some(None),
+        _ => None,
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,383 @@
+use super::{Api, FakeServer, Request, Response, changed_since};
+use crate::cloud::webdav::Client;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, NON_ALPHANUMERIC};
+use std::collections::BTreeMap;
+
+/// The path of the user's root collection on the fake server.
+const ROOT_PATH: &str = "/dav/files/alice";
+
+/// A fake WebDAV server hosting a single user's files.
+pub(crate) type FakeWebDav = FakeServer<WebDav>;
+
+impl FakeWebDav {
+
+    /// Starts a fake generic WebDAV server, which neither propagates
+    /// ETags to collections nor supports sync collection reports.
+    pub async fn new() -> Self {
+        Self::start(WebDav::default()).await
+    }
+
+    /// Starts a fake Nextcloud server, which reports file IDs,
+    /// propagates ETags to collections and supports sync collection
+    /// reports.
+    pub async fn nextcloud() -> Self {
+        Self::start(WebDav { is_nextcloud: true, ..WebDav::default() }).await
+    }
+
+    /// A client authorized to use the fake.
+    pub fn client(&self) -> Client {
+        Client::new(&format!("{}{ROOT_PATH}", self.url()), "alice", "secret")
+    }
+
+    /// The number of PROPFIND requests received so far.
+    pub fn propfinds(&self) -> usize {
+        self.with_api(|api| api.propfinds)
+    }
+}
+
+struct Node {
+    is_collection: bool,
+    content: Vec<u8>,
+    etag: usize,
+    file_id: usize,
+    modified_at: String,
+}
+
+/// The emulated state of a WebDAV server. Nodes are keyed by their
+/// path relative to [ROOT_PATH], and the root collection has the
+/// empty path.
+pub(crate) struct WebDav {
+    nodes: BTreeMap<String, Node>,
+    log: Vec<String>,
+    next_id: usize,
+    is_nextcloud: bool,
+    propfinds: usize,
+}
+
+impl Default for WebDav {
+    fn default() -> Self {
+        let mut nodes = BTreeMap::new();
+        nodes.insert(String::new(), Node {
+            is_collection: true,
+            content: Vec::new(),
+            etag: 0,
+            file_id: 0,
+            modified_at: chrono::Utc::now().to_rfc2822(),
+        });
+
+        Self {
+            nodes,
+            log: Vec::new(),
+            next_id: 0,
+            is_nextcloud: false,
+            propfinds: 0,
+        }
+    }
+}
+
+impl Api for WebDav {
+    fn handle(&mut self, req: &Request, _base: &str) -> Response {
+        let credentials = format!("Basic {}", STANDARD.encode("alice:secret"));
+        if req.header("authorization") != Some(credentials.as_str()) {
+            return Self::error(401, "invalid credentials");
+        }
+
+        let Some(path) = to_path(&req.path) else {
+            return Self::error(404, "outside of the user's files");
+        };
+
+        match req.method.as_str() {
+            "PROPFIND" => self.propfind(&path, req),
+            "REPORT" => self.report(&path, req),
+            "MKCOL" => self.mkcol(&path),
+            "PUT" => self.put(&path, req.body.clone()),
+            "GET" => self.get(&path),
+            "DELETE" => self.delete(&path),
+            "COPY" => self.relocate(&path, req, false),
+            "MOVE" => self.relocate(&path, req, true),
+            _ => Self::error(405, "method not allowed"),
+        }
+    }
+
+    fn error(status: u16, message: &str) -> Response {
+        Response::bytes(status, format!("<?xml version=\"1.0\"?>\
+            <d:error xmlns:d=\"DAV:\" xmlns:s=\"http://sabredav.org/ns\">\
+            <s:message>{message}</s:message></d:error>").into_bytes())
+            .header("Content-Type", "application/xml")
+    }
+
+    /// Requests are authenticated with basic authentication, which
+    /// [WebDav::handle] checks.
+    fn requires_auth(&self, _req: &Request) -> bool {
+        false
+    }
+}
+
+impl WebDav {
+
+    fn is_collection(&self, path: &str) -> bool {
+        self.nodes.get(path).is_some_and(|node| node.is_collection)
+    }
+
+    /// Whether the parent of `path` is an existing collection.
+    fn has_parent(&self, path: &str) -> bool {
+        self.is_collection(parent(path))
+    }
+
+    fn children(&self, path: &str) -> Vec<String> {
+        self.nodes.keys()
+            .filter(|other| !other.is_empty() && parent(other) == path)
+            .cloned()
+            .collect()
+    }
+
+    fn descendants(&self, path: &str) -> Vec<String> {
+        let prefix = format!("{path}/");
+        self.nodes.keys()
+            .filter(|other| (path.is_empty() && !other.is_empty()) || other.starts_with(&prefix))
+            .cloned()
+            .collect()
+    }
+
+    /// Records a change to `path`, updating its ETag and, on
+    /// Nextcloud, the ETags of its ancestors.
+    fn touch(&mut self, path: &str) {
+        self.next_id += 1;
+        self.log.push(path.to_string());
+        if let Some(node) = self.nodes.get_mut(path) {
+            node.etag = self.next_id;
+            node.modified_at = chrono::Utc::now().to_rfc2822();
+        }
+
+        let mut ancestor = path;
+        while self.is_nextcloud && !ancestor.is_empty() {
+            ancestor = parent(ancestor);
+            if let Some(node) = self.nodes.get_mut(ancestor) {
+                node.etag = self.next_id;
+            }
+        }
+    }
+
+    fn insert(&mut self, path: &str, is_collection: bool, content: Vec<u8>) {
+        self.next_id += 1;
+        self.nodes.insert(path.to_string(), Node {
+            is_collection,
+            content,
+            etag: 0,
+            file_id: self.next_id,
+            modified_at: String::new(),
+        });
+
+        self.touch(path);
+    }
+
+    fn to_xml(&self, path: &str) -> String {
+        let node = &self.nodes[path];
+        let mut href = ROOT_PATH.to_string();
+        for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+            href.push('/');
+            href.push_str(&utf8_percent_encode(segment, NON_ALPHANUMERIC).to_string());
+        }
+
+        if node.is_collection {
+            href.push('/');
+        }
+
+        let mut found = format!("<d:getlastmodified>{}</d:getlastmodified>", node.modified_at);
+        let mut missing = String::from("<d:creationdate/>");
+        match node.is_collection {
+            true => found.push_str("<d:resourcetype><d:collection/></d:resourcetype>"),
+            false => {
+                found.push_str("<d:resourcetype/>");
+                found.push_str(&format!("<d:getcontentlength>{}</d:getcontentlength>", node.content.len()));
+            }
+        }
+
+        match (self.is_nextcloud, node.is_collection) {
+            (false, true) => missing.push_str("<d:getetag/><oc:fileid/>"),
+            (false, false) => {
+                found.push_str(&format!("<d:getetag>\"{}\"</d:getetag>", node.etag));
+                missing.push_str("<oc:fileid/>");
+            }
+            (true, _) => {
+                found.push_str(&format!("<d:getetag>\"{}\"</d:getetag>", node.etag));
+                found.push_str(&format!("<oc:fileid>{}</oc:fileid>", node.file_id));
+            }
+        }
+
+        format!("<d:response><d:href>{href}</d:href>\
+            <d:propstat><d:prop>{found}</d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat>\
+            <d:propstat><d:prop>{missing}</d:prop><d:status>HTTP/1.1 404 Not Found</d:status></d:propstat>\
+            </d:response>")
+    }
+
+    fn multistatus(responses: &[String], sync_token: Option<String>) -> Response {
+        let sync_token = sync_token
+            .map(|token| format!("<d:sync-token>{token}</d:sync-token>"))
+            .unwrap_or_default();
+
+        let body = format!("<?xml version=\"1.0\"?>\
+            <d:multistatus xmlns:d=\"DAV:\" xmlns:oc=\"http://owncloud.org/ns\">{}{sync_token}</d:multistatus>",
+            responses.concat());
+
+        Response::bytes(207, body.into_bytes())
+            .header("Content-Type", "application/xml; charset=utf-8")
+    }
+
+    fn propfind(&mut self, path: &str, req: &Request) -> Response {
+        self.propfinds += 1;
+        if !self.nodes.contains_key(path) {
+            return Self::error(404, "not found");
+        }
+
+        let mut responses = vec![self.to_xml(path)];
+        if req.header("depth") == Some("1") {
+            responses.extend(self.children(path).iter().map(|child| self.to_xml(child)));
+        }
+
+        Self::multistatus(&responses, None)
+    }
+
+    fn report(&self, path: &str, req: &Request) -> Response {
+        if !self.is_nextcloud {
+            return Self::error(405, "method not allowed");
+        }
+
+        let body = String::from_utf8_lossy(&req.body);
+        let token = body.split_once("<d:sync-token>")
+            .and_then(|(_, rest)| rest.split_once("</d:sync-token>"))
+            .map(|(token, _)| token)
+            .unwrap_or_default();
+
+        let changed = match token {
+            "" => self.descendants(path),
+            token => match token.strip_prefix("http://fake/sync/").and_then(|n| n.parse().ok()) {
+                Some(since) => changed_since(&self.log, since),
+                None => return Self::error(403, "invalid sync token"),
+            },
+        };
+
+        let responses: Vec<String> = changed.iter()
+            .map(|changed| match self.nodes.contains_key(changed) {
+                true => self.to_xml(changed),
+                false => format!("<d:response><d:href>{ROOT_PATH}/{}</d:href>\
+                    <d:status>HTTP/1.1 404 Not Found</d:status></d:response>",
+                    utf8_percent_encode(changed, NON_ALPHANUMERIC)
+                        .to_string().replace("%2F", "/")),
+            })
+            .collect();
+
+        Self::multistatus(&responses, Some(format!("http://fake/sync/{}", self.log.len())))
+    }
+
+    fn mkcol(&mut self, path: &str) -> Response {
+        if self.nodes.contains_key(path) {
+            return Self::error(405, "the resource already exists");
+        }
+
+        if !self.has_parent(path) {
+            return Self::error(409, "parent collection does not exist");
+        }
+
+        self.insert(path, true, Vec::new());
+        Response::empty(201)
+    }
+
+    fn put(&mut self, path: &str, content: Vec<u8>) -> Response {
+        if self.is_collection(path) {
+            return Self::error(405, "cannot PUT to a collection");
+        }
+
+        if !self.has_parent(path) {
+            return Self::error(409, "parent collection does not exist");
+        }
+
+        if let Some(node) = self.nodes.get_mut(path) {
+            node.content = content;
+            self.touch(path);
+            return Response::empty(204);
+        }
+
+        self.insert(path, false, content);
+        Response::empty(201)
+    }
+
+    fn get(&self, path: &str) -> Response {
+        match self.nodes.get(path) {
+            Some(node) if !node.is_collection => Response::bytes(200, node.content.clone()),
+            Some(_) => Self::error(405, "cannot GET a collection"),
+            None => Self::error(404, "not found"),
+        }
+    }
+
+    fn delete(&mut self, path: &str) -> Response {
+        if path.is_empty() || !self.nodes.contains_key(path) {
+            return Self::error(404, "not found");
+        }
+
+        for descendant in self.descendants(path) {
+            self.nodes.remove(&descendant);
+            self.log.push(descendant);
+        }
+
+        self.nodes.remove(path);
+        self.touch(path);
+        Response::empty(204)
+    }
+
+    fn relocate(&mut self, path: &str, req: &Request, is_move: bool) -> Response {
+        let destination = req.header("destination")
+            .and_then(|destination| destination.split_once("://"))
+            .and_then(|(_, rest)| rest.find('/').map(|i| rest[i..].to_string()))
+            .and_then(|destination| to_path(&destination));
+
+        let Some(destination) = destination else {
+            return Self::error(400, "invalid destination");
+        };
+
+        if path.is_empty() || !self.nodes.contains_key(path) {
+            return Self::error(404, "not found");
+        }
+
+        if self.nodes.contains_key(&destination) {
+            return Self::error(412, "the destination already exists");
+        }
+
+        if !self.has_parent(&destination) {
+            return Self::error(409, "parent collection does not exist");
+        }
+
+        let mut paths = vec![path.to_string()];
+        paths.append(&mut self.descendants(path));
+        for source in paths {
+            let target = format!("{destination}{}", &source[path.len()..]);
+            let node = &self.nodes[&source];
+            let (is_collection, content) = (node.is_collection, node.content.clone());
+            if is_move {
+                let node = self.nodes.remove(&source).unwrap();
+                self.nodes.insert(target.clone(), node);
+                self.touch(&source);
+                self.touch(&target);
+            } else {
+                self.insert(&target, is_collection, content);
+            }
+        }
+
+        Response::empty(201)
+    }
+}
+
+/// Converts a request path to a node path, or `None` if the path is
+/// outside of [ROOT_PATH].
+fn to_path(path: &str) -> Option<String> {
+    let path = percent_decode_str(path).decode_utf8_lossy();
+    let path = path.strip_prefix(ROOT_PATH)?;
+    Some(path.trim_matches('/').to_string())
+}
+
+fn parent(path: &str) -> &str {
+    path.rsplit_once('/').map_or("", |(parent, _)| parent)
+}
@@ -59,6 +59,12 @@ impl Into<File> for DriveChange {
         let name = self.file.clone().map(|file| file.name)
             .unwrap_or("Untitled".to_string());
 
+        let sha1 = self.file.as_ref().and_then(|file| file.sha1_checksum.clone());
+        let sha256 = self.file.as_ref().and_then(|file| file.sha256_checksum.clone());
+        let size = self.file.as_ref()
+            .and_then(|file| file.size.as_ref())
+            .and_then(|size| size.parse().ok());
+
         File {
             id: self.file_id,
             name,
@@ -66,7 +72,11 @@ impl Into<File> for DriveChange {
             created_at,
             parent_id,
             is_folder,
-            is_deleted: self.removed,
+            is_deleted: self.removed || self.file.as_ref()
+                .is_some_and(|file| file.trashed.is_some_and(|b| b)),
+            sha1,
+            sha256,
+            size,
         }
     }
 }
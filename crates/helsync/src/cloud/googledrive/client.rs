@@ -1,21 +1,47 @@
-use crate::core::{FileSystem, Delta, Result, Error};
-use crate::oauth2::{Token, Config};
+use crate::core::{RetryPolicy, FileSystem, Delta, Page, Result, Error, ByteStream};
+use crate::core::{UploadSession, UploadStore, is_committed, load_session, read_exact_chunk, response_stream, skip_committed};
+use crate::oauth2::{Token, TokenStore, Config};
 use super::error::GoogleDriveError;
 use super::change::DriveChange;
 use super::file::DriveFile;
 
-use form_urlencoded::byte_serialize;
 use reqwest::header::AUTHORIZATION;
 use serde_json::{from_value, Value};
+use sha2::{Digest, Sha256};
 use std::sync::Arc;
+use tokio::io::AsyncRead;
 
 /// Google API endpoint for Google Drive.
 pub const API_ENDPOINT: &str = "https://www.googleapis.com/drive/v3";
 
+/// Google API endpoint for uploading Google Drive file content.
+pub const UPLOAD_ENDPOINT: &str = "https://www.googleapis.com/upload/drive/v3";
+
+/// The [DriveFile] fields requested from the API. Checksums are
+/// omitted from responses unless requested.
+const FILE_FIELDS: &str = "id,name,parents,mimeType,size,createdTime,modifiedTime,trashed,version,sha1Checksum,sha256Checksum";
+
+/// The fields requested when listing changes.
+const CHANGE_FIELDS: &str = "nextPageToken,newStartPageToken,changes(kind,removed,fileId,time,driveId,file(id,name,parents,mimeType,size,createdTime,modifiedTime,trashed,version,sha1Checksum,sha256Checksum))";
+
+/// Maximum size of files uploaded in a single request. Larger
+/// files are uploaded in chunks using a resumable upload.
+const SMALL_FILE_LIMIT: usize = 5 * 1024 * 1024;
+
+/// Size of the chunks sent to a resumable upload, which must be a
+/// multiple of 256 KiB.
+const CHUNK_SIZE: u64 = 256 * 1024;
+
+/// Maximum number of files requested per page of a listing.
+const PAGE_SIZE: &str = "1000";
+
 /// Implements a Google Drive API client.
 pub struct Client {
     client: crate::core::Client,
     req: Arc<reqwest::Client>,
+    api_endpoint: String,
+    upload_endpoint: String,
+    upload_store: Option<Arc<dyn UploadStore>>,
 }
 
 impl Client {
@@ -25,53 +51,118 @@ impl Client {
         Self {
             client: crate::core::Client::new(token, config),
             req: Arc::new(reqwest::Client::new()),
+            api_endpoint: API_ENDPOINT.to_string(),
+            upload_endpoint: UPLOAD_ENDPOINT.to_string(),
+            upload_store: None,
         }
     }
 
-    /// Upload large files (>= 5MB) using resumable upload
-    async fn upload_large_file(&self, id: &str, buf: &[u8]) -> Result<DriveFile> {
-        let metadata = serde_json::json!({});
-        let session_url = format!("https://www.googleapis.com/upload/drive/v3/files/{id}?uploadType=resumable");
-        let req = self.req.clone().patch(&session_url)
-            .header(AUTHORIZATION, self.client.bearer().await?)
-            .header("Content-Type", "application/json; charset=UTF-8")
-            .json(&metadata);
+    /// Overrides the Drive API endpoint (defaults to
+    /// [API_ENDPOINT]), e.g. to target a local test server.
+    pub fn with_api_endpoint(mut self, endpoint: &str) -> Self {
+        self.api_endpoint = endpoint.trim_end_matches('/').to_string();
+        self
+    }
 
-        let res = self.client.execute_with_retry(req)
-            .await?.error_for_status()?;
+    /// Overrides the Drive upload endpoint (defaults to
+    /// [UPLOAD_ENDPOINT]).
+    pub fn with_upload_endpoint(mut self, endpoint: &str) -> Self {
+        self.upload_endpoint = endpoint.trim_end_matches('/').to_string();
+        self
+    }
 
-        let upload_url = res.headers().get("location")
-            .and_then(|h| h.to_str().ok())
-            .ok_or(GoogleDriveError {
-                code: 400,
-                message: "No location header in resumable upload response".to_string(),
-            })?;
+    /// Overrides the [RetryPolicy] used for API requests.
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.client = self.client.with_retry_policy(policy);
+        self
+    }
 
-        // Upload in chunks
-        let chunk_size = 256 * 1024; // 256KB chunks.
-        let total_size = buf.len();
-        let mut offset = 0;
+    /// Saves the client's token in `store` every time it is
+    /// refreshed.
+    pub fn with_token_store(mut self, store: Arc<dyn TokenStore>) -> Self {
+        self.client = self.client.with_token_store(store);
+        self
+    }
 
-        while offset < total_size {
-            let end = std::cmp::min(offset + chunk_size, total_size);
-            let chunk = &buf[offset..end];
+    /// Saves the sessions of large uploads in `store`, so that
+    /// interrupted uploads can be resumed.
+    pub fn with_upload_store(mut self, store: Arc<dyn UploadStore>) -> Self {
+        self.upload_store = Some(store);
+        self
+    }
 
-            let req = self.req.clone().put(upload_url)
-                .header("Content-Range", format!("bytes {}-{}/{}", offset, end - 1, total_size))
+    /// Upload large files (> 5MB) using resumable upload, reading
+    /// `size` bytes from `reader` one chunk at a time.
+    ///
+    /// If an [UploadStore] is set, the session is persisted after
+    /// every chunk, along with the hash of the content sent so far.
+    /// A `resumed` session (see [resume_session](Self::resume_session))
+    /// continues from the range received by the server, once the
+    /// content read from `reader` is found to match that range.
+    async fn upload_large_file<R>(&self, id: &str, mut reader: R, size: u64, resumed: Option<UploadSession>) -> Result<DriveFile>
+    where R: AsyncRead + Unpin {
+        let store = self.upload_store.as_deref();
+        let mut hasher = Sha256::new();
+        let mut session = match resumed {
+            Some(session) => {
+                if !skip_committed(&mut reader, &session, &mut hasher).await? {
+                    if let Some(store) = store {
+                        store.remove(id).await?;
+                    }
+
+                    return Err(GoogleDriveError {
+                        code: 400,
+                        message: "content changed since the upload was interrupted".to_string(),
+                    }.into());
+                }
+                session
+            },
+            None => {
+                let upload_url = self.create_session(id).await?;
+                let session = UploadSession {
+                    file_id: id.to_string(),
+                    upload_url,
+                    size,
+                    sha256: format!("{:x}", hasher.clone().finalize()),
+                    committed: 0,
+                };
+
+                if let Some(store) = store {
+                    store.save(&session).await?;
+                }
+                session
+            },
+        };
+
+        while session.committed < size {
+            let offset = session.committed;
+            let end = std::cmp::min(offset + CHUNK_SIZE, size);
+            let chunk = read_exact_chunk(&mut reader, (end - offset) as usize).await?;
+            hasher.update(&chunk);
+            let req = self.req.clone().put(&session.upload_url)
+                .header("Content-Range", format!("bytes {}-{}/{}", offset, end - 1, size))
                 .header("Content-Length", chunk.len().to_string())
-                .body(chunk.to_vec());
+                .body(chunk);
 
             let res = self.client.execute_with_retry(req).await?
                 .error_for_status()?;
 
             // Check if upload is complete.
-            if end >= total_size {
+            if end >= size {
+                if let Some(store) = store {
+                    store.remove(id).await?;
+                }
+
                 let json: Value = res.json().await?;
                 let item: DriveFile = from_value(json)?;
                 return Ok(item);
             }
 
-            offset = end;
+            session.committed = end;
+            session.sha256 = format!("{:x}", hasher.clone().finalize());
+            if let Some(store) = store {
+                store.save(&session).await?;
+            }
         }
 
         Err(GoogleDriveError {
@@ -80,10 +171,73 @@ impl Client {
         }.into())
     }
 
-    /// Upload small files (< 5MB) directly
+    /// Initiates a resumable upload of a file's content, returning
+    /// its session URI.
+    async fn create_session(&self, id: &str) -> Result<String> {
+        let metadata = serde_json::json!({});
+        let session_url = format!("{}/files/{id}?uploadType=resumable&fields={FILE_FIELDS}", self.upload_endpoint);
+        let req = self.req.clone().patch(&session_url)
+            .header(AUTHORIZATION, self.client.authorization().await?)
+            .header("Content-Type", "application/json; charset=UTF-8")
+            .json(&metadata);
+
+        let res = self.client.execute_with_retry(req)
+            .await?.error_for_status()?;
+
+        let upload_url = res.headers().get("location")
+            .and_then(|h| h.to_str().ok())
+            .ok_or(GoogleDriveError {
+                code: 400,
+                message: "No location header in resumable upload response".to_string(),
+            })?;
+
+        Ok(upload_url.to_string())
+    }
+
+    /// Resumes the stored session of an upload of `size` bytes,
+    /// if an [UploadStore] is set. Sessions that have expired, or
+    /// whose committed range differs from the range received by the
+    /// server, are discarded.
+    ///
+    /// Documentation: [Resume an Interrupted Upload](https://developers.google.com/workspace/drive/api/guides/manage-uploads#resume-upload)
+    async fn resume_session(&self, id: &str, size: u64) -> Result<Option<UploadSession>> {
+        let Some(store) = self.upload_store.as_deref() else {
+            return Ok(None);
+        };
+
+        let Some(session) = load_session(store, id, size).await? else {
+            return Ok(None);
+        };
+
+        let req = self.req.clone().put(&session.upload_url)
+            .header("Content-Range", format!("bytes */{size}"))
+            .header("Content-Length", "0");
+
+        // An incomplete upload responds with the range received so
+        // far, if any. Only the committed range has a known hash, so
+        // sessions are only resumed from the end of that range.
+        let res = self.client.execute_with_retry(req).await?;
+        let committed = match res.status().as_u16() {
+            308 => Some(res.headers().get("range")
+                .and_then(|range| range.to_str().ok())
+                .and_then(|range| range.rsplit_once('-'))
+                .and_then(|(_, end)| end.parse::<u64>().ok())
+                .map_or(0, |end| end + 1)),
+            _ => None,
+        };
+
+        if committed != Some(session.committed) {
+            store.remove(id).await?;
+            return Ok(None);
+        }
+
+        Ok(Some(session))
+    }
+
+    /// Upload small files (<= 5MB) directly
     async fn upload_small_file(&self, id: &str, buf: &[u8]) -> Result<DriveFile> {
         let metadata = serde_json::json!({});
-        let url = format!("https://www.googleapis.com/upload/drive/v3/files/{id}?uploadType=multipart");
+        let url = format!("{}/files/{id}?uploadType=multipart&fields={FILE_FIELDS}", self.upload_endpoint);
 
         // Create multipart body
         let boundary = "boundary123456789";
@@ -102,7 +256,7 @@ impl Client {
         body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
 
         let req = self.req.clone().patch(&url)
-            .header(AUTHORIZATION, self.client.bearer().await?)
+            .header(AUTHORIZATION, self.client.authorization().await?)
             .header("Content-Type", format!("multipart/related; boundary={}", boundary))
             .body(body);
 
@@ -112,6 +266,15 @@ impl Client {
 
         Ok(file)
     }
+
+    /// Sends a request for the content of a file.
+    async fn download(&self, id: &str) -> Result<reqwest::Response> {
+        let url = format!("{}/files/{}?alt=media", self.api_endpoint, id);
+        let req = self.req.clone().get(&url)
+            .header(AUTHORIZATION, self.client.authorization().await?);
+
+        Ok(self.client.execute_with_retry(req).await?.error_for_status()?)
+    }
 }
 
 impl FileSystem for Client {
@@ -122,8 +285,8 @@ impl FileSystem for Client {
     ///
     /// API Reference: [Get](https://developers.google.com/workspace/drive/api/reference/rest/v3/files/get)
     async fn get_file(&self, id: &str) -> Result<DriveFile> {
-        let url = format!("{}/files/{}", API_ENDPOINT, id);
-        let req = self.req.clone().get(&url).header(AUTHORIZATION, self.client.bearer().await?);
+        let url = format!("{}/files/{}?fields={}", self.api_endpoint, id, FILE_FIELDS);
+        let req = self.req.clone().get(&url).header(AUTHORIZATION, self.client.authorization().await?);
         let res = self.client.execute_with_retry(req).await?;
 
         let json: Value = res.json().await?;
@@ -135,12 +298,20 @@ impl FileSystem for Client {
         Ok(from_value(json)?)
     }
 
+    /// Gets the ID of the "My Drive" folder, which is the parent of
+    /// the files at the root.
+    ///
+    /// API Reference: [Get](https://developers.google.com/workspace/drive/api/reference/rest/v3/files/get)
+    async fn get_root_id(&self) -> Result<Option<String>> {
+        Ok(Some(self.get_file("root").await?.id))
+    }
+
     /// Creates a copy of a file and applies any requested updates
     /// with patch semantics.
     ///
     /// API Reference: [Copy](https://developers.google.com/workspace/drive/api/reference/rest/v3/files/copy)
     async fn copy_file(&self, source_id: &str, parent_id: Option<&str>, name: Option<&str>) -> Result<DriveFile> {
-        let url = format!("{}/files/{}/copy", API_ENDPOINT, source_id);
+        let url = format!("{}/files/{}/copy?fields={}", self.api_endpoint, source_id, FILE_FIELDS);
         let mut body = serde_json::json!({});
         if let Some(new_name) = name {
             body["name"] = serde_json::Value::String(new_name.to_string());
@@ -151,7 +322,7 @@ impl FileSystem for Client {
         }
 
         let req = self.req.clone().post(&url)
-            .header(AUTHORIZATION, self.client.bearer().await?)
+            .header(AUTHORIZATION, self.client.authorization().await?)
             .json(&body);
 
         let res = self.client.execute_with_retry(req).await?;
@@ -169,7 +340,7 @@ impl FileSystem for Client {
     /// API Reference: [Update](https://developers.google.com/workspace/drive/api/reference/rest/v3/files/update)
     async fn move_file(&self, source_id: &str, parent_id: Option<&str>, name: Option<&str>) -> Result<DriveFile> {
         let current_file = self.get_file(source_id).await?;
-        let mut url = format!("{}/files/{}", API_ENDPOINT, source_id);
+        let mut url = format!("{}/files/{}", self.api_endpoint, source_id);
         let mut params = Vec::new();
 
         if let Some(parent_id) = parent_id {
@@ -181,10 +352,7 @@ impl FileSystem for Client {
         }
 
         // Add fields parameter to ensure we get back the parents field
-        params.push(
-            "fields=id,name,parents,mimeType,size,createdTime,modifiedTime,trashed,version"
-                .to_string(),
-        );
+        params.push(format!("fields={FILE_FIELDS}"));
 
         if !params.is_empty() {
             url.push_str("?");
@@ -197,7 +365,7 @@ impl FileSystem for Client {
         }
 
         let req = self.req.clone().patch(&url)
-            .header(AUTHORIZATION, self.client.bearer().await?)
+            .header(AUTHORIZATION, self.client.authorization().await?)
             .json(&body);
 
         let res = self.client.execute_with_retry(req).await?;
@@ -217,9 +385,9 @@ impl FileSystem for Client {
     ///
     /// API Reference: [Delete](https://developers.google.com/workspace/drive/api/reference/rest/v3/files/delete)
     async fn remove_file(&self, id: &str) -> Result<()> {
-        let url = format!("{}/files/{}", API_ENDPOINT, id);
+        let url = format!("{}/files/{}", self.api_endpoint, id);
         let req = self.req.clone().delete(&url)
-            .header(AUTHORIZATION, self.client.bearer().await?);
+            .header(AUTHORIZATION, self.client.authorization().await?);
 
         let res = self.client.execute_with_retry(req).await?;
         let json: std::result::Result<Value, reqwest::Error> = res.json().await;
@@ -243,8 +411,8 @@ impl FileSystem for Client {
         });
 
         let req = self.req.clone()
-            .post(format!("{}/files", API_ENDPOINT))
-            .header(AUTHORIZATION, self.client.bearer().await?)
+            .post(format!("{}/files?fields={}", self.api_endpoint, FILE_FIELDS))
+            .header(AUTHORIZATION, self.client.authorization().await?)
             .json(&body);
 
         let res = self.client.execute_with_retry(req).await?;
@@ -267,8 +435,8 @@ impl FileSystem for Client {
         });
 
         let req = self.req.clone()
-            .post(format!("{}/files", API_ENDPOINT))
-            .header(AUTHORIZATION, self.client.bearer().await?)
+            .post(format!("{}/files?fields={}", self.api_endpoint, FILE_FIELDS))
+            .header(AUTHORIZATION, self.client.authorization().await?)
             .json(&body);
 
         let res = self.client.execute_with_retry(req).await?;
@@ -283,18 +451,45 @@ impl FileSystem for Client {
 
     /// Lists the user's files.
     ///
+    /// Every page of files is fetched; see
+    /// [list_files_paged](FileSystem::list_files_paged) to process
+    /// large folders page by page.
+    ///
     /// API Reference: [List](https://developers.google.com/workspace/drive/api/reference/rest/v3/files/list)
     async fn list_files(&self, parent_id: Option<&str>) -> Result<Vec<DriveFile>> {
-        let mut url = format!("{API_ENDPOINT}/files");
+        let mut files: Vec<DriveFile> = Vec::new();
+        let mut pages = self.list_files_paged(parent_id);
+        while let Some(mut page) = pages.next().await? {
+            files.append(&mut page);
+        }
+
+        Ok(files)
+    }
+
+    /// Lists a single page of the user's files. The page token is
+    /// the previous page's `nextPageToken`.
+    ///
+    /// API Reference: [List](https://developers.google.com/workspace/drive/api/reference/rest/v3/files/list)
+    async fn list_files_page(&self, parent_id: Option<&str>, page_token: Option<&str>) -> Result<Page<DriveFile>> {
+        let mut params = vec![
+            ("fields", format!("nextPageToken,files({FILE_FIELDS})")),
+            ("pageSize", PAGE_SIZE.to_string()),
+        ];
+
         if let Some(p) = parent_id {
-            let q = format!("parents in '{p}'");
-            url.push_str(&format!(
-                "?q={}",
-                byte_serialize(q.as_bytes()).collect::<String>()
-            ))
+            params.push(("q", format!("parents in '{p}'")));
+        }
+
+        if let Some(page_token) = page_token {
+            params.push(("pageToken", page_token.to_string()));
         }
 
-        let req = self.req.clone().get(&url).header(AUTHORIZATION, self.client.bearer().await?);
+        let url = format!("{}/files?{}", self.api_endpoint,
+            form_urlencoded::Serializer::new(String::new())
+                .extend_pairs(params)
+                .finish()
+        );
+        let req = self.req.clone().get(&url).header(AUTHORIZATION, self.client.authorization().await?);
         let res = self.client.execute_with_retry(req).await?;
         let json: Value = res.json().await?;
         if let Some(error) = json.get("error") {
@@ -302,7 +497,11 @@ impl FileSystem for Client {
             return Err(Error::GoogleDrive(err));
         }
 
-        Ok(from_value(json["files"].clone())?)
+        let files = from_value(json["files"].clone())?;
+        let next_page_token = json["nextPageToken"].as_str()
+            .map(|token| token.to_string());
+
+        Ok((files, next_page_token))
     }
 
     /// Updates the contents of a file, otherwise creating it if it
@@ -314,10 +513,13 @@ impl FileSystem for Client {
     /// Documentation: [Upload File Data](https://developers.google.com/workspace/drive/api/guides/manage-uploads)
     /// API Reference: [Create](https://developers.google.com/workspace/drive/api/reference/rest/v3/files/create)
     async fn write_to_file(&self, id: &str, buf: &[u8]) -> Result<DriveFile> {
-        if buf.len() > 5 * 1024 * 1024 {
-            self.upload_large_file(id, buf).await
-        } else {
+        if buf.len() <= SMALL_FILE_LIMIT {
             self.upload_small_file(id, buf).await
+        } else {
+            let resumed = self.resume_session(id, buf.len() as u64).await?
+                .filter(|session| is_committed(session, buf));
+
+            self.upload_large_file(id, buf, buf.len() as u64, resumed).await
         }
     }
 
@@ -326,15 +528,31 @@ impl FileSystem for Client {
     ///
     /// API Reference: [Download](https://developers.google.com/workspace/drive/api/reference/rest/v3/files/download)
     async fn read_from_file(&self, id: &str) -> Result<Vec<u8>> {
-        let url = format!("{}/files/{}?alt=media", API_ENDPOINT, id);
-        let req = self.req.clone().get(&url)
-            .header(AUTHORIZATION, self.client.bearer().await?);
+        let bytes = self.download(id).await?.bytes().await?;
+        Ok(bytes.to_vec())
+    }
 
-        let res = self.client.execute_with_retry(req).await?
-            .error_for_status()?;
+    /// Uploads the content of a file from `reader`. Files larger
+    /// than 5MB are sent to a resumable upload in chunks.
+    ///
+    /// An interrupted upload is resumed if the content read so far
+    /// matches the content that was sent. Otherwise the upload fails
+    /// and its session is discarded, so the next upload starts over.
+    async fn write_from_reader<R>(&self, id: &str, mut reader: R, size: u64) -> Result<DriveFile>
+    where R: AsyncRead + Unpin + Send {
+        if size <= SMALL_FILE_LIMIT as u64 {
+            let buf = read_exact_chunk(&mut reader, size as usize).await?;
+            self.upload_small_file(id, &buf).await
+        } else {
+            let resumed = self.resume_session(id, size).await?;
+            self.upload_large_file(id, reader, size, resumed).await
+        }
+    }
 
-        let bytes = res.bytes().await?;
-        Ok(bytes.to_vec())
+    /// Downloads the content of a file as it is received.
+    async fn read_stream(&self, id: &str) -> Result<ByteStream<Error>> {
+        let res = self.download(id).await?;
+        Ok(Box::pin(response_stream(res)))
     }
 }
 
@@ -343,20 +561,21 @@ impl Delta for Client {
 
     /// Report file changes.
     async fn list_deltas(&self, token: Option<&str>) -> Result<(Vec<DriveChange>, String)> {
-        let mut url = format!("{}/changes?{}", API_ENDPOINT,
+        let mut url = format!("{}/changes?{}", self.api_endpoint,
             form_urlencoded::Serializer::new(String::new())
                 .append_pair("includeRemoved", "true")
                 .append_pair("restrictToMyDrive", "true")
-                .append_pair("pageSize", "20")
+                .append_pair("pageSize", PAGE_SIZE)
+                .append_pair("fields", CHANGE_FIELDS)
                 .finish()
         );
 
         if let Some(token) = token {
             url.push_str(&format!("&pageToken={token}"));
         } else {
-            let start_token_url = format!("{API_ENDPOINT}/changes/startPageToken");
+            let start_token_url = format!("{}/changes/startPageToken", self.api_endpoint);
             let req = self.req.clone().get(start_token_url)
-                .header(AUTHORIZATION, self.client.bearer().await?);
+                .header(AUTHORIZATION, self.client.authorization().await?);
 
             let res = self.client.execute_with_retry(req).await?.error_for_status()?;
             let json: Value = res.json().await?;
@@ -370,7 +589,7 @@ impl Delta for Client {
         }
 
         let mut changes: Vec<DriveChange> = Vec::new();
-        let req = self.req.clone().get(&url).header(AUTHORIZATION, self.client.bearer().await?);
+        let req = self.req.clone().get(&url).header(AUTHORIZATION, self.client.authorization().await?);
         let res = self.client.execute_with_retry(req).await?.error_for_status()?;
         let mut json: Value = res.json().await?;
         let mut items: Vec<DriveChange> = from_value(json["changes"].clone())?;
@@ -380,16 +599,17 @@ impl Delta for Client {
         while let Some(next_token) = json.get("nextPageToken").and_then(|t| t.as_str()) {
             let next_url = format!(
                 "{}/changes?{}",
-                API_ENDPOINT,
+                self.api_endpoint,
                 form_urlencoded::Serializer::new(String::new())
                     .append_pair("includeRemoved", "true")
                     .append_pair("restrictToMyDrive", "true")
                     .append_pair("pageToken", next_token)
-                    .append_pair("pageSize", "20")
+                    .append_pair("pageSize", PAGE_SIZE)
+                    .append_pair("fields", CHANGE_FIELDS)
                     .finish()
             );
 
-            let req = self.req.clone().get(&next_url).header(AUTHORIZATION, self.client.bearer().await?);
+            let req = self.req.clone().get(&next_url).header(AUTHORIZATION, self.client.authorization().await?);
             let res = self.client.execute_with_retry(req).await?.error_for_status()?;
             json = res.json().await?;
             let mut items: Vec<DriveChange> = from_value(json["changes"].clone())?;
@@ -410,45 +630,12 @@ impl Delta for Client {
 mod tests {
 
     use super::*;
-    use crate::oauth2;
-    use dotenv::dotenv;
-    use std::env;
-
-    use std::sync::Arc;
-    use tokio::sync::OnceCell;
-    static TOKEN: OnceCell<Arc<oauth2::Token>> = OnceCell::const_new();
-
-    async fn get_test_client() -> Client {
-        dotenv().ok();
-
-        let client_id =
-            env::var("GOOGLEDRIVE_CLIENT_ID").expect("missing GOOGLEDRIVE_CLIENT_ID env variable");
-
-        let client_secret = env::var("GOOGLEDRIVE_CLIENT_SECRET")
-            .expect("missing GOOGLEDRIVE_CLIENT_SECRET env variable");
-
-        let redirect_uri = env::var("GOOGLEDRIVE_REDIRECT_URI")
-            .expect("missing GOOGLEDRIVE_CLIENT_SECRET env variable");
-
-        let refresh_token = env::var("GOOGLEDRIVE_REFRESH_TOKEN")
-            .expect("missing GOOGLEDRIVE_REFRESH_TOKEN env variable");
-
-        let app_config =
-            oauth2::Config::googledrive(&client_id, &client_secret, &redirect_uri);
-
-        let token = TOKEN.get_or_init(|| async {
-                let token = oauth2::Token::from_refresh_token(&refresh_token, &app_config)
-                    .await.unwrap();
-
-                Arc::new(token)
-            }).await.clone();
-
-        Client::new(&token, &app_config)
-    }
+    use crate::cloud::fake::FakeGoogleDrive;
 
     #[tokio::test]
     async fn test_get_file() {
-        let client = get_test_client().await;
+        let fake = FakeGoogleDrive::new().await;
+        let client = fake.client();
         let file = client.create_file(None, "helsync-get-file.txt")
             .await.unwrap();
 
@@ -457,9 +644,22 @@ mod tests {
         client.remove_file(&file.id).await.unwrap();
     }
 
+    #[tokio::test]
+    async fn test_get_root_id() {
+        let fake = FakeGoogleDrive::new().await;
+        let client = fake.client();
+        let file = client.create_file(None, "helsync-get-root-id.txt")
+            .await.unwrap();
+
+        let root_id = client.get_root_id().await.unwrap();
+        assert!(root_id.is_some());
+        assert_eq!(file.parents.unwrap().first(), root_id.as_ref());
+    }
+
     #[tokio::test]
     async fn test_copy_file() {
-        let client = get_test_client().await;
+        let fake = FakeGoogleDrive::new().await;
+        let client = fake.client();
         let file = client.create_file(None, "helsync-copy-file.txt")
             .await.unwrap();
 
@@ -479,7 +679,8 @@ mod tests {
 
     #[tokio::test]
     async fn test_move_file() {
-        let client = get_test_client().await;
+        let fake = FakeGoogleDrive::new().await;
+        let client = fake.client();
         let child = client.create_file(None, "helsync-child-file.txt")
             .await.unwrap();
 
@@ -492,7 +693,8 @@ mod tests {
 
     #[tokio::test]
     async fn test_create_folder() {
-        let client = get_test_client().await;
+        let fake = FakeGoogleDrive::new().await;
+        let client = fake.client();
         let parent = client.create_folder(None, "helsync-test-folder")
             .await.unwrap();
 
@@ -504,7 +706,8 @@ mod tests {
 
     #[tokio::test]
     async fn test_list_files() {
-        let client = get_test_client().await;
+        let fake = FakeGoogleDrive::new().await;
+        let client = fake.client();
         let parent = client.create_folder(None, "helsync-test-list")
             .await.unwrap();
 
@@ -517,29 +720,108 @@ mod tests {
 
     #[tokio::test]
     async fn test_list_deltas() {
-        let client = get_test_client().await;
+        let fake = FakeGoogleDrive::new().await;
+        let client = fake.client();
         let (_, token) = client.list_deltas(None).await.unwrap();
 
-        // Changes propagate slowly, especially when a lot of tests
-        // are simultaneously committing changes. Adding significant
-        // delays allows the changes to accumulate.
-
         // Make a change.
         let file = client.create_file(None, "helsync-track-changes.txt")
             .await.unwrap();
 
-        // Wait a bit for the change to propagate.
-        tokio::time::sleep(tokio::time::Duration::from_millis(1000)).await;
+        let (changes, token) = client.list_deltas(Some(&token)).await.unwrap();
+        assert!(changes.iter().any(|change| change.file_id == file.id));
 
+        client.remove_file(&file.id).await.unwrap();
         let (changes, _) = client.list_deltas(Some(&token)).await.unwrap();
-        changes.iter().for_each(|item| println!("{:?}", item));
+        assert!(changes.len() == 1);
+        assert!(changes[0].file_id == file.id && changes[0].removed);
+    }
 
-        client.remove_file(&file.id).await.unwrap();
+    #[tokio::test]
+    async fn test_list_deltas_paged() {
+        let fake = FakeGoogleDrive::new().await;
+        let client = fake.client();
+        fake.set_page_size(2);
+
+        let (_, token) = client.list_deltas(None).await.unwrap();
+        for i in 0..5 {
+            client.create_file(None, &format!("helsync-delta-{i}.txt"))
+                .await.unwrap();
+        }
+
+        let (changes, token) = client.list_deltas(Some(&token)).await.unwrap();
+        assert!(changes.len() == 5);
+
+        let (changes, _) = client.list_deltas(Some(&token)).await.unwrap();
+        assert!(changes.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_list_files_paged() {
+        let fake = FakeGoogleDrive::new().await;
+        let client = fake.client();
+        fake.set_page_size(2);
+
+        let parent = client.create_folder(None, "helsync-test-paged")
+            .await.unwrap();
+
+        for i in 0..5 {
+            client.create_file(Some(&parent.id), &format!("helsync-page-{i}.txt"))
+                .await.unwrap();
+        }
+
+        let (page, page_token) = client.list_files_page(Some(&parent.id), None)
+            .await.unwrap();
+
+        assert!(page.len() == 2);
+        assert!(page_token.is_some());
+
+        let files = client.list_files(Some(&parent.id)).await.unwrap();
+        assert!(files.len() == 5);
+    }
+
+    #[tokio::test]
+    async fn test_get_missing_file() {
+        let fake = FakeGoogleDrive::new().await;
+        let client = fake.client();
+        let err = client.get_file("missing").await.unwrap_err();
+        assert!(matches!(err, Error::GoogleDrive(GoogleDriveError { code: 404, .. })));
+    }
+
+    #[tokio::test]
+    async fn test_retry_server_error() {
+        let fake = FakeGoogleDrive::new().await;
+        let client = fake.client();
+        let file = client.create_file(None, "helsync-retry.txt")
+            .await.unwrap();
+
+        fake.fail_next(503, 1);
+        let requests = fake.requests();
+        assert!(client.get_file(&file.id).await.is_ok());
+        assert!(fake.requests() == requests + 2);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_token() {
+        let fake = FakeGoogleDrive::new().await;
+        let mut token = fake.token();
+        token.expires_in = 0;
+
+        // An expired token is refreshed against the token endpoint.
+        fake.revoke_token();
+        let client = Client::new(&token, &fake.config())
+            .with_api_endpoint(&format!("{}/drive/v3", fake.url()));
+
+        let folder = client.create_folder(None, "helsync-refresh")
+            .await.unwrap();
+
+        assert!(folder.name == "helsync-refresh");
     }
 
     #[tokio::test]
     async fn test_read_write_small() {
-        let client = get_test_client().await;
+        let fake = FakeGoogleDrive::new().await;
+        let client = fake.client();
         let file = client.create_file(None, "helsync-write-small.txt")
             .await.unwrap();
 
@@ -557,10 +839,12 @@ mod tests {
 
     #[tokio::test]
     async fn test_read_write_large() {
-        let client = get_test_client().await;
+        let fake = FakeGoogleDrive::new().await;
+        let client = fake.client();
 
-        // 5MB of 'A' characters.
-        let file_size = 5 * 1024 * 1024;
+        // Just over 5MB of 'A' characters, so that a resumable
+        // upload is used.
+        let file_size = 5 * 1024 * 1024 + 1;
         let buf = vec![b'A'; file_size];
 
         let file = client.create_file(None, "helsync-write-large.txt")
@@ -570,6 +854,122 @@ mod tests {
             .await.unwrap();
 
         assert!(file.name == "helsync-write-large.txt");
+        assert!(client.read_from_file(&file.id).await.unwrap() == buf);
+        client.remove_file(&file.id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_stream() {
+        use crate::core::ProgressReader;
+        use futures_util::StreamExt;
+
+        let fake = FakeGoogleDrive::new().await;
+        let client = fake.client();
+        let file = client.create_file(None, "helsync-stream.txt")
+            .await.unwrap();
+
+        // Large enough to be uploaded in chunks.
+        let buf: Vec<u8> = (0..SMALL_FILE_LIMIT + 1).map(|i| (i % 251) as u8).collect();
+        let mut sent = 0;
+        let reader = ProgressReader::new(&buf[..], |n| sent = n);
+        let file = client.write_from_reader(&file.id, reader, buf.len() as u64)
+            .await.unwrap();
+
+        assert!(file.name == "helsync-stream.txt");
+        assert_eq!(sent, buf.len() as u64);
+
+        let mut chunks = client.read_stream(&file.id).await.unwrap();
+        let mut read = Vec::new();
+        while let Some(chunk) = chunks.next().await {
+            read.extend_from_slice(&chunk.unwrap());
+        }
+        assert!(read == buf);
+
+        // Small files are uploaded in a single request.
+        client.write_from_reader(&file.id, &b"Hello, World!"[..], 13)
+            .await.unwrap();
+
+        assert!(client.read_from_file(&file.id).await.unwrap() == b"Hello, World!");
+        client.remove_file(&file.id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_resume_upload() {
+        use crate::cloud::fake::FakeUploadStore;
+
+        let fake = FakeGoogleDrive::new().await;
+        let store = Arc::new(FakeUploadStore::default());
+        let client = fake.client()
+            .with_retry_policy(RetryPolicy::none())
+            .with_upload_store(store.clone());
+
+        let file = client.create_file(None, "helsync-resume.txt")
+            .await.unwrap();
+
+        let buf: Vec<u8> = (0..SMALL_FILE_LIMIT + 1).map(|i| (i % 251) as u8).collect();
+        fake.set_upload_limit(Some(buf.len() / 2));
+        assert!(client.write_to_file(&file.id, &buf).await.is_err());
+
+        // The session outlives the interrupted upload.
+        let session = store.session(&file.id).unwrap();
+        assert!(session.committed > 0);
+        assert!(session.committed < buf.len() as u64);
+
+        fake.set_upload_limit(None);
+        let requests = fake.requests();
+        let file = client.write_to_file(&file.id, &buf).await.unwrap();
+        assert!(file.name == "helsync-resume.txt");
+        assert!(store.session(&file.id).is_none());
+
+        // Only the remaining chunks were sent.
+        let chunks = buf.len().div_ceil(CHUNK_SIZE as usize);
+        assert!(fake.requests() - requests < chunks);
+        assert!(client.read_from_file(&file.id).await.unwrap() == buf);
+        client.remove_file(&file.id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_resume_stream() {
+        use crate::cloud::fake::FakeUploadStore;
+
+        let fake = FakeGoogleDrive::new().await;
+        let store = Arc::new(FakeUploadStore::default());
+        let client = fake.client()
+            .with_retry_policy(RetryPolicy::none())
+            .with_upload_store(store.clone());
+
+        let file = client.create_file(None, "helsync-resume-stream.txt")
+            .await.unwrap();
+
+        let buf: Vec<u8> = (0..SMALL_FILE_LIMIT + 1).map(|i| (i % 251) as u8).collect();
+        let size = buf.len() as u64;
+        fake.set_upload_limit(Some(buf.len() / 2));
+        assert!(client.write_from_reader(&file.id, &buf[..], size).await.is_err());
+        assert!(store.session(&file.id).unwrap().committed > 0);
+
+        // The stream is resumed from the range received by the server.
+        fake.set_upload_limit(None);
+        let requests = fake.requests();
+        let file = client.write_from_reader(&file.id, &buf[..], size).await.unwrap();
+        assert!(file.name == "helsync-resume-stream.txt");
+        assert!(store.session(&file.id).is_none());
+
+        let chunks = buf.len().div_ceil(CHUNK_SIZE as usize);
+        assert!(fake.requests() - requests < chunks);
+        assert!(client.read_from_file(&file.id).await.unwrap() == buf);
+
+        // A stream of different content is not appended to the
+        // interrupted upload, which is started over instead.
+        fake.set_upload_limit(Some(buf.len() / 2));
+        assert!(client.write_from_reader(&file.id, &buf[..], size).await.is_err());
+
+        fake.set_upload_limit(None);
+        let changed: Vec<u8> = buf.iter().map(|byte| byte.wrapping_add(1)).collect();
+        assert!(client.write_from_reader(&file.id, &changed[..], size).await.is_err());
+        assert!(store.session(&file.id).is_none());
+
+        client.write_from_reader(&file.id, &changed[..], size).await.unwrap();
+        assert!(client.read_from_file(&file.id).await.unwrap() == changed);
         client.remove_file(&file.id).await.unwrap();
     }
 }
@@ -126,6 +126,9 @@ impl Into<File> for DriveFile {
             parent_id,
             is_folder,
             is_deleted: self.trashed.is_some_and(|b| b),
+            sha1: self.sha1_checksum,
+            sha256: self.sha256_checksum,
+            size: self.size.and_then(|size| size.parse().ok()),
         }
     }
 }
@@ -1,5 +1,11 @@
 //! Cloud virtual [FileSystem](crate::core::FileSystem)
 //! implementations.
 
+pub mod dropbox;
 pub mod googledrive;
 pub mod onedrive;
+pub mod s3;
+pub mod webdav;
+
+#[cfg(test)]
+pub(crate) mod fake;
@@ -1,20 +1,36 @@
-use crate::core::{Result, Error, FileSystem, Delta, extract_query_params};
+use crate::core::{RetryPolicy, Result, Error, FileSystem, Delta, Page, ByteStream, extract_query_params};
+use crate::core::{UploadSession, UploadStore, is_committed, load_session, read_exact_chunk, response_stream, skip_committed};
 use super::status::{JobStatus, StatusReport};
-use crate::oauth2::{Config, Token};
+use crate::oauth2::{Config, Token, TokenStore};
 use super::error::OneDriveError;
 use super::file::DriveItem;
 
 use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
 use serde_json::{from_value, Value};
+use sha2::{Digest, Sha256};
 use std::sync::Arc;
+use tokio::io::AsyncRead;
 
 /// Microsoft Graph API endpoint for OneDrive.
 pub const API_ENDPOINT: &str = "https://graph.microsoft.com/v1.0/me/drive";
 
+/// Maximum size of files uploaded in a single request. Larger
+/// files are uploaded in chunks using an upload session.
+const SMALL_FILE_LIMIT: usize = 4 * 1024 * 1024;
+
+/// Size of the chunks sent to an upload session, which must be a
+/// multiple of 320 KiB.
+const CHUNK_SIZE: u64 = 320 * 1024;
+
+/// Maximum number of items requested per page of a listing.
+const PAGE_SIZE: usize = 1000;
+
 /// Implements a OneDrive API client.
 pub struct Client {
     client: crate::core::Client,
     req: Arc<reqwest::Client>,
+    api_endpoint: String,
+    upload_store: Option<Arc<dyn UploadStore>>,
 }
 
 impl Client {
@@ -24,14 +40,44 @@ impl Client {
         Self {
             client: crate::core::Client::new(token, config),
             req: Arc::new(reqwest::Client::new()),
+            api_endpoint: API_ENDPOINT.to_string(),
+            upload_store: None,
         }
     }
 
-    /// Upload small files (< 4MB) directly.
+    /// Overrides the Graph API endpoint (defaults to
+    /// [API_ENDPOINT]), e.g. to target a national cloud or a local
+    /// test server.
+    pub fn with_api_endpoint(mut self, endpoint: &str) -> Self {
+        self.api_endpoint = endpoint.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Overrides the [RetryPolicy] used for API requests.
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.client = self.client.with_retry_policy(policy);
+        self
+    }
+
+    /// Saves the client's token in `store` every time it is
+    /// refreshed.
+    pub fn with_token_store(mut self, store: Arc<dyn TokenStore>) -> Self {
+        self.client = self.client.with_token_store(store);
+        self
+    }
+
+    /// Saves the sessions of large uploads in `store`, so that
+    /// interrupted uploads can be resumed.
+    pub fn with_upload_store(mut self, store: Arc<dyn UploadStore>) -> Self {
+        self.upload_store = Some(store);
+        self
+    }
+
+    /// Upload small files (<= 4MB) directly.
     async fn upload_small_file(&self, id: &str, buf: &[u8]) -> Result<DriveItem> {
-        let url = format!("{API_ENDPOINT}/items/{id}/content");
+        let url = format!("{}/items/{id}/content", self.api_endpoint);
         let req = self.req.clone().put(&url)
-            .header(AUTHORIZATION, self.client.bearer().await?)
+            .header(AUTHORIZATION, self.client.authorization().await?)
             .header(CONTENT_TYPE, "application/octet-stream")
             .body(buf.to_vec());
 
@@ -44,15 +90,96 @@ impl Client {
         Ok(item)
     }
 
-    /// Upload large files (>= 4MB) using upload session.
-    async fn upload_large_file(&self, id: &str, buf: &[u8]) -> Result<DriveItem> {
-        let session_url = format!("{API_ENDPOINT}/items/{id}/createUploadSession");
+    /// Upload large files (> 4MB) using upload session, reading
+    /// `size` bytes from `reader` one chunk at a time.
+    ///
+    /// If an [UploadStore] is set, the session is persisted after
+    /// every chunk, along with the hash of the content sent so far.
+    /// A `resumed` session (see [resume_session](Self::resume_session))
+    /// continues from the range received by the server, once the
+    /// content read from `reader` is found to match that range.
+    async fn upload_large_file<R>(&self, id: &str, mut reader: R, size: u64, resumed: Option<UploadSession>) -> Result<DriveItem>
+    where R: AsyncRead + Unpin {
+        let store = self.upload_store.as_deref();
+        let mut hasher = Sha256::new();
+        let mut session = match resumed {
+            Some(session) => {
+                if !skip_committed(&mut reader, &session, &mut hasher).await? {
+                    if let Some(store) = store {
+                        store.remove(id).await?;
+                    }
+
+                    return Err(OneDriveError {
+                        code: "UPLOAD_LARGE_FILE_ERR".to_string(),
+                        message: "content changed since the upload was interrupted".to_string(),
+                    }.into());
+                }
+                session
+            },
+            None => {
+                let upload_url = self.create_session(id).await?;
+                let session = UploadSession {
+                    file_id: id.to_string(),
+                    upload_url,
+                    size,
+                    sha256: format!("{:x}", hasher.clone().finalize()),
+                    committed: 0,
+                };
+
+                if let Some(store) = store {
+                    store.save(&session).await?;
+                }
+                session
+            },
+        };
+
+        while session.committed < size {
+            let offset = session.committed;
+            let end = std::cmp::min(offset + CHUNK_SIZE, size);
+            let chunk = read_exact_chunk(&mut reader, (end - offset) as usize).await?;
+            hasher.update(&chunk);
+            let req = self.req.clone().put(&session.upload_url)
+                .header("Content-Range", format!("bytes {}-{}/{}", offset, end - 1, size))
+                .header("Content-Length", chunk.len())
+                .body(chunk);
+
+            let res = self.client.execute_with_retry(req).await?
+                .error_for_status()?;
+
+            // Check if upload is complete.
+            if res.status() == 201 || res.status() == 200 {
+                if let Some(store) = store {
+                    store.remove(id).await?;
+                }
+
+                let json: Value = res.json().await?;
+                let item: DriveItem = from_value(json)?;
+                return Ok(item);
+            }
+
+            session.committed = end;
+            session.sha256 = format!("{:x}", hasher.clone().finalize());
+            if let Some(store) = store {
+                store.save(&session).await?;
+            }
+        }
+
+        Err(OneDriveError {
+            code: "UPLOAD_LARGE_FILE_ERR".to_string(),
+            message: "upload completed but no response received".to_string(),
+        }.into())
+    }
+
+    /// Creates an upload session for the content of a [DriveItem],
+    /// returning its upload URL.
+    async fn create_session(&self, id: &str) -> Result<String> {
+        let session_url = format!("{}/items/{id}/createUploadSession", self.api_endpoint);
         let session_body = serde_json::json!({
             "item": {"@microsoft.graph.conflictBehavior": "replace"}
         });
 
         let req = self.req.clone().post(&session_url)
-            .header(AUTHORIZATION, self.client.bearer().await?)
+            .header(AUTHORIZATION, self.client.authorization().await?)
             .json(&session_body);
 
         let res = self.client.execute_with_retry(req).await?
@@ -65,36 +192,48 @@ impl Client {
                 message: "no uploadUrl in session response".to_string(),
             })?;
 
-        // Upload file in chunks.
-        let chunk_size = 320 * 1024; // 320 KB chunks.
-        let total_size = buf.len();
-        let mut offset = 0;
+        Ok(upload_url.to_string())
+    }
 
-        while offset < total_size {
-            let end = std::cmp::min(offset + chunk_size, total_size);
-            let chunk = &buf[offset..end];
-            let req = self.req.clone().put(upload_url)
-                .header("Content-Range", format!("bytes {}-{}/{}", offset, end - 1, total_size))
-                .header("Content-Length", chunk.len())
-                .body(chunk.to_vec());
+    /// Resumes the stored session of an upload of `size` bytes,
+    /// if an [UploadStore] is set. Sessions that have expired, or
+    /// whose committed range differs from the range received by the
+    /// server, are discarded.
+    ///
+    /// API Reference: [Resume an Upload](https://learn.microsoft.com/en-us/onedrive/developer/rest-api/api/driveitem_createuploadsession?view=odsp-graph-online#resuming-an-in-progress-upload)
+    async fn resume_session(&self, id: &str, size: u64) -> Result<Option<UploadSession>> {
+        let Some(store) = self.upload_store.as_deref() else {
+            return Ok(None);
+        };
 
-            let res = self.client.execute_with_retry(req).await?
-                .error_for_status()?;
+        let Some(session) = load_session(store, id, size).await? else {
+            return Ok(None);
+        };
 
-            // Check if upload is complete.
-            if res.status() == 201 || res.status() == 200 {
-                let json: Value = res.json().await?;
-                let item: DriveItem = from_value(json)?;
-                return Ok(item);
-            }
+        // Only the committed range has a known hash, so sessions are
+        // only resumed from the end of that range.
+        let req = self.req.clone().get(&session.upload_url);
+        let res = self.client.execute_with_retry(req).await?;
+        let committed = match res.status().is_success() {
+            true => res.json::<Value>().await?["nextExpectedRanges"][0].as_str()
+                .and_then(|range| range.split('-').next())
+                .and_then(|start| start.parse::<u64>().ok()),
+            false => None,
+        };
 
-            offset = end;
+        if committed != Some(session.committed) {
+            store.remove(id).await?;
+            return Ok(None);
         }
 
-        Err(OneDriveError {
-            code: "UPLOAD_LARGE_FILE_ERR".to_string(),
-            message: "upload completed but no response received".to_string(),
-        }.into())
+        Ok(Some(session))
+    }
+
+    /// Sends a request for the content of a [DriveItem].
+    async fn download(&self, id: &str) -> Result<reqwest::Response> {
+        let url = format!("{}/items/{}/content", self.api_endpoint, id);
+        let req = self.req.clone().get(&url).header(AUTHORIZATION, self.client.authorization().await?);
+        Ok(self.client.execute_with_retry(req).await?.error_for_status()?)
     }
 }
 
@@ -106,9 +245,9 @@ impl FileSystem for Client {
     ///
     /// API Reference: [Get Item](https://learn.microsoft.com/en-us/onedrive/developer/rest-api/api/driveitem_get?view=odsp-graph-online)
     async fn get_file(&self, source_id: &str) -> Result<DriveItem> {
-        let url = format!("{}/items/{}", API_ENDPOINT, source_id);
+        let url = format!("{}/items/{}", self.api_endpoint, source_id);
         let req = self.req.clone().get(&url)
-            .header(AUTHORIZATION, self.client.bearer().await?);
+            .header(AUTHORIZATION, self.client.authorization().await?);
 
         let res = self.client.execute_with_retry(req).await?
             .error_for_status()?;
@@ -119,6 +258,24 @@ impl FileSystem for Client {
         Ok(item)
     }
 
+    /// Retrieve the ID of the drive's root folder, which is the
+    /// parent of the items at the root.
+    ///
+    /// API Reference: [Get Item](https://learn.microsoft.com/en-us/onedrive/developer/rest-api/api/driveitem_get?view=odsp-graph-online)
+    async fn get_root_id(&self) -> Result<Option<String>> {
+        let url = format!("{}/root", self.api_endpoint);
+        let req = self.req.clone().get(&url)
+            .header(AUTHORIZATION, self.client.authorization().await?);
+
+        let res = self.client.execute_with_retry(req).await?
+            .error_for_status()?;
+
+        let json: Value = res.json().await?;
+        let item: DriveItem = from_value(json)?;
+
+        Ok(Some(item.id))
+    }
+
     /// Copy a [DriveItem].
     ///
     /// Copies the file with id `source_id` to the parent
@@ -128,7 +285,7 @@ impl FileSystem for Client {
     ///
     /// API Reference: [Copy a DriveItem](https://learn.microsoft.com/en-us/onedrive/developer/rest-api/api/driveitem_copy?view=odsp-graph-online)
     async fn copy_file(&self, source_id: &str, parent_id: Option<&str>, name: Option<&str>) -> Result<DriveItem> {
-        let url = format!("{}/items/{}/copy", API_ENDPOINT, source_id);
+        let url = format!("{}/items/{}/copy", self.api_endpoint, source_id);
         let mut body = serde_json::json!({});
         if let Some(parent_id) = parent_id {
             body["parentReference"] = serde_json::json!({
@@ -142,7 +299,7 @@ impl FileSystem for Client {
 
         // Post the copy request.
         let req = self.req.clone().post(&url)
-            .header(AUTHORIZATION, self.client.bearer().await?)
+            .header(AUTHORIZATION, self.client.authorization().await?)
             .json(&body);
 
         let res = self.client.execute_with_retry(req).await?
@@ -196,7 +353,7 @@ impl FileSystem for Client {
     ///
     /// API Reference: [Move](https://learn.microsoft.com/en-us/onedrive/developer/rest-api/api/driveitem_move?view=odsp-graph-online)
     async fn move_file(&self, source_id: &str, parent_id: Option<&str>, name: Option<&str>) -> Result<DriveItem> {
-        let url = format!("{}/items/{}", API_ENDPOINT, source_id);
+        let url = format!("{}/items/{}", self.api_endpoint, source_id);
         let mut body = serde_json::json!({});
         if let Some(parent_id) = parent_id {
             body["parentReference"] = serde_json::json!({
@@ -209,7 +366,7 @@ impl FileSystem for Client {
         }
 
         let req = self.req.clone().patch(&url)
-            .header(AUTHORIZATION, self.client.bearer().await?)
+            .header(AUTHORIZATION, self.client.authorization().await?)
             .json(&body);
 
         let res = self.client.execute_with_retry(req).await?
@@ -228,9 +385,9 @@ impl FileSystem for Client {
     ///
     /// API Reference: [Delete](https://learn.microsoft.com/en-us/onedrive/developer/rest-api/api/driveitem_delete?view=odsp-graph-online)
     async fn remove_file(&self, id: &str) -> Result<()> {
-        let url = format!("{}/items/{}", API_ENDPOINT, id);
+        let url = format!("{}/items/{}", self.api_endpoint, id);
         let req = self.req.clone().delete(&url)
-            .header(AUTHORIZATION, self.client.bearer().await?);
+            .header(AUTHORIZATION, self.client.authorization().await?);
 
         self.client.execute_with_retry(req).await?
             .error_for_status()?;
@@ -247,8 +404,8 @@ impl FileSystem for Client {
     /// API Reference: [Create folder](https://learn.microsoft.com/en-us/onedrive/developer/rest-api/api/driveitem_post_children?view=odsp-graph-online)
     async fn create_folder(&self, parent_id: Option<&str>, name: &str) -> Result<DriveItem> {
         let url = match parent_id {
-            Some(p) => format!("{}/items/{}/children", API_ENDPOINT, p),
-            None => format!("{}/root/children", API_ENDPOINT),
+            Some(p) => format!("{}/items/{}/children", self.api_endpoint, p),
+            None => format!("{}/root/children", self.api_endpoint),
         };
 
         let items = serde_json::json!({
@@ -258,7 +415,7 @@ impl FileSystem for Client {
         });
 
         let req = self.req.clone().post(&url)
-            .header(AUTHORIZATION, self.client.bearer().await?)
+            .header(AUTHORIZATION, self.client.authorization().await?)
             .json(&items);
 
         let res = self.client.execute_with_retry(req)
@@ -278,8 +435,8 @@ impl FileSystem for Client {
     /// API Reference: [Create folder](https://learn.microsoft.com/en-us/onedrive/developer/rest-api/api/driveitem_post_children?view=odsp-graph-online)
     async fn create_file(&self, parent_id: Option<&str>, name: &str) -> Result<Self::File> {
         let url = match parent_id {
-            Some(p) => format!("{API_ENDPOINT}/items/{p}/children"),
-            None => format!("{API_ENDPOINT}/root/children"),
+            Some(p) => format!("{}/items/{p}/children", self.api_endpoint),
+            None => format!("{}/root/children", self.api_endpoint),
         };
 
         let items = serde_json::json!({
@@ -289,7 +446,7 @@ impl FileSystem for Client {
         });
 
         let req = self.req.clone().post(&url)
-            .header(AUTHORIZATION, self.client.bearer().await?)
+            .header(AUTHORIZATION, self.client.authorization().await?)
             .json(&items);
 
         let res = self.client.execute_with_retry(req)
@@ -308,35 +465,57 @@ impl FileSystem for Client {
     /// the children of the root directory.
     ///
     /// DriveItems with a non-null folder or package facet can have
-    /// one or more child DriveItems.
+    /// one or more child DriveItems. Every page of children is
+    /// fetched; see [list_files_paged](FileSystem::list_files_paged)
+    /// to process large folders page by page.
     ///
     /// API Reference: [List Children](https://learn.microsoft.com/en-us/onedrive/developer/rest-api/api/driveitem_list_children?view=odsp-graph-online)
     async fn list_files(&self, id: Option<&str>) -> Result<Vec<DriveItem>> {
-        let url = match id {
-            Some(p) => format!("{API_ENDPOINT}/items/{p}/children"),
-            None => format!("{API_ENDPOINT}/root/children"),
+        let mut items: Vec<DriveItem> = Vec::new();
+        let mut pages = self.list_files_paged(id);
+        while let Some(mut page) = pages.next().await? {
+            items.append(&mut page);
+        }
+
+        Ok(items)
+    }
+
+    /// List a single page of children of a [DriveItem]. The page
+    /// token is the page's `@odata.nextLink`.
+    ///
+    /// API Reference: [List Children](https://learn.microsoft.com/en-us/onedrive/developer/rest-api/api/driveitem_list_children?view=odsp-graph-online)
+    async fn list_files_page(&self, id: Option<&str>, page_token: Option<&str>) -> Result<Page<DriveItem>> {
+        let url = match (page_token, id) {
+            (Some(next_link), _) => next_link.to_string(),
+            (None, Some(p)) => format!("{}/items/{p}/children?$top={PAGE_SIZE}", self.api_endpoint),
+            (None, None) => format!("{}/root/children?$top={PAGE_SIZE}", self.api_endpoint),
         };
 
         let req = self.req.clone().get(&url)
-            .header(AUTHORIZATION, self.client.bearer().await?);
+            .header(AUTHORIZATION, self.client.authorization().await?);
 
         let res = self.client.execute_with_retry(req).await?
             .error_for_status()?;
 
         let json: Value = res.json().await?;
         let items: Vec<DriveItem> = from_value(json["value"].clone())?;
+        let next_link = json["@odata.nextLink"].as_str()
+            .map(|link| link.to_string());
 
-        Ok(items)
+        Ok((items, next_link))
     }
 
     /// Upload or replace the contents of a [DriveItem].
     ///
     /// API Reference: [Upload](https://learn.microsoft.com/en-us/onedrive/developer/rest-api/api/driveitem_createuploadsession?view=odsp-graph-online)
     async fn write_to_file(&self, id: &str, buf: &[u8]) -> Result<DriveItem> {
-        if buf.len() <= 4 * 1024 * 1024 {
+        if buf.len() <= SMALL_FILE_LIMIT {
             self.upload_small_file(id, buf).await
         } else {
-            self.upload_large_file(id, buf).await
+            let resumed = self.resume_session(id, buf.len() as u64).await?
+                .filter(|session| is_committed(session, buf));
+
+            self.upload_large_file(id, buf, buf.len() as u64, resumed).await
         }
     }
 
@@ -346,14 +525,32 @@ impl FileSystem for Client {
     ///
     /// API Reference: [Download](https://learn.microsoft.com/en-us/onedrive/developer/rest-api/api/driveitem_get_content?view=odsp-graph-online)
     async fn read_from_file(&self, id: &str) -> Result<Vec<u8>> {
-        let url = format!("{}/items/{}/content", API_ENDPOINT, id);
-        let req = self.req.clone().get(&url).header(AUTHORIZATION, self.client.bearer().await?);
-        let res = self.client.execute_with_retry(req).await?
-            .error_for_status()?;
-
-        let bytes = res.bytes().await?;
+        let bytes = self.download(id).await?.bytes().await?;
         Ok(bytes.to_vec())
     }
+
+    /// Upload the contents of a [DriveItem] from `reader`. Files
+    /// larger than 4MB are sent to an upload session in chunks.
+    ///
+    /// An interrupted upload is resumed if the content read so far
+    /// matches the content that was sent. Otherwise the upload fails
+    /// and its session is discarded, so the next upload starts over.
+    async fn write_from_reader<R>(&self, id: &str, mut reader: R, size: u64) -> Result<DriveItem>
+    where R: AsyncRead + Unpin + Send {
+        if size <= SMALL_FILE_LIMIT as u64 {
+            let buf = read_exact_chunk(&mut reader, size as usize).await?;
+            self.upload_small_file(id, &buf).await
+        } else {
+            let resumed = self.resume_session(id, size).await?;
+            self.upload_large_file(id, reader, size, resumed).await
+        }
+    }
+
+    /// Download the contents of a [DriveItem] as they are received.
+    async fn read_stream(&self, id: &str) -> Result<ByteStream<Error>> {
+        let res = self.download(id).await?;
+        Ok(Box::pin(response_stream(res)))
+    }
 }
 
 impl Delta for Client {
@@ -369,7 +566,7 @@ impl Delta for Client {
 
         // Paginated endpoint: initial request.
         let mut changes: Vec<DriveItem> = Vec::new();
-        let url = format!("{API_ENDPOINT}/root/delta");
+        let url = format!("{}/root/delta", self.api_endpoint);
 
         // Applying a delta omits changes that have already been viewed.
         let url = match delta {
@@ -378,7 +575,7 @@ impl Delta for Client {
         };
 
         let req = self.req.clone().get(&url)
-            .header(AUTHORIZATION, self.client.bearer().await?);
+            .header(AUTHORIZATION, self.client.authorization().await?);
 
         let res = self.client.execute_with_retry(req).await?
             .error_for_status()?;
@@ -395,7 +592,7 @@ impl Delta for Client {
             })?;
 
             let req = self.req.clone().get(url_str)
-                .header(AUTHORIZATION, self.client.bearer().await?);
+                .header(AUTHORIZATION, self.client.authorization().await?);
 
             let res = self.client.execute_with_retry(req).await?
                 .error_for_status()?;
@@ -419,36 +616,12 @@ impl Delta for Client {
 mod tests {
 
     use super::*;
-    use crate::oauth2;
-    use dotenv::dotenv;
-    use std::env;
-    use std::sync::Arc;
-    use tokio::sync::OnceCell;
-    static TOKEN: OnceCell<Arc<oauth2::Token>> = OnceCell::const_new();
-
-    async fn get_test_client() -> Client {
-        dotenv().ok();
-        let client_id = env::var("ONEDRIVE_CLIENT_ID")
-            .expect("missing ONEDRIVE_CLIENT_ID env variable");
-
-        let redirect_uri = env::var("ONEDRIVE_REDIRECT_URI")
-            .expect("missing ONEDRIVE_REDIRECT_URI env variable");
-
-        let refresh_token = env::var("ONEDRIVE_REFRESH_TOKEN")
-            .expect("missing ONEDRIVE_REFRESH_TOKEN env variable");
-
-        let app_config = oauth2::Config::onedrive(&client_id, &redirect_uri);
-        let token = TOKEN.get_or_init(|| async {
-            let token = oauth2::Token::from_refresh_token(&refresh_token, &app_config).await.unwrap();
-            Arc::new(token)
-        }).await.clone();
-
-        Client::new(&token, &app_config)
-    }
+    use crate::cloud::fake::FakeOneDrive;
 
     #[tokio::test]
     async fn test_get_file() {
-        let client = get_test_client().await;
+        let fake = FakeOneDrive::new().await;
+        let client = fake.client();
         let file = client.create_file(None, "helsync-get-file.txt")
             .await.unwrap();
 
@@ -457,9 +630,22 @@ mod tests {
         client.remove_file(&file.id).await.unwrap();
     }
 
+    #[tokio::test]
+    async fn test_get_root_id() {
+        let fake = FakeOneDrive::new().await;
+        let client = fake.client();
+        let file = client.create_file(None, "helsync-get-root-id.txt")
+            .await.unwrap();
+
+        let root_id = client.get_root_id().await.unwrap();
+        assert!(root_id.is_some());
+        assert_eq!(file.parent_reference.unwrap().id, root_id);
+    }
+
     #[tokio::test]
     async fn test_copy_file() {
-        let client = get_test_client().await;
+        let fake = FakeOneDrive::new().await;
+        let client = fake.client();
         let file = client.create_file(None, "helsync-copy-file.txt")
             .await.unwrap();
 
@@ -479,7 +665,8 @@ mod tests {
 
     #[tokio::test]
     async fn test_move_file() {
-        let client = get_test_client().await;
+        let fake = FakeOneDrive::new().await;
+        let client = fake.client();
         let child = client.create_file(None, "helsync-child-file.txt")
             .await.unwrap();
 
@@ -495,7 +682,8 @@ mod tests {
 
     #[tokio::test]
     async fn test_create_folder() {
-        let client = get_test_client().await;
+        let fake = FakeOneDrive::new().await;
+        let client = fake.client();
         let parent = client.create_folder(None, "helsync-test-folder")
             .await.unwrap();
 
@@ -508,7 +696,8 @@ mod tests {
 
     #[tokio::test]
     async fn test_list_files() {
-        let client = get_test_client().await;
+        let fake = FakeOneDrive::new().await;
+        let client = fake.client();
         let parent = client.create_folder(None, "helsync-test-list")
             .await.unwrap();
 
@@ -521,7 +710,8 @@ mod tests {
 
     #[tokio::test]
     async fn test_read_write_small() {
-        let client = get_test_client().await;
+        let fake = FakeOneDrive::new().await;
+        let client = fake.client();
         let file = client.create_file(None, "helsync-write-small.txt")
             .await.unwrap();
 
@@ -539,7 +729,8 @@ mod tests {
 
     #[tokio::test]
     async fn test_read_write_large() {
-        let client = get_test_client().await;
+        let fake = FakeOneDrive::new().await;
+        let client = fake.client();
         let file = client.create_file(None, "helsync-write-large.txt")
             .await.unwrap();
 
@@ -551,12 +742,14 @@ mod tests {
             .await.unwrap();
 
         assert!(file.name.unwrap() == "helsync-write-large.txt");
+        assert!(client.read_from_file(&file.id).await.unwrap() == buf);
         client.remove_file(&file.id).await.unwrap();
     }
 
     #[tokio::test]
     async fn test_list_deltas() {
-        let client = get_test_client().await;
+        let fake = FakeOneDrive::new().await;
+        let client = fake.client();
         let (_, token) = client.list_deltas(Some("latest"))
             .await.unwrap();
 
@@ -564,10 +757,213 @@ mod tests {
         let file = client.create_file(None, "helsync-track-changes.txt")
             .await.unwrap();
 
-        let (changes, _) = client.list_deltas(Some(&token)).await.unwrap();
+        let (changes, token) = client.list_deltas(Some(&token)).await.unwrap();
         let find = changes.iter().find(|f| f.id == file.id);
         assert!(find.is_some());
 
         client.remove_file(&file.id).await.unwrap();
+        let (changes, _) = client.list_deltas(Some(&token)).await.unwrap();
+        assert!(changes.len() == 1);
+        assert!(changes[0].id == file.id && changes[0].deleted.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_list_deltas_paged() {
+        let fake = FakeOneDrive::new().await;
+        let client = fake.client();
+        fake.set_page_size(2);
+
+        let (_, token) = client.list_deltas(Some("latest")).await.unwrap();
+        for i in 0..5 {
+            client.create_file(None, &format!("helsync-delta-{i}.txt"))
+                .await.unwrap();
+        }
+
+        let (changes, token) = client.list_deltas(Some(&token)).await.unwrap();
+        assert!(changes.len() == 5);
+
+        let (changes, _) = client.list_deltas(Some(&token)).await.unwrap();
+        assert!(changes.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_list_files_paged() {
+        let fake = FakeOneDrive::new().await;
+        let client = fake.client();
+        fake.set_page_size(2);
+
+        let parent = client.create_folder(None, "helsync-test-paged")
+            .await.unwrap();
+
+        for i in 0..5 {
+            client.create_file(Some(&parent.id), &format!("helsync-page-{i}.txt"))
+                .await.unwrap();
+        }
+
+        let (page, next_link) = client.list_files_page(Some(&parent.id), None)
+            .await.unwrap();
+
+        assert!(page.len() == 2);
+        assert!(next_link.is_some());
+
+        let files = client.list_files(Some(&parent.id)).await.unwrap();
+        assert!(files.len() == 5);
+    }
+
+    #[tokio::test]
+    async fn test_get_missing_file() {
+        let fake = FakeOneDrive::new().await;
+        let client = fake.client();
+        assert!(client.get_file("missing").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_retry_server_error() {
+        let fake = FakeOneDrive::new().await;
+        let client = fake.client();
+        let file = client.create_file(None, "helsync-retry.txt")
+            .await.unwrap();
+
+        // A transient failure is retried.
+        fake.fail_next(503, 1);
+        let requests = fake.requests();
+        assert!(client.get_file(&file.id).await.is_ok());
+        assert!(fake.requests() == requests + 2);
+
+        // Persistent failures are reported.
+        fake.fail_next(500, 3);
+        assert!(client.get_file(&file.id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_refresh_token() {
+        let fake = FakeOneDrive::new().await;
+        let mut token = fake.token();
+        token.expires_in = 0;
+
+        // An expired token is refreshed against the token endpoint.
+        fake.revoke_token();
+        let client = Client::new(&token, &fake.config())
+            .with_api_endpoint(&format!("{}/drive", fake.url()));
+
+        let folder = client.create_folder(None, "helsync-refresh")
+            .await.unwrap();
+
+        assert!(folder.name.unwrap() == "helsync-refresh");
+    }
+
+    #[tokio::test]
+    async fn test_stream() {
+        use crate::core::ProgressReader;
+        use futures_util::StreamExt;
+
+        let fake = FakeOneDrive::new().await;
+        let client = fake.client();
+        let file = client.create_file(None, "helsync-stream.txt")
+            .await.unwrap();
+
+        // Large enough to be uploaded in chunks.
+        let buf: Vec<u8> = (0..SMALL_FILE_LIMIT + 1).map(|i| (i % 251) as u8).collect();
+        let mut sent = 0;
+        let reader = ProgressReader::new(&buf[..], |n| sent = n);
+        let file = client.write_from_reader(&file.id, reader, buf.len() as u64)
+            .await.unwrap();
+
+        assert!(file.name.unwrap() == "helsync-stream.txt");
+        assert_eq!(sent, buf.len() as u64);
+
+        let mut chunks = client.read_stream(&file.id).await.unwrap();
+        let mut read = Vec::new();
+        while let Some(chunk) = chunks.next().await {
+            read.extend_from_slice(&chunk.unwrap());
+        }
+        assert!(read == buf);
+
+        // Small files are uploaded in a single request.
+        client.write_from_reader(&file.id, &b"Hello, World!"[..], 13)
+            .await.unwrap();
+
+        assert!(client.read_from_file(&file.id).await.unwrap() == b"Hello, World!");
+        client.remove_file(&file.id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_resume_upload() {
+        use crate::cloud::fake::FakeUploadStore;
+
+        let fake = FakeOneDrive::new().await;
+        let store = Arc::new(FakeUploadStore::default());
+        let client = fake.client()
+            .with_retry_policy(RetryPolicy::none())
+            .with_upload_store(store.clone());
+
+        let file = client.create_file(None, "helsync-resume.txt")
+            .await.unwrap();
+
+        let buf: Vec<u8> = (0..SMALL_FILE_LIMIT + 1).map(|i| (i % 251) as u8).collect();
+        fake.set_upload_limit(Some(buf.len() / 2));
+        assert!(client.write_to_file(&file.id, &buf).await.is_err());
+
+        // The session outlives the interrupted upload.
+        let session = store.session(&file.id).unwrap();
+        assert!(session.committed > 0);
+        assert!(session.committed < buf.len() as u64);
+
+        fake.set_upload_limit(None);
+        let requests = fake.requests();
+        let file = client.write_to_file(&file.id, &buf).await.unwrap();
+        assert!(file.name.unwrap() == "helsync-resume.txt");
+        assert!(store.session(&file.id).is_none());
+
+        // Only the remaining chunks were sent.
+        let chunks = buf.len().div_ceil(CHUNK_SIZE as usize);
+        assert!(fake.requests() - requests < chunks);
+        assert!(client.read_from_file(&file.id).await.unwrap() == buf);
+        client.remove_file(&file.id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_resume_stream() {
+        use crate::cloud::fake::FakeUploadStore;
+
+        let fake = FakeOneDrive::new().await;
+        let store = Arc::new(FakeUploadStore::default());
+        let client = fake.client()
+            .with_retry_policy(RetryPolicy::none())
+            .with_upload_store(store.clone());
+
+        let file = client.create_file(None, "helsync-resume-stream.txt")
+            .await.unwrap();
+
+        let buf: Vec<u8> = (0..SMALL_FILE_LIMIT + 1).map(|i| (i % 251) as u8).collect();
+        let size = buf.len() as u64;
+        fake.set_upload_limit(Some(buf.len() / 2));
+        assert!(client.write_from_reader(&file.id, &buf[..], size).await.is_err());
+        assert!(store.session(&file.id).unwrap().committed > 0);
+
+        // The stream is resumed from the range received by the server.
+        fake.set_upload_limit(None);
+        let requests = fake.requests();
+        let file = client.write_from_reader(&file.id, &buf[..], size).await.unwrap();
+        assert!(file.name.unwrap() == "helsync-resume-stream.txt");
+        assert!(store.session(&file.id).is_none());
+
+        let chunks = buf.len().div_ceil(CHUNK_SIZE as usize);
+        assert!(fake.requests() - requests < chunks);
+        assert!(client.read_from_file(&file.id).await.unwrap() == buf);
+
+        // A stream of different content is not appended to the
+        // interrupted upload, which is started over instead.
+        fake.set_upload_limit(Some(buf.len() / 2));
+        assert!(client.write_from_reader(&file.id, &buf[..], size).await.is_err());
+
+        fake.set_upload_limit(None);
+        let changed: Vec<u8> = buf.iter().map(|byte| byte.wrapping_add(1)).collect();
+        assert!(client.write_from_reader(&file.id, &changed[..], size).await.is_err());
+        assert!(store.session(&file.id).is_none());
+
+        client.write_from_reader(&file.id, &changed[..], size).await.unwrap();
+        assert!(client.read_from_file(&file.id).await.unwrap() == changed);
+        client.remove_file(&file.id).await.unwrap();
     }
 }
@@ -63,6 +63,31 @@ pub struct FileMetadata {
     /// server and might not be the value provided when the file was
     /// uploaded. Read-only.
     pub mime_type: Option<String>,
+
+    /// Hashes of the file's binary content, if available. Read-only.
+    pub hashes: Option<Hashes>,
+}
+
+/// The Hashes resource groups available hashes into a single
+/// structure for an item.
+///
+/// Not all services provide a value for all hash properties listed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Hashes {
+
+    /// A CRC32 value of the file in little endian (if available).
+    pub crc32_hash: Option<String>,
+
+    /// A proprietary hash of the file that can be used to determine
+    /// if the contents of the file have changed (if available).
+    pub quick_xor_hash: Option<String>,
+
+    /// SHA1 hash for the contents of the file (if available).
+    pub sha1_hash: Option<String>,
+
+    /// SHA256 hash for the contents of the file (if available).
+    pub sha256_hash: Option<String>,
 }
 
 /// The Folder resource groups folder-related data on an item into a
@@ -119,6 +144,10 @@ impl Into<File> for DriveItem {
         let parent_id = self.parent_reference.map(|file| file.id)
             .unwrap_or(None);
 
+        let hashes = self.file.and_then(|file| file.hashes);
+        let sha1 = hashes.as_ref().and_then(|h| h.sha1_hash.clone());
+        let sha256 = hashes.and_then(|h| h.sha256_hash);
+
         File {
             id: self.id,
             name: self.name.unwrap_or("Untitled".to_string()),
@@ -127,6 +156,9 @@ impl Into<File> for DriveItem {
             parent_id,
             is_folder: self.folder.is_some(),
             is_deleted: self.deleted.is_some(),
+            sha1,
+            sha256,
+            size: self.size.and_then(|size| u64::try_from(size).ok()),
         }
     }
 }
@@ -0,0 +1,829 @@
+use crate::core::{RetryPolicy, Result, Error, ClientError, FileSystem, Delta, Page};
+use super::sigv4::{self, Credentials};
+use super::error::S3Error;
+use super::file::Object;
+use super::xml::{self, Contents, ListBucketResult};
+
+use reqwest::header::{HeaderMap, AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE, ETAG, LAST_MODIFIED};
+use reqwest::{Method, Response, StatusCode, Url};
+use chrono::DateTime;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// Region used to sign requests unless overridden. S3-compatible
+/// services that ignore regions (e.g. MinIO) accept it.
+pub const DEFAULT_REGION: &str = "us-east-1";
+
+/// Maximum size of objects uploaded in a single request. Larger
+/// objects are uploaded in parts using a multipart upload.
+const SMALL_FILE_LIMIT: usize = 8 * 1024 * 1024;
+
+/// Size of the parts of a multipart upload. Every part but the last
+/// must be at least 5MB.
+const PART_SIZE: usize = 5 * 1024 * 1024;
+
+/// Maximum number of keys requested per page of a listing.
+const PAGE_SIZE: usize = 1000;
+
+/// Prefix of delta tokens that hold a [Manifest].
+const MANIFEST_PREFIX: &str = "etags:";
+
+/// Maps the ID of every object seen by a listing to its version
+/// (its ETag, or its modification date if the service doesn't
+/// report ETags) and whether it is a folder.
+type Manifest = BTreeMap<String, (Option<String>, bool)>;
+
+/// Implements an S3 client, which also supports S3-compatible
+/// services such as MinIO, Cloudflare R2 and Backblaze B2.
+pub struct Client {
+    client: crate::core::Client,
+    req: Arc<reqwest::Client>,
+    endpoint: String,
+    bucket: String,
+    prefix: String,
+    credentials: Credentials,
+}
+
+impl Client {
+
+    /// Instantiate a new S3 client for the `bucket` hosted at
+    /// `endpoint` (e.g. `https://s3.us-east-1.amazonaws.com`),
+    /// signing requests with the given access key.
+    ///
+    /// Buckets are addressed by path (`{endpoint}/{bucket}/{key}`),
+    /// which every S3-compatible service supports.
+    pub fn new(endpoint: &str, bucket: &str, access_key_id: &str, secret_access_key: &str) -> Self {
+        Self {
+            client: crate::core::Client::signed(),
+            req: Arc::new(reqwest::Client::new()),
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            bucket: bucket.to_string(),
+            prefix: String::new(),
+            credentials: Credentials {
+                access_key_id: access_key_id.to_string(),
+                secret_access_key: secret_access_key.to_string(),
+                region: DEFAULT_REGION.to_string(),
+            },
+        }
+    }
+
+    /// Overrides the region that requests are signed for (defaults
+    /// to [DEFAULT_REGION]). Cloudflare R2 expects `auto`.
+    pub fn with_region(mut self, region: &str) -> Self {
+        self.credentials.region = region.to_string();
+        self
+    }
+
+    /// Stores objects below the key `prefix` (e.g. `notes/`)
+    /// instead of the bucket's root, so that the bucket can be
+    /// shared with other applications.
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        let prefix = prefix.trim_matches('/');
+        self.prefix = match prefix.is_empty() {
+            true => String::new(),
+            false => format!("{prefix}/"),
+        };
+
+        self
+    }
+
+    /// Overrides the [RetryPolicy] used for requests.
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.client = self.client.with_retry_policy(policy);
+        self
+    }
+
+    /// Formats the key of the object with `id`.
+    fn key_of(&self, id: &str) -> String {
+        format!("{}{id}", self.prefix)
+    }
+
+    /// Formats the key prefix of the members of the folder with
+    /// `id`, or of the root folder if `id` is `None`.
+    fn folder_prefix(&self, id: Option<&str>) -> String {
+        match id {
+            Some(id) if !id.is_empty() => format!("{}{id}/", self.prefix),
+            _ => self.prefix.clone(),
+        }
+    }
+
+    /// Converts a key to an object ID.
+    fn id_of(&self, key: &str) -> String {
+        key.strip_prefix(&self.prefix).unwrap_or(key)
+            .trim_end_matches('/')
+            .to_string()
+    }
+
+    /// Signs and sends a request for the object with `key`, or for
+    /// the bucket if `key` is empty, retrying according to the
+    /// client's [RetryPolicy].
+    async fn execute(&self, method: Method, key: &str, query: &[(&str, &str)], headers: &[(&str, &str)], body: Vec<u8>) -> Result<Response> {
+        let mut path = format!("/{}", sigv4::encode(&self.bucket));
+        if !key.is_empty() {
+            let key: Vec<String> = key.split('/').map(sigv4::encode).collect();
+            path = format!("{path}/{}", key.join("/"));
+        }
+
+        let query = sigv4::canonical_query(query);
+        let url = match query.is_empty() {
+            true => format!("{}{path}", self.endpoint),
+            false => format!("{}{path}?{query}", self.endpoint),
+        };
+
+        let url = Url::parse(&url).map_err(|err| S3Error {
+            code: "INVALID_ENDPOINT".to_string(),
+            message: err.to_string(),
+        })?;
+
+        let host = match url.port() {
+            Some(port) => format!("{}:{port}", url.host_str().unwrap_or_default()),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+
+        let payload_hash = sigv4::sha256_hex(&body);
+        let mut signed = BTreeMap::from([
+            ("host".to_string(), host),
+            ("x-amz-content-sha256".to_string(), payload_hash.clone()),
+            ("x-amz-date".to_string(), chrono::Utc::now().format("%Y%m%dT%H%M%SZ").to_string()),
+        ]);
+
+        signed.extend(headers.iter()
+            .map(|(name, value)| (name.to_lowercase(), value.to_string())));
+
+        let authorization = sigv4::authorization(&self.credentials, method.as_str(),
+            url.path(), &query, &signed, &payload_hash);
+
+        let mut req = self.req.clone().request(method, url)
+            .header(AUTHORIZATION, authorization)
+            .body(body);
+
+        // The HTTP client sets the host header itself.
+        for (name, value) in signed.iter().filter(|(name, _)| *name != "host") {
+            req = req.header(name, value);
+        }
+
+        self.client.execute_with_retry(req).await
+    }
+
+    /// Like [Self::execute], but fails on error responses.
+    async fn send(&self, method: Method, key: &str, query: &[(&str, &str)], headers: &[(&str, &str)], body: Vec<u8>) -> Result<Response> {
+        let res = self.execute(method, key, query, headers, body).await?;
+        let status = res.status();
+        if !status.is_client_error() && !status.is_server_error() {
+            return Ok(res);
+        }
+
+        let url = res.url().to_string();
+        let body = res.text().await.unwrap_or_default();
+        Err(match xml::parse_error(&body) {
+            Some(err) => err.into(),
+            None => Error::Client(ClientError {
+                status: Some(status.as_u16()),
+                message: "received error status response".to_string(),
+                url: Some(url),
+            }),
+        })
+    }
+
+    /// Fetches the metadata of the object with `key`, or `None` if
+    /// there is no such object.
+    ///
+    /// Reference: [HeadObject](https://docs.aws.amazon.com/AmazonS3/latest/API/API_HeadObject.html)
+    async fn head(&self, key: &str) -> Result<Option<Object>> {
+        let res = self.execute(Method::HEAD, key, &[], &[], Vec::new()).await?;
+        if res.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        let res = res.error_for_status()?;
+        let headers = res.headers();
+        let id = self.id_of(key);
+        Ok(Some(Object {
+            name: id.rsplit('/').next().unwrap_or_default().to_string(),
+            id,
+            is_folder: key.ends_with('/'),
+            is_deleted: false,
+            etag: header(headers, ETAG.as_str()),
+            size: header(headers, CONTENT_LENGTH.as_str()).and_then(|size| size.parse().ok()),
+            last_modified: header(headers, LAST_MODIFIED.as_str())
+                .and_then(|ts| DateTime::parse_from_rfc2822(&ts).ok())
+                .map(|dt| dt.to_rfc3339()),
+        }))
+    }
+
+    /// Fetches a page of the keys starting with `prefix`. If
+    /// `delimiter` is set, keys that continue below a folder are
+    /// grouped into common prefixes.
+    ///
+    /// Reference: [ListObjectsV2](https://docs.aws.amazon.com/AmazonS3/latest/API/API_ListObjectsV2.html)
+    async fn list_page(&self, prefix: &str, delimiter: bool, continuation_token: Option<&str>) -> Result<ListBucketResult> {
+        let page_size = PAGE_SIZE.to_string();
+        let mut query = vec![("list-type", "2"), ("prefix", prefix), ("max-keys", page_size.as_str())];
+        if delimiter {
+            query.push(("delimiter", "/"));
+        }
+
+        if let Some(token) = continuation_token {
+            query.push(("continuation-token", token));
+        }
+
+        let res = self.send(Method::GET, "", &query, &[], Vec::new()).await?;
+        xml::parse_list(&res.text().await?)
+    }
+
+    /// Lists every key starting with `prefix`, including the keys
+    /// of nested folders.
+    async fn list_all(&self, prefix: &str) -> Result<Vec<Contents>> {
+        let mut contents = Vec::new();
+        let mut token: Option<String> = None;
+        loop {
+            let page = self.list_page(prefix, false, token.as_deref()).await?;
+            contents.extend(page.contents);
+            token = page.next_continuation_token;
+            if token.is_none() {
+                return Ok(contents);
+            }
+        }
+    }
+
+    /// Creates an object, replacing any object with the same key.
+    ///
+    /// Reference: [PutObject](https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutObject.html)
+    async fn put(&self, key: &str, buf: &[u8]) -> Result<()> {
+        let headers = [(CONTENT_TYPE.as_str(), "application/octet-stream")];
+        self.send(Method::PUT, key, &[], &headers, buf.to_vec()).await?;
+        Ok(())
+    }
+
+    /// Copies the object with key `source` to `target`.
+    ///
+    /// Reference: [CopyObject](https://docs.aws.amazon.com/AmazonS3/latest/API/API_CopyObject.html)
+    async fn copy_object(&self, source: &str, target: &str) -> Result<()> {
+        let source: Vec<String> = source.split('/').map(sigv4::encode).collect();
+        let source = format!("/{}/{}", sigv4::encode(&self.bucket), source.join("/"));
+        self.send(Method::PUT, target, &[], &[("x-amz-copy-source", &source)], Vec::new()).await?;
+        Ok(())
+    }
+
+    /// Uploads large objects (> 8MB) in parts. The upload is aborted
+    /// if any part fails, so that the uploaded parts are discarded.
+    ///
+    /// Reference: [Multipart Upload](https://docs.aws.amazon.com/AmazonS3/latest/userguide/mpuoverview.html)
+    async fn upload_multipart(&self, key: &str, buf: &[u8]) -> Result<()> {
+        let res = self.send(Method::POST, key, &[("uploads", "")], &[], Vec::new()).await?;
+        let upload_id = xml::parse_upload_id(&res.text().await?)?;
+        let res = self.upload_parts(key, &upload_id, buf).await;
+        if res.is_err() {
+            let query = [("uploadId", upload_id.as_str())];
+            let _ = self.execute(Method::DELETE, key, &query, &[], Vec::new()).await;
+        }
+
+        res
+    }
+
+    /// Uploads the parts of a multipart upload and completes it.
+    async fn upload_parts(&self, key: &str, upload_id: &str, buf: &[u8]) -> Result<()> {
+        let mut parts = String::new();
+        for (i, chunk) in buf.chunks(PART_SIZE).enumerate() {
+            let number = (i + 1).to_string();
+            let query = [("partNumber", number.as_str()), ("uploadId", upload_id)];
+            let res = self.send(Method::PUT, key, &query, &[], chunk.to_vec()).await?;
+            let etag = header(res.headers(), ETAG.as_str()).unwrap_or_default();
+            parts.push_str(&format!("<Part><PartNumber>{number}</PartNumber><ETag>{}</ETag></Part>",
+                xml::escape(&etag)));
+        }
+
+        let body = format!("<CompleteMultipartUpload>{parts}</CompleteMultipartUpload>");
+        let headers = [(CONTENT_TYPE.as_str(), "application/xml")];
+        self.send(Method::POST, key, &[("uploadId", upload_id)], &headers, body.into_bytes()).await?;
+        Ok(())
+    }
+
+    /// Copies the object or folder with `source_id`, returning the
+    /// copy. A folder is copied along with its members.
+    async fn copy(&self, source_id: &str, parent_id: Option<&str>, name: Option<&str>) -> Result<Object> {
+        let source = self.get_file(source_id).await?;
+        let name = name.unwrap_or(&source.name);
+        let target_id = child_id(parent_id, name);
+        if !source.is_folder {
+            self.copy_object(&self.key_of(source_id), &self.key_of(&target_id)).await?;
+            return self.get_file(&target_id).await;
+        }
+
+        let source_prefix = self.folder_prefix(Some(source_id));
+        let target_prefix = self.folder_prefix(Some(&target_id));
+        self.put(&target_prefix, &[]).await?;
+        for contents in self.list_all(&source_prefix).await? {
+            let rest = &contents.key[source_prefix.len()..];
+            if !rest.is_empty() {
+                self.copy_object(&contents.key, &format!("{target_prefix}{rest}")).await?;
+            }
+        }
+
+        self.get_file(&target_id).await
+    }
+
+    /// Lists changes by comparing every object's version against
+    /// the `previous` manifest.
+    async fn scan(&self, previous: &Manifest) -> Result<(Vec<Object>, String)> {
+        let mut objects: BTreeMap<String, Object> = BTreeMap::new();
+        for contents in self.list_all(&self.prefix).await? {
+            let id = self.id_of(&contents.key);
+            if id.is_empty() {
+                continue;
+            }
+
+            // Folders may exist only as the prefix of their members.
+            let mut parent = id.as_str();
+            while let Some((ancestor, _)) = parent.rsplit_once('/') {
+                objects.entry(ancestor.to_string())
+                    .or_insert_with(|| Object::folder(ancestor));
+
+                parent = ancestor;
+            }
+
+            let object = Object {
+                name: id.rsplit('/').next().unwrap_or_default().to_string(),
+                id: id.clone(),
+                is_folder: contents.key.ends_with('/'),
+                is_deleted: false,
+                etag: contents.etag,
+                size: contents.size,
+                last_modified: contents.last_modified,
+            };
+
+            objects.insert(id, object);
+        }
+
+        let manifest: Manifest = objects.iter()
+            .map(|(id, object)| {
+                let version = object.etag.clone().or(object.last_modified.clone());
+                (id.clone(), (version, object.is_folder))
+            })
+            .collect();
+
+        let mut changes: Vec<Object> = objects.into_values()
+            .filter(|object| previous.get(&object.id) != manifest.get(&object.id))
+            .collect();
+
+        // Objects that are no longer listed have been deleted.
+        for (id, (_, is_folder)) in previous {
+            if !manifest.contains_key(id) {
+                changes.push(Object::deleted(id, *is_folder));
+            }
+        }
+
+        let token = format!("{MANIFEST_PREFIX}{}", serde_json::to_string(&manifest)?);
+        Ok((changes, token))
+    }
+}
+
+impl FileSystem for Client {
+    type File = Object;
+    type Error = Error;
+
+    /// Retrieve the metadata of an object or folder.
+    ///
+    /// Folders are found by their marker object or, failing that,
+    /// by the keys of their members.
+    async fn get_file(&self, id: &str) -> Result<Object> {
+        if let Some(object) = self.head(&self.key_of(id)).await? {
+            return Ok(object);
+        }
+
+        let prefix = self.folder_prefix(Some(id));
+        if let Some(folder) = self.head(&prefix).await? {
+            return Ok(folder);
+        }
+
+        let page = self.list_page(&prefix, false, None).await?;
+        match page.contents.is_empty() {
+            true => Err(S3Error {
+                code: "NoSuchKey".to_string(),
+                message: format!("no object or folder with id {id:?}"),
+            }.into()),
+            false => Ok(Object::folder(id)),
+        }
+    }
+
+    /// Copy an object or folder.
+    ///
+    /// Copies the object with id `source_id` to the folder
+    /// `parent_id`. If `parent_id` is `None`, the object is copied
+    /// to the root folder. Optionally specifying a `name` will
+    /// rename the copied object.
+    ///
+    /// Reference: [CopyObject](https://docs.aws.amazon.com/AmazonS3/latest/API/API_CopyObject.html)
+    async fn copy_file(&self, source_id: &str, parent_id: Option<&str>, name: Option<&str>) -> Result<Object> {
+        self.copy(source_id, parent_id, name).await
+    }
+
+    /// Move an object or folder to a new folder.
+    ///
+    /// S3 can't rename keys, so the object is copied and then
+    /// deleted. Since objects are identified by their key, the moved
+    /// object has a new ID.
+    async fn move_file(&self, source_id: &str, parent_id: Option<&str>, name: Option<&str>) -> Result<Object> {
+        let object = self.copy(source_id, parent_id, name).await?;
+        self.remove_file(source_id).await?;
+        Ok(object)
+    }
+
+    /// Delete an object or folder, along with its members.
+    ///
+    /// Reference: [DeleteObject](https://docs.aws.amazon.com/AmazonS3/latest/API/API_DeleteObject.html)
+    async fn remove_file(&self, id: &str) -> Result<()> {
+        self.send(Method::DELETE, &self.key_of(id), &[], &[], Vec::new()).await?;
+        for contents in self.list_all(&self.folder_prefix(Some(id))).await? {
+            self.send(Method::DELETE, &contents.key, &[], &[], Vec::new()).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Create a new folder by uploading its marker object.
+    ///
+    /// If `parent_id` is `None`, the folder is created in the root
+    /// folder.
+    async fn create_folder(&self, parent_id: Option<&str>, name: &str) -> Result<Object> {
+        let id = child_id(parent_id, name);
+        self.put(&self.folder_prefix(Some(&id)), &[]).await?;
+        self.get_file(&id).await
+    }
+
+    /// Create a new, empty object, replacing any object with the
+    /// same key.
+    async fn create_file(&self, parent_id: Option<&str>, name: &str) -> Result<Object> {
+        let id = child_id(parent_id, name);
+        self.write_to_file(&id, &[]).await
+    }
+
+    /// List the immediate members of a folder. Set `parent_id` to
+    /// `None` to list the members of the root folder.
+    async fn list_files(&self, parent_id: Option<&str>) -> Result<Vec<Object>> {
+        let mut objects = Vec::new();
+        let mut pages = self.list_files_paged(parent_id);
+        while let Some(page) = pages.next().await? {
+            objects.extend(page);
+        }
+
+        Ok(objects)
+    }
+
+    /// List a page of the immediate members of a folder, grouping
+    /// the keys of nested folders by their common prefix.
+    ///
+    /// Reference: [ListObjectsV2](https://docs.aws.amazon.com/AmazonS3/latest/API/API_ListObjectsV2.html)
+    async fn list_files_page(&self, parent_id: Option<&str>, page_token: Option<&str>) -> Result<Page<Object>> {
+        let prefix = self.folder_prefix(parent_id);
+        let page = self.list_page(&prefix, true, page_token).await?;
+        let folders = page.common_prefixes.iter()
+            .map(|prefix| Object::folder(&self.id_of(prefix)));
+
+        // The folder's own marker object is listed too.
+        let files = page.contents.into_iter()
+            .filter(|contents| contents.key != prefix)
+            .map(|contents| {
+                let id = self.id_of(&contents.key);
+                Object {
+                    name: id.rsplit('/').next().unwrap_or_default().to_string(),
+                    id,
+                    is_folder: false,
+                    is_deleted: false,
+                    etag: contents.etag,
+                    size: contents.size,
+                    last_modified: contents.last_modified,
+                }
+            });
+
+        let objects = folders.chain(files).collect();
+        Ok((objects, page.next_continuation_token))
+    }
+
+    /// Upload or replace the contents of an object.
+    async fn write_to_file(&self, id: &str, buf: &[u8]) -> Result<Object> {
+        let key = self.key_of(id);
+        if buf.len() <= SMALL_FILE_LIMIT {
+            self.put(&key, buf).await?;
+        } else {
+            self.upload_multipart(&key, buf).await?;
+        }
+
+        self.get_file(id).await
+    }
+
+    /// Download the contents of an object.
+    ///
+    /// Reference: [GetObject](https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetObject.html)
+    async fn read_from_file(&self, id: &str) -> Result<Vec<u8>> {
+        let res = self.send(Method::GET, &self.key_of(id), &[], &[], Vec::new()).await?;
+        let bytes = res.bytes().await?;
+        Ok(bytes.to_vec())
+    }
+}
+
+impl Delta for Client {
+    type File = Object;
+
+    /// Track changes below the client's prefix.
+    ///
+    /// S3 has no change feed, so every key is listed and compared
+    /// against a manifest of ETags, which the returned token holds.
+    /// If `token` is `None`, every object is listed.
+    async fn list_deltas(&self, token: Option<&str>) -> Result<(Vec<Object>, String)> {
+        let Some(token) = token else {
+            return self.scan(&Manifest::new()).await;
+        };
+
+        match token.strip_prefix(MANIFEST_PREFIX) {
+            Some(manifest) => self.scan(&serde_json::from_str(manifest)?).await,
+            None => Err(S3Error {
+                code: "INVALID_TOKEN".to_string(),
+                message: format!("unrecognized delta token {token:?}"),
+            }.into()),
+        }
+    }
+}
+
+/// Formats the ID of the object called `name` in the folder
+/// `parent_id`.
+fn child_id(parent_id: Option<&str>, name: &str) -> String {
+    match parent_id {
+        Some(parent_id) if !parent_id.is_empty() => format!("{parent_id}/{name}"),
+        _ => name.to_string(),
+    }
+}
+
+fn header(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers.get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::cloud::fake::FakeS3;
+
+    #[tokio::test]
+    async fn test_get_file() {
+        let fake = FakeS3::new().await;
+        let client = fake.client();
+        let parent = client.create_folder(None, "helsync parent")
+            .await.unwrap();
+
+        let file = client.create_file(Some(&parent.id), "helsync-get-file.txt")
+            .await.unwrap();
+
+        let res = client.get_file(&file.id).await.unwrap();
+        assert!(res.id == "helsync parent/helsync-get-file.txt");
+        assert!(res.name == "helsync-get-file.txt");
+        assert!(res.parent_id() == Some(parent.id.clone()));
+        assert!(!res.is_folder && res.etag.is_some());
+
+        let res = client.get_file(&parent.id).await.unwrap();
+        assert!(res.is_folder && res.name == "helsync parent");
+        assert!(client.get_file("missing").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_implicit_folder() {
+        let fake = FakeS3::new().await;
+        let client = fake.client();
+
+        // Folders needn't have a marker object.
+        let file = client.create_file(Some("a/b"), "helsync-file.txt")
+            .await.unwrap();
+
+        assert!(file.parent_id().as_deref() == Some("a/b"));
+        assert!(client.get_file("a/b").await.unwrap().is_folder);
+
+        let files = client.list_files(Some("a")).await.unwrap();
+        assert!(files.len() == 1 && files[0].id == "a/b" && files[0].is_folder);
+    }
+
+    #[tokio::test]
+    async fn test_copy_file() {
+        let fake = FakeS3::new().await;
+        let client = fake.client();
+        let file = client.create_file(None, "helsync-copy-file.txt")
+            .await.unwrap();
+
+        client.write_to_file(&file.id, b"Hello, World!").await.unwrap();
+        let copied = client.copy_file(&file.id, None, Some("new-name.txt"))
+            .await.unwrap();
+
+        assert!(copied.id == "new-name.txt");
+        assert!(client.read_from_file(&copied.id).await.unwrap() == b"Hello, World!");
+        assert!(client.read_from_file(&file.id).await.unwrap() == b"Hello, World!");
+    }
+
+    #[tokio::test]
+    async fn test_move_file() {
+        let fake = FakeS3::new().await;
+        let client = fake.client();
+        let child = client.create_file(None, "helsync-child-file.txt")
+            .await.unwrap();
+
+        let parent = client.create_folder(None, "helsync-parent")
+            .await.unwrap();
+
+        let moved = client.move_file(&child.id, Some(&parent.id), None)
+            .await.unwrap();
+
+        assert!(moved.id == "helsync-parent/helsync-child-file.txt");
+        assert!(moved.parent_id() == Some(parent.id));
+        assert!(client.get_file(&child.id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_move_folder() {
+        let fake = FakeS3::new().await;
+        let client = fake.client();
+        let folder = client.create_folder(None, "helsync-folder")
+            .await.unwrap();
+
+        let file = client.create_file(Some(&folder.id), "helsync-file.txt")
+            .await.unwrap();
+
+        client.write_to_file(&file.id, b"content").await.unwrap();
+        let moved = client.move_file(&folder.id, None, Some("helsync-renamed"))
+            .await.unwrap();
+
+        assert!(moved.is_folder && moved.id == "helsync-renamed");
+        assert!(fake.keys() == ["helsync-renamed/", "helsync-renamed/helsync-file.txt"]);
+        let content = client.read_from_file("helsync-renamed/helsync-file.txt")
+            .await.unwrap();
+
+        assert!(content == b"content");
+    }
+
+    #[tokio::test]
+    async fn test_create_folder() {
+        let fake = FakeS3::new().await;
+        let client = fake.client();
+        let parent = client.create_folder(None, "helsync-test-folder")
+            .await.unwrap();
+
+        assert!(parent.name == "helsync-test-folder");
+        assert!(parent.is_folder);
+
+        let child = client.create_folder(Some(&parent.id), "helsync-child")
+            .await.unwrap();
+
+        client.create_file(Some(&child.id), "helsync-file.txt").await.unwrap();
+        client.remove_file(&parent.id).await.unwrap();
+        assert!(client.get_file(&child.id).await.is_err());
+        assert!(fake.keys().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_list_files() {
+        let fake = FakeS3::new().await;
+        let client = fake.client();
+        let parent = client.create_folder(None, "helsync-test-list")
+            .await.unwrap();
+
+        for i in 0..5 {
+            client.create_file(Some(&parent.id), &format!("helsync-{i}.txt"))
+                .await.unwrap();
+        }
+
+        client.create_folder(Some(&parent.id), "helsync-child").await.unwrap();
+
+        let files = client.list_files(None).await.unwrap();
+        assert!(files.len() == 1 && files[0].id == parent.id);
+
+        // Listings are paginated.
+        fake.set_page_size(2);
+        let files = client.list_files(Some(&parent.id)).await.unwrap();
+        assert!(files.len() == 6);
+        assert!(files.iter().filter(|f| f.is_folder).count() == 1);
+        assert!(files.iter().all(|f| f.parent_id() == Some(parent.id.clone())));
+    }
+
+    #[tokio::test]
+    async fn test_read_write() {
+        let fake = FakeS3::new().await;
+        let client = fake.client();
+
+        // Keys are percent-encoded in URLs and signatures.
+        let file = client.create_file(None, "helsync wríte #1+2.txt")
+            .await.unwrap();
+
+        let buf = "Hello, World!".as_bytes();
+        let file = client.write_to_file(&file.id, buf)
+            .await.unwrap();
+
+        assert!(file.name == "helsync wríte #1+2.txt");
+        assert!(file.size == Some(buf.len() as u64));
+        assert!(client.read_from_file(&file.id).await.unwrap() == buf);
+    }
+
+    #[tokio::test]
+    async fn test_read_write_large() {
+        let fake = FakeS3::new().await;
+        let client = fake.client();
+        let buf: Vec<u8> = (0..SMALL_FILE_LIMIT + 1).map(|i| i as u8).collect();
+        let file = client.write_to_file("helsync-write-large.txt", &buf)
+            .await.unwrap();
+
+        assert!(file.size == Some(buf.len() as u64));
+        assert!(client.read_from_file(&file.id).await.unwrap() == buf);
+        assert!(fake.pending_uploads() == 0);
+    }
+
+    #[tokio::test]
+    async fn test_abort_multipart_upload() {
+        let fake = FakeS3::new().await;
+        let client = fake.client().with_retry_policy(RetryPolicy::none());
+        let buf = vec![0; SMALL_FILE_LIMIT + 1];
+
+        // Fail the upload of the second part.
+        let write = client.write_to_file("helsync-abort.txt", &buf);
+        let fail = async {
+            while fake.pending_uploads() == 0 {
+                tokio::task::yield_now().await;
+            }
+
+            fake.fail_next(500, 1);
+        };
+
+        let (res, _) = tokio::join!(write, fail);
+        assert!(res.is_err());
+        assert!(fake.pending_uploads() == 0);
+        assert!(fake.keys().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_prefix() {
+        let fake = FakeS3::new().await;
+        let client = fake.client().with_prefix("/notes/");
+        let folder = client.create_folder(None, "helsync-folder")
+            .await.unwrap();
+
+        let file = client.create_file(Some(&folder.id), "helsync-file.txt")
+            .await.unwrap();
+
+        assert!(file.id == "helsync-folder/helsync-file.txt");
+        assert!(fake.keys() == ["notes/helsync-folder/", "notes/helsync-folder/helsync-file.txt"]);
+
+        let files = client.list_files(None).await.unwrap();
+        assert!(files.len() == 1 && files[0].id == folder.id);
+    }
+
+    #[tokio::test]
+    async fn test_list_deltas() {
+        let fake = FakeS3::new().await;
+        let client = fake.client();
+        let folder = client.create_folder(None, "helsync-folder")
+            .await.unwrap();
+
+        let file = client.create_file(Some(&folder.id), "helsync-file.txt")
+            .await.unwrap();
+
+        // The first call lists every file.
+        let (changes, token) = client.list_deltas(None).await.unwrap();
+        assert!(changes.len() == 2);
+        assert!(changes[0].id == folder.id && changes[0].is_folder);
+        assert!(token.starts_with(MANIFEST_PREFIX));
+
+        let (changes, token) = client.list_deltas(Some(&token)).await.unwrap();
+        assert!(changes.is_empty());
+
+        client.write_to_file(&file.id, b"changed").await.unwrap();
+        let other = client.create_file(Some("implicit"), "helsync-other.txt")
+            .await.unwrap();
+
+        let (changes, token) = client.list_deltas(Some(&token)).await.unwrap();
+        assert!(changes.len() == 3);
+        assert!(changes.iter().any(|f| f.id == file.id));
+        assert!(changes.iter().any(|f| f.id == other.id));
+        assert!(changes.iter().any(|f| f.id == "implicit" && f.is_folder));
+
+        client.remove_file(&folder.id).await.unwrap();
+        let (changes, _) = client.list_deltas(Some(&token)).await.unwrap();
+        assert!(changes.len() == 2);
+        assert!(changes.iter().all(|f| f.is_deleted));
+        assert!(changes.iter().any(|f| f.id == folder.id && f.is_folder));
+
+        assert!(client.list_deltas(Some("invalid")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_signature() {
+        let fake = FakeS3::new().await;
+        let client = Client::new(fake.url(), "helsync", "minioadmin", "wrong");
+        let res = client.list_files(None).await;
+        assert!(matches!(res, Err(Error::S3(S3Error { code, .. })) if code == "SignatureDoesNotMatch"));
+    }
+
+    #[tokio::test]
+    async fn test_retry_server_error() {
+        let fake = FakeS3::new().await;
+        let client = fake.client();
+        fake.fail_next(503, 1);
+        assert!(client.list_files(None).await.is_ok());
+    }
+}
@@ -0,0 +1,21 @@
+use serde::{Serialize, Deserialize};
+
+/// S3 API error.
+///
+/// Reference: [Error Responses](https://docs.aws.amazon.com/AmazonS3/latest/API/ErrorResponses.html)
+#[derive(Debug, Clone, Serialize, Deserialize, thiserror::Error)]
+#[serde(rename_all = "camelCase")]
+pub struct S3Error {
+
+    /// Message describing the error.
+    pub message: String,
+
+    /// Error code (e.g. `NoSuchKey`).
+    pub code: String,
+}
+
+impl std::fmt::Display for S3Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", serde_json::to_string(self).unwrap())
+    }
+}
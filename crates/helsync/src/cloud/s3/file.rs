@@ -0,0 +1,92 @@
+use crate::core::File;
+use serde::{Serialize, Deserialize};
+use chrono::DateTime;
+
+/// An object in an S3 bucket: either a file or a folder.
+///
+/// S3 has a flat namespace, so folders are emulated with key
+/// prefixes: an object's ID is its key relative to the client's
+/// prefix, and a folder's ID is the prefix of its members without
+/// the trailing slash. Folders are created as empty marker objects
+/// whose key ends with a slash.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Object {
+
+    /// Key of the object relative to the client's prefix.
+    pub id: String,
+
+    /// The last segment of the object's key.
+    pub name: String,
+
+    /// Whether the object is a folder.
+    pub is_folder: bool,
+
+    /// Whether the object has been deleted. Only set in deltas.
+    pub is_deleted: bool,
+
+    /// The object's entity tag, which changes whenever its content
+    /// changes. Folders without a marker object have none.
+    pub etag: Option<String>,
+
+    /// Size of the object in bytes.
+    pub size: Option<u64>,
+
+    /// RFC 3339 timestamp of the last modification (if available).
+    pub last_modified: Option<String>,
+}
+
+impl Object {
+
+    /// The ID of the object's parent folder, or `None` if the parent
+    /// is the root folder.
+    pub fn parent_id(&self) -> Option<String> {
+        self.id.rsplit_once('/').map(|(parent, _)| parent.to_string())
+    }
+
+    /// A folder with no metadata besides its ID.
+    pub(crate) fn folder(id: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            name: id.rsplit('/').next().unwrap_or_default().to_string(),
+            is_folder: true,
+            is_deleted: false,
+            etag: None,
+            size: None,
+            last_modified: None,
+        }
+    }
+
+    /// A deleted object, of which only the ID is known.
+    pub(crate) fn deleted(id: &str, is_folder: bool) -> Self {
+        Self {
+            is_folder,
+            is_deleted: true,
+            ..Self::folder(id)
+        }
+    }
+}
+
+impl From<Object> for File {
+    fn from(object: Object) -> Self {
+        let modified_at = object.last_modified.as_deref()
+            .and_then(|ts| DateTime::parse_from_rfc3339(ts).ok())
+            .map(|dt| dt.timestamp())
+            .unwrap_or(0);
+
+        // S3 doesn't record when objects are created, and replacing
+        // an object resets its modification date.
+        File {
+            parent_id: object.parent_id(),
+            id: object.id,
+            name: object.name,
+            modified_at,
+            created_at: modified_at,
+            is_folder: object.is_folder,
+            is_deleted: object.is_deleted,
+            sha1: None,
+            sha256: None,
+            size: object.size,
+        }
+    }
+}
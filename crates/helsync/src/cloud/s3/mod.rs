@@ -0,0 +1,71 @@
+//! S3 [FileSystem](crate::core::FileSystem), for Amazon S3 and
+//! S3-compatible object storage such as MinIO, Cloudflare R2 and
+//! Backblaze B2.
+//!
+//! Official API Reference: [Amazon S3 API](https://docs.aws.amazon.com/AmazonS3/latest/API/Welcome.html)
+//!
+//! The S3 client signs requests with an access key (AWS Signature
+//! Version 4) instead of OAuth2. S3 buckets have no folders, so the
+//! folder tree is mapped onto key prefixes: objects are identified
+//! by their key, and folders by the prefix of their members.
+//!
+//! # Examples
+//! ## Listing all Files
+//! ```no_run
+//! use helsync::cloud::s3;
+//! use helsync::core::FileSystem;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let client = s3::Client::new(
+//!         "https://s3.us-east-1.amazonaws.com", "my-bucket",
+//!         "access-key-id", "secret-access-key",
+//!     ).with_prefix("notes");
+//!
+//!     // Passing None selects the root folder. Alternatively, pass
+//!     // a folder's key: e.g. Some("Work/Projects").
+//!     let files = client.list_files(None).await.unwrap();
+//!
+//!     for file in files {
+//!         // Perform some file-wise operation...
+//!     }
+//! }
+//! ```
+//! ## Tracking Changes
+//! ```no_run
+//! use helsync::cloud::s3;
+//! use helsync::core::{Delta, FileSystem};
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let client = s3::Client::new(
+//!         "http://localhost:9000", "my-bucket",
+//!         "access-key-id", "secret-access-key",
+//!     );
+//!
+//!     // List every file, retrieving a token.
+//!     let (_, token) = client.list_deltas(None).await.unwrap();
+//!
+//!     // Perform some change (e.g. create a file).
+//!     let file = client.create_file(None, "my-new-file.txt")
+//!         .await.unwrap();
+//!
+//!     // Fetch the change using the previously retrieved token.
+//!     let (changes, _) = client.list_deltas(Some(&token)).await.unwrap();
+//!
+//!     let find = changes.iter().find(|f| f.id == file.id);
+//!     assert!(find.is_some());
+//! }
+//! ```
+
+mod client;
+pub use client::*;
+
+mod error;
+pub use error::*;
+
+mod file;
+pub use file::*;
+
+pub(crate) mod sigv4;
+mod xml;
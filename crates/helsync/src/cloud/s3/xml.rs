@@ -0,0 +1,117 @@
+use super::error::S3Error;
+use crate::core::Result;
+
+use roxmltree::{Document, Node};
+
+/// An object listed by `ListObjectsV2`.
+pub(crate) struct Contents {
+    pub key: String,
+    pub etag: Option<String>,
+    pub size: Option<u64>,
+    pub last_modified: Option<String>,
+}
+
+/// A page of a `ListObjectsV2` listing.
+pub(crate) struct ListBucketResult {
+    pub contents: Vec<Contents>,
+
+    /// The prefixes of the listed "folders", if the listing has a
+    /// delimiter.
+    pub common_prefixes: Vec<String>,
+
+    /// The token of the next page, if the listing is truncated.
+    pub next_continuation_token: Option<String>,
+}
+
+/// Parses a `ListObjectsV2` response body.
+///
+/// Reference: [ListObjectsV2](https://docs.aws.amazon.com/AmazonS3/latest/API/API_ListObjectsV2.html)
+pub(crate) fn parse_list(xml: &str) -> Result<ListBucketResult> {
+    let doc = parse(xml, "ListBucketResult")?;
+    let root = doc.root_element();
+    let contents = children(root, "Contents")
+        .filter_map(|node| Some(Contents {
+            key: child_text(node, "Key")?,
+            etag: child_text(node, "ETag"),
+            size: child_text(node, "Size").and_then(|size| size.parse().ok()),
+            last_modified: child_text(node, "LastModified"),
+        }))
+        .collect();
+
+    let common_prefixes = children(root, "CommonPrefixes")
+        .filter_map(|node| child_text(node, "Prefix"))
+        .collect();
+
+    let is_truncated = child_text(root, "IsTruncated").as_deref() == Some("true");
+    let next_continuation_token = match is_truncated {
+        true => child_text(root, "NextContinuationToken"),
+        false => None,
+    };
+
+    Ok(ListBucketResult { contents, common_prefixes, next_continuation_token })
+}
+
+/// Parses the upload ID of a `CreateMultipartUpload` response body.
+///
+/// Reference: [CreateMultipartUpload](https://docs.aws.amazon.com/AmazonS3/latest/API/API_CreateMultipartUpload.html)
+pub(crate) fn parse_upload_id(xml: &str) -> Result<String> {
+    let doc = parse(xml, "InitiateMultipartUploadResult")?;
+    child_text(doc.root_element(), "UploadId")
+        .ok_or(S3Error {
+            code: "INVALID_RESPONSE".to_string(),
+            message: "missing UploadId".to_string(),
+        }.into())
+}
+
+/// Parses an error response body, which may be empty (e.g. for
+/// `HEAD` requests).
+pub(crate) fn parse_error(xml: &str) -> Option<S3Error> {
+    let doc = Document::parse(xml).ok()?;
+    let root = doc.root_element();
+    if root.tag_name().name() != "Error" {
+        return None;
+    }
+
+    Some(S3Error {
+        code: child_text(root, "Code").unwrap_or_default(),
+        message: child_text(root, "Message").unwrap_or_default(),
+    })
+}
+
+/// Escapes text for inclusion in an XML document.
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Parses a document, checking the name of its root element. The
+/// S3 namespace is optional, since not every S3-compatible service
+/// declares it.
+fn parse<'input>(xml: &'input str, root: &str) -> Result<Document<'input>> {
+    let doc = Document::parse(xml).map_err(|err| S3Error {
+        code: "INVALID_RESPONSE".to_string(),
+        message: err.to_string(),
+    })?;
+
+    let name = doc.root_element().tag_name().name();
+    if name != root {
+        return Err(S3Error {
+            code: "INVALID_RESPONSE".to_string(),
+            message: format!("unexpected root element {name:?}"),
+        }.into());
+    }
+
+    Ok(doc)
+}
+
+fn children<'a, 'input>(node: Node<'a, 'input>, name: &'a str) -> impl Iterator<Item = Node<'a, 'input>> + 'a {
+    node.children().filter(move |child| child.tag_name().name() == name)
+}
+
+fn child_text(node: Node, name: &str) -> Option<String> {
+    node.children()
+        .find(|child| child.tag_name().name() == name)
+        .map(|child| child.text().unwrap_or_default().to_string())
+}
@@ -0,0 +1,631 @@
+use crate::core::{RetryPolicy, Result, Error, FileSystem, Delta};
+use super::multistatus::{self, Entry, PROPS};
+use super::error::WebDavError;
+use super::file::Resource;
+
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
+use reqwest::{Method, StatusCode};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// Characters that are percent-encoded in path segments.
+const SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// Prefix of delta tokens that hold a server sync token.
+const SYNC_TOKEN_PREFIX: &str = "sync:";
+
+/// Prefix of delta tokens that hold a [Manifest].
+const MANIFEST_PREFIX: &str = "etags:";
+
+/// Maps the ID of every resource seen by a scan to its version (its
+/// ETag, or its modification date if the server doesn't report
+/// ETags) and whether it is a collection.
+type Manifest = BTreeMap<String, (Option<String>, bool)>;
+
+/// Implements a WebDAV client, which also supports Nextcloud and
+/// ownCloud servers.
+pub struct Client {
+    client: crate::core::Client,
+    req: Arc<reqwest::Client>,
+    url: String,
+    base_path: String,
+}
+
+impl Client {
+
+    /// Instantiate a new WebDAV client for the collection at `url`
+    /// (e.g. `https://cloud.example.com/remote.php/dav/files/alice`
+    /// on Nextcloud), authenticating with basic authentication.
+    ///
+    /// Nextcloud users with two-factor authentication should use an
+    /// app password as their `password`.
+    pub fn new(url: &str, username: &str, password: &str) -> Self {
+        let url = url.trim_end_matches('/').to_string();
+        let base_path = reqwest::Url::parse(&url)
+            .map(|url| decode(url.path()).trim_end_matches('/').to_string())
+            .unwrap_or_default();
+
+        Self {
+            client: crate::core::Client::basic(username, password),
+            req: Arc::new(reqwest::Client::new()),
+            url,
+            base_path,
+        }
+    }
+
+    /// Overrides the [RetryPolicy] used for requests.
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.client = self.client.with_retry_policy(policy);
+        self
+    }
+
+    /// Formats the URL of the resource with `id`.
+    fn url_of(&self, id: &str) -> String {
+        let path: Vec<String> = id.split('/')
+            .map(|segment| utf8_percent_encode(segment, SEGMENT).to_string())
+            .collect();
+
+        format!("{}/{}", self.url, path.join("/"))
+    }
+
+    /// Converts the `href` of a multi-status response, which is
+    /// either an absolute path or a URL, to a resource ID.
+    fn id_of(&self, href: &str) -> String {
+        let path = match href.split_once("://") {
+            Some((_, rest)) => rest.find('/').map_or("", |i| &rest[i..]),
+            None => href,
+        };
+
+        let path = decode(path);
+        path.strip_prefix(&self.base_path).unwrap_or(&path)
+            .trim_matches('/')
+            .to_string()
+    }
+
+    fn to_resource(&self, entry: Entry) -> Resource {
+        let id = self.id_of(&entry.href);
+        if !entry.is_found {
+            return Resource::deleted(&id, entry.href.ends_with('/'));
+        }
+
+        Resource {
+            name: id.rsplit('/').next().unwrap_or_default().to_string(),
+            id,
+            is_collection: entry.is_collection,
+            is_deleted: false,
+            etag: entry.etag,
+            content_length: entry.content_length,
+            last_modified: entry.last_modified,
+            creation_date: entry.creation_date,
+            file_id: entry.file_id,
+        }
+    }
+
+    /// Sends a request with a method that reqwest has no shorthand
+    /// for, retrying according to the client's [RetryPolicy].
+    async fn send(&self, method: &str, url: &str, headers: &[(&str, &str)], body: Vec<u8>) -> Result<reqwest::Response> {
+        let method = Method::from_bytes(method.as_bytes())
+            .map_err(|err| WebDavError {
+                code: "INVALID_METHOD".to_string(),
+                message: err.to_string(),
+            })?;
+
+        let mut req = self.req.clone().request(method, url)
+            .header(AUTHORIZATION, self.client.authorization().await?)
+            .body(body);
+
+        for (name, value) in headers {
+            req = req.header(*name, *value);
+        }
+
+        self.client.execute_with_retry(req).await
+    }
+
+    /// Fetches the properties of the resource with `id` and, if
+    /// `depth` is "1", of its members.
+    async fn propfind(&self, id: &str, depth: &str) -> Result<Vec<Resource>> {
+        let body = format!("<?xml version=\"1.0\" encoding=\"utf-8\"?>\
+            <d:propfind xmlns:d=\"DAV:\" xmlns:oc=\"http://owncloud.org/ns\">{PROPS}</d:propfind>");
+
+        let headers = [("Depth", depth), (CONTENT_TYPE.as_str(), "application/xml")];
+        let res = self.send("PROPFIND", &self.url_of(id), &headers, body.into_bytes()).await?
+            .error_for_status()?;
+
+        let multistatus = multistatus::parse(&res.text().await?)?;
+        Ok(multistatus.entries.into_iter()
+            .map(|entry| self.to_resource(entry))
+            .collect())
+    }
+
+    /// Copies or moves a resource, returning the resource at its
+    /// destination.
+    async fn relocate(&self, method: &str, source_id: &str, parent_id: Option<&str>, name: Option<&str>) -> Result<Resource> {
+        let name = name.unwrap_or(source_id.rsplit('/').next().unwrap_or_default());
+        let target_id = child_id(parent_id, name);
+        let destination = self.url_of(&target_id);
+        let headers = [("Destination", destination.as_str()), ("Overwrite", "F")];
+        self.send(method, &self.url_of(source_id), &headers, Vec::new()).await?
+            .error_for_status()?;
+
+        self.get_file(&target_id).await
+    }
+
+    /// Lists changes since `token` using a sync collection report
+    /// ([RFC 6578](https://datatracker.ietf.org/doc/html/rfc6578)),
+    /// as supported by Nextcloud and other sabre/dav servers.
+    /// Returns `None` if the server doesn't support reports.
+    async fn sync_collection(&self, token: Option<&str>) -> Result<Option<(Vec<Resource>, String)>> {
+        let body = format!("<?xml version=\"1.0\" encoding=\"utf-8\"?>\
+            <d:sync-collection xmlns:d=\"DAV:\" xmlns:oc=\"http://owncloud.org/ns\">\
+            <d:sync-token>{}</d:sync-token><d:sync-level>infinite</d:sync-level>{PROPS}\
+            </d:sync-collection>", escape(token.unwrap_or_default()));
+
+        let headers = [(CONTENT_TYPE.as_str(), "application/xml")];
+        let res = self.send("REPORT", &self.url_of(""), &headers, body.into_bytes()).await?;
+        let is_unsupported = matches!(res.status(),
+            StatusCode::BAD_REQUEST |
+            StatusCode::FORBIDDEN |
+            StatusCode::METHOD_NOT_ALLOWED |
+            StatusCode::UNSUPPORTED_MEDIA_TYPE |
+            StatusCode::NOT_IMPLEMENTED);
+
+        if token.is_none() && is_unsupported {
+            return Ok(None);
+        }
+
+        let res = res.error_for_status()?;
+        let multistatus = multistatus::parse(&res.text().await?)?;
+        let Some(sync_token) = multistatus.sync_token else {
+            return match token {
+                Some(_) => Err(WebDavError {
+                    code: "SYNC_COLLECTION_ERR".to_string(),
+                    message: "no sync-token in report".to_string(),
+                }.into()),
+                None => Ok(None),
+            };
+        };
+
+        let changes = multistatus.entries.into_iter()
+            .map(|entry| self.to_resource(entry))
+            .filter(|resource| !resource.id.is_empty())
+            .collect();
+
+        Ok(Some((changes, format!("{SYNC_TOKEN_PREFIX}{sync_token}"))))
+    }
+
+    /// Lists changes by comparing every resource's version against
+    /// the `previous` manifest.
+    ///
+    /// Nextcloud and ownCloud propagate ETag changes up to parent
+    /// collections, so collections they report as unchanged aren't
+    /// scanned again.
+    async fn scan(&self, previous: &Manifest) -> Result<(Vec<Resource>, String)> {
+        let mut manifest = Manifest::new();
+        let mut changes: Vec<Resource> = Vec::new();
+        let mut stack = vec![String::new()];
+        while let Some(collection_id) = stack.pop() {
+            for resource in self.propfind(&collection_id, "1").await? {
+                if resource.id == collection_id {
+                    continue;
+                }
+
+                let version = resource.etag.clone().or(resource.last_modified.clone());
+                let is_changed = match previous.get(&resource.id) {
+                    Some((previous, _)) if resource.is_collection => previous != &version,
+                    Some((previous, _)) => version.is_none() || previous != &version,
+                    None => true,
+                };
+
+                manifest.insert(resource.id.clone(), (version.clone(), resource.is_collection));
+                if resource.is_collection {
+                    let is_propagated = resource.file_id.is_some() && version.is_some();
+                    if !is_changed && is_propagated {
+                        let prefix = format!("{}/", resource.id);
+                        manifest.extend(previous.range(prefix.clone()..)
+                            .take_while(|(id, _)| id.starts_with(&prefix))
+                            .map(|(id, entry)| (id.clone(), entry.clone())));
+
+                        continue;
+                    }
+
+                    stack.push(resource.id.clone());
+                }
+
+                if is_changed {
+                    changes.push(resource);
+                }
+            }
+        }
+
+        // Resources that are no longer listed have been deleted.
+        for (id, (_, is_collection)) in previous {
+            if !manifest.contains_key(id) {
+                changes.push(Resource::deleted(id, *is_collection));
+            }
+        }
+
+        let token = format!("{MANIFEST_PREFIX}{}", serde_json::to_string(&manifest)?);
+        Ok((changes, token))
+    }
+}
+
+impl FileSystem for Client {
+    type File = Resource;
+    type Error = Error;
+
+    /// Retrieve the properties of a resource.
+    ///
+    /// Reference: [PROPFIND](https://datatracker.ietf.org/doc/html/rfc4918#section-9.1)
+    async fn get_file(&self, id: &str) -> Result<Resource> {
+        self.propfind(id, "0").await?.into_iter().next()
+            .ok_or(WebDavError {
+                code: "GET_FILE_ERR".to_string(),
+                message: "empty multi-status response".to_string(),
+            }.into())
+    }
+
+    /// Copy a resource.
+    ///
+    /// Copies the resource with id `source_id` to the collection
+    /// `parent_id`. If `parent_id` is `None`, the resource is copied
+    /// to the root collection. Optionally specifying a `name` will
+    /// rename the copied resource.
+    ///
+    /// Reference: [COPY](https://datatracker.ietf.org/doc/html/rfc4918#section-9.8)
+    async fn copy_file(&self, source_id: &str, parent_id: Option<&str>, name: Option<&str>) -> Result<Resource> {
+        self.relocate("COPY", source_id, parent_id, name).await
+    }
+
+    /// Move a resource to a new collection.
+    ///
+    /// Setting `parent_id` to `None` moves the resource to the root
+    /// collection. Specifying a `name` renames the resource. Since
+    /// resources are identified by their path, the moved resource
+    /// has a new ID.
+    ///
+    /// Reference: [MOVE](https://datatracker.ietf.org/doc/html/rfc4918#section-9.9)
+    async fn move_file(&self, source_id: &str, parent_id: Option<&str>, name: Option<&str>) -> Result<Resource> {
+        self.relocate("MOVE", source_id, parent_id, name).await
+    }
+
+    /// Delete a resource, along with its members.
+    ///
+    /// Reference: [DELETE](https://datatracker.ietf.org/doc/html/rfc4918#section-9.6)
+    async fn remove_file(&self, id: &str) -> Result<()> {
+        self.send("DELETE", &self.url_of(id), &[], Vec::new()).await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    /// Create a new collection.
+    ///
+    /// If `parent_id` is `None`, the collection is created in the
+    /// root collection.
+    ///
+    /// Reference: [MKCOL](https://datatracker.ietf.org/doc/html/rfc4918#section-9.3)
+    async fn create_folder(&self, parent_id: Option<&str>, name: &str) -> Result<Resource> {
+        let id = child_id(parent_id, name);
+        self.send("MKCOL", &self.url_of(&id), &[], Vec::new()).await?
+            .error_for_status()?;
+
+        self.get_file(&id).await
+    }
+
+    /// Create a new, empty file, replacing any file with the same
+    /// name.
+    ///
+    /// Reference: [PUT](https://datatracker.ietf.org/doc/html/rfc4918#section-9.7)
+    async fn create_file(&self, parent_id: Option<&str>, name: &str) -> Result<Resource> {
+        let id = child_id(parent_id, name);
+        self.write_to_file(&id, &[]).await
+    }
+
+    /// List the members of a collection. Set `parent_id` to `None`
+    /// to list the members of the root collection.
+    ///
+    /// Reference: [PROPFIND](https://datatracker.ietf.org/doc/html/rfc4918#section-9.1)
+    async fn list_files(&self, parent_id: Option<&str>) -> Result<Vec<Resource>> {
+        let parent_id = parent_id.unwrap_or_default();
+        let resources = self.propfind(parent_id, "1").await?;
+        Ok(resources.into_iter()
+            .filter(|resource| resource.id != parent_id)
+            .collect())
+    }
+
+    /// Upload or replace the contents of a file.
+    ///
+    /// Reference: [PUT](https://datatracker.ietf.org/doc/html/rfc4918#section-9.7)
+    async fn write_to_file(&self, id: &str, buf: &[u8]) -> Result<Resource> {
+        let headers = [(CONTENT_TYPE.as_str(), "application/octet-stream")];
+        self.send("PUT", &self.url_of(id), &headers, buf.to_vec()).await?
+            .error_for_status()?;
+
+        self.get_file(id).await
+    }
+
+    /// Download the contents of a file.
+    ///
+    /// Reference: [GET](https://datatracker.ietf.org/doc/html/rfc4918#section-9.4)
+    async fn read_from_file(&self, id: &str) -> Result<Vec<u8>> {
+        let res = self.send("GET", &self.url_of(id), &[], Vec::new()).await?
+            .error_for_status()?;
+
+        let bytes = res.bytes().await?;
+        Ok(bytes.to_vec())
+    }
+}
+
+impl Delta for Client {
+    type File = Resource;
+
+    /// Track changes below the root collection.
+    ///
+    /// Servers that support sync collection reports (e.g.
+    /// Nextcloud) are asked for the changes since the previous
+    /// token. Otherwise, every collection is listed and compared
+    /// against a manifest of ETags, which the returned token holds.
+    /// If `token` is `None`, every resource is listed.
+    async fn list_deltas(&self, token: Option<&str>) -> Result<(Vec<Resource>, String)> {
+        let Some(token) = token else {
+            return match self.sync_collection(None).await? {
+                Some(deltas) => Ok(deltas),
+                None => self.scan(&Manifest::new()).await,
+            };
+        };
+
+        if let Some(sync_token) = token.strip_prefix(SYNC_TOKEN_PREFIX) {
+            return self.sync_collection(Some(sync_token)).await?
+                .ok_or(WebDavError {
+                    code: "SYNC_COLLECTION_ERR".to_string(),
+                    message: "server no longer supports sync-collection reports".to_string(),
+                }.into());
+        }
+
+        match token.strip_prefix(MANIFEST_PREFIX) {
+            Some(manifest) => self.scan(&serde_json::from_str(manifest)?).await,
+            None => Err(WebDavError {
+                code: "INVALID_TOKEN".to_string(),
+                message: format!("unrecognized delta token {token:?}"),
+            }.into()),
+        }
+    }
+}
+
+/// Formats the ID of the resource called `name` in the collection
+/// `parent_id`.
+fn child_id(parent_id: Option<&str>, name: &str) -> String {
+    match parent_id {
+        Some(parent_id) if !parent_id.is_empty() => format!("{parent_id}/{name}"),
+        _ => name.to_string(),
+    }
+}
+
+fn decode(path: &str) -> String {
+    percent_decode_str(path).decode_utf8_lossy().to_string()
+}
+
+/// Escapes text for inclusion in an XML document.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::cloud::fake::FakeWebDav;
+
+    #[tokio::test]
+    async fn test_get_file() {
+        let fake = FakeWebDav::new().await;
+        let client = fake.client();
+        let parent = client.create_folder(None, "helsync parent")
+            .await.unwrap();
+
+        let file = client.create_file(Some(&parent.id), "helsync-get-file.txt")
+            .await.unwrap();
+
+        let res = client.get_file(&file.id).await.unwrap();
+        assert!(res.id == "helsync parent/helsync-get-file.txt");
+        assert!(res.name == "helsync-get-file.txt");
+        assert!(res.parent_id() == Some(parent.id));
+        assert!(!res.is_collection && res.etag.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_copy_file() {
+        let fake = FakeWebDav::new().await;
+        let client = fake.client();
+        let file = client.create_file(None, "helsync-copy-file.txt")
+            .await.unwrap();
+
+        client.write_to_file(&file.id, b"Hello, World!").await.unwrap();
+        let copied = client.copy_file(&file.id, None, Some("new-name.txt"))
+            .await.unwrap();
+
+        assert!(copied.id == "new-name.txt");
+        assert!(client.read_from_file(&copied.id).await.unwrap() == b"Hello, World!");
+
+        // Existing files aren't overwritten.
+        assert!(client.copy_file(&file.id, None, Some("new-name.txt")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_move_file() {
+        let fake = FakeWebDav::new().await;
+        let client = fake.client();
+        let child = client.create_file(None, "helsync-child-file.txt")
+            .await.unwrap();
+
+        let parent = client.create_folder(None, "helsync-parent")
+            .await.unwrap();
+
+        let moved = client.move_file(&child.id, Some(&parent.id), None)
+            .await.unwrap();
+
+        assert!(moved.id == "helsync-parent/helsync-child-file.txt");
+        assert!(moved.parent_id() == Some(parent.id));
+        assert!(client.get_file(&child.id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_create_folder() {
+        let fake = FakeWebDav::new().await;
+        let client = fake.client();
+        let parent = client.create_folder(None, "helsync-test-folder")
+            .await.unwrap();
+
+        assert!(parent.name == "helsync-test-folder");
+        assert!(parent.is_collection);
+
+        let child = client.create_folder(Some(&parent.id), "helsync-child")
+            .await.unwrap();
+
+        client.remove_file(&parent.id).await.unwrap();
+        assert!(client.get_file(&child.id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_list_files() {
+        let fake = FakeWebDav::new().await;
+        let client = fake.client();
+        let parent = client.create_folder(None, "helsync-test-list")
+            .await.unwrap();
+
+        for i in 0..3 {
+            client.create_file(Some(&parent.id), &format!("helsync-{i}.txt"))
+                .await.unwrap();
+        }
+
+        let files = client.list_files(None).await.unwrap();
+        assert!(files.len() == 1 && files[0].id == parent.id);
+
+        let files = client.list_files(Some(&parent.id)).await.unwrap();
+        assert!(files.len() == 3);
+        assert!(files.iter().all(|f| f.parent_id() == Some(parent.id.clone())));
+    }
+
+    #[tokio::test]
+    async fn test_read_write() {
+        let fake = FakeWebDav::new().await;
+        let client = fake.client();
+
+        // Names are percent-encoded in URLs.
+        let file = client.create_file(None, "helsync wríte #1.txt")
+            .await.unwrap();
+
+        let buf = "Hello, World!".as_bytes();
+        let file = client.write_to_file(&file.id, buf)
+            .await.unwrap();
+
+        assert!(file.name == "helsync wríte #1.txt");
+        assert!(file.content_length == Some(buf.len() as u64));
+        assert!(client.read_from_file(&file.id).await.unwrap() == buf);
+    }
+
+    #[tokio::test]
+    async fn test_list_deltas_etags() {
+        let fake = FakeWebDav::new().await;
+        let client = fake.client();
+        let folder = client.create_folder(None, "helsync-folder")
+            .await.unwrap();
+
+        let file = client.create_file(Some(&folder.id), "helsync-file.txt")
+            .await.unwrap();
+
+        // The first call lists every file.
+        let (changes, token) = client.list_deltas(None).await.unwrap();
+        assert!(changes.len() == 2);
+        assert!(token.starts_with(MANIFEST_PREFIX));
+
+        let (changes, token) = client.list_deltas(Some(&token)).await.unwrap();
+        assert!(changes.is_empty());
+
+        client.write_to_file(&file.id, b"changed").await.unwrap();
+        let other = client.create_file(None, "helsync-other.txt")
+            .await.unwrap();
+
+        let (changes, token) = client.list_deltas(Some(&token)).await.unwrap();
+        assert!(changes.len() == 2);
+        assert!(changes.iter().any(|f| f.id == file.id));
+        assert!(changes.iter().any(|f| f.id == other.id));
+
+        client.remove_file(&folder.id).await.unwrap();
+        let (changes, _) = client.list_deltas(Some(&token)).await.unwrap();
+        assert!(changes.len() == 2);
+        assert!(changes.iter().all(|f| f.is_deleted));
+        assert!(changes.iter().any(|f| f.id == folder.id && f.is_collection));
+    }
+
+    #[tokio::test]
+    async fn test_list_deltas_propagated_etags() {
+        let fake = FakeWebDav::nextcloud().await;
+        let client = fake.client();
+        let folder = client.create_folder(None, "helsync-folder")
+            .await.unwrap();
+
+        let file = client.create_file(Some(&folder.id), "helsync-file.txt")
+            .await.unwrap();
+
+        let (_, token) = client.scan(&Manifest::new()).await.unwrap();
+
+        // Unchanged folders aren't listed again.
+        let propfinds = fake.propfinds();
+        let (changes, token) = client.list_deltas(Some(&token)).await.unwrap();
+        assert!(changes.is_empty());
+        assert!(fake.propfinds() == propfinds + 1);
+
+        client.write_to_file(&file.id, b"changed").await.unwrap();
+        let (changes, _) = client.list_deltas(Some(&token)).await.unwrap();
+        assert!(changes.len() == 2);
+        assert!(changes.iter().any(|f| f.id == file.id));
+    }
+
+    #[tokio::test]
+    async fn test_list_deltas_sync_token() {
+        let fake = FakeWebDav::nextcloud().await;
+        let client = fake.client();
+        let folder = client.create_folder(None, "helsync-folder")
+            .await.unwrap();
+
+        let (changes, token) = client.list_deltas(None).await.unwrap();
+        assert!(changes.len() == 1 && changes[0].id == folder.id);
+        assert!(token.starts_with(SYNC_TOKEN_PREFIX));
+
+        let file = client.create_file(Some(&folder.id), "helsync-file.txt")
+            .await.unwrap();
+
+        let (changes, token) = client.list_deltas(Some(&token)).await.unwrap();
+        assert!(changes.len() == 1 && changes[0].id == file.id);
+
+        client.remove_file(&file.id).await.unwrap();
+        let (changes, _) = client.list_deltas(Some(&token)).await.unwrap();
+        assert!(changes.len() == 1);
+        assert!(changes[0].id == file.id && changes[0].is_deleted);
+    }
+
+    #[tokio::test]
+    async fn test_unauthorized() {
+        let fake = FakeWebDav::new().await;
+        let client = Client::new(&format!("{}/dav/files/alice", fake.url()), "alice", "wrong");
+        let requests = fake.requests();
+        assert!(client.list_files(None).await.is_err());
+        assert!(fake.requests() == requests + 1);
+    }
+
+    #[tokio::test]
+    async fn test_retry_server_error() {
+        let fake = FakeWebDav::new().await;
+        let client = fake.client();
+        fake.fail_next(503, 1);
+        assert!(client.list_files(None).await.is_ok());
+    }
+}
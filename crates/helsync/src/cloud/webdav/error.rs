@@ -0,0 +1,19 @@
+use serde::{Serialize, Deserialize};
+
+/// WebDAV client error.
+#[derive(Debug, Clone, Serialize, Deserialize, thiserror::Error)]
+#[serde(rename_all = "camelCase")]
+pub struct WebDavError {
+
+    /// Message describing the error.
+    pub message: String,
+
+    /// Error code.
+    pub code: String,
+}
+
+impl std::fmt::Display for WebDavError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", serde_json::to_string(self).unwrap())
+    }
+}
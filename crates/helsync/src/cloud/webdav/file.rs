@@ -0,0 +1,93 @@
+use crate::core::File;
+use serde::{Serialize, Deserialize};
+use chrono::DateTime;
+
+/// A WebDAV resource: either a file or a collection (folder).
+///
+/// WebDAV addresses resources by path, so a resource's ID is its
+/// path relative to the client's root collection, without leading
+/// or trailing slashes. The root collection's ID is empty.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Resource {
+
+    /// Path of the resource relative to the root collection.
+    pub id: String,
+
+    /// The last segment of the resource's path.
+    pub name: String,
+
+    /// Whether the resource is a collection.
+    pub is_collection: bool,
+
+    /// Whether the resource has been deleted. Only set in deltas.
+    pub is_deleted: bool,
+
+    /// The resource's entity tag, which changes whenever its content
+    /// changes (if available).
+    pub etag: Option<String>,
+
+    /// Size of the resource in bytes (if available).
+    pub content_length: Option<u64>,
+
+    /// HTTP date of the last modification (if available).
+    pub last_modified: Option<String>,
+
+    /// RFC 3339 timestamp of the resource's creation (if available).
+    pub creation_date: Option<String>,
+
+    /// The Nextcloud or ownCloud file ID, which is stable across
+    /// moves (if available).
+    pub file_id: Option<String>,
+}
+
+impl Resource {
+
+    /// The ID of the resource's parent collection, or `None` if the
+    /// parent is the root collection.
+    pub fn parent_id(&self) -> Option<String> {
+        self.id.rsplit_once('/').map(|(parent, _)| parent.to_string())
+    }
+
+    /// A deleted resource, of which only the path is known.
+    pub(crate) fn deleted(id: &str, is_collection: bool) -> Self {
+        Self {
+            id: id.to_string(),
+            name: id.rsplit('/').next().unwrap_or_default().to_string(),
+            is_collection,
+            is_deleted: true,
+            etag: None,
+            content_length: None,
+            last_modified: None,
+            creation_date: None,
+            file_id: None,
+        }
+    }
+}
+
+impl From<Resource> for File {
+    fn from(resource: Resource) -> Self {
+        let modified_at = resource.last_modified.as_deref()
+            .and_then(|ts| DateTime::parse_from_rfc2822(ts).ok())
+            .map(|dt| dt.timestamp())
+            .unwrap_or(0);
+
+        let created_at = resource.creation_date.as_deref()
+            .and_then(|ts| DateTime::parse_from_rfc3339(ts).ok())
+            .map(|dt| dt.timestamp())
+            .unwrap_or(modified_at);
+
+        File {
+            parent_id: resource.parent_id(),
+            id: resource.id,
+            name: resource.name,
+            modified_at,
+            created_at,
+            is_folder: resource.is_collection,
+            is_deleted: resource.is_deleted,
+            sha1: None,
+            sha256: None,
+            size: resource.content_length,
+        }
+    }
+}
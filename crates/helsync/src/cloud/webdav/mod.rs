@@ -0,0 +1,69 @@
+//! WebDAV [FileSystem](crate::core::FileSystem), for self-hosted
+//! servers such as Nextcloud and ownCloud.
+//!
+//! Official API Reference: [RFC 4918](https://datatracker.ietf.org/doc/html/rfc4918)
+//!
+//! Unlike the other cloud clients, the WebDAV client authenticates
+//! with a username and password (or a Nextcloud app password)
+//! instead of OAuth2. Resources are identified by their path
+//! relative to the client's root collection.
+//!
+//! # Examples
+//! ## Listing all Files
+//! ```no_run
+//! use helsync::cloud::webdav;
+//! use helsync::core::FileSystem;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let client = webdav::Client::new(
+//!         "https://cloud.example.com/remote.php/dav/files/alice",
+//!         "alice", "app-password",
+//!     );
+//!
+//!     // Passing None selects the root collection. Alternatively,
+//!     // pass a collection's path: e.g. Some("Notes/Work").
+//!     let files = client.list_files(None).await.unwrap();
+//!
+//!     for file in files {
+//!         // Perform some file-wise operation...
+//!     }
+//! }
+//! ```
+//! ## Tracking Changes
+//! ```no_run
+//! use helsync::cloud::webdav;
+//! use helsync::core::{Delta, FileSystem};
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let client = webdav::Client::new(
+//!         "https://cloud.example.com/remote.php/dav/files/alice",
+//!         "alice", "app-password",
+//!     );
+//!
+//!     // List every file, retrieving a token.
+//!     let (_, token) = client.list_deltas(None).await.unwrap();
+//!
+//!     // Perform some change (e.g. create a file).
+//!     let file = client.create_file(None, "my-new-file.txt")
+//!         .await.unwrap();
+//!
+//!     // Fetch the change using the previously retrieved token.
+//!     let (changes, _) = client.list_deltas(Some(&token)).await.unwrap();
+//!
+//!     let find = changes.iter().find(|f| f.id == file.id);
+//!     assert!(find.is_some());
+//! }
+//! ```
+
+mod client;
+pub use client::*;
+
+mod error;
+pub use error::*;
+
+mod file;
+pub use file::*;
+
+mod multistatus;
@@ -0,0 +1,124 @@
+use super::error::WebDavError;
+use crate::core::Result;
+
+use roxmltree::{Document, Node};
+
+/// The WebDAV XML namespace.
+const DAV: &str = "DAV:";
+
+/// The ownCloud XML namespace, also used by Nextcloud.
+const OC: &str = "http://owncloud.org/ns";
+
+/// The properties requested by PROPFIND and REPORT requests.
+pub(crate) const PROPS: &str = "<d:prop>\
+<d:resourcetype/><d:getetag/><d:getcontentlength/><d:getlastmodified/><d:creationdate/><oc:fileid/>\
+</d:prop>";
+
+/// A `DAV:response` element of a multi-status response.
+pub(crate) struct Entry {
+
+    /// The percent-encoded path or URL of the resource.
+    pub href: String,
+
+    /// Whether the resource exists. Sync collection reports list
+    /// removed resources with a 404 status.
+    pub is_found: bool,
+
+    pub is_collection: bool,
+    pub etag: Option<String>,
+    pub content_length: Option<u64>,
+    pub last_modified: Option<String>,
+    pub creation_date: Option<String>,
+    pub file_id: Option<String>,
+}
+
+/// A parsed `207 Multi-Status` response body.
+pub(crate) struct Multistatus {
+    pub entries: Vec<Entry>,
+
+    /// The collection's new sync token, for sync collection reports.
+    pub sync_token: Option<String>,
+}
+
+/// Parses a multi-status response body.
+pub(crate) fn parse(xml: &str) -> Result<Multistatus> {
+    let doc = Document::parse(xml).map_err(|err| WebDavError {
+        code: "INVALID_MULTISTATUS".to_string(),
+        message: err.to_string(),
+    })?;
+
+    let root = doc.root_element();
+    if !root.has_tag_name((DAV, "multistatus")) {
+        return Err(WebDavError {
+            code: "INVALID_MULTISTATUS".to_string(),
+            message: format!("unexpected root element {:?}", root.tag_name().name()),
+        }.into());
+    }
+
+    let entries = children(root, DAV, "response")
+        .filter_map(parse_response)
+        .collect();
+
+    let sync_token = child(root, DAV, "sync-token").map(text);
+    Ok(Multistatus { entries, sync_token })
+}
+
+fn parse_response(response: Node) -> Option<Entry> {
+    let href = text(child(response, DAV, "href")?);
+    let is_found = child(response, DAV, "status")
+        .map_or(true, |status| is_success(&text(status)));
+
+    let mut entry = Entry {
+        href,
+        is_found,
+        is_collection: false,
+        etag: None,
+        content_length: None,
+        last_modified: None,
+        creation_date: None,
+        file_id: None,
+    };
+
+    // Properties are grouped by status; only found ones are read.
+    let props = children(response, DAV, "propstat")
+        .filter(|propstat| child(*propstat, DAV, "status")
+            .is_some_and(|status| is_success(&text(status))))
+        .filter_map(|propstat| child(propstat, DAV, "prop"))
+        .flat_map(|prop| prop.children().filter(|node| node.is_element()));
+
+    for prop in props {
+        let tag = prop.tag_name();
+        match (tag.namespace(), tag.name()) {
+            (Some(DAV), "resourcetype") => {
+                entry.is_collection = child(prop, DAV, "collection").is_some();
+            }
+            (Some(DAV), "getetag") => entry.etag = Some(text(prop)),
+            (Some(DAV), "getcontentlength") => entry.content_length = text(prop).parse().ok(),
+            (Some(DAV), "getlastmodified") => entry.last_modified = Some(text(prop)),
+            (Some(DAV), "creationdate") => entry.creation_date = Some(text(prop)),
+            (Some(OC), "fileid") => entry.file_id = Some(text(prop)),
+            _ => (),
+        }
+    }
+
+    Some(entry)
+}
+
+/// Checks whether an HTTP status line (e.g. `HTTP/1.1 200 OK`) has
+/// a 2xx status.
+fn is_success(status: &str) -> bool {
+    status.split_whitespace().nth(1)
+        .is_some_and(|code| code.starts_with('2'))
+}
+
+fn children<'a, 'input>(node: Node<'a, 'input>, namespace: &'a str, name: &'a str) -> impl Iterator<Item = Node<'a, 'input>> + 'a {
+    node.children().filter(move |child| child.has_tag_name((namespace, name)))
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, namespace: &str, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| child.has_tag_name((namespace, name)))
+}
+
+fn text(node: Node) -> String {
+    node.text().unwrap_or_default().trim().to_string()
+}
@@ -1,15 +1,36 @@
-use crate::oauth2::{Config, Token};
-use crate::core::Result;
+use crate::oauth2::{Config, Token, TokenStore};
+use crate::core::{Error, Result};
+use super::retry::{retry_after, RetryPolicy};
 
-use reqwest::{Response, RequestBuilder};
+use reqwest::header::{HeaderValue, AUTHORIZATION};
+use reqwest::{Response, RequestBuilder, StatusCode};
 use std::collections::HashMap;
-use std::sync::{Arc, RwLock};
-use std::time::Duration;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use std::sync::Arc;
+use tokio::sync::RwLock;
 use tokio::time::sleep;
 
 pub(crate) struct Client {
-    token: Arc<RwLock<Token>>,
-    config: Arc<Config>,
+    auth: Auth,
+    retry: RetryPolicy,
+    store: Option<Arc<dyn TokenStore>>,
+}
+
+/// How a [Client] authenticates its requests.
+enum Auth {
+
+    /// An OAuth2 access token, refreshed when it expires or is
+    /// rejected.
+    OAuth2 { token: Arc<RwLock<Token>>, config: Arc<Config> },
+
+    /// HTTP basic authentication, holding the `Authorization`
+    /// header value.
+    Basic(String),
+
+    /// No shared credentials: the caller signs each request itself
+    /// (e.g. with AWS Signature Version 4).
+    Signed,
 }
 
 impl Client {
@@ -17,72 +38,187 @@ impl Client {
     /// Initializes a new client.
     pub fn new(token: &Token, config: &Config) -> Self {
         Self {
-            token: Arc::new(RwLock::new(token.clone())),
-            config: Arc::new(config.clone()),
+            auth: Auth::OAuth2 {
+                token: Arc::new(RwLock::new(token.clone())),
+                config: Arc::new(config.clone()),
+            },
+            retry: RetryPolicy::default(),
+            store: None,
+        }
+    }
+
+    /// Initializes a client that uses HTTP basic authentication
+    /// instead of OAuth2.
+    pub fn basic(username: &str, password: &str) -> Self {
+        let credentials = STANDARD.encode(format!("{username}:{password}"));
+        Self {
+            auth: Auth::Basic(format!("Basic {credentials}")),
+            retry: RetryPolicy::default(),
+            store: None,
         }
     }
 
+    /// Initializes a client for requests that are signed by the
+    /// caller, to which it adds no credentials.
+    pub fn signed() -> Self {
+        Self {
+            auth: Auth::Signed,
+            retry: RetryPolicy::default(),
+            store: None,
+        }
+    }
+
+    /// Sets the [TokenStore] in which refreshed tokens are saved.
+    pub fn with_token_store(mut self, store: Arc<dyn TokenStore>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// Sets the [RetryPolicy] of requests made through
+    /// [Self::execute_with_retry].
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
     /// Check if the access token has expired and refreshes if needed.
     pub async fn refresh_if_expired(&self) -> Result<()> {
+        let Auth::OAuth2 { token, config } = &self.auth else {
+            return Ok(());
+        };
 
         // Use read lock to opportunistically check if expired.
         {
-            let token = self.token.read().unwrap();
+            let token = token.read().await;
             if !token.is_expired() {
                 return Ok(());
             }
         }
 
-        // Refresh access token.
-        let mut token = self.token.write().unwrap();
+        // Refresh access token. The async lock is held while
+        // refreshing so that concurrent requests wait for it.
+        let mut token = token.write().await;
 
         // Double-check pattern.
-        token.refresh_if_expired(&self.config).await?;
+        if token.is_expired() {
+            token.refresh(config).await?;
+            self.save_token(&token).await?;
+        }
 
         Ok(())
     }
 
-    /// Executes a request, retrying up to 3 times for certain errors
-    /// using exponential backoff.
+    /// Saves a refreshed token in the client's [TokenStore], if any.
+    async fn save_token(&self, token: &Token) -> Result<()> {
+        match &self.store {
+            Some(store) => store.save(token).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Executes a request according to the client's
+    /// [RetryPolicy].
+    ///
+    /// A request rejected with 401 is retried once with a refreshed
+    /// OAuth2 access token. Requests that are still throttled (429, 503)
+    /// after the last attempt fail with [Error::Throttled]; other
+    /// error responses are returned for the caller to handle.
     pub async fn execute_with_retry(&self, req: RequestBuilder) -> Result<Response> {
-        const MAX_RETRIES: u32 = 3;
-        let mut last_error = None;
-        for attempt in 0..MAX_RETRIES {
-            let request = req.try_clone().unwrap();
-            match request.send().await {
-                Ok(res) => {
-                    if should_retry_status(res.status()) {
-                        if attempt < MAX_RETRIES - 1 {
-                            let delay = Duration::from_millis(250 * (2_u64.pow(attempt)));
-                            sleep(delay).await;
-                            continue;
-                        }
-                    }
-                    return Ok(res);
+        let (client, request) = req.build_split();
+        let mut request = request?;
+        let mut attempt = 0;
+        let mut is_refreshed = false;
+        loop {
+            // Streaming bodies can't be replayed, so they're sent once.
+            let Some(next) = request.try_clone() else {
+                return Ok(client.execute(request).await?);
+            };
+
+            attempt += 1;
+            let res = match client.execute(next).await {
+                Ok(res) => res,
+                Err(err) if should_retry_error(&err) && attempt < self.retry.max_attempts => {
+                    sleep(self.retry.backoff(attempt)).await;
+                    continue;
                 },
-                Err(err) => {
-                    if should_retry_error(&err) {
-                        last_error = Some(err);
-                        if attempt < MAX_RETRIES - 1 {
-                            let delay = Duration::from_millis(250 * (2_u64.pow(attempt)));
-                            sleep(delay).await;
-                            continue;
-                        }
-                    } else {
-                        return Err(err.into());
-                    }
+                Err(err) => return Err(err.into()),
+            };
+
+            let status = res.status();
+            let is_oauth2 = matches!(self.auth, Auth::OAuth2 { .. });
+            if status == StatusCode::UNAUTHORIZED && is_oauth2 && !is_refreshed {
+                if let Some(rejected) = request.headers().get(AUTHORIZATION).cloned() {
+                    let bearer = self.refresh_rejected(&rejected).await?;
+                    let bearer = HeaderValue::from_str(&bearer)
+                        .map_err(|err| Error::OAuth2(err.to_string()))?;
+
+                    request.headers_mut().insert(AUTHORIZATION, bearer);
+                    is_refreshed = true;
+                    attempt -= 1;
+                    continue;
                 }
             }
+
+            if !should_retry_status(status) {
+                return Ok(res);
+            }
+
+            let retry_after = retry_after(&res);
+            let is_throttled = matches!(status,
+                StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE);
+
+            if attempt >= self.retry.max_attempts || retry_after.is_some_and(|delay| delay > self.retry.max_delay) {
+                return match is_throttled {
+                    true => Err(Error::Throttled {
+                        retry_after: retry_after.map(|delay| delay.as_secs()),
+                    }),
+                    false => Ok(res),
+                };
+            }
+
+            let delay = match retry_after {
+                Some(delay) if is_throttled => delay,
+                _ => self.retry.backoff(attempt),
+            };
+
+            sleep(delay).await;
         }
-        return Err(last_error.unwrap().into());
     }
 
-    /// Acquires a Bearer authentication string, refreshing the
-    /// underlying token if needed.
-    pub async fn bearer(&self) -> Result<String> {
+    /// Refreshes the access token after the server rejected it,
+    /// returning the new Bearer authentication string. The token
+    /// isn't refreshed again if a concurrent request already
+    /// replaced the `rejected` one.
+    async fn refresh_rejected(&self, rejected: &HeaderValue) -> Result<String> {
+        let Auth::OAuth2 { token, config } = &self.auth else {
+            return self.authorization().await;
+        };
+
+        let mut token = token.write().await;
+        let bearer = format!("Bearer {}", token.access_token);
+        if rejected.to_str().ok() != Some(bearer.as_str()) {
+            return Ok(bearer);
+        }
+
+        token.refresh(config).await?;
+        self.save_token(&token).await?;
+        Ok(format!("Bearer {}", token.access_token))
+    }
+
+    /// Acquires the `Authorization` header value: a Bearer
+    /// authentication string, refreshing the underlying token if
+    /// needed, or a Basic authentication string. Signed clients
+    /// have no shared value, so it is empty.
+    pub async fn authorization(&self) -> Result<String> {
         self.refresh_if_expired().await?;
-        let token = self.token.read().unwrap();
-        return Ok(format!("Bearer {}", token.access_token));
+        match &self.auth {
+            Auth::OAuth2 { token, .. } => {
+                let token = token.read().await;
+                Ok(format!("Bearer {}", token.access_token))
+            }
+            Auth::Basic(credentials) => Ok(credentials.clone()),
+            Auth::Signed => Ok(String::new()),
+        }
     }
 }
 
@@ -90,8 +226,6 @@ impl Client {
 fn should_retry_status(status: reqwest::StatusCode) -> bool {
     matches!(
         status,
-        reqwest::StatusCode::UNAUTHORIZED |           // 401
-        reqwest::StatusCode::NOT_FOUND |              // 404
         reqwest::StatusCode::REQUEST_TIMEOUT |        // 408
         reqwest::StatusCode::TOO_MANY_REQUESTS |      // 429
         reqwest::StatusCode::INTERNAL_SERVER_ERROR |  // 500
@@ -124,3 +258,120 @@ pub(crate) fn extract_query_params(url: &str) -> HashMap<String, String> {
     }
     params
 }
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::cloud::fake::FakeOneDrive;
+    use crate::core::FileSystem;
+    use crate::oauth2::FileTokenStore;
+    use std::time::{Duration, Instant};
+
+    #[tokio::test]
+    async fn test_retry_after() {
+        let fake = FakeOneDrive::new().await;
+        let client = fake.client();
+        let folder = client.create_folder(None, "helsync-retry-after")
+            .await.unwrap();
+
+        fake.throttle_next(1, 1);
+        let start = Instant::now();
+        assert!(client.get_file(&folder.id).await.is_ok());
+        assert!(start.elapsed() >= Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_throttled() {
+        let fake = FakeOneDrive::new().await;
+        let client = fake.client().with_retry_policy(RetryPolicy {
+            max_attempts: 2,
+            ..RetryPolicy::default()
+        });
+
+        fake.throttle_next(2, 0);
+        let res = client.list_files(None).await;
+        assert!(matches!(res, Err(Error::Throttled { retry_after: Some(0) })));
+
+        // Throttling for longer than the maximum delay fails
+        // without retrying.
+        fake.throttle_next(1, 120);
+        let requests = fake.requests();
+        let res = client.list_files(None).await;
+        assert!(matches!(res, Err(Error::Throttled { retry_after: Some(120) })));
+        assert!(fake.requests() == requests + 1);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_on_unauthorized() {
+        let fake = FakeOneDrive::new().await;
+        let client = fake.client().with_retry_policy(RetryPolicy::none());
+
+        // The token hasn't expired, but the server no longer accepts
+        // it.
+        fake.revoke_token();
+        let folder = client.create_folder(None, "helsync-unauthorized")
+            .await.unwrap();
+
+        assert!(folder.name.unwrap() == "helsync-unauthorized");
+    }
+
+    #[tokio::test]
+    async fn test_token_store() {
+        let fake = FakeOneDrive::new().await;
+        let path = std::env::temp_dir()
+            .join(format!("hs-client-token-store-{}.json", std::process::id()));
+
+        let store = Arc::new(FileTokenStore::new(&path));
+        let client = fake.client().with_token_store(store.clone());
+
+        // Refreshing a rejected token saves the new token.
+        fake.revoke_token();
+        client.create_folder(None, "helsync-token-store").await.unwrap();
+        let saved = store.load().await.unwrap().unwrap();
+        assert!(saved.access_token == fake.token().access_token);
+
+        // As does refreshing an expired token.
+        let mut token = fake.token();
+        token.expires_in = 0;
+        let client = crate::cloud::onedrive::Client::new(&token, &fake.config())
+            .with_api_endpoint(&format!("{}/drive", fake.url()))
+            .with_token_store(store.clone());
+
+        client.list_files(None).await.unwrap();
+        let saved = store.load().await.unwrap().unwrap();
+        assert!(saved.access_token != token.access_token);
+        assert!(saved.access_token == fake.token().access_token);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_not_found_not_retried() {
+        let fake = FakeOneDrive::new().await;
+        let client = fake.client();
+        let requests = fake.requests();
+        assert!(client.get_file("missing").await.is_err());
+        assert!(fake.requests() == requests + 1);
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(1),
+            jitter: false,
+        };
+
+        assert!(policy.backoff(1) == Duration::from_millis(100));
+        assert!(policy.backoff(3) == Duration::from_millis(400));
+        assert!(policy.backoff(8) == Duration::from_secs(1));
+
+        let policy = RetryPolicy { jitter: true, ..policy };
+        for attempt in 1..10 {
+            let delay = policy.backoff(attempt);
+            assert!(delay <= Duration::from_secs(1));
+            assert!(delay >= Duration::from_millis(50));
+        }
+    }
+}
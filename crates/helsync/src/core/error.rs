@@ -3,6 +3,9 @@ use std::time::SystemTimeError;
 
 use crate::cloud::onedrive::OneDriveError;
 use crate::cloud::googledrive::GoogleDriveError;
+use crate::cloud::dropbox::DropboxError;
+use crate::cloud::webdav::WebDavError;
+use crate::cloud::s3::S3Error;
 
 /// Helsync result alias.
 pub type Result<T> = std::result::Result<T, Error>;
@@ -24,6 +27,18 @@ pub enum Error {
     #[error("googledrive: {0}")]
     GoogleDrive(#[from] GoogleDriveError),
 
+    /// Dropbox API error.
+    #[error("dropbox: {0}")]
+    Dropbox(#[from] DropboxError),
+
+    /// WebDAV client error.
+    #[error("webdav: {0}")]
+    WebDav(#[from] WebDavError),
+
+    /// S3 API error.
+    #[error("s3: {0}")]
+    S3(#[from] S3Error),
+
     /// A JSON decoding error.
     #[error("json: {0}")]
     Json(String),
@@ -32,10 +47,21 @@ pub enum Error {
     #[error("systemTime: {0}")]
     SystemItem(String),
 
+    /// The API kept throttling requests after every retry.
+    /// `retry_after` is the number of seconds the server asked to
+    /// wait before retrying, if specified.
+    #[error("throttled: retry after {retry_after:?} seconds")]
+    Throttled { retry_after: Option<u64> },
+
     /// OAuth2 authentication error.
     #[error("oauth2: {0}")]
     OAuth2(String),
 
+    /// Encryption error, e.g. a wrong passphrase or a file that
+    /// could not be decrypted.
+    #[error("encryption: {0}")]
+    Encryption(String),
+
     /// Input/output error.
     #[error("io: {0}")]
     Io(String),
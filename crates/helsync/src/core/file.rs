@@ -27,4 +27,16 @@ pub struct File {
 
     /// Whether the file/delta has been deleted.
     pub is_deleted: bool,
+
+    /// Hex-encoded SHA1 hash of the file's content, if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha1: Option<String>,
+
+    /// Hex-encoded SHA256 hash of the file's content, if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+
+    /// Size of the file's content in bytes, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
 }
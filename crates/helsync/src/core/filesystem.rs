@@ -1,5 +1,13 @@
 use std::future::Future;
 use super::file::File;
+use super::stream::{ByteStream, read_exact_chunk};
+
+use bytes::Bytes;
+use futures_util::stream;
+use tokio::io::AsyncRead;
+
+/// A page of files, along with the token of the next page (if any).
+pub type Page<F> = (Vec<F>, Option<String>);
 
 /// Defines a file system interface.
 pub trait FileSystem {
@@ -10,6 +18,15 @@ pub trait FileSystem {
     fn get_file(&self, id: &str) ->
     impl Future<Output = Result<Self::File, Self::Error>>;
 
+    /// Fetches the ID of the root directory.
+    ///
+    /// Returns `None` for filesystems that report the parent of the
+    /// files at the root as `None` rather than by ID.
+    fn get_root_id(&self) ->
+    impl Future<Output = Result<Option<String>, Self::Error>> {
+        async { Ok(None) }
+    }
+
     /// Copy a file.
     ///
     /// Copies a file and moves it under `parent_id`. If `parent_id` is
@@ -51,6 +68,31 @@ pub trait FileSystem {
     fn list_files(&self, parent_id: Option<&str>) ->
     impl Future<Output = Result<Vec<Self::File>, Self::Error>>;
 
+    /// Lists a single page of the immediate files belonging to
+    /// `parent_id`, returning the files and the token of the next
+    /// page (if any).
+    ///
+    /// `page_token` is unspecified for the first page. Filesystems
+    /// that don't paginate their listings return every file in the
+    /// first page.
+    fn list_files_page(&self, parent_id: Option<&str>, _page_token: Option<&str>) ->
+    impl Future<Output = Result<Page<Self::File>, Self::Error>> {
+        async move { Ok((self.list_files(parent_id).await?, None)) }
+    }
+
+    /// Pages through the immediate files belonging to `parent_id`,
+    /// so that large folders can be processed without buffering the
+    /// whole listing.
+    fn list_files_paged(&self, parent_id: Option<&str>) -> FilePages<'_, Self>
+    where Self: Sized {
+        FilePages {
+            fs: self,
+            parent_id: parent_id.map(|id| id.to_string()),
+            page_token: None,
+            is_done: false,
+        }
+    }
+
     /// Write to a file in the filesystem.
     fn write_to_file(&self, id: &str, buf: &[u8]) ->
     impl Future<Output = Result<Self::File, Self::Error>>;
@@ -58,4 +100,78 @@ pub trait FileSystem {
     /// Read from a file in the filesystem.
     fn read_from_file(&self, id: &str) ->
     impl Future<Output = Result<Vec<u8>, Self::Error>>;
+
+    /// Write `size` bytes read from `reader` to a file, replacing
+    /// its content.
+    ///
+    /// Filesystems that support it read and upload the content in
+    /// chunks, so that large files can be written with bounded
+    /// memory. Fails if `reader` ends before `size` bytes have been
+    /// read. Progress can be reported by wrapping `reader` in a
+    /// [ProgressReader](super::ProgressReader).
+    fn write_from_reader<R>(&self, id: &str, mut reader: R, size: u64) ->
+    impl Future<Output = Result<Self::File, Self::Error>>
+    where R: AsyncRead + Unpin + Send, Self::Error: From<std::io::Error> {
+        async move {
+            let buf = read_exact_chunk(&mut reader, size as usize).await?;
+            self.write_to_file(id, &buf).await
+        }
+    }
+
+    /// Read a file's content as a stream of chunks.
+    ///
+    /// Filesystems that support it download the content as it is
+    /// consumed, so that large files can be read with bounded
+    /// memory. Others read the whole file into a single chunk.
+    fn read_stream(&self, id: &str) ->
+    impl Future<Output = Result<ByteStream<Self::Error>, Self::Error>>
+    where Self::Error: Send + 'static {
+        async move {
+            let content = Bytes::from(self.read_from_file(id).await?);
+            Ok(Box::pin(stream::once(async move { Ok(content) })) as ByteStream<Self::Error>)
+        }
+    }
+}
+
+/// Iterates over the pages of a folder listing. See
+/// [FileSystem::list_files_paged].
+///
+/// # Examples
+/// ```no_run
+/// use helsync::core::FileSystem;
+///
+/// async fn count_files<F: FileSystem>(fs: &F) -> Result<usize, F::Error> {
+///     let mut count = 0;
+///     let mut pages = fs.list_files_paged(None);
+///     while let Some(page) = pages.next().await? {
+///         count += page.len();
+///     }
+///
+///     Ok(count)
+/// }
+/// ```
+pub struct FilePages<'a, F: FileSystem> {
+    fs: &'a F,
+    parent_id: Option<String>,
+    page_token: Option<String>,
+    is_done: bool,
+}
+
+impl<F: FileSystem> FilePages<'_, F> {
+
+    /// Fetches the next page of files, or `None` if every page has
+    /// been fetched.
+    pub async fn next(&mut self) -> Result<Option<Vec<F::File>>, F::Error> {
+        if self.is_done {
+            return Ok(None);
+        }
+
+        let (files, page_token) = self.fs
+            .list_files_page(self.parent_id.as_deref(), self.page_token.as_deref())
+            .await?;
+
+        self.is_done = page_token.is_none();
+        self.page_token = page_token;
+        Ok(Some(files))
+    }
 }
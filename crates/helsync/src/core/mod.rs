@@ -3,6 +3,9 @@
 mod client;
 pub(crate) use client::*;
 
+mod retry;
+pub use retry::*;
+
 mod error;
 pub use error::*;
 
@@ -14,3 +17,11 @@ pub use filesystem::*;
 
 mod delta;
 pub use delta::*;
+
+mod upload;
+pub use upload::{UploadSession, UploadStore};
+pub(crate) use upload::{is_committed, load_session, skip_committed};
+
+mod stream;
+pub use stream::{ByteStream, ProgressReader};
+pub(crate) use stream::{read_exact_chunk, response_stream};
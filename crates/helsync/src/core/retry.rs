@@ -0,0 +1,93 @@
+use chrono::DateTime;
+use reqwest::header::RETRY_AFTER;
+use reqwest::Response;
+use std::time::{Duration, SystemTime};
+
+/// Configures how cloud clients retry failed requests.
+///
+/// Requests that fail with a network error or a transient status
+/// (408, 429, 500, 502, 503, 504) are retried with exponential
+/// backoff. Throttled requests (429, 503) wait for as long as the
+/// server's `Retry-After` header requests instead.
+///
+/// # Examples
+/// ```no_run
+/// use helsync::cloud::onedrive;
+/// use helsync::core::RetryPolicy;
+/// use helsync::oauth2;
+/// use std::time::Duration;
+///
+/// # fn example(token: &oauth2::Token, config: &oauth2::Config) {
+/// let client = onedrive::Client::new(token, config)
+///     .with_retry_policy(RetryPolicy {
+///         max_attempts: 5,
+///         max_delay: Duration::from_secs(10),
+///         ..RetryPolicy::default()
+///     });
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+
+    /// Maximum number of attempts per request, including the first.
+    pub max_attempts: u32,
+
+    /// Delay before the first retry. The delay doubles with every
+    /// subsequent retry.
+    pub base_delay: Duration,
+
+    /// Maximum delay between attempts. Requests throttled for longer
+    /// than this fail immediately with
+    /// [Error::Throttled](super::Error::Throttled).
+    pub max_delay: Duration,
+
+    /// Whether to randomize backoff delays, so that concurrent
+    /// requests don't retry in lockstep.
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(250),
+            max_delay: Duration::from_secs(60),
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+
+    /// A policy that never retries.
+    pub fn none() -> Self {
+        Self { max_attempts: 1, ..Self::default() }
+    }
+
+    /// The delay before retrying after the given (1-based) failed
+    /// `attempt`.
+    pub(crate) fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self.base_delay
+            .saturating_mul(2_u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+
+        // Equal jitter: wait between half and all of the delay.
+        match self.jitter {
+            true => delay / 2 + delay.mul_f64(rand::random_range(0.0..0.5)),
+            false => delay,
+        }
+    }
+}
+
+/// Parses a response's `Retry-After` header, which is either a
+/// number of seconds or an HTTP date.
+pub(crate) fn retry_after(res: &Response) -> Option<Duration> {
+    let value = res.headers().get(RETRY_AFTER)?.to_str().ok()?;
+    if let Ok(secs) = value.trim().parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    let date = SystemTime::from(date);
+    Some(date.duration_since(SystemTime::now()).unwrap_or_default())
+}
@@ -0,0 +1,124 @@
+use super::error::Error;
+
+use bytes::Bytes;
+use futures_util::{stream, Stream};
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncReadExt, ReadBuf};
+
+/// A stream of chunks of a file's content. See
+/// [FileSystem::read_stream](super::FileSystem::read_stream).
+pub type ByteStream<E> = Pin<Box<dyn Stream<Item = Result<Bytes, E>> + Send>>;
+
+/// Reports the progress of a transfer by calling `progress` with
+/// the total number of bytes read so far.
+///
+/// # Examples
+/// ```no_run
+/// use helsync::core::{FileSystem, ProgressReader, Result};
+/// use helsync::local;
+/// use tokio::fs::File;
+///
+/// async fn upload(fs: &local::Client, id: &str, path: &str) -> Result<()> {
+///     let file = File::open(path).await?;
+///     let size = file.metadata().await?.len();
+///     let reader = ProgressReader::new(file, |sent| {
+///         println!("{sent}/{size} bytes");
+///     });
+///
+///     fs.write_from_reader(id, reader, size).await?;
+///     Ok(())
+/// }
+/// ```
+pub struct ProgressReader<R, F> {
+    inner: R,
+    progress: F,
+    transferred: u64,
+}
+
+impl<R: AsyncRead + Unpin, F: FnMut(u64) + Unpin> ProgressReader<R, F> {
+
+    /// Wraps `inner`, calling `progress` after every read.
+    pub fn new(inner: R, progress: F) -> Self {
+        Self { inner, progress, transferred: 0 }
+    }
+
+    /// The number of bytes read so far.
+    pub fn transferred(&self) -> u64 {
+        self.transferred
+    }
+}
+
+impl<R: AsyncRead + Unpin, F: FnMut(u64) + Unpin> AsyncRead for ProgressReader<R, F> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+        let this = &mut *self;
+        let poll = Pin::new(&mut this.inner).poll_read(cx, buf);
+        let read = buf.filled().len() - filled;
+        if read > 0 {
+            this.transferred += read as u64;
+            (this.progress)(this.transferred);
+        }
+
+        poll
+    }
+}
+
+/// Reads the next `size` bytes from `reader`, or the remaining bytes
+/// if it ends before then.
+pub(crate) async fn read_chunk<R: AsyncRead + Unpin>(reader: &mut R, size: usize) -> io::Result<Vec<u8>> {
+    let mut chunk = Vec::with_capacity(size);
+    reader.take(size as u64).read_to_end(&mut chunk).await?;
+    Ok(chunk)
+}
+
+/// Reads exactly `size` bytes from `reader`, failing if it ends
+/// before then.
+pub(crate) async fn read_exact_chunk<R: AsyncRead + Unpin>(reader: &mut R, size: usize) -> io::Result<Vec<u8>> {
+    let chunk = read_chunk(reader, size).await?;
+    if chunk.len() < size {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, format!(
+            "content ended after {} of {size} bytes", chunk.len()
+        )));
+    }
+
+    Ok(chunk)
+}
+
+/// Streams the body of a response as it is received.
+pub(crate) fn response_stream(res: reqwest::Response) -> impl Stream<Item = Result<Bytes, Error>> + Send {
+    stream::try_unfold(res, |mut res| async move {
+        Ok(res.chunk().await?.map(|chunk| (chunk, res)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_chunk, read_exact_chunk, ProgressReader};
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn test_progress_reader() {
+        let content = vec![7u8; 10_000];
+        let mut reports = Vec::new();
+        let mut reader = ProgressReader::new(&content[..], |sent| reports.push(sent));
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(reader.transferred(), 10_000);
+        assert_eq!(buf, content);
+        assert_eq!(reports.last(), Some(&10_000));
+        assert!(reports.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[tokio::test]
+    async fn test_read_chunk() {
+        let mut reader = &b"hello, world"[..];
+        assert_eq!(read_chunk(&mut reader, 5).await.unwrap(), b"hello");
+        assert_eq!(read_chunk(&mut reader, 100).await.unwrap(), b", world");
+        assert!(read_chunk(&mut reader, 5).await.unwrap().is_empty());
+
+        let mut reader = &b"hello"[..];
+        assert!(read_exact_chunk(&mut reader, 6).await.is_err());
+    }
+}
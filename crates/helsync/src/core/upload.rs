@@ -0,0 +1,98 @@
+use crate::oauth2::StoreFuture;
+use super::error::Result;
+use super::stream::read_exact_chunk;
+
+use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncRead;
+
+/// Number of bytes read at once when checking the committed range of
+/// a resumed upload.
+const VERIFY_CHUNK_SIZE: u64 = 64 * 1024;
+
+/// An upload session of a large file, through which its content is
+/// sent in chunks.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadSession {
+
+    /// Remote ID of the file being uploaded.
+    pub file_id: String,
+
+    /// The pre-authenticated URL to which chunks are sent.
+    pub upload_url: String,
+
+    /// Total size of the content in bytes.
+    pub size: u64,
+
+    /// Hex-encoded SHA256 hash of the first `committed` bytes of the
+    /// content, so that a session is only resumed to upload the same
+    /// content. The hash is computed as the content is sent, so
+    /// uploads of streamed content can be resumed too.
+    pub sha256: String,
+
+    /// Number of bytes committed by the server.
+    pub committed: u64,
+}
+
+/// Persists [UploadSession]s across runs.
+///
+/// Cloud clients save a session when they start uploading a large
+/// file and after every chunk, and remove it once the upload is
+/// complete. If an upload is interrupted (e.g. the app quits or the
+/// network drops), writing the same content to the file again
+/// resumes the session from the range received by the server instead
+/// of starting over.
+pub trait UploadStore: Send + Sync {
+
+    /// Loads the session of the upload to the file with `file_id`,
+    /// if any.
+    fn load<'a>(&'a self, file_id: &'a str) -> StoreFuture<'a, Option<UploadSession>>;
+
+    /// Stores `session`, replacing any session of the same file.
+    fn save<'a>(&'a self, session: &'a UploadSession) -> StoreFuture<'a, ()>;
+
+    /// Removes the session of the upload to the file with
+    /// `file_id`.
+    fn remove<'a>(&'a self, file_id: &'a str) -> StoreFuture<'a, ()>;
+}
+
+/// Loads the stored session of an upload of `size` bytes to the file
+/// with `file_id`, discarding sessions for content of another size.
+pub(crate) async fn load_session(
+    store: &dyn UploadStore,
+    file_id: &str,
+    size: u64,
+) -> Result<Option<UploadSession>> {
+    let Some(session) = store.load(file_id).await? else {
+        return Ok(None);
+    };
+
+    if session.size != size {
+        store.remove(file_id).await?;
+        return Ok(None);
+    }
+
+    Ok(Some(session))
+}
+
+/// Whether `content` starts with the range committed to `session`.
+pub(crate) fn is_committed(session: &UploadSession, content: &[u8]) -> bool {
+    content.get(..session.committed as usize)
+        .is_some_and(|prefix| format!("{:x}", Sha256::digest(prefix)) == session.sha256)
+}
+
+/// Reads the range committed to `session` from `reader`, adding it to
+/// `hasher`. Returns whether the range matches the content that was
+/// committed.
+pub(crate) async fn skip_committed<R>(reader: &mut R, session: &UploadSession, hasher: &mut Sha256) -> Result<bool>
+where R: AsyncRead + Unpin {
+    let mut remaining = session.committed;
+    while remaining > 0 {
+        let size = std::cmp::min(remaining, VERIFY_CHUNK_SIZE);
+        hasher.update(read_exact_chunk(reader, size as usize).await?);
+        remaining -= size;
+    }
+
+    Ok(format!("{:x}", hasher.clone().finalize()) == session.sha256)
+}
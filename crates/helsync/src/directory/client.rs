@@ -0,0 +1,558 @@
+use crate::core::{Result, Error, FileSystem, Delta};
+use super::entry::Entry;
+
+use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::fs;
+
+/// The extension of note files.
+const EXTENSION: &str = ".md";
+
+/// Prefix of delta tokens that hold a [Manifest].
+const MANIFEST_PREFIX: &str = "scan:";
+
+/// Modification times closer than this to the time of a scan aren't
+/// trusted: the file may be modified again within the resolution of
+/// the file system's timestamps without changing them.
+const RACY_WINDOW: Duration = Duration::from_secs(2);
+
+/// What a scan recorded about an entry.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Scanned {
+    is_folder: bool,
+
+    /// Modification time in nanoseconds, or `None` if it can't be
+    /// trusted to detect later changes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mtime: Option<i64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sha256: Option<String>,
+}
+
+/// Maps the ID of every entry seen by a scan to what was recorded
+/// about it.
+type Manifest = BTreeMap<String, Scanned>;
+
+/// Implements a [FileSystem] backed by a directory on disk.
+///
+/// Folders are stored as directories and notes as `.md` files, so
+/// that they can be read and edited by other applications. Hidden
+/// entries (whose name starts with a dot) and files with other
+/// extensions are ignored.
+pub struct Client {
+    root: PathBuf,
+}
+
+impl Client {
+
+    /// Instantiate a new client for the directory at `root`, which
+    /// must exist.
+    pub fn new(root: impl AsRef<Path>) -> Self {
+        Self { root: root.as_ref().to_path_buf() }
+    }
+
+    /// The client's root directory.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Resolves the path of the entry with `id`, rejecting IDs that
+    /// would escape the root directory.
+    fn path_of(&self, id: &str) -> Result<PathBuf> {
+        let mut path = self.root.clone();
+        for segment in id.split('/').filter(|segment| !segment.is_empty()) {
+            check_segment(segment)?;
+            path.push(segment);
+        }
+
+        Ok(path)
+    }
+
+    /// Fetches the metadata of the entry with `id`.
+    async fn entry(&self, id: &str) -> Result<Entry> {
+        let metadata = fs::metadata(self.path_of(id)?).await?;
+        let modified_at = metadata.modified().map(timestamp).unwrap_or(0);
+        Ok(Entry {
+            id: id.to_string(),
+            name: name_of(id, metadata.is_dir()),
+            is_folder: metadata.is_dir(),
+            is_deleted: false,
+            size: if metadata.is_dir() { 0 } else { metadata.len() },
+            modified_at,
+            created_at: metadata.created().map(timestamp).unwrap_or(modified_at),
+            sha256: None,
+        })
+    }
+
+    /// Lists the IDs of the folders and notes in the folder `id`,
+    /// along with their metadata.
+    async fn read_dir(&self, id: &str) -> Result<Vec<(String, std::fs::Metadata)>> {
+        let mut entries = Vec::new();
+        let mut dir = fs::read_dir(self.path_of(id)?).await?;
+        while let Some(dir_entry) = dir.next_entry().await? {
+            let Ok(name) = dir_entry.file_name().into_string() else {
+                continue;
+            };
+
+            let metadata = dir_entry.metadata().await?;
+            let is_note = metadata.is_file() && name.ends_with(EXTENSION);
+            if name.starts_with('.') || !(metadata.is_dir() || is_note) {
+                continue;
+            }
+
+            entries.push((child_id(Some(id), &name), metadata));
+        }
+
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+        Ok(entries)
+    }
+
+    /// Resolves the ID of a new entry called `name` in the folder
+    /// `parent_id`, failing if it already exists.
+    async fn new_id(&self, parent_id: Option<&str>, name: &str, is_folder: bool) -> Result<String> {
+        let name = file_name(name, is_folder);
+        check_segment(&name)?;
+
+        let id = child_id(parent_id, &name);
+        if fs::try_exists(self.path_of(&id)?).await? {
+            return Err(Error::Io(format!("\"{id}\" already exists")));
+        }
+
+        Ok(id)
+    }
+
+    /// Lists changes by comparing every entry against the
+    /// `previous` manifest. Notes whose size and modification time
+    /// are unchanged aren't read again.
+    async fn scan(&self, previous: &Manifest) -> Result<(Vec<Entry>, String)> {
+        let now = SystemTime::now();
+        let mut manifest = Manifest::new();
+        let mut changes: Vec<Entry> = Vec::new();
+        let mut stack = vec![String::new()];
+        while let Some(folder_id) = stack.pop() {
+            for (id, metadata) in self.read_dir(&folder_id).await? {
+                let modified = metadata.modified().ok();
+                let is_racy = modified.map_or(true, |modified| {
+                    now.duration_since(modified).map_or(true, |age| age < RACY_WINDOW)
+                });
+
+                let mut scanned = Scanned {
+                    is_folder: metadata.is_dir(),
+                    mtime: modified.filter(|_| !is_racy).map(timestamp_nanos),
+                    size: None,
+                    sha256: None,
+                };
+
+                let old = previous.get(&id);
+                let is_changed = if metadata.is_dir() {
+                    stack.push(id.clone());
+                    old.map_or(true, |old| !old.is_folder)
+                } else {
+                    scanned.size = Some(metadata.len());
+                    let is_unchanged = old.is_some_and(|old| {
+                        old.mtime.is_some() && old.mtime == modified.map(timestamp_nanos) &&
+                            old.size == scanned.size
+                    });
+
+                    scanned.sha256 = match is_unchanged {
+                        true => old.and_then(|old| old.sha256.clone()),
+                        false => Some(sha256(&fs::read(self.path_of(&id)?).await?)),
+                    };
+
+                    old.map_or(true, |old| old.is_folder || old.sha256 != scanned.sha256)
+                };
+
+                if is_changed {
+                    let modified_at = modified.map(timestamp).unwrap_or(0);
+                    changes.push(Entry {
+                        name: name_of(&id, scanned.is_folder),
+                        id: id.clone(),
+                        is_folder: scanned.is_folder,
+                        is_deleted: false,
+                        size: scanned.size.unwrap_or(0),
+                        modified_at,
+                        created_at: metadata.created().map(timestamp).unwrap_or(modified_at),
+                        sha256: scanned.sha256.clone(),
+                    });
+                }
+
+                manifest.insert(id, scanned);
+            }
+        }
+
+        // Entries that are no longer listed have been deleted.
+        for (id, scanned) in previous {
+            if !manifest.contains_key(id) {
+                changes.push(Entry::deleted(id, scanned.is_folder));
+            }
+        }
+
+        let token = format!("{MANIFEST_PREFIX}{}", serde_json::to_string(&manifest)?);
+        Ok((changes, token))
+    }
+}
+
+impl FileSystem for Client {
+    type File = Entry;
+    type Error = Error;
+
+    /// Retrieve the metadata of a folder or note.
+    async fn get_file(&self, id: &str) -> Result<Entry> {
+        self.entry(id).await
+    }
+
+    /// Copy a folder or note.
+    ///
+    /// Copies the entry with id `source_id` to the folder
+    /// `parent_id`. If `parent_id` is `None`, the entry is copied to
+    /// the root directory. Optionally specifying a `name` will
+    /// rename the copy. Fails if an entry with the same name exists.
+    async fn copy_file(&self, source_id: &str, parent_id: Option<&str>, name: Option<&str>) -> Result<Entry> {
+        let source = self.entry(source_id).await?;
+        let target_id = self.new_id(parent_id, name.unwrap_or(&source.name), source.is_folder).await?;
+        if !source.is_folder {
+            fs::copy(self.path_of(source_id)?, self.path_of(&target_id)?).await?;
+            return self.entry(&target_id).await;
+        }
+
+        let mut stack = vec![(source_id.to_string(), target_id.clone())];
+        while let Some((source_id, target_id)) = stack.pop() {
+            fs::create_dir(self.path_of(&target_id)?).await?;
+            for (id, metadata) in self.read_dir(&source_id).await? {
+                let name = id.rsplit('/').next().unwrap_or_default();
+                let copy_id = child_id(Some(&target_id), name);
+                match metadata.is_dir() {
+                    true => stack.push((id, copy_id)),
+                    false => { fs::copy(self.path_of(&id)?, self.path_of(&copy_id)?).await?; },
+                }
+            }
+        }
+
+        self.entry(&target_id).await
+    }
+
+    /// Move a folder or note to a new folder.
+    ///
+    /// Setting `parent_id` to `None` moves the entry to the root
+    /// directory. Specifying a `name` renames the entry. Since
+    /// entries are identified by their path, the moved entry has a
+    /// new ID. Fails if an entry with the same name exists.
+    async fn move_file(&self, source_id: &str, parent_id: Option<&str>, name: Option<&str>) -> Result<Entry> {
+        let source = self.entry(source_id).await?;
+        let target_id = self.new_id(parent_id, name.unwrap_or(&source.name), source.is_folder).await?;
+        fs::rename(self.path_of(source_id)?, self.path_of(&target_id)?).await?;
+        self.entry(&target_id).await
+    }
+
+    /// Delete a folder or note, along with the folder's content.
+    async fn remove_file(&self, id: &str) -> Result<()> {
+        let path = self.path_of(id)?;
+        match fs::metadata(&path).await?.is_dir() {
+            true => fs::remove_dir_all(path).await?,
+            false => fs::remove_file(path).await?,
+        }
+
+        Ok(())
+    }
+
+    /// Create a new folder.
+    ///
+    /// If `parent_id` is `None`, the folder is created in the root
+    /// directory.
+    async fn create_folder(&self, parent_id: Option<&str>, name: &str) -> Result<Entry> {
+        let id = self.new_id(parent_id, name, true).await?;
+        fs::create_dir(self.path_of(&id)?).await?;
+        self.entry(&id).await
+    }
+
+    /// Create a new, empty note.
+    ///
+    /// If `parent_id` is `None`, the note is created in the root
+    /// directory.
+    async fn create_file(&self, parent_id: Option<&str>, name: &str) -> Result<Entry> {
+        let id = self.new_id(parent_id, name, false).await?;
+        fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(self.path_of(&id)?)
+            .await?;
+
+        self.entry(&id).await
+    }
+
+    /// List the folders and notes in a folder. Set `parent_id` to
+    /// `None` to list the root directory.
+    async fn list_files(&self, parent_id: Option<&str>) -> Result<Vec<Entry>> {
+        let mut entries = Vec::new();
+        for (id, metadata) in self.read_dir(parent_id.unwrap_or_default()).await? {
+            let modified_at = metadata.modified().map(timestamp).unwrap_or(0);
+            entries.push(Entry {
+                name: name_of(&id, metadata.is_dir()),
+                id,
+                is_folder: metadata.is_dir(),
+                is_deleted: false,
+                size: if metadata.is_dir() { 0 } else { metadata.len() },
+                modified_at,
+                created_at: metadata.created().map(timestamp).unwrap_or(modified_at),
+                sha256: None,
+            });
+        }
+
+        Ok(entries)
+    }
+
+    /// Replace the content of a note.
+    ///
+    /// The content is written to a hidden temporary file which then
+    /// replaces the note, so that other applications never read a
+    /// partially written note.
+    async fn write_to_file(&self, id: &str, buf: &[u8]) -> Result<Entry> {
+        let path = self.path_of(id)?;
+        if fs::metadata(&path).await?.is_dir() {
+            return Err(Error::Io(format!("\"{id}\" is a folder")));
+        }
+
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let temp = path.with_file_name(format!(".{name}.helsync-tmp"));
+        fs::write(&temp, buf).await?;
+        if let Err(err) = fs::rename(&temp, &path).await {
+            let _ = fs::remove_file(&temp).await;
+            return Err(err.into());
+        }
+
+        let mut entry = self.entry(id).await?;
+        entry.sha256 = Some(sha256(buf));
+        Ok(entry)
+    }
+
+    /// Read the content of a note.
+    async fn read_from_file(&self, id: &str) -> Result<Vec<u8>> {
+        Ok(fs::read(self.path_of(id)?).await?)
+    }
+}
+
+impl Delta for Client {
+    type File = Entry;
+
+    /// Track changes below the root directory.
+    ///
+    /// Every folder is listed and compared against a manifest of
+    /// modification times, sizes and hashes, which the returned
+    /// token holds. If `token` is `None`, every entry is listed.
+    ///
+    /// Entries that are renamed or moved by other applications are
+    /// reported as a deletion and a creation.
+    async fn list_deltas(&self, token: Option<&str>) -> Result<(Vec<Entry>, String)> {
+        let Some(token) = token else {
+            return self.scan(&Manifest::new()).await;
+        };
+
+        match token.strip_prefix(MANIFEST_PREFIX) {
+            Some(manifest) => self.scan(&serde_json::from_str(manifest)?).await,
+            None => Err(Error::Io(format!("unrecognized delta token {token:?}"))),
+        }
+    }
+}
+
+/// Formats the name of the file or directory of an entry called
+/// `name`.
+fn file_name(name: &str, is_folder: bool) -> String {
+    match is_folder {
+        true => name.to_string(),
+        false => format!("{name}{EXTENSION}"),
+    }
+}
+
+/// The name of the entry with `id`: its file name, without the
+/// extension for notes.
+pub(crate) fn name_of(id: &str, is_folder: bool) -> String {
+    let name = id.rsplit('/').next().unwrap_or_default();
+    match is_folder {
+        true => name.to_string(),
+        false => name.strip_suffix(EXTENSION).unwrap_or(name).to_string(),
+    }
+}
+
+/// Formats the ID of the entry with file name `name` in the folder
+/// `parent_id`.
+fn child_id(parent_id: Option<&str>, name: &str) -> String {
+    match parent_id {
+        Some(parent_id) if !parent_id.is_empty() => format!("{parent_id}/{name}"),
+        _ => name.to_string(),
+    }
+}
+
+/// Checks that a path segment names an entry within its parent
+/// directory.
+fn check_segment(segment: &str) -> Result<()> {
+    let is_valid = segment != "." && segment != ".." &&
+        !segment.contains(['/', '\\', '\0']);
+
+    match is_valid {
+        true => Ok(()),
+        false => Err(std::io::Error::new(ErrorKind::InvalidInput,
+            format!("invalid file name \"{segment}\"")).into()),
+    }
+}
+
+fn timestamp(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
+fn timestamp_nanos(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as i64
+}
+
+fn sha256(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    /// Creates an empty directory for a test.
+    fn temp_dir(name: &str) -> PathBuf {
+        let path = std::env::temp_dir()
+            .join(format!("hs-directory-{name}-{}", std::process::id()));
+
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn test_create_file() {
+        let root = temp_dir("create");
+        let client = Client::new(&root);
+        let folder = client.create_folder(None, "Work").await.unwrap();
+        let note = client.create_file(Some(&folder.id), "Untitled").await.unwrap();
+
+        assert!(note.id == "Work/Untitled.md" && note.name == "Untitled");
+        assert!(note.parent_id() == Some(folder.id.clone()));
+        assert!(root.join("Work").join("Untitled.md").is_file());
+
+        // Entries aren't replaced.
+        assert!(client.create_file(Some(&folder.id), "Untitled").await.is_err());
+        assert!(client.create_folder(None, "Work").await.is_err());
+
+        // Names can't escape their parent.
+        assert!(client.create_file(None, "../escape").await.is_err());
+        assert!(client.get_file("../escape.md").await.is_err());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_list_files() {
+        let root = temp_dir("list");
+        let client = Client::new(&root);
+        client.create_folder(None, "Work").await.unwrap();
+        client.create_file(None, "Note").await.unwrap();
+
+        // Hidden files and files that aren't notes are ignored.
+        std::fs::write(root.join("image.png"), b"").unwrap();
+        std::fs::create_dir(root.join(".git")).unwrap();
+
+        let files = client.list_files(None).await.unwrap();
+        assert!(files.len() == 2);
+        assert!(files.iter().any(|f| f.id == "Work" && f.is_folder));
+        assert!(files.iter().any(|f| f.id == "Note.md" && f.name == "Note"));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_copy_move() {
+        let root = temp_dir("copy-move");
+        let client = Client::new(&root);
+        let folder = client.create_folder(None, "Work").await.unwrap();
+        let note = client.create_file(Some(&folder.id), "Todo").await.unwrap();
+        client.write_to_file(&note.id, b"- [ ] milk").await.unwrap();
+
+        let copy = client.copy_file(&folder.id, None, Some("Archive")).await.unwrap();
+        assert!(copy.id == "Archive" && copy.is_folder);
+        assert!(client.read_from_file("Archive/Todo.md").await.unwrap() == b"- [ ] milk");
+
+        let moved = client.move_file(&note.id, None, Some("Done")).await.unwrap();
+        assert!(moved.id == "Done.md" && moved.parent_id().is_none());
+        assert!(client.get_file(&note.id).await.is_err());
+
+        // Moves don't replace existing entries.
+        assert!(client.move_file("Archive/Todo.md", None, Some("Done")).await.is_err());
+
+        client.remove_file(&copy.id).await.unwrap();
+        assert!(!root.join("Archive").exists());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_read_write() {
+        let root = temp_dir("read-write");
+        let client = Client::new(&root);
+        let note = client.create_file(None, "Note").await.unwrap();
+        let written = client.write_to_file(&note.id, b"# Hello").await.unwrap();
+
+        assert!(written.size == 7);
+        assert!(written.sha256 == Some(sha256(b"# Hello")));
+        assert!(client.read_from_file(&note.id).await.unwrap() == b"# Hello");
+
+        // The temporary file is renamed over the note.
+        assert!(std::fs::read_dir(&root).unwrap().count() == 1);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_list_deltas() {
+        let root = temp_dir("deltas");
+        let client = Client::new(&root);
+        let folder = client.create_folder(None, "Work").await.unwrap();
+        let note = client.create_file(Some(&folder.id), "Todo").await.unwrap();
+
+        // The first call lists every entry.
+        let (changes, token) = client.list_deltas(None).await.unwrap();
+        assert!(changes.len() == 2);
+        assert!(changes.iter().all(|f| !f.is_deleted));
+        assert!(token.starts_with(MANIFEST_PREFIX));
+
+        let (changes, token) = client.list_deltas(Some(&token)).await.unwrap();
+        assert!(changes.is_empty());
+
+        // Changes made by other applications are detected.
+        std::fs::write(root.join("Work").join("Todo.md"), b"- [x] milk").unwrap();
+        std::fs::write(root.join("Ideas.md"), b"").unwrap();
+        let (changes, token) = client.list_deltas(Some(&token)).await.unwrap();
+        assert!(changes.len() == 2);
+        assert!(changes.iter().any(|f| f.id == note.id && f.sha256 == Some(sha256(b"- [x] milk"))));
+        assert!(changes.iter().any(|f| f.id == "Ideas.md"));
+
+        // Touching a note without changing it isn't a change.
+        let file = std::fs::File::options().write(true)
+            .open(root.join("Ideas.md")).unwrap();
+
+        file.set_modified(SystemTime::now() - Duration::from_secs(60)).unwrap();
+        let (changes, token) = client.list_deltas(Some(&token)).await.unwrap();
+        assert!(changes.is_empty());
+
+        std::fs::remove_dir_all(root.join("Work")).unwrap();
+        let (changes, _) = client.list_deltas(Some(&token)).await.unwrap();
+        assert!(changes.len() == 2);
+        assert!(changes.iter().all(|f| f.is_deleted));
+        assert!(changes.iter().any(|f| f.id == folder.id && f.is_folder));
+        assert!(changes.iter().any(|f| f.id == note.id && f.name == "Todo"));
+
+        assert!(client.list_deltas(Some("invalid")).await.is_err());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
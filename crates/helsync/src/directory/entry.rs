@@ -0,0 +1,80 @@
+use crate::core::File;
+use serde::{Serialize, Deserialize};
+
+/// A folder or note in a [Client](super::Client)'s directory.
+///
+/// An entry's ID is its path relative to the client's root
+/// directory, using `/` as the separator on every platform. The
+/// root directory's ID is empty.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Entry {
+
+    /// Path of the entry relative to the root directory.
+    pub id: String,
+
+    /// The entry's name. Notes are named after their file, without
+    /// the `.md` extension.
+    pub name: String,
+
+    /// Whether the entry is a folder.
+    pub is_folder: bool,
+
+    /// Whether the entry has been deleted. Only set in deltas.
+    pub is_deleted: bool,
+
+    /// Size of the note in bytes.
+    pub size: u64,
+
+    /// Unix timestamp of the last modification.
+    pub modified_at: i64,
+
+    /// Unix timestamp of the entry's creation, or of its last
+    /// modification if the platform doesn't record it.
+    pub created_at: i64,
+
+    /// Hex-encoded SHA256 hash of the note's content. Only known
+    /// for entries returned by deltas and writes, since hashing
+    /// requires reading the note.
+    pub sha256: Option<String>,
+}
+
+impl Entry {
+
+    /// The ID of the entry's parent folder, or `None` if the parent
+    /// is the root directory.
+    pub fn parent_id(&self) -> Option<String> {
+        self.id.rsplit_once('/').map(|(parent, _)| parent.to_string())
+    }
+
+    /// A deleted entry, of which only the ID is known.
+    pub(crate) fn deleted(id: &str, is_folder: bool) -> Self {
+        Self {
+            id: id.to_string(),
+            name: super::client::name_of(id, is_folder),
+            is_folder,
+            is_deleted: true,
+            size: 0,
+            modified_at: 0,
+            created_at: 0,
+            sha256: None,
+        }
+    }
+}
+
+impl From<Entry> for File {
+    fn from(entry: Entry) -> Self {
+        File {
+            parent_id: entry.parent_id(),
+            id: entry.id,
+            name: entry.name,
+            modified_at: entry.modified_at,
+            created_at: entry.created_at,
+            is_folder: entry.is_folder,
+            is_deleted: entry.is_deleted,
+            sha1: None,
+            sha256: entry.sha256,
+            size: (!entry.is_folder).then_some(entry.size),
+        }
+    }
+}
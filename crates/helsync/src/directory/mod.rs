@@ -0,0 +1,34 @@
+//! Directory [FileSystem](crate::core::FileSystem), which stores
+//! notes as Markdown files in a folder on disk.
+//!
+//! Syncing the [local](crate::local) database with a directory
+//! mirrors the user's notes to plain files, which can be edited by
+//! other applications (e.g. text editors or Obsidian) and tracked
+//! with tools such as git. Changes made by either side are picked
+//! up by [Sync](crate::sync::Sync).
+//!
+//! # Examples
+//! ## Mirroring the Database to a Folder
+//! ```no_run
+//! use helsync::directory;
+//! use helsync::local;
+//! use helsync::sync::Sync;
+//! use std::sync::Arc;
+//!
+//! async fn mirror(local: Arc<local::Client>) -> helsync::core::Result<()> {
+//!     std::fs::create_dir_all("/home/alice/Notes")?;
+//!     let remote = Arc::new(directory::Client::new("/home/alice/Notes"));
+//!
+//!     // Creates the notes that are missing on either side, and
+//!     // then syncs the changes made since the last sync.
+//!     let mut sync = Sync::load(local, remote).await?;
+//!     sync.sync().await?;
+//!     Ok(())
+//! }
+//! ```
+
+mod client;
+pub use client::*;
+
+mod entry;
+pub use entry::*;
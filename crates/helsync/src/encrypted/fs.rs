@@ -0,0 +1,234 @@
+use crate::core::{Delta, Error, File, FileSystem, Page, Result};
+use super::key::{Key, CONTENT_OVERHEAD, KEY_FILE};
+
+use std::sync::Arc;
+
+/// A [FileSystem] that encrypts the content (and optionally the
+/// names) of the files of another file system.
+///
+/// Content is encrypted with XChaCha20-Poly1305 under a key derived
+/// from a passphrase, and decrypted when read. The salt and
+/// parameters of the key derivation are stored in a key-check file
+/// at the root of the wrapped file system, which is hidden from
+/// listings and deltas. Opening the file system with the wrong
+/// passphrase fails with an [Encryption](Error::Encryption) error.
+///
+/// Since the server only sees ciphertext, content hashes are not
+/// reported and [Sync](crate::sync::Sync) compares content after
+/// downloading it.
+pub struct EncryptedFs<F> {
+    inner: Arc<F>,
+    key: Key,
+    key_file_id: String,
+    encrypt_names: bool,
+}
+
+impl<F: FileSystem<Error = Error>> EncryptedFs<F> {
+
+    /// Opens the encrypted file system stored in `inner` with
+    /// `passphrase`.
+    ///
+    /// If `inner` has no key-check file, then a new key is derived
+    /// from `passphrase` and its key-check file is created.
+    pub async fn open(inner: Arc<F>, passphrase: &str) -> Result<Self> {
+        let key_file = inner.list_files(None).await?
+            .into_iter()
+            .map(Into::<File>::into)
+            .find(|file| file.name == KEY_FILE && !file.is_folder && !file.is_deleted);
+
+        let passphrase = passphrase.to_string();
+        let (key, key_file_id) = match key_file {
+            Some(file) => {
+                let header = inner.read_from_file(&file.id).await?;
+                let key = tokio::task::spawn_blocking(move || Key::unlock(&passphrase, &header))
+                    .await
+                    .map_err(|err| Error::Encryption(err.to_string()))??;
+
+                (key, file.id)
+            },
+            None => {
+                let (key, header) = tokio::task::spawn_blocking(move || Key::create(&passphrase))
+                    .await
+                    .map_err(|err| Error::Encryption(err.to_string()))??;
+
+                let file: File = inner.create_file(None, KEY_FILE).await?.into();
+                let file: File = inner.write_to_file(&file.id, &header).await?.into();
+                (key, file.id)
+            },
+        };
+
+        Ok(Self { inner, key, key_file_id, encrypt_names: false })
+    }
+
+    /// Sets whether file names are encrypted. Names are stored in
+    /// plaintext by default.
+    ///
+    /// Names that cannot be decrypted (e.g. of files that were
+    /// created before names were encrypted) are listed as is.
+    pub fn with_encrypted_names(mut self, encrypt_names: bool) -> Self {
+        self.encrypt_names = encrypt_names;
+        self
+    }
+
+    /// The wrapped file system.
+    pub fn inner(&self) -> &Arc<F> {
+        &self.inner
+    }
+
+    /// Encrypts `name` if names are encrypted.
+    fn encrypt_name(&self, name: &str) -> Result<String> {
+        match self.encrypt_names {
+            true => self.key.encrypt_name(name),
+            false => Ok(name.to_string()),
+        }
+    }
+
+    /// Converts a file of the wrapped file system, decrypting its
+    /// name, reporting the size of its decrypted content and
+    /// discarding the hashes of its encrypted content.
+    fn decrypt_file(&self, file: F::File) -> File {
+        let mut file: File = file.into();
+        if self.encrypt_names {
+            if let Some(name) = self.key.decrypt_name(&file.name) {
+                file.name = name;
+            }
+        }
+
+        // Empty files are stored as is, without encryption overhead.
+        if !file.is_folder {
+            file.size = file.size.map(|size| size.saturating_sub(CONTENT_OVERHEAD));
+        }
+
+        file.sha1 = None;
+        file.sha256 = None;
+        file
+    }
+
+    /// Decrypts a listing, hiding the key-check file.
+    fn decrypt_files(&self, files: Vec<F::File>) -> Vec<File> {
+        files.into_iter()
+            .map(|file| self.decrypt_file(file))
+            .filter(|file| file.id != self.key_file_id)
+            .collect()
+    }
+}
+
+impl<F: FileSystem<Error = Error>> FileSystem for EncryptedFs<F> {
+    type File = File;
+    type Error = Error;
+
+    async fn get_file(&self, id: &str) -> Result<File> {
+        Ok(self.decrypt_file(self.inner.get_file(id).await?))
+    }
+
+    async fn get_root_id(&self) -> Result<Option<String>> {
+        self.inner.get_root_id().await
+    }
+
+    async fn copy_file(&self, source_id: &str, parent_id: Option<&str>, name: Option<&str>) -> Result<File> {
+        let name = name.map(|name| self.encrypt_name(name)).transpose()?;
+        Ok(self.decrypt_file(self.inner.copy_file(source_id, parent_id, name.as_deref()).await?))
+    }
+
+    async fn move_file(&self, source_id: &str, parent_id: Option<&str>, name: Option<&str>) -> Result<File> {
+        let name = name.map(|name| self.encrypt_name(name)).transpose()?;
+        Ok(self.decrypt_file(self.inner.move_file(source_id, parent_id, name.as_deref()).await?))
+    }
+
+    async fn remove_file(&self, id: &str) -> Result<()> {
+        self.inner.remove_file(id).await
+    }
+
+    async fn create_folder(&self, parent_id: Option<&str>, name: &str) -> Result<File> {
+        let name = self.encrypt_name(name)?;
+        Ok(self.decrypt_file(self.inner.create_folder(parent_id, &name).await?))
+    }
+
+    async fn create_file(&self, parent_id: Option<&str>, name: &str) -> Result<File> {
+        let name = self.encrypt_name(name)?;
+        Ok(self.decrypt_file(self.inner.create_file(parent_id, &name).await?))
+    }
+
+    async fn list_files(&self, parent_id: Option<&str>) -> Result<Vec<File>> {
+        Ok(self.decrypt_files(self.inner.list_files(parent_id).await?))
+    }
+
+    async fn list_files_page(&self, parent_id: Option<&str>, page_token: Option<&str>) -> Result<Page<File>> {
+        let (files, page_token) = self.inner.list_files_page(parent_id, page_token).await?;
+        Ok((self.decrypt_files(files), page_token))
+    }
+
+    async fn write_to_file(&self, id: &str, buf: &[u8]) -> Result<File> {
+        let sealed = self.key.seal(buf)?;
+        Ok(self.decrypt_file(self.inner.write_to_file(id, &sealed).await?))
+    }
+
+    async fn read_from_file(&self, id: &str) -> Result<Vec<u8>> {
+        self.key.open(&self.inner.read_from_file(id).await?)
+    }
+}
+
+impl<F: FileSystem<Error = Error> + Delta<File = <F as FileSystem>::File>> Delta for EncryptedFs<F> {
+    type File = File;
+
+    async fn list_deltas(&self, token: Option<&str>) -> Result<(Vec<File>, String)> {
+        let (files, token) = self.inner.list_deltas(token).await?;
+        Ok((self.decrypt_files(files), token))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::MemoryFs;
+
+    #[tokio::test]
+    async fn test_encrypted_fs() {
+        let inner = Arc::new(MemoryFs::new());
+        let fs = EncryptedFs::open(inner.clone(), "passphrase").await.unwrap();
+        let file = fs.create_file(None, "todo.md").await.unwrap();
+        let written = fs.write_to_file(&file.id, b"- [ ] milk").await.unwrap();
+        assert!(written.sha256.is_none());
+
+        // Content is only stored encrypted.
+        assert_eq!(fs.read_from_file(&file.id).await.unwrap(), b"- [ ] milk");
+        let sealed = inner.read_from_file(&file.id).await.unwrap();
+        assert!(!sealed.windows(10).any(|w| w == b"- [ ] milk"));
+
+        // The key-check file is hidden.
+        assert_eq!(inner.list_files(None).await.unwrap().len(), 2);
+        let files = fs.list_files(None).await.unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].name, "todo.md");
+        let (deltas, _) = fs.list_deltas(None).await.unwrap();
+        assert!(deltas.iter().all(|file| file.name != KEY_FILE));
+
+        // Reopening requires the same passphrase.
+        let fs = EncryptedFs::open(inner.clone(), "passphrase").await.unwrap();
+        assert_eq!(fs.read_from_file(&file.id).await.unwrap(), b"- [ ] milk");
+        let res = EncryptedFs::open(inner.clone(), "wrong").await;
+        assert!(matches!(res, Err(Error::Encryption(_))));
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_names() {
+        let inner = Arc::new(MemoryFs::new());
+        let fs = EncryptedFs::open(inner.clone(), "passphrase").await.unwrap()
+            .with_encrypted_names(true);
+
+        let folder = fs.create_folder(None, "journal").await.unwrap();
+        let file = fs.create_file(Some(&folder.id), "today.md").await.unwrap();
+        assert_eq!(file.name, "today.md");
+        assert_ne!(inner.get_file(&file.id).await.unwrap().name, "today.md");
+
+        let file = fs.move_file(&file.id, None, Some("tomorrow.md")).await.unwrap();
+        assert_eq!(file.name, "tomorrow.md");
+        assert_eq!(fs.get_file(&file.id).await.unwrap().name, "tomorrow.md");
+
+        let mut names: Vec<_> = fs.list_files(None).await.unwrap()
+            .into_iter().map(|file| file.name).collect();
+
+        names.sort();
+        assert_eq!(names, ["journal", "tomorrow.md"]);
+    }
+}
@@ -0,0 +1,226 @@
+use crate::core::{Error, Result};
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chacha20poly1305::aead::Aead;
+use chacha20poly1305::{KeyInit, XChaCha20Poly1305, XNonce};
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use sha2::Sha256;
+
+/// Name of the key-check file stored at the root of an encrypted
+/// file system.
+pub const KEY_FILE: &str = ".helsync-key";
+
+/// Identifies the key-check file format.
+const KEY_MAGIC: &[u8; 4] = b"HSK1";
+
+/// Identifies the encrypted content format.
+const CONTENT_MAGIC: &[u8; 4] = b"HSE1";
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const CHECK_LEN: usize = 32;
+
+/// Number of bytes of the key check stored with every file, so that
+/// files encrypted with another key are told apart from corrupted
+/// ones.
+const KEY_ID_LEN: usize = 8;
+
+/// Length of the authentication tag appended to encrypted content.
+const TAG_LEN: usize = 16;
+
+/// Number of bytes that [Key::seal] adds to file content.
+pub(crate) const CONTENT_OVERHEAD: u64 = (CONTENT_MAGIC.len() + KEY_ID_LEN + NONCE_LEN + TAG_LEN) as u64;
+
+/// Argon2id memory cost (in KiB), iterations and parallelism used
+/// for new keys. Tests use cheap parameters so that they run quickly
+/// in debug builds.
+const KDF_PARAMS: (u32, u32, u32) = match cfg!(test) {
+    true => (64, 1, 1),
+    false => (19 * 1024, 2, 1),
+};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Keys derived from a passphrase.
+///
+/// A master key is derived from the passphrase with Argon2id, from
+/// which separate keys for content, names and the key check are
+/// derived with HMAC-SHA256.
+pub(crate) struct Key {
+    content: XChaCha20Poly1305,
+    names: XChaCha20Poly1305,
+    name_nonces: [u8; 32],
+    check: [u8; CHECK_LEN],
+}
+
+impl Key {
+
+    /// Derives a new key from `passphrase` with a random salt,
+    /// returning it along with its key-check file.
+    pub fn create(passphrase: &str) -> Result<(Self, Vec<u8>)> {
+        let mut salt = [0u8; SALT_LEN];
+        rand::rng().fill(&mut salt);
+
+        let (memory, iterations, parallelism) = KDF_PARAMS;
+        let key = Self::derive(passphrase, &salt, memory, iterations, parallelism)?;
+        let mut header = Vec::with_capacity(KEY_MAGIC.len() + 12 + SALT_LEN + CHECK_LEN);
+        header.extend_from_slice(KEY_MAGIC);
+        header.extend_from_slice(&memory.to_le_bytes());
+        header.extend_from_slice(&iterations.to_le_bytes());
+        header.extend_from_slice(&parallelism.to_le_bytes());
+        header.extend_from_slice(&salt);
+        header.extend_from_slice(&key.check);
+        Ok((key, header))
+    }
+
+    /// Derives the key described by the key-check file `header` from
+    /// `passphrase`, failing if the passphrase is wrong.
+    pub fn unlock(passphrase: &str, header: &[u8]) -> Result<Self> {
+        let (magic, rest) = header.split_at(KEY_MAGIC.len().min(header.len()));
+        if magic != KEY_MAGIC || rest.len() != 12 + SALT_LEN + CHECK_LEN {
+            return Err(Error::Encryption("invalid key-check file".to_string()));
+        }
+
+        let param = |i: usize| u32::from_le_bytes(rest[i * 4..i * 4 + 4].try_into().unwrap());
+        let (salt, check) = rest[12..].split_at(SALT_LEN);
+        let key = Self::derive(passphrase, salt, param(0), param(1), param(2))?;
+        if !constant_time_eq(&key.check, check) {
+            return Err(Error::Encryption("wrong passphrase".to_string()));
+        }
+
+        Ok(key)
+    }
+
+    fn derive(passphrase: &str, salt: &[u8], memory: u32, iterations: u32, parallelism: u32) -> Result<Self> {
+        let params = Params::new(memory, iterations, parallelism, Some(32))
+            .map_err(|err| Error::Encryption(err.to_string()))?;
+
+        let mut master = [0u8; 32];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), salt, &mut master)
+            .map_err(|err| Error::Encryption(err.to_string()))?;
+
+        Ok(Self {
+            content: XChaCha20Poly1305::new(&hmac(&master, b"helsync content").into()),
+            names: XChaCha20Poly1305::new(&hmac(&master, b"helsync names").into()),
+            name_nonces: hmac(&master, b"helsync name nonces"),
+            check: hmac(&master, b"helsync key check"),
+        })
+    }
+
+    /// Encrypts file content with a random nonce.
+    pub fn seal(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::rng().fill(&mut nonce);
+
+        let ciphertext = self.content.encrypt(XNonce::from_slice(&nonce), plaintext)
+            .map_err(|_| Error::Encryption("failed to encrypt content".to_string()))?;
+
+        let mut sealed = Vec::with_capacity(CONTENT_MAGIC.len() + KEY_ID_LEN + NONCE_LEN + ciphertext.len());
+        sealed.extend_from_slice(CONTENT_MAGIC);
+        sealed.extend_from_slice(&self.check[..KEY_ID_LEN]);
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&ciphertext);
+        Ok(sealed)
+    }
+
+    /// Decrypts file content encrypted by [Key::seal]. Empty content
+    /// is left as is, since files are created empty.
+    pub fn open(&self, sealed: &[u8]) -> Result<Vec<u8>> {
+        if sealed.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let header_len = CONTENT_MAGIC.len() + KEY_ID_LEN + NONCE_LEN;
+        if sealed.len() < header_len || &sealed[..CONTENT_MAGIC.len()] != CONTENT_MAGIC {
+            return Err(Error::Encryption("file is not encrypted".to_string()));
+        }
+
+        let (key_id, rest) = sealed[CONTENT_MAGIC.len()..].split_at(KEY_ID_LEN);
+        if key_id != &self.check[..KEY_ID_LEN] {
+            return Err(Error::Encryption("file is encrypted with a different key".to_string()));
+        }
+
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+        self.content.decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| Error::Encryption("file is corrupted".to_string()))
+    }
+
+    /// Encrypts a file name.
+    ///
+    /// The nonce is derived from the name, so that a name always
+    /// encrypts to the same string. This keeps names unique within
+    /// a folder, at the cost of revealing which files share a name.
+    pub fn encrypt_name(&self, name: &str) -> Result<String> {
+        let nonce = hmac(&self.name_nonces, name.as_bytes());
+        let nonce = XNonce::from_slice(&nonce[..NONCE_LEN]);
+
+        let ciphertext = self.names.encrypt(nonce, name.as_bytes())
+            .map_err(|_| Error::Encryption("failed to encrypt name".to_string()))?;
+
+        let mut encrypted = nonce.to_vec();
+        encrypted.extend_from_slice(&ciphertext);
+        Ok(URL_SAFE_NO_PAD.encode(encrypted))
+    }
+
+    /// Decrypts a name encrypted by [Key::encrypt_name], or returns
+    /// `None` if it isn't one.
+    pub fn decrypt_name(&self, name: &str) -> Option<String> {
+        let encrypted = URL_SAFE_NO_PAD.decode(name).ok()?;
+        if encrypted.len() < NONCE_LEN {
+            return None;
+        }
+
+        let (nonce, ciphertext) = encrypted.split_at(NONCE_LEN);
+        let name = self.names.decrypt(XNonce::from_slice(nonce), ciphertext).ok()?;
+        String::from_utf8(name).ok()
+    }
+}
+
+/// Computes the HMAC-SHA256 of `data` under `key`.
+fn hmac(key: &[u8], data: &[u8]) -> [u8; 32] {
+    let mut mac = <HmacSha256 as Mac>::new_from_slice(key).unwrap();
+    mac.update(data);
+    mac.finalize().into_bytes().into()
+}
+
+/// Compares two byte strings in constant time.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Key, CONTENT_OVERHEAD};
+
+    #[test]
+    fn test_key() {
+        let (key, header) = Key::create("passphrase").unwrap();
+        let sealed = key.seal(b"Hello, World!").unwrap();
+        assert!(!sealed.windows(13).any(|w| w == b"Hello, World!"));
+        assert_eq!(sealed.len() as u64, 13 + CONTENT_OVERHEAD);
+
+        // The key is recovered from its key-check file.
+        let unlocked = Key::unlock("passphrase", &header).unwrap();
+        assert_eq!(unlocked.open(&sealed).unwrap(), b"Hello, World!");
+        assert!(Key::unlock("wrong", &header).is_err());
+
+        // Tampered content is rejected.
+        let mut tampered = sealed.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(key.open(&tampered).is_err());
+
+        // Content encrypted with another key is rejected.
+        let (other, _) = Key::create("passphrase").unwrap();
+        assert!(other.open(&sealed).is_err());
+
+        // Names encrypt to the same string every time.
+        let name = key.encrypt_name("todo.md").unwrap();
+        assert_eq!(name, unlocked.encrypt_name("todo.md").unwrap());
+        assert_eq!(key.decrypt_name(&name), Some("todo.md".to_string()));
+        assert!(key.decrypt_name("todo.md").is_none());
+    }
+}
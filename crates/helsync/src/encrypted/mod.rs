@@ -0,0 +1,35 @@
+//! Client-side encryption for any [FileSystem](crate::core::FileSystem).
+//!
+//! [EncryptedFs] wraps a file system (e.g. a cloud drive) so that
+//! file content, and optionally file names, are encrypted with a key
+//! derived from the user's passphrase before they leave the device.
+//! The provider only ever stores ciphertext. Since [EncryptedFs]
+//! implements [FileSystem](crate::core::FileSystem) and
+//! [Delta](crate::core::Delta), it can be used as the remote of a
+//! [Sync](crate::sync::Sync) like any other backend.
+//!
+//! # Examples
+//! ## Syncing with an Encrypted Drive
+//! ```no_run
+//! use helsync::cloud::onedrive;
+//! use helsync::encrypted::EncryptedFs;
+//! use helsync::local;
+//! use helsync::sync::Sync;
+//! use std::sync::Arc;
+//!
+//! async fn encrypted_sync(local: Arc<local::Client>, remote: onedrive::Client) -> helsync::core::Result<()> {
+//!     // Fails if the drive was encrypted with another passphrase.
+//!     let remote = EncryptedFs::open(Arc::new(remote), "correct horse battery staple").await?
+//!         .with_encrypted_names(true);
+//!
+//!     let mut sync = Sync::load(local, Arc::new(remote)).await?;
+//!     sync.sync().await?;
+//!     Ok(())
+//! }
+//! ```
+
+mod fs;
+pub use fs::*;
+
+mod key;
+pub use key::KEY_FILE;
@@ -2,8 +2,11 @@
 
 pub mod cloud;
 pub mod core;
+pub mod directory;
+pub mod encrypted;
 pub mod oauth2;
 pub mod local;
+pub mod memory;
 pub mod sync;
 
 #[cfg(feature = "plugin")]
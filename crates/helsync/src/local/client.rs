@@ -1,12 +1,25 @@
-use crate::core::{FileSystem, Delta, Result, Error};
+use crate::core::{FileSystem, Delta, Result, Error, ByteStream, read_exact_chunk};
+use crate::core::{UploadSession, UploadStore};
 use super::tags::{Tag, TagWithFiles};
+use super::state::SyncState;
+use super::drive::{Drive, Provider};
+use crate::oauth2::{StoreFuture, Token, TokenStore};
 use super::file::LocalFile;
+use crate::sync::{Rule, SyncRules};
 
 use std::time::{SystemTime, UNIX_EPOCH};
 use std::collections::HashMap;
+use sha2::{Digest, Sha256};
 use database::Database;
 use std::sync::Arc;
-use sqlx::Acquire;
+use sqlx::{Acquire, SqliteConnection};
+use bytes::Bytes;
+use futures_util::stream;
+use tokio::io::AsyncRead;
+
+/// Size of the chunks in which content is streamed to and from the
+/// database.
+const CHUNK_SIZE: usize = 256 * 1024;
 
 /// Local virtual [FileSystem](crate::core::FileSystem).
 pub struct Client {
@@ -21,9 +34,10 @@ impl Client {
     }
 
     /// Fetch a file using its remote ID.
-    pub(crate) async fn get_remote_file(&self, remote_id: &str) -> Result<Option<LocalFile>> {
+    pub async fn get_remote_file(&self, remote_id: &str) -> Result<Option<LocalFile>> {
         let mut conn = self.db.acquire().await?;
-        let file = sqlx::query_as("SELECT * FROM File WHERE remote_id=?")
+        // Prefer live files over deleted files sharing the same remote_id.
+        let file = sqlx::query_as("SELECT * FROM File WHERE remote_id=? ORDER BY is_deleted ASC")
             .bind(remote_id)
             .fetch_optional(&mut *conn)
             .await?;
@@ -31,12 +45,298 @@ impl Client {
         Ok(file)
     }
 
+    /// Fetch a file by its ID, including deleted files.
+    pub async fn find_file(&self, id: i64) -> Result<Option<LocalFile>> {
+        let mut conn = self.db.acquire().await?;
+        let file = sqlx::query_as("SELECT * FROM File WHERE id=?")
+            .bind(id)
+            .fetch_optional(&mut *conn)
+            .await?;
+
+        Ok(file)
+    }
+
     /// Sets a file's remote id.
+    ///
+    /// Deleted files linked to the same remote file (e.g. a file
+    /// that was deleted locally while it was edited remotely, and has
+    /// been downloaded again) are unlinked, so that their deletion
+    /// isn't mistaken for a change to the remote file.
     pub(crate) async fn set_remote_id(&self, local_id: i64, remote_id: &str) -> Result<()> {
         let mut conn = self.db.acquire().await?;
+        let mut tx = conn.begin().await?;
+        sqlx::query("UPDATE File SET remote_id=NULL WHERE remote_id=? AND is_deleted=TRUE AND id!=?")
+            .bind(remote_id)
+            .bind(local_id)
+            .execute(&mut *tx)
+            .await?;
+
         sqlx::query("UPDATE File SET remote_id=? WHERE id=?")
             .bind(remote_id)
             .bind(local_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Sets the time at which a file was last synchronized.
+    pub(crate) async fn set_synced_at(&self, local_id: i64, synced_at: i64) -> Result<()> {
+        let mut conn = self.db.acquire().await?;
+        sqlx::query("UPDATE File SET synced_at=? WHERE id=?")
+            .bind(synced_at)
+            .bind(local_id)
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Marks all deleted files as synchronized.
+    pub(crate) async fn set_deleted_synced_at(&self, synced_at: i64) -> Result<()> {
+        let mut conn = self.db.acquire().await?;
+        sqlx::query("UPDATE File SET synced_at=? WHERE is_deleted=TRUE")
+            .bind(synced_at)
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Fetch the persisted synchronization state.
+    pub(crate) async fn get_sync_state(&self) -> Result<Option<SyncState>> {
+        let mut conn = self.db.acquire().await?;
+        let state = sqlx::query_as("SELECT * FROM SyncState WHERE id=0")
+            .fetch_optional(&mut *conn)
+            .await?;
+
+        Ok(state)
+    }
+
+    /// Persist the synchronization state.
+    pub(crate) async fn set_sync_state(&self, state: &SyncState) -> Result<()> {
+        let mut conn = self.db.acquire().await?;
+        sqlx::query("INSERT INTO SyncState (id, local_token, remote_token,
+        remote_root) VALUES (0, ?, ?, ?) ON CONFLICT (id) DO UPDATE SET
+        local_token=excluded.local_token, remote_token=excluded.remote_token,
+        remote_root=excluded.remote_root")
+            .bind(&state.local_token)
+            .bind(&state.remote_token)
+            .bind(&state.remote_root)
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Fetch the session of an interrupted upload to the remote file
+    /// with `remote_id`, if any.
+    pub async fn get_upload_session(&self, remote_id: &str) -> Result<Option<UploadSession>> {
+        let mut conn = self.db.acquire().await?;
+        let row: Option<(String, String, i64, String, i64)> = sqlx::query_as("SELECT
+        remote_id, upload_url, size, hash, committed FROM UploadSession WHERE remote_id=?")
+            .bind(remote_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+
+        Ok(row.map(|(file_id, upload_url, size, sha256, committed)| UploadSession {
+            file_id, upload_url, sha256,
+            size: size as u64,
+            committed: committed as u64,
+        }))
+    }
+
+    /// Persist an upload session, replacing any session of the same
+    /// remote file.
+    pub async fn set_upload_session(&self, session: &UploadSession) -> Result<()> {
+        let mut conn = self.db.acquire().await?;
+        sqlx::query("INSERT INTO UploadSession (remote_id, upload_url, size,
+        hash, committed) VALUES (?, ?, ?, ?, ?) ON CONFLICT (remote_id) DO UPDATE
+        SET upload_url=excluded.upload_url, size=excluded.size,
+        hash=excluded.hash, committed=excluded.committed")
+            .bind(&session.file_id)
+            .bind(&session.upload_url)
+            .bind(session.size as i64)
+            .bind(&session.sha256)
+            .bind(session.committed as i64)
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Remove the upload session of the remote file with
+    /// `remote_id`.
+    pub async fn remove_upload_session(&self, remote_id: &str) -> Result<()> {
+        let mut conn = self.db.acquire().await?;
+        sqlx::query("DELETE FROM UploadSession WHERE remote_id=?")
+            .bind(remote_id)
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Fetch the registered cloud [Drive], if any.
+    pub async fn get_drive(&self) -> Result<Option<Drive>> {
+        let mut conn = self.db.acquire().await?;
+        let drive = sqlx::query_as("SELECT * FROM Drive WHERE id=0")
+            .fetch_optional(&mut *conn)
+            .await?;
+
+        Ok(drive)
+    }
+
+    /// Register a cloud [Drive], replacing the previous registration
+    /// (and its token).
+    pub async fn set_drive(
+        &self,
+        provider: Provider,
+        client_id: &str,
+        client_secret: Option<&str>,
+        port: u16,
+    ) -> Result<Drive> {
+        let mut conn = self.db.acquire().await?;
+        let drive = sqlx::query_as("INSERT OR REPLACE INTO Drive (id, provider,
+        client_id, client_secret, port) VALUES (0, ?, ?, ?, ?) RETURNING *")
+            .bind(provider)
+            .bind(client_id)
+            .bind(client_secret)
+            .bind(port)
+            .fetch_one(&mut *conn)
+            .await?;
+
+        Ok(drive)
+    }
+
+    /// Store the OAuth2 [Token] of the registered [Drive].
+    pub async fn set_drive_token(&self, token: &Token) -> Result<()> {
+        let mut conn = self.db.acquire().await?;
+        let res = sqlx::query("UPDATE Drive SET access_token=?, refresh_token=?,
+        created_at=?, expires_in=? WHERE id=0")
+            .bind(&token.access_token)
+            .bind(&token.refresh_token)
+            .bind(token.created_at)
+            .bind(token.expires_in)
+            .execute(&mut *conn)
+            .await?;
+
+        if res.rows_affected() == 0 {
+            return Err(Error::Sql("no drive has been registered".to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// Remove the registered [Drive] and forget all synchronization
+    /// state, so that connecting a new drive starts from scratch.
+    pub async fn remove_drive(&self) -> Result<()> {
+        let mut conn = self.db.acquire().await?;
+        let mut tx = conn.begin().await?;
+        sqlx::query("DELETE FROM Drive").execute(&mut *tx).await?;
+        Self::clear_sync(&mut tx, None).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Forget all synchronization state and start synchronizing with
+    /// the remote folder `remote_root`. Local files are unlinked from
+    /// their remote counterparts, which are matched again on the next
+    /// sync.
+    pub async fn reset_sync(&self, remote_root: Option<&str>) -> Result<()> {
+        let mut conn = self.db.acquire().await?;
+        let mut tx = conn.begin().await?;
+        Self::clear_sync(&mut tx, remote_root).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn clear_sync(conn: &mut SqliteConnection, remote_root: Option<&str>) -> Result<()> {
+        sqlx::query("DELETE FROM SyncState").execute(&mut *conn).await?;
+        sqlx::query("DELETE FROM FileSnapshot").execute(&mut *conn).await?;
+        sqlx::query("DELETE FROM UploadSession").execute(&mut *conn).await?;
+        sqlx::query("UPDATE File SET remote_id=NULL, synced_at=NULL")
+            .execute(&mut *conn)
+            .await?;
+
+        if remote_root.is_some() {
+            sqlx::query("INSERT INTO SyncState (id, remote_root) VALUES (0, ?)")
+                .bind(remote_root)
+                .execute(&mut *conn)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Fetch the selective sync rules.
+    pub async fn get_sync_rules(&self) -> Result<SyncRules> {
+        let mut conn = self.db.acquire().await?;
+        let rows: Vec<(String, String, String)> = sqlx::query_as("SELECT
+        kind, matcher, value FROM SyncRule ORDER BY id")
+            .fetch_all(&mut *conn)
+            .await?;
+
+        rows.iter()
+            .map(|(kind, matcher, value)| Rule::from_columns(kind, matcher, value)
+                 .ok_or_else(|| Error::Sql(format!("invalid sync rule: {kind} {matcher} {value}"))))
+            .collect::<Result<Vec<Rule>>>()
+            .map(SyncRules::from)
+    }
+
+    /// Replace the selective sync rules.
+    ///
+    /// Files that were previously excluded are only picked up by the
+    /// next full sync (see [Sync::sync_full](crate::sync::Sync::sync_full)).
+    pub async fn set_sync_rules(&self, rules: &SyncRules) -> Result<()> {
+        let mut conn = self.db.acquire().await?;
+        let mut tx = conn.begin().await?;
+        sqlx::query("DELETE FROM SyncRule").execute(&mut *tx).await?;
+        for rule in rules.rules() {
+            let (kind, matcher, value) = rule.to_columns();
+            sqlx::query("INSERT INTO SyncRule (kind, matcher, value) VALUES (?, ?, ?)")
+                .bind(kind)
+                .bind(matcher)
+                .bind(value)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Fetch the size of a file's content in bytes.
+    pub(crate) async fn get_file_size(&self, id: i64) -> Result<u64> {
+        let mut conn = self.db.acquire().await?;
+        let size: Option<i64> = sqlx::query_scalar("SELECT
+        length(CAST(content AS BLOB)) FROM FileData WHERE id=?")
+            .bind(id)
+            .fetch_optional(&mut *conn)
+            .await?;
+
+        Ok(size.unwrap_or(0) as u64)
+    }
+
+    /// Fetch the content of a file as it was when it was last synced.
+    pub(crate) async fn get_snapshot(&self, id: i64) -> Result<Option<Vec<u8>>> {
+        let mut conn = self.db.acquire().await?;
+        let content = sqlx::query_scalar("SELECT content FROM FileSnapshot WHERE id=?")
+            .bind(id)
+            .fetch_optional(&mut *conn)
+            .await?;
+
+        Ok(content)
+    }
+
+    /// Record the content of a file at the time it was synced.
+    pub(crate) async fn set_snapshot(&self, id: i64, content: &[u8]) -> Result<()> {
+        let mut conn = self.db.acquire().await?;
+        sqlx::query("INSERT INTO FileSnapshot (id, content) VALUES (?, ?)
+        ON CONFLICT (id) DO UPDATE SET content=excluded.content")
+            .bind(id)
+            .bind(content)
             .execute(&mut *conn)
             .await?;
 
@@ -313,6 +613,24 @@ impl FileSystem for Client {
             .unwrap_or_default()
             .as_secs() as i64;
 
+        // A folder can't be moved into itself or its descendants.
+        if let Some(parent_id) = parent_id {
+            let is_within: bool = sqlx::query_scalar("
+              WITH RECURSIVE Ancestor(id) AS (
+                SELECT id FROM File WHERE id=?
+                UNION SELECT File.parent FROM File JOIN Ancestor ON File.id=Ancestor.id
+                WHERE File.parent IS NOT NULL
+              ) SELECT EXISTS(SELECT 1 FROM Ancestor WHERE id=CAST(? AS INTEGER))")
+                .bind(parent_id)
+                .bind(source_id)
+                .fetch_one(&mut *tx)
+                .await?;
+
+            if is_within {
+                return Err(Error::Io(format!("cannot move \"{source_id}\" into itself")));
+            }
+        }
+
         match name {
             Some(name) =>
                 sqlx::query("UPDATE FILE SET parent=?, name=?, modified_at=? WHERE id=?")
@@ -348,11 +666,16 @@ impl FileSystem for Client {
             .unwrap_or_default()
             .as_secs() as i64;
 
+        // Descendants are deleted along with the file, so that no
+        // live file is left under a deleted folder.
         let res = sqlx::query("UPDATE File SET is_deleted=TRUE,
-            modified_at=? WHERE is_deleted=FALSE AND (id=? OR parent=?)")
+            modified_at=? WHERE is_deleted=FALSE AND id IN (
+              WITH RECURSIVE Tree(id) AS (
+                SELECT id FROM File WHERE id=? AND is_deleted=FALSE
+                UNION SELECT File.id FROM File JOIN Tree ON File.parent=Tree.id
+              ) SELECT id FROM Tree)")
             .bind(modified_at)
             .bind(id)
-            .bind(id)
             .execute(&mut *conn)
             .await?;
 
@@ -438,21 +761,47 @@ impl FileSystem for Client {
 
     /// Write a slice of bytes to a file.
     ///
-    /// The file's content will be replaced with the bytes.
+    /// The file's content will be replaced with the bytes. The
+    /// file's hash and `modified_at` are only updated if the content
+    /// has changed.
     async fn write_to_file(&self, id: &str, content: &[u8]) -> Result<LocalFile> {
         let mut conn = self.db.acquire().await?;
+        let mut tx = conn.begin().await?;
+        let file: LocalFile = sqlx::query_as("SELECT * FROM File WHERE id=?")
+            .bind(id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+        let hash = format!("{:x}", Sha256::digest(content));
+        if file.hash.as_ref() == Some(&hash) {
+            return Ok(file);
+        }
+
+        let modified_at: i64 = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64;
+
         sqlx::query("INSERT INTO FileData (id, content) VALUES (?, ?)
     ON CONFLICT (id) DO UPDATE SET content = excluded.content")
             .bind(id)
             .bind(content)
-            .execute(&mut *conn)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("UPDATE File SET hash=?, modified_at=? WHERE id=?")
+            .bind(&hash)
+            .bind(modified_at)
+            .bind(id)
+            .execute(&mut *tx)
             .await?;
 
         let file: LocalFile = sqlx::query_as("SELECT * FROM File WHERE id=?")
             .bind(id)
-            .fetch_one(&mut *conn)
+            .fetch_one(&mut *tx)
             .await?;
 
+        tx.commit().await?;
         Ok(file)
     }
 
@@ -470,6 +819,97 @@ impl FileSystem for Client {
             None => Ok(Vec::new()),
         }
     }
+
+    /// Write the bytes read from `reader` to a file, in chunks.
+    ///
+    /// Like [write_to_file](Self::write_to_file), the file's hash and
+    /// `modified_at` are only updated if the content has changed.
+    async fn write_from_reader<R>(&self, id: &str, reader: R, size: u64) -> Result<LocalFile>
+    where R: AsyncRead + Unpin + Send {
+        let mut conn = self.db.acquire().await?;
+        let mut tx = conn.begin().await?;
+        let file: LocalFile = sqlx::query_as("SELECT * FROM File WHERE id=?")
+            .bind(id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+        sqlx::query("INSERT INTO FileData (id, content) VALUES (?, X'')
+    ON CONFLICT (id) DO UPDATE SET content = excluded.content")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        // Chunks are appended to the content, so that only a single
+        // chunk is held in memory at a time.
+        let mut reader = reader;
+        let mut hasher = Sha256::new();
+        let mut written: u64 = 0;
+        while written < size {
+            let len = std::cmp::min(CHUNK_SIZE as u64, size - written) as usize;
+            let chunk = read_exact_chunk(&mut reader, len).await?;
+            sqlx::query("UPDATE FileData SET content = CAST(content || ? AS BLOB) WHERE id=?")
+                .bind(&chunk)
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+
+            hasher.update(&chunk);
+            written += chunk.len() as u64;
+        }
+
+        // Discard the transaction if the content hasn't changed.
+        let hash = format!("{:x}", hasher.finalize());
+        if file.hash.as_ref() == Some(&hash) {
+            return Ok(file);
+        }
+
+        let modified_at: i64 = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64;
+
+        sqlx::query("UPDATE File SET hash=?, modified_at=? WHERE id=?")
+            .bind(&hash)
+            .bind(modified_at)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        let file: LocalFile = sqlx::query_as("SELECT * FROM File WHERE id=?")
+            .bind(id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(file)
+    }
+
+    /// Read the file's binary data in chunks.
+    async fn read_stream(&self, id: &str) -> Result<ByteStream<Error>> {
+        let db = self.db.clone();
+        let id = id.to_string();
+        let chunks = stream::try_unfold(0usize, move |offset| {
+            let (db, id) = (db.clone(), id.clone());
+            async move {
+                let mut conn = db.acquire().await?;
+
+                // SQLite's substr() indexes the bytes of a blob,
+                // starting at 1.
+                let chunk: Option<Vec<u8>> = sqlx::query_scalar("SELECT
+        substr(content, ?, ?) FROM FileData WHERE id=?")
+                    .bind((offset + 1) as i64)
+                    .bind(CHUNK_SIZE as i64)
+                    .bind(&id)
+                    .fetch_optional(&mut *conn)
+                    .await?;
+
+                Ok(chunk.filter(|chunk| !chunk.is_empty())
+                    .map(|chunk| (Bytes::from(chunk), offset + CHUNK_SIZE)))
+            }
+        });
+
+        Ok(Box::pin(chunks))
+    }
 }
 
 impl Delta for Client {
@@ -499,11 +939,42 @@ impl Delta for Client {
     }
 }
 
+/// Stores the token of the registered [Drive], so that tokens
+/// refreshed by cloud clients survive restarts.
+impl TokenStore for Client {
+    fn load(&self) -> StoreFuture<'_, Option<Token>> {
+        Box::pin(async move {
+            Ok(self.get_drive().await?.and_then(|drive| drive.token))
+        })
+    }
+
+    fn save<'a>(&'a self, token: &'a Token) -> StoreFuture<'a, ()> {
+        Box::pin(self.set_drive_token(token))
+    }
+}
+
+/// Stores the sessions of large uploads, so that interrupted
+/// uploads are resumed by the next sync.
+impl UploadStore for Client {
+    fn load<'a>(&'a self, file_id: &'a str) -> StoreFuture<'a, Option<UploadSession>> {
+        Box::pin(self.get_upload_session(file_id))
+    }
+
+    fn save<'a>(&'a self, session: &'a UploadSession) -> StoreFuture<'a, ()> {
+        Box::pin(self.set_upload_session(session))
+    }
+
+    fn remove<'a>(&'a self, file_id: &'a str) -> StoreFuture<'a, ()> {
+        Box::pin(self.remove_upload_session(file_id))
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
     use crate::local::schema;
     use super::*;
+    use crate::sync::Matcher;
     use std::sync::Arc;
     use tokio::sync::OnceCell;
     static CLIENT: OnceCell<Arc<Client>> = OnceCell::const_new();
@@ -518,7 +989,8 @@ mod tests {
 
             // Add files for testing.
             const TESTING_SCHEMA: &str = r#"
-INSERT INTO File VALUES
+INSERT OR REPLACE INTO File (id, name, parent, remote_id, is_deleted,
+  created_at, modified_at, synced_at, is_folder, is_bookmarked) VALUES
   (0, "test.txt", NULL, NULL, FALSE, 0, 0, NULL, FALSE, FALSE),
   (1, "test-dltd", NULL, NULL, TRUE, 0, 0, NULL, TRUE, FALSE),
   (2, "my_folder", NULL, NULL, FALSE, 0, 0, NULL, TRUE, FALSE),
@@ -543,21 +1015,17 @@ INSERT INTO TagBind VALUES
   ("tag2", 0);
 "#;
 
+            let mut migrations = schema::migrations();
+            migrations.push(database::Migration {
+                version: migrations.len() as i64,
+                sql: TESTING_SCHEMA.to_string(),
+                kind: database::MigrationType::Up,
+            });
+
             let db = database::Database::new(&database::Config {
                 max_connections: 1,
                 local_path: db_name.to_string(),
-                migrations: vec![
-                    database::Migration {
-                        version: 0,
-                        sql: schema::SCHEMA_VERSION_0.to_string(),
-                        kind: database::MigrationType::Up,
-                    },
-                    database::Migration {
-                        version: 1,
-                        sql: TESTING_SCHEMA.to_string(),
-                        kind: database::MigrationType::Up,
-                    }
-                ]
+                migrations,
             }).await.unwrap();
             Arc::new(Client::new(Arc::new(db)))
         }).await.clone()
@@ -633,6 +1101,39 @@ INSERT INTO TagBind VALUES
         assert!(fs.move_file("3", Some("1"), None).await.is_err());
     }
 
+    #[tokio::test]
+    async fn test_move_file_into_itself() {
+        let fs = get_local_fs().await;
+        let folder = fs.create_folder(None, "cyclic").await.unwrap();
+        let folder_id = folder.id.to_string();
+        let child = fs.create_folder(Some(&folder_id), "cyclic-child").await.unwrap();
+        let child_id = child.id.to_string();
+
+        // Should not be able to move a folder into itself or its
+        // descendants.
+        assert!(fs.move_file(&folder_id, Some(&folder_id), None).await.is_err());
+        assert!(fs.move_file(&folder_id, Some(&child_id), None).await.is_err());
+
+        // Should be able to once the descendant is moved out.
+        assert!(fs.move_file(&child_id, None, None).await.is_ok());
+        assert!(fs.move_file(&folder_id, Some(&child_id), None).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_set_remote_id() {
+        let fs = get_local_fs().await;
+        let deleted = fs.create_file(None, "relinked.txt").await.unwrap();
+        fs.set_remote_id(deleted.id, "relinked").await.unwrap();
+        fs.remove_file(&deleted.id.to_string()).await.unwrap();
+
+        // Linking another file to the same remote file unlinks the
+        // deleted file.
+        let file = fs.create_file(None, "relinked.txt").await.unwrap();
+        fs.set_remote_id(file.id, "relinked").await.unwrap();
+        assert!(fs.find_file(deleted.id).await.unwrap().unwrap().remote_id.is_none());
+        assert_eq!(fs.get_remote_file("relinked").await.unwrap().unwrap().id, file.id);
+    }
+
     #[tokio::test]
     async fn test_remove_file() {
         let fs = get_local_fs().await;
@@ -642,6 +1143,14 @@ INSERT INTO TagBind VALUES
         // Children of deleted files must also be deleted.
         assert!(fs.get_file("6").await.is_err());
 
+        // Including the children of their children.
+        let folder = fs.create_folder(None, "remove-nested").await.unwrap();
+        let child = fs.create_folder(Some(&folder.id.to_string()), "child").await.unwrap();
+        let grandchild = fs.create_file(Some(&child.id.to_string()), "grandchild.txt").await.unwrap();
+        assert!(fs.remove_file(&folder.id.to_string()).await.is_ok());
+        assert!(fs.get_file(&child.id.to_string()).await.is_err());
+        assert!(fs.get_file(&grandchild.id.to_string()).await.is_err());
+
         // Do not allow already-deleted files to be deleted again.
         assert!(fs.remove_file("1").await.is_err());
     }
@@ -728,12 +1237,69 @@ INSERT INTO TagBind VALUES
 
     #[tokio::test]
     async fn test_write_to_file() {
+        let fs = get_local_fs().await;
+        let file = fs.create_file(None, "write_test.md").await.unwrap();
+        assert!(file.hash.is_none());
+
+        // Writing updates the file's hash and modified_at.
+        let written = fs.write_to_file(&file.id.to_string(), b"hello").await.unwrap();
+        assert_eq!(written.hash.as_deref(),
+            Some("2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"));
+        assert!(written.modified_at >= file.modified_at);
+        assert_eq!(fs.read_from_file(&file.id.to_string()).await.unwrap(), b"hello");
+
+        // Writing the same content is a no-op.
+        let mut conn = fs.db.acquire().await.unwrap();
+        sqlx::query("UPDATE File SET modified_at=0 WHERE id=?")
+            .bind(file.id)
+            .execute(&mut *conn)
+            .await.unwrap();
+        drop(conn);
+        let rewritten = fs.write_to_file(&file.id.to_string(), b"hello").await.unwrap();
+        assert_eq!(rewritten.modified_at, 0);
     }
 
     #[tokio::test]
     async fn test_read_from_file() {
     }
 
+    #[tokio::test]
+    async fn test_stream() {
+        use crate::core::ProgressReader;
+        use futures_util::StreamExt;
+
+        let fs = get_local_fs().await;
+        let file = fs.create_file(None, "stream_test.md").await.unwrap();
+        let id = file.id.to_string();
+
+        // Content spans several chunks and isn't valid UTF-8.
+        let content: Vec<u8> = (0..CHUNK_SIZE * 2 + 10).map(|i| (i % 256) as u8).collect();
+        let mut sent = 0;
+        let reader = ProgressReader::new(&content[..], |n| sent = n);
+        let written = fs.write_from_reader(&id, reader, content.len() as u64).await.unwrap();
+        assert_eq!(sent, content.len() as u64);
+        assert_eq!(written.hash, Some(format!("{:x}", Sha256::digest(&content))));
+        assert_eq!(fs.read_from_file(&id).await.unwrap(), content);
+
+        let mut chunks = fs.read_stream(&id).await.unwrap();
+        let mut read = Vec::new();
+        let mut count = 0;
+        while let Some(chunk) = chunks.next().await {
+            read.extend_from_slice(&chunk.unwrap());
+            count += 1;
+        }
+        assert_eq!(read, content);
+        assert_eq!(count, 3);
+
+        // A reader that ends early doesn't change the content.
+        assert!(fs.write_from_reader(&id, &content[..10], 20).await.is_err());
+        assert_eq!(fs.read_from_file(&id).await.unwrap(), content);
+
+        // Empty files are streamed as no chunks.
+        fs.write_from_reader(&id, &b""[..], 0).await.unwrap();
+        assert!(fs.read_stream(&id).await.unwrap().next().await.is_none());
+    }
+
     #[tokio::test]
     async fn test_create_bookmark() {
         let fs = get_local_fs().await;
@@ -858,4 +1424,85 @@ INSERT INTO TagBind VALUES
         assert!(fs.remove_tag_bind("0", "no-such-tag").await.is_ok());
         assert!(fs.remove_tag_bind("999", "tag1").await.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_drive() {
+        let fs = get_local_fs().await;
+
+        // Tokens cannot be stored before a drive is registered.
+        let token = Token {
+            access_token: "access".to_string(),
+            refresh_token: "refresh".to_string(),
+            created_at: 0,
+            expires_in: 3600,
+        };
+        assert!(fs.get_drive().await.unwrap().is_none());
+        assert!(fs.set_drive_token(&token).await.is_err());
+
+        let drive = fs.set_drive(Provider::OneDrive, "client-id", None, 6969).await.unwrap();
+        assert_eq!(drive.provider, Provider::OneDrive);
+        assert_eq!(drive.port, 6969);
+        assert!(!drive.is_connected);
+
+        fs.set_drive_token(&token).await.unwrap();
+        let drive = fs.get_drive().await.unwrap().unwrap();
+        assert!(drive.is_connected);
+        assert_eq!(drive.token.unwrap().refresh_token, "refresh");
+
+        // Tokens saved by cloud clients are stored with the drive.
+        let rotated = Token { refresh_token: "rotated".to_string(), ..token.clone() };
+        TokenStore::save(fs.as_ref(), &rotated).await.unwrap();
+        let stored = TokenStore::load(fs.as_ref()).await.unwrap().unwrap();
+        assert_eq!(stored.refresh_token, "rotated");
+
+        // Registering a new drive discards the token.
+        let drive = fs.set_drive(Provider::GoogleDrive, "id", Some("secret"), 6970).await.unwrap();
+        assert_eq!(drive.client_secret, Some("secret".to_string()));
+        assert!(drive.token.is_none());
+
+        fs.reset_sync(Some("remote-root")).await.unwrap();
+        let state = fs.get_sync_state().await.unwrap().unwrap();
+        assert_eq!(state.remote_root, Some("remote-root".to_string()));
+        assert!(state.remote_token.is_none());
+
+        fs.remove_drive().await.unwrap();
+        assert!(fs.get_drive().await.unwrap().is_none());
+        assert!(fs.get_sync_state().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_upload_session() {
+        let fs = get_local_fs().await;
+        let session = UploadSession {
+            file_id: "upload-remote-id".to_string(),
+            upload_url: "https://example.com/upload".to_string(),
+            size: 1024,
+            sha256: "hash".to_string(),
+            committed: 0,
+        };
+
+        fs.set_upload_session(&session).await.unwrap();
+        let committed = UploadSession { committed: 512, ..session.clone() };
+        UploadStore::save(fs.as_ref(), &committed).await.unwrap();
+        let stored = fs.get_upload_session(&session.file_id).await.unwrap();
+        assert_eq!(stored, Some(committed));
+
+        fs.remove_upload_session(&session.file_id).await.unwrap();
+        assert!(fs.get_upload_session(&session.file_id).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_sync_rules() {
+        let fs = get_local_fs().await;
+        let rules = SyncRules::default()
+            .include(Matcher::Folder("folder-id".to_string()))
+            .exclude(Matcher::Path("**/*.tmp".to_string()))
+            .exclude(Matcher::LargerThan(1024));
+
+        fs.set_sync_rules(&rules).await.unwrap();
+        assert_eq!(fs.get_sync_rules().await.unwrap(), rules);
+
+        fs.set_sync_rules(&SyncRules::default()).await.unwrap();
+        assert!(fs.get_sync_rules().await.unwrap().is_empty());
+    }
 }
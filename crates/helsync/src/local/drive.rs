@@ -0,0 +1,81 @@
+use crate::oauth2::{self, Config, Token};
+
+use serde::{Serialize, Deserialize};
+use sqlx::sqlite::SqliteRow;
+use sqlx::{FromRow, Row};
+
+/// Enumerates the cloud APIs that a [Drive] may connect to.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, sqlx::Type)]
+pub enum Provider {
+    GoogleDrive,
+    OneDrive,
+}
+
+impl std::fmt::Display for Provider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Provider::GoogleDrive => write!(f, "GoogleDrive"),
+            Provider::OneDrive => write!(f, "OneDrive"),
+        }
+    }
+}
+
+/// A cloud drive that the local filesystem synchronizes with.
+///
+/// The OAuth2 client secret and [Token] are never serialized.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Drive {
+    pub provider: Provider,
+    pub client_id: String,
+
+    #[serde(skip)]
+    pub client_secret: Option<String>,
+
+    /// Port of the local server that captures the OAuth2
+    /// authorization grant redirect.
+    pub port: u16,
+
+    #[serde(skip)]
+    pub token: Option<Token>,
+
+    /// Whether the drive has been authorized.
+    pub is_connected: bool,
+}
+
+impl FromRow<'_, SqliteRow> for Drive {
+    fn from_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        let access_token: Option<String> = row.try_get("access_token")?;
+        let mut token: Option<Token> = None;
+        if let Some(access_token) = access_token {
+            token = Some(Token {
+                access_token,
+                refresh_token: row.try_get("refresh_token")?,
+                created_at: row.try_get("created_at")?,
+                expires_in: row.try_get("expires_in")?,
+            })
+        }
+        Ok(Self {
+            provider: row.try_get("provider")?,
+            client_id: row.try_get("client_id")?,
+            client_secret: row.try_get("client_secret")?,
+            port: row.try_get("port")?,
+            is_connected: token.is_some(),
+            token,
+        })
+    }
+}
+
+impl From<&Drive> for Config {
+    fn from(drive: &Drive) -> Self {
+        let provider = drive.provider.to_string();
+        Config {
+            auth_endpoint: oauth2::auth_endpoint(&provider),
+            token_endpoint: oauth2::token_endpoint(&provider),
+            client_id: drive.client_id.clone(),
+            client_secret: drive.client_secret.clone(),
+            redirect_uri: format!("http://localhost:{}", drive.port),
+            scope: oauth2::scope(&provider),
+        }
+    }
+}
@@ -19,6 +19,9 @@ pub struct LocalFile {
     pub synced_at: Option<i64>,
     pub is_folder: bool,
     pub is_bookmarked: bool,
+    #[sqlx(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
 }
 
 impl Into<File> for LocalFile {
@@ -31,6 +34,9 @@ impl Into<File> for LocalFile {
             parent_id: self.parent.map(|p| p.to_string()),
             is_folder: self.is_folder,
             is_deleted: self.is_deleted,
+            sha1: None,
+            sha256: self.hash,
+            size: None,
         }
     }
 }
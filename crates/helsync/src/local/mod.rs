@@ -3,11 +3,17 @@
 mod client;
 pub use client::*;
 
+mod drive;
+pub use drive::*;
+
 mod file;
 pub use file::*;
 
 mod schema;
 pub use schema::*;
 
+mod state;
+pub use state::*;
+
 mod tags;
 pub use tags::*;
@@ -115,3 +115,82 @@ BEGIN
   END;
 END;
 ";
+
+pub const SCHEMA_VERSION_1: &str = "
+CREATE TABLE IF NOT EXISTS SyncState (
+  id           INTEGER PRIMARY KEY CHECK (id = 0),
+  local_token  TEXT,
+  remote_token TEXT,
+  remote_root  TEXT
+);
+";
+
+pub const SCHEMA_VERSION_2: &str = "
+CREATE TABLE IF NOT EXISTS FileSnapshot (
+  id INTEGER PRIMARY KEY,
+  content BLOB NOT NULL,
+  FOREIGN KEY (id) REFERENCES File(id)
+    ON UPDATE CASCADE
+    ON DELETE CASCADE
+);
+";
+
+pub const SCHEMA_VERSION_3: &str = "
+ALTER TABLE File ADD COLUMN hash TEXT;
+";
+
+pub const SCHEMA_VERSION_4: &str = "
+CREATE TABLE IF NOT EXISTS Drive (
+  id            INTEGER PRIMARY KEY CHECK (id = 0),
+  provider      VARCHAR(20) NOT NULL,
+  client_id     TEXT        NOT NULL,
+  client_secret TEXT,
+  port          INTEGER     NOT NULL,
+
+  access_token  TEXT,
+  refresh_token TEXT,
+  created_at    INTEGER,
+  expires_in    INTEGER
+);
+";
+
+pub const SCHEMA_VERSION_5: &str = "
+CREATE TABLE IF NOT EXISTS UploadSession (
+  remote_id  TEXT    PRIMARY KEY,
+  upload_url TEXT    NOT NULL,
+  size       INTEGER NOT NULL,
+  hash       TEXT    NOT NULL,
+  committed  INTEGER NOT NULL
+);
+";
+
+pub const SCHEMA_VERSION_6: &str = "
+CREATE TABLE IF NOT EXISTS SyncRule (
+  id      INTEGER     PRIMARY KEY,
+  kind    VARCHAR(10) NOT NULL,
+  matcher VARCHAR(10) NOT NULL,
+  value   TEXT        NOT NULL
+);
+";
+
+/// Migrations that create and upgrade the local database schema,
+/// from [SCHEMA_VERSION_0] to the latest version.
+pub fn migrations() -> Vec<database::Migration> {
+    let schemas = [
+        SCHEMA_VERSION_0,
+        SCHEMA_VERSION_1,
+        SCHEMA_VERSION_2,
+        SCHEMA_VERSION_3,
+        SCHEMA_VERSION_4,
+        SCHEMA_VERSION_5,
+        SCHEMA_VERSION_6,
+    ];
+
+    schemas.iter().enumerate()
+        .map(|(version, sql)| database::Migration {
+            version: version as i64,
+            sql: sql.to_string(),
+            kind: database::MigrationType::Up,
+        })
+        .collect()
+}
@@ -0,0 +1,21 @@
+use serde::{Serialize, Deserialize};
+use sqlx::FromRow;
+
+/// Persisted state of a [Sync](crate::sync::Sync) between the local
+/// filesystem and a remote filesystem.
+#[derive(Debug, FromRow, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncState {
+
+    /// Delta token of the local filesystem.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub local_token: Option<String>,
+
+    /// Delta token of the remote filesystem.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote_token: Option<String>,
+
+    /// ID of the remote folder that mirrors the local root.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote_root: Option<String>,
+}
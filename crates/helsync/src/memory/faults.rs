@@ -0,0 +1,47 @@
+use std::time::Duration;
+
+/// Failures injected into the operations of a
+/// [MemoryFs](super::MemoryFs).
+///
+/// Faults are decided before an operation has any effect, so a
+/// failed operation leaves the file system unchanged. The default
+/// injects no faults.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Faults {
+
+    /// Maximum delay added to every operation. Each operation waits
+    /// for a random duration between zero and `latency`.
+    pub latency: Duration,
+
+    /// Probability (between 0 and 1) that an operation fails with
+    /// an [Io](crate::core::Error::Io) error.
+    pub error_rate: f64,
+
+    /// Probability (between 0 and 1) that an operation that names a
+    /// file (create, copy, move or rename) fails as if a file with
+    /// the same name already existed in the target folder.
+    pub collision_rate: f64,
+}
+
+impl Faults {
+
+    /// Sets the maximum delay added to every operation.
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Sets the probability that an operation fails, clamped to
+    /// [0, 1].
+    pub fn with_error_rate(mut self, error_rate: f64) -> Self {
+        self.error_rate = error_rate.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Sets the probability that naming a file collides with an
+    /// existing file, clamped to [0, 1].
+    pub fn with_collision_rate(mut self, collision_rate: f64) -> Self {
+        self.collision_rate = collision_rate.clamp(0.0, 1.0);
+        self
+    }
+}
@@ -0,0 +1,608 @@
+use crate::core::{Result, Error, File, FileSystem, Delta, Page};
+use super::faults::Faults;
+
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Default number of files per page of a listing.
+const PAGE_SIZE: usize = 100;
+
+/// Implements a [FileSystem] that keeps files in memory.
+///
+/// Files are identified by stable IDs (`mem-1`, `mem-2`, ...) that
+/// are kept when files are moved or renamed, and names are unique
+/// within a folder. Every change is recorded so that [Delta] tokens
+/// can be resumed like those of a cloud drive. Deleted files are
+/// kept as tombstones, which are reported by
+/// [list_deltas](Delta::list_deltas) but are otherwise not found.
+///
+/// Operations can be slowed down or made to fail by setting
+/// [Faults]. Faults are decided by a random number generator seeded
+/// with [with_seed](Self::with_seed), so the same sequence of
+/// operations fails in the same way on every run.
+pub struct MemoryFs {
+    state: Mutex<State>,
+
+    /// Number of operations in progress, and the largest number of
+    /// operations that have been in progress at once.
+    in_flight: AtomicUsize,
+    max_in_flight: AtomicUsize,
+
+    /// Number of files read with [read_from_file](FileSystem::read_from_file).
+    reads: AtomicUsize,
+}
+
+struct State {
+    files: BTreeMap<String, (File, Vec<u8>)>,
+
+    /// IDs of changed files, in the order of the changes. A delta
+    /// token is an index into this list.
+    changes: Vec<String>,
+
+    next_id: u64,
+    page_size: usize,
+    faults: Faults,
+    rng: StdRng,
+}
+
+/// The kind of operation a fault is injected into.
+#[derive(Clone, Copy, PartialEq)]
+enum Operation {
+    Read,
+    Write,
+
+    /// An operation that gives a file a name in a folder.
+    Name,
+}
+
+impl Default for MemoryFs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryFs {
+
+    /// Instantiate an empty file system that injects no faults.
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(State {
+                files: BTreeMap::new(),
+                changes: Vec::new(),
+                next_id: 0,
+                page_size: PAGE_SIZE,
+                faults: Faults::default(),
+                rng: StdRng::seed_from_u64(0),
+            }),
+            in_flight: AtomicUsize::new(0),
+            max_in_flight: AtomicUsize::new(0),
+            reads: AtomicUsize::new(0),
+        }
+    }
+
+    /// Seeds the random number generator that decides which
+    /// operations fail.
+    pub fn with_seed(self, seed: u64) -> Self {
+        self.state.lock().unwrap().rng = StdRng::seed_from_u64(seed);
+        self
+    }
+
+    /// Sets the faults that are injected into every operation.
+    pub fn with_faults(self, faults: Faults) -> Self {
+        self.set_faults(faults);
+        self
+    }
+
+    /// Sets the number of files per page of a listing, which must be
+    /// at least 1.
+    pub fn with_page_size(self, page_size: usize) -> Self {
+        self.state.lock().unwrap().page_size = page_size.max(1);
+        self
+    }
+
+    /// Replaces the faults that are injected into subsequent
+    /// operations, e.g. to let a sync complete after exercising it
+    /// against failures.
+    pub fn set_faults(&self, faults: Faults) {
+        self.state.lock().unwrap().faults = faults;
+    }
+
+    /// Sets the modification time of a file, bypassing faults and
+    /// without recording a change.
+    ///
+    /// Useful to simulate edits made by other clients, whose clocks
+    /// needn't agree with ours.
+    pub fn set_modified_at(&self, id: &str, modified_at: i64) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state.get(id)?;
+        state.files.get_mut(id).unwrap().0.modified_at = modified_at;
+        Ok(())
+    }
+
+    /// Number of files (including folders) that have not been
+    /// deleted.
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().files.values()
+            .filter(|(file, _)| !file.is_deleted)
+            .count()
+    }
+
+    /// Whether the file system has no files.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The largest number of operations that have been in progress at
+    /// once. Operations only overlap while they are delayed by the
+    /// configured [latency](Faults::latency).
+    pub fn max_in_flight(&self) -> usize {
+        self.max_in_flight.load(Ordering::SeqCst)
+    }
+
+    /// Number of files that have been read, including reads that
+    /// failed.
+    pub fn reads(&self) -> usize {
+        self.reads.load(Ordering::SeqCst)
+    }
+
+    /// The name of a file that is copied or moved: `name`, or the
+    /// source file's current name.
+    fn target_name(&self, source_id: &str, name: Option<&str>) -> String {
+        match name {
+            Some(name) => name.to_string(),
+            None => self.state.lock().unwrap().files.get(source_id)
+                .map(|(file, _)| file.name.clone())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Waits and decides whether to fail, according to the
+    /// configured [Faults].
+    async fn inject(&self, operation: Operation, name: &str) -> Result<()> {
+        let (delay, result) = self.state.lock().unwrap().roll(operation, name);
+        if !delay.is_zero() {
+            let _guard = InFlight::new(&self.in_flight, &self.max_in_flight);
+            tokio::time::sleep(delay).await;
+        }
+
+        result
+    }
+}
+
+/// Counts an operation as in progress until it is dropped, i.e. until
+/// the operation completes or is cancelled.
+struct InFlight<'a>(&'a AtomicUsize);
+
+impl<'a> InFlight<'a> {
+    fn new(in_flight: &'a AtomicUsize, max_in_flight: &AtomicUsize) -> Self {
+        let count = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        max_in_flight.fetch_max(count, Ordering::SeqCst);
+        Self(in_flight)
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl State {
+
+    /// Draws the delay and outcome of an operation.
+    fn roll(&mut self, operation: Operation, name: &str) -> (Duration, Result<()>) {
+        let faults = self.faults.clone();
+        let delay = match faults.latency.is_zero() {
+            true => Duration::ZERO,
+            false => faults.latency.mul_f64(self.rng.random::<f64>()),
+        };
+
+        if self.rng.random_bool(faults.error_rate.clamp(0.0, 1.0)) {
+            let kind = match operation {
+                Operation::Read => "read",
+                Operation::Write | Operation::Name => "write",
+            };
+            return (delay, Err(Error::Io(format!("memory: injected {kind} failure"))));
+        }
+
+        if operation == Operation::Name && self.rng.random_bool(faults.collision_rate.clamp(0.0, 1.0)) {
+            return (delay, Err(already_exists(name)));
+        }
+
+        (delay, Ok(()))
+    }
+
+    fn get(&self, id: &str) -> Result<File> {
+        self.files.get(id)
+            .filter(|(file, _)| !file.is_deleted)
+            .map(|(file, _)| file.clone())
+            .ok_or(Error::Io(format!("file \"{id}\" not found")))
+    }
+
+    /// Checks that `parent_id` is an existing folder (or the root)
+    /// without a child named `name`, other than `except`.
+    fn check_target(&self, parent_id: Option<&str>, name: &str, except: Option<&str>) -> Result<()> {
+        if name.is_empty() || name.contains('/') {
+            return Err(Error::Io(format!("invalid file name \"{name}\"")));
+        }
+
+        if let Some(parent_id) = parent_id {
+            if !self.get(parent_id)?.is_folder {
+                return Err(Error::Io(format!("\"{parent_id}\" is not a folder")));
+            }
+        }
+
+        let is_taken = self.children(parent_id).iter()
+            .any(|file| file.name == name && Some(file.id.as_str()) != except);
+
+        match is_taken {
+            true => Err(already_exists(name)),
+            false => Ok(()),
+        }
+    }
+
+    fn insert(&mut self, parent_id: Option<&str>, name: &str, is_folder: bool, content: Vec<u8>) -> Result<File> {
+        self.check_target(parent_id, name, None)?;
+        self.next_id += 1;
+        let file = File {
+            id: format!("mem-{}", self.next_id),
+            name: name.to_string(),
+            modified_at: now(),
+            created_at: now(),
+            parent_id: parent_id.map(|id| id.to_string()),
+            is_folder,
+            is_deleted: false,
+            sha1: None,
+            sha256: (!is_folder).then(|| sha256(&content)),
+            size: (!is_folder).then_some(content.len() as u64),
+        };
+
+        self.files.insert(file.id.clone(), (file.clone(), content));
+        self.changes.push(file.id.clone());
+        Ok(file)
+    }
+
+    fn children(&self, parent_id: Option<&str>) -> Vec<File> {
+        self.files.values()
+            .map(|(file, _)| file)
+            .filter(|file| !file.is_deleted && file.parent_id.as_deref() == parent_id)
+            .cloned()
+            .collect()
+    }
+
+    /// Whether `id` is `ancestor_id` or one of its descendants.
+    fn is_within(&self, id: Option<&str>, ancestor_id: &str) -> bool {
+        let mut id = id.map(|id| id.to_string());
+        while let Some(current) = id {
+            if current == ancestor_id {
+                return true;
+            }
+            id = self.files.get(&current).and_then(|(file, _)| file.parent_id.clone());
+        }
+
+        false
+    }
+}
+
+impl FileSystem for MemoryFs {
+    type File = File;
+    type Error = Error;
+
+    async fn get_file(&self, id: &str) -> Result<File> {
+        self.inject(Operation::Read, id).await?;
+        self.state.lock().unwrap().get(id)
+    }
+
+    /// Copies a file, or a folder along with its descendants.
+    async fn copy_file(&self, source_id: &str, parent_id: Option<&str>, name: Option<&str>) -> Result<File> {
+        self.inject(Operation::Name, &self.target_name(source_id, name)).await?;
+        let mut state = self.state.lock().unwrap();
+        let source = state.get(source_id)?;
+        if source.is_folder && state.is_within(parent_id, source_id) {
+            return Err(Error::Io(format!("cannot copy \"{source_id}\" into itself")));
+        }
+
+        let name = name.unwrap_or(&source.name);
+        let content = state.files[source_id].1.clone();
+        let copy = state.insert(parent_id, name, source.is_folder, content)?;
+        let mut stack = vec![(source.id, copy.id.clone())];
+        while let Some((source_id, copy_id)) = stack.pop() {
+            for child in state.children(Some(&source_id)) {
+                let content = state.files[&child.id].1.clone();
+                let child_copy = state.insert(Some(&copy_id), &child.name, child.is_folder, content)?;
+                if child.is_folder {
+                    stack.push((child.id, child_copy.id));
+                }
+            }
+        }
+
+        Ok(copy)
+    }
+
+    async fn move_file(&self, source_id: &str, parent_id: Option<&str>, name: Option<&str>) -> Result<File> {
+        self.inject(Operation::Name, &self.target_name(source_id, name)).await?;
+        let mut state = self.state.lock().unwrap();
+        let mut file = state.get(source_id)?;
+        if file.is_folder && state.is_within(parent_id, source_id) {
+            return Err(Error::Io(format!("cannot move \"{source_id}\" into itself")));
+        }
+
+        let name = name.unwrap_or(&file.name).to_string();
+        state.check_target(parent_id, &name, Some(source_id))?;
+        file.parent_id = parent_id.map(|id| id.to_string());
+        file.name = name;
+        file.modified_at = now();
+        state.files.get_mut(source_id).unwrap().0 = file.clone();
+        state.changes.push(file.id.clone());
+        Ok(file)
+    }
+
+    /// Deletes a file, or a folder along with its descendants.
+    async fn remove_file(&self, id: &str) -> Result<()> {
+        self.inject(Operation::Write, id).await?;
+        let mut state = self.state.lock().unwrap();
+        state.get(id)?;
+        let mut stack = vec![id.to_string()];
+        while let Some(id) = stack.pop() {
+            stack.extend(state.children(Some(&id)).into_iter().map(|file| file.id));
+            let file = &mut state.files.get_mut(&id).unwrap().0;
+            file.is_deleted = true;
+            file.modified_at = now();
+            state.changes.push(id);
+        }
+
+        Ok(())
+    }
+
+    async fn create_folder(&self, parent_id: Option<&str>, name: &str) -> Result<File> {
+        self.inject(Operation::Name, name).await?;
+        self.state.lock().unwrap().insert(parent_id, name, true, Vec::new())
+    }
+
+    async fn create_file(&self, parent_id: Option<&str>, name: &str) -> Result<File> {
+        self.inject(Operation::Name, name).await?;
+        self.state.lock().unwrap().insert(parent_id, name, false, Vec::new())
+    }
+
+    async fn list_files(&self, parent_id: Option<&str>) -> Result<Vec<File>> {
+        self.inject(Operation::Read, parent_id.unwrap_or_default()).await?;
+        let state = self.state.lock().unwrap();
+        if let Some(parent_id) = parent_id {
+            state.get(parent_id)?;
+        }
+
+        Ok(state.children(parent_id))
+    }
+
+    /// Lists the files of a folder in pages of
+    /// [with_page_size](MemoryFs::with_page_size) files. The page
+    /// token is the index of the page's first file.
+    async fn list_files_page(&self, parent_id: Option<&str>, page_token: Option<&str>) -> Result<Page<File>> {
+        self.inject(Operation::Read, parent_id.unwrap_or_default()).await?;
+        let state = self.state.lock().unwrap();
+        if let Some(parent_id) = parent_id {
+            state.get(parent_id)?;
+        }
+
+        let start: usize = match page_token {
+            Some(token) => token.parse()
+                .map_err(|_| Error::Io(format!("invalid page token \"{token}\"")))?,
+            None => 0,
+        };
+
+        let children = state.children(parent_id);
+        let start = std::cmp::min(start, children.len());
+        let end = std::cmp::min(start + state.page_size, children.len());
+        let next = (end < children.len()).then(|| end.to_string());
+        Ok((children[start..end].to_vec(), next))
+    }
+
+    async fn write_to_file(&self, id: &str, buf: &[u8]) -> Result<File> {
+        self.inject(Operation::Write, id).await?;
+        let mut state = self.state.lock().unwrap();
+        if state.get(id)?.is_folder {
+            return Err(Error::Io(format!("\"{id}\" is a folder")));
+        }
+
+        let (file, content) = state.files.get_mut(id).unwrap();
+        file.modified_at = now();
+        file.sha256 = Some(sha256(buf));
+        file.size = Some(buf.len() as u64);
+        *content = buf.to_vec();
+        let file = file.clone();
+        state.changes.push(id.to_string());
+        Ok(file)
+    }
+
+    async fn read_from_file(&self, id: &str) -> Result<Vec<u8>> {
+        self.reads.fetch_add(1, Ordering::SeqCst);
+        self.inject(Operation::Read, id).await?;
+        let state = self.state.lock().unwrap();
+        if state.get(id)?.is_folder {
+            return Err(Error::Io(format!("\"{id}\" is a folder")));
+        }
+
+        Ok(state.files[id].1.clone())
+    }
+}
+
+impl Delta for MemoryFs {
+    type File = File;
+
+    /// Lists the latest version of every file that changed since
+    /// `token`, including deleted files.
+    async fn list_deltas(&self, token: Option<&str>) -> Result<(Vec<File>, String)> {
+        self.inject(Operation::Read, token.unwrap_or_default()).await?;
+        let state = self.state.lock().unwrap();
+        let start: usize = match token {
+            Some(token) => token.parse().ok()
+                .filter(|start| *start <= state.changes.len())
+                .ok_or(Error::Io(format!("invalid delta token \"{token}\"")))?,
+            None => 0,
+        };
+
+        let mut deltas: Vec<File> = Vec::new();
+        for id in &state.changes[start..] {
+            deltas.retain(|file| &file.id != id);
+            deltas.push(state.files[id].0.clone());
+        }
+
+        Ok((deltas, state.changes.len().to_string()))
+    }
+}
+
+fn already_exists(name: &str) -> Error {
+    Error::Io(format!("file \"{name}\" already exists"))
+}
+
+fn sha256(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MemoryFs;
+    use crate::core::{Delta, FileSystem};
+    use crate::memory::Faults;
+    use std::time::{Duration, Instant};
+
+    #[tokio::test]
+    async fn test_files() {
+        let fs = MemoryFs::new();
+        let folder = fs.create_folder(None, "Diary").await.unwrap();
+        let note = fs.create_file(Some(&folder.id), "today").await.unwrap();
+        assert_eq!(note.parent_id.as_deref(), Some(folder.id.as_str()));
+
+        let written = fs.write_to_file(&note.id, b"Dear diary").await.unwrap();
+        assert_eq!(written.sha256, Some(super::sha256(b"Dear diary")));
+        assert_eq!(fs.read_from_file(&note.id).await.unwrap(), b"Dear diary");
+
+        // Names are unique within a folder.
+        assert!(fs.create_file(Some(&folder.id), "today").await.is_err());
+        assert!(fs.move_file(&folder.id, Some(&folder.id), None).await.is_err());
+
+        // Moves keep the file's ID.
+        let moved = fs.move_file(&note.id, None, Some("yesterday")).await.unwrap();
+        assert_eq!(moved.id, note.id);
+        assert_eq!(moved.parent_id, None);
+
+        let copy = fs.copy_file(&folder.id, None, Some("Journal")).await.unwrap();
+        fs.copy_file(&note.id, Some(&copy.id), None).await.unwrap();
+        assert_eq!(fs.list_files(Some(&copy.id)).await.unwrap().len(), 1);
+        assert_eq!(fs.len(), 4);
+
+        fs.remove_file(&copy.id).await.unwrap();
+        assert_eq!(fs.len(), 2);
+        assert!(fs.get_file(&copy.id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_list_files_paged() {
+        let fs = MemoryFs::new().with_page_size(2);
+        for name in ["a", "b", "c", "d", "e"] {
+            fs.create_file(None, name).await.unwrap();
+        }
+
+        let mut pages = fs.list_files_paged(None);
+        let mut names = Vec::new();
+        let mut count = 0;
+        while let Some(page) = pages.next().await.unwrap() {
+            names.extend(page.into_iter().map(|file| file.name));
+            count += 1;
+        }
+
+        assert_eq!(names, ["a", "b", "c", "d", "e"]);
+        assert_eq!(count, 3);
+    }
+
+    #[tokio::test]
+    async fn test_list_deltas() {
+        let fs = MemoryFs::new();
+        let folder = fs.create_folder(None, "Diary").await.unwrap();
+        let note = fs.create_file(Some(&folder.id), "today").await.unwrap();
+        let (deltas, token) = fs.list_deltas(None).await.unwrap();
+        assert_eq!(deltas.len(), 2);
+
+        let (deltas, token) = fs.list_deltas(Some(&token)).await.unwrap();
+        assert!(deltas.is_empty());
+
+        // Changes to the same file are reported once, and deleting a
+        // folder deletes its descendants.
+        fs.write_to_file(&note.id, b"Dear diary").await.unwrap();
+        fs.remove_file(&folder.id).await.unwrap();
+        let (deltas, _) = fs.list_deltas(Some(&token)).await.unwrap();
+        assert_eq!(deltas.len(), 2);
+        assert!(deltas.iter().all(|file| file.is_deleted));
+
+        assert!(fs.list_deltas(Some("100")).await.is_err());
+        assert!(fs.list_deltas(Some("scan:{}")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_faults() {
+        let faults = Faults::default()
+            .with_error_rate(0.3)
+            .with_collision_rate(0.3);
+
+        // Returns which of 100 operations failed.
+        async fn failures(fs: &MemoryFs) -> Vec<bool> {
+            let mut failures = Vec::new();
+            for i in 0..100 {
+                let result = match i % 2 {
+                    0 => fs.create_file(None, &format!("note {i}")).await.map(|_| ()),
+                    _ => fs.list_files(None).await.map(|_| ()),
+                };
+                failures.push(result.is_err());
+            }
+            failures
+        }
+
+        // The same seed injects the same faults.
+        let a = failures(&MemoryFs::new().with_seed(7).with_faults(faults.clone())).await;
+        let b = failures(&MemoryFs::new().with_seed(7).with_faults(faults.clone())).await;
+        let c = failures(&MemoryFs::new().with_seed(8).with_faults(faults.clone())).await;
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+
+        // Creating files fails more often, since they can also
+        // collide.
+        let creates = a.iter().step_by(2).filter(|failed| **failed).count();
+        let lists = a.iter().skip(1).step_by(2).filter(|failed| **failed).count();
+        assert!(creates > lists && lists > 0);
+
+        // Failed operations have no effect.
+        let fs = MemoryFs::new().with_faults(Faults::default().with_error_rate(1.0));
+        assert!(fs.create_folder(None, "Diary").await.is_err());
+        assert!(fs.is_empty());
+
+        let fs = MemoryFs::new().with_faults(Faults::default().with_latency(Duration::from_millis(20)));
+        let start = Instant::now();
+        for _ in 0..10 {
+            fs.list_files(None).await.unwrap();
+        }
+        assert!(start.elapsed() > Duration::from_millis(20));
+
+        assert_eq!(fs.max_in_flight(), 1);
+
+        let listings = (0..4).map(|_| fs.list_files(None));
+        futures_util::future::try_join_all(listings).await.unwrap();
+        assert!(fs.max_in_flight() > 1);
+
+        fs.set_faults(Faults::default());
+        let start = Instant::now();
+        fs.list_files(None).await.unwrap();
+        assert!(start.elapsed() < Duration::from_millis(20));
+    }
+}
@@ -0,0 +1,41 @@
+//! In-memory [FileSystem](crate::core::FileSystem), for testing
+//! code that syncs with remote file systems.
+//!
+//! [MemoryFs] behaves like a cloud drive that identifies files by
+//! stable IDs and tracks changes with delta tokens, but keeps
+//! everything in memory. [Faults] makes it misbehave the way real
+//! backends do (slow responses, failed requests and name
+//! collisions) using a seeded random number generator, so that
+//! every run with the same seed makes the same decisions.
+//!
+//! # Examples
+//! ## Syncing with an Unreliable Remote
+//! ```no_run
+//! use helsync::local;
+//! use helsync::memory::{Faults, MemoryFs};
+//! use helsync::sync::Sync;
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//!
+//! async fn flaky_sync(local: Arc<local::Client>) -> helsync::core::Result<()> {
+//!     let faults = Faults::default()
+//!         .with_latency(Duration::from_millis(5))
+//!         .with_error_rate(0.2);
+//!
+//!     let remote = Arc::new(MemoryFs::new().with_seed(42).with_faults(faults));
+//!     let mut sync = Sync::new(local, remote.clone());
+//!
+//!     // Retry until the sync gets through.
+//!     while sync.sync_full().await.is_err() {}
+//!
+//!     // Verify the result against a well-behaved remote.
+//!     remote.set_faults(Faults::default());
+//!     Ok(())
+//! }
+//! ```
+
+mod faults;
+pub use faults::*;
+
+mod fs;
+pub use fs::*;
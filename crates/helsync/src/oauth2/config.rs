@@ -47,6 +47,18 @@ impl Config {
         }
     }
 
+    /// Constructs an [Config] for a Dropbox API client.
+    pub fn dropbox(client_id: &str, redirect_uri: &str) -> Self {
+        Self {
+            auth_endpoint: auth_endpoint("Dropbox"),
+            token_endpoint: token_endpoint("Dropbox"),
+            client_id: client_id.to_string(),
+            client_secret: None,
+            redirect_uri: redirect_uri.to_string(),
+            scope: scope("Dropbox"),
+        }
+    }
+
     /// Obtain an OAuth2 authorization [grant](Grant) by requesting
     /// the user's authorization via browser agent and capture an
     /// authorization grant redirect using a local HTTP server that
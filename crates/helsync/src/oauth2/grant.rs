@@ -138,7 +138,13 @@ impl Grant {
 /// request_auth_grant(&pkce, &app_config).unwrap();
 /// ```
 pub fn request_auth_grant(pkce: &PKCE, config: &Config) -> Result<()> {
-    let auth_url = format!("{}?{}", config.auth_endpoint,
+    // Some endpoints carry their own query parameters.
+    let separator = match config.auth_endpoint.contains('?') {
+        true => '&',
+        false => '?',
+    };
+
+    let auth_url = format!("{}{separator}{}", config.auth_endpoint,
         form_urlencoded::Serializer::new(String::new())
             .append_pair("response_type", "code")
             .append_pair("client_id", &config.client_id)
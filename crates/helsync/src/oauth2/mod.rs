@@ -101,6 +101,9 @@ pub use grant::*;
 mod pkce;
 pub use pkce::*;
 
+mod store;
+pub use store::*;
+
 mod token;
 pub use token::*;
 
@@ -0,0 +1,124 @@
+use crate::core::Result;
+use super::Token;
+
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+
+/// The future returned by [TokenStore] methods.
+pub type StoreFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+/// Persists a [Token] across sessions.
+///
+/// Cloud clients call [TokenStore::save] every time they refresh
+/// their token, so that rotated refresh tokens aren't lost when the
+/// client is dropped.
+///
+/// # Examples
+/// ```no_run
+/// use helsync::cloud::onedrive;
+/// use helsync::oauth2::{self, FileTokenStore, TokenStore};
+/// use std::sync::Arc;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let app_config = oauth2::Config::onedrive(
+///         "client-id", "http://localhost:6969"
+///     );
+///
+///     let store = Arc::new(FileTokenStore::new("./token.json"));
+///     let token = store.load().await.unwrap()
+///         .expect("not signed in");
+///
+///     let client = onedrive::Client::new(&token, &app_config)
+///         .with_token_store(store);
+/// }
+/// ```
+pub trait TokenStore: Send + Sync {
+
+    /// Loads the stored token, if any.
+    fn load(&self) -> StoreFuture<'_, Option<Token>>;
+
+    /// Stores `token`, replacing the previously stored token.
+    fn save<'a>(&'a self, token: &'a Token) -> StoreFuture<'a, ()>;
+}
+
+/// A [TokenStore] that keeps the token in a JSON file.
+pub struct FileTokenStore {
+    path: PathBuf,
+}
+
+impl FileTokenStore {
+
+    /// Instantiates a store backed by the file at `path`. The file
+    /// is created when the first token is saved.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl TokenStore for FileTokenStore {
+    fn load(&self) -> StoreFuture<'_, Option<Token>> {
+        Box::pin(async move {
+            match tokio::fs::read(&self.path).await {
+                Ok(buf) => Ok(Some(serde_json::from_slice(&buf)?)),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(err) => Err(err.into()),
+            }
+        })
+    }
+
+    /// Writes the token to a temporary file and renames it over the
+    /// previous one, so that a crash never leaves a truncated token
+    /// behind. On Unix, the file is only readable by its owner.
+    fn save<'a>(&'a self, token: &'a Token) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            let buf = serde_json::to_vec(token)?;
+            let mut tmp_path = self.path.clone().into_os_string();
+            tmp_path.push(".tmp");
+
+            let mut options = tokio::fs::OpenOptions::new();
+            options.write(true).create(true).truncate(true);
+
+            #[cfg(unix)]
+            options.mode(0o600);
+
+            let mut file = options.open(&tmp_path).await?;
+            tokio::io::AsyncWriteExt::write_all(&mut file, &buf).await?;
+            file.sync_all().await?;
+            tokio::fs::rename(&tmp_path, &self.path).await?;
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_file_token_store() {
+        let path = std::env::temp_dir()
+            .join(format!("hs-token-store-{}.json", std::process::id()));
+
+        let store = FileTokenStore::new(&path);
+        assert!(store.load().await.unwrap().is_none());
+
+        let mut token = Token {
+            access_token: "access".to_string(),
+            refresh_token: "refresh".to_string(),
+            created_at: 1,
+            expires_in: 3600,
+        };
+
+        store.save(&token).await.unwrap();
+        token.refresh_token = "rotated".to_string();
+        store.save(&token).await.unwrap();
+
+        let loaded = store.load().await.unwrap().unwrap();
+        assert_eq!(loaded.access_token, "access");
+        assert_eq!(loaded.refresh_token, "rotated");
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -36,7 +36,7 @@ use serde::{Serialize, Deserialize};
 ///     token.refresh_if_expired(&app_config).await.unwrap();
 /// }
 /// ```
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Token {
     pub access_token: String,
     pub refresh_token: String,
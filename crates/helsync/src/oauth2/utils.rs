@@ -1,40 +1,46 @@
 /// Get the OAuth2 authorization endpoint for the given cloud
-/// provider. Currently supported options are "OneDrive" and
-/// "GoogleDrive".
+/// provider. Currently supported options are "OneDrive",
+/// "GoogleDrive" and "Dropbox".
 ///
 /// # Panics
-/// Panics if `provider` is neither `OneDrive` nor `GoogleDrive`.
+/// Panics if `provider` is not `OneDrive`, `GoogleDrive` or
+/// `Dropbox`.
 pub fn auth_endpoint(provider: &str) -> String {
     match provider.to_lowercase().as_str() {
         "onedrive" => ONEDRIVE_AUTH_ENDPOINT.to_string(),
         "googledrive" => GOOGLEDRIVE_AUTH_ENDPOINT.to_string(),
+        "dropbox" => DROPBOX_AUTH_ENDPOINT.to_string(),
         _ => panic!("unsupported provider \"{provider}\""),
     }
 }
 
 /// Get the OAuth2 token endpoint for the given cloud
-/// provider. Currently supported options are "OneDrive" and
-/// "GoogleDrive".
+/// provider. Currently supported options are "OneDrive",
+/// "GoogleDrive" and "Dropbox".
 ///
 /// # Panics
-/// Panics if `provider` is neither `OneDrive` nor `GoogleDrive`.
+/// Panics if `provider` is not `OneDrive`, `GoogleDrive` or
+/// `Dropbox`.
 pub fn token_endpoint(provider: &str) -> String {
     match provider.to_lowercase().as_str() {
         "onedrive" => ONEDRIVE_TOKEN_ENDPOINT.to_string(),
         "googledrive" => GOOGLEDRIVE_TOKEN_ENDPOINT.to_string(),
+        "dropbox" => DROPBOX_TOKEN_ENDPOINT.to_string(),
         _ => panic!("unsupported provider \"{provider}\""),
     }
 }
 
 /// Get the API OAuth2 scope for the given cloud provider. Currently
-/// supported options are "OneDrive" and "GoogleDrive".
+/// supported options are "OneDrive", "GoogleDrive" and "Dropbox".
 ///
 /// # Panics
-/// Panics if `provider` is neither `OneDrive` nor `GoogleDrive`.
+/// Panics if `provider` is not `OneDrive`, `GoogleDrive` or
+/// `Dropbox`.
 pub fn scope(provider: &str) -> String {
     match provider.to_lowercase().as_str() {
         "onedrive" => ONEDRIVE_SCOPE.to_string(),
         "googledrive" => GOOGLEDRIVE_SCOPE.to_string(),
+        "dropbox" => DROPBOX_SCOPE.to_string(),
         _ => panic!("unsupported provider \"{provider}\""),
     }
 }
@@ -66,3 +72,18 @@ pub const GOOGLEDRIVE_TOKEN_ENDPOINT: &str =
 /// The Google OAuth2 scope required for accessing OneDrive resources.
 pub const GOOGLEDRIVE_SCOPE: &str =
     "https://www.googleapis.com/auth/drive";
+
+/// The Dropbox API authentication endpoint for obtaining
+/// authorization grants. Requests offline access, as Dropbox only
+/// issues refresh tokens to offline apps.
+pub const DROPBOX_AUTH_ENDPOINT: &str =
+    "https://www.dropbox.com/oauth2/authorize?token_access_type=offline";
+
+/// The Dropbox API token endpoint for obtaining and refreshing
+/// access tokens.
+pub const DROPBOX_TOKEN_ENDPOINT: &str =
+    "https://api.dropboxapi.com/oauth2/token";
+
+/// The Dropbox OAuth2 scope required for accessing Dropbox files.
+pub const DROPBOX_SCOPE: &str =
+    "files.metadata.read files.metadata.write files.content.read files.content.write";
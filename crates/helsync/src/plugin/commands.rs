@@ -1,5 +1,6 @@
-use crate::local::{Tag, TagWithFiles, LocalFile};
-use crate::core::Result;
+use crate::local::{Drive, Provider, Tag, TagWithFiles, LocalFile};
+use crate::core::{File, Result};
+use crate::sync::SyncStatus;
 use super::HelsyncExt;
 
 use tauri::{AppHandle, command, Runtime, Emitter};
@@ -169,3 +170,87 @@ pub(crate) async fn remove_tag_bind<R: Runtime>(
     app.emit("helsync-tags-change", "")?;
     app.helsync().remove_tag_bind(file_id, tag_name).await
 }
+
+#[command]
+pub(crate) async fn start_sync<R: Runtime>(
+    app: AppHandle<R>,
+) -> Result<()> {
+    app.helsync().start_sync()
+}
+
+#[command]
+pub(crate) async fn pause_sync<R: Runtime>(
+    app: AppHandle<R>,
+) -> Result<()> {
+    app.helsync().pause_sync()
+}
+
+#[command]
+pub(crate) async fn stop_sync<R: Runtime>(
+    app: AppHandle<R>,
+) -> Result<()> {
+    app.helsync().stop_sync()
+}
+
+#[command]
+pub(crate) async fn sync_status<R: Runtime>(
+    app: AppHandle<R>,
+) -> Result<SyncStatus> {
+    app.helsync().sync_status()
+}
+
+#[command]
+pub(crate) async fn get_drive<R: Runtime>(
+    app: AppHandle<R>,
+) -> Result<Option<Drive>> {
+    app.helsync().get_drive().await
+}
+
+#[command]
+pub(crate) async fn register_drive<R: Runtime>(
+    app: AppHandle<R>,
+    provider: Provider,
+    client_id: &str,
+    client_secret: Option<&str>,
+    port: u16,
+) -> Result<Drive> {
+    let drive = app.helsync().register_drive(provider, client_id, client_secret, port).await?;
+    app.emit("helsync-drive-change", "")?;
+    Ok(drive)
+}
+
+#[command]
+pub(crate) async fn connect_drive<R: Runtime>(
+    app: AppHandle<R>,
+) -> Result<Drive> {
+    let drive = app.helsync().connect_drive().await?;
+    app.emit("helsync-drive-change", "")?;
+    Ok(drive)
+}
+
+#[command]
+pub(crate) async fn list_remote_folders<R: Runtime>(
+    app: AppHandle<R>,
+    parent_id: Option<&str>,
+) -> Result<Vec<File>> {
+    app.helsync().list_remote_folders(parent_id).await
+}
+
+#[command]
+pub(crate) async fn set_remote_root<R: Runtime>(
+    app: AppHandle<R>,
+    remote_root: Option<&str>,
+) -> Result<()> {
+    app.helsync().set_remote_root(remote_root).await?;
+    app.emit("helsync-drive-change", "")?;
+    Ok(())
+}
+
+#[command]
+pub(crate) async fn disconnect_drive<R: Runtime>(
+    app: AppHandle<R>,
+) -> Result<()> {
+    app.helsync().disconnect_drive().await?;
+    app.emit("helsync-drive-change", "")?;
+    Ok(())
+}
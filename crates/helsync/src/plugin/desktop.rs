@@ -1,7 +1,11 @@
-use crate::local::{Client, LocalFile, Tag, TagWithFiles};
-use crate::core::{FileSystem, Result};
+use crate::local::{Client, Drive, LocalFile, Provider, Tag, TagWithFiles};
+use crate::core::{Error, File, FileSystem, Result};
+use crate::cloud::{googledrive, onedrive};
+use crate::oauth2::Config;
+use crate::sync::{Scheduler, SyncStatus};
+use super::scheduler::{RemoteSync, FS_CHANGE_EVENT};
 
-use tauri::{plugin::PluginApi, AppHandle, Runtime};
+use tauri::{plugin::PluginApi, async_runtime, AppHandle, Listener, Runtime};
 use serde::de::DeserializeOwned;
 use markdown::mdast::Node;
 use database::Database;
@@ -12,9 +16,25 @@ pub fn init<R: Runtime, C: DeserializeOwned>(
     _api: PluginApi<R, C>,
     db: Arc<Database>,
 ) -> super::Result<Helsync<R>> {
+    let scheduler = Arc::new(Scheduler::new(async_runtime::handle().inner().clone(), app.clone()));
+    let listener = scheduler.clone();
+    app.listen(FS_CHANGE_EVENT, move |_| listener.notify_change());
+
+    // Restore the connection to a previously connected drive.
+    let local = Arc::new(Client::new(db));
+    let (restore_local, restore_scheduler) = (local.clone(), scheduler.clone());
+    async_runtime::spawn(async move {
+        if let Ok(Some(drive)) = restore_local.get_drive().await {
+            if let Ok(sync) = RemoteSync::load(restore_local, &drive).await {
+                restore_scheduler.set_sync(Some(sync)).await;
+            }
+        }
+    });
+
     Ok(Helsync {
         _app: app.clone(),
-        local: Arc::new(Client::new(db)),
+        local,
+        scheduler,
     })
 }
 
@@ -22,6 +42,7 @@ pub fn init<R: Runtime, C: DeserializeOwned>(
 pub struct Helsync<R: Runtime> {
     _app: AppHandle<R>,
     local: Arc<Client>,
+    scheduler: Arc<Scheduler<RemoteSync, AppHandle<R>>>,
 }
 
 impl<R: Runtime> Helsync<R> {
@@ -167,4 +188,126 @@ impl<R: Runtime> Helsync<R> {
     ) -> Result<()> {
         Ok(self.local.remove_tag_bind(file_id, tag_name).await?)
     }
+
+    /// Sets the [RemoteSync] run by the background sync scheduler.
+    pub async fn set_sync(&self, sync: Option<RemoteSync>) {
+        self.scheduler.set_sync(sync).await
+    }
+
+    /// Start (or resume) background synchronization.
+    pub fn start_sync(&self) -> Result<()> {
+        self.scheduler.start();
+        Ok(())
+    }
+
+    /// Pause background synchronization.
+    pub fn pause_sync(&self) -> Result<()> {
+        self.scheduler.pause();
+        Ok(())
+    }
+
+    /// Stop background synchronization.
+    pub fn stop_sync(&self) -> Result<()> {
+        self.scheduler.stop();
+        Ok(())
+    }
+
+    /// Retrieve the status of background synchronization.
+    pub fn sync_status(&self) -> Result<SyncStatus> {
+        Ok(self.scheduler.status())
+    }
+
+    /// Retrieve the registered cloud drive, if any.
+    pub async fn get_drive(&self) -> Result<Option<Drive>> {
+        self.local.get_drive().await
+    }
+
+    /// Register a cloud drive's OAuth2 app. The drive must then be
+    /// authorized using [connect_drive](Self::connect_drive).
+    ///
+    /// Replaces (and disconnects) any previously registered drive.
+    pub async fn register_drive(
+        &self,
+        provider: Provider,
+        client_id: &str,
+        client_secret: Option<&str>,
+        port: u16,
+    ) -> Result<Drive> {
+        if provider == Provider::GoogleDrive && client_secret.is_none() {
+            let msg = "Google Drive apps require a client secret";
+            return Err(Error::OAuth2(msg.to_string()));
+        }
+
+        self.scheduler.set_sync(None).await;
+        self.local.remove_drive().await?;
+        self.local.set_drive(provider, client_id, client_secret, port).await
+    }
+
+    /// Authorize the registered drive by running the OAuth2 PKCE
+    /// flow in the user's browser agent.
+    pub async fn connect_drive(&self) -> Result<Drive> {
+        let drive = self.registered_drive().await?;
+        let token = Config::from(&drive)
+            .from_grant_server(drive.port).await?
+            .to_token().await?;
+
+        self.local.set_drive_token(&token).await?;
+        let drive = self.registered_drive().await?;
+        let sync = RemoteSync::load(self.local.clone(), &drive).await?;
+        self.scheduler.set_sync(Some(sync)).await;
+        Ok(drive)
+    }
+
+    /// List the folders under a parent in the connected drive.
+    ///
+    /// If `parent_id` is `None`, the drive's root folders are listed.
+    pub async fn list_remote_folders(
+        &self,
+        parent_id: Option<&str>
+    ) -> Result<Vec<File>> {
+        let drive = self.registered_drive().await?;
+        let token = drive.token.as_ref()
+            .ok_or(Error::OAuth2("drive is not connected".to_string()))?;
+
+        let config = Config::from(&drive);
+        let files: Vec<File> = match drive.provider {
+            Provider::OneDrive => onedrive::Client::new(token, &config)
+                .with_token_store(self.local.clone())
+                .list_files(parent_id).await?
+                .into_iter().map(|file| file.into()).collect(),
+            Provider::GoogleDrive => googledrive::Client::new(token, &config)
+                .with_token_store(self.local.clone())
+                .list_files(parent_id).await?
+                .into_iter().map(|file| file.into()).collect(),
+        };
+
+        Ok(files.into_iter()
+            .filter(|file| file.is_folder && !file.is_deleted)
+            .collect())
+    }
+
+    /// Sets the remote folder that mirrors the local root directory.
+    /// If `None`, the drive's root folder is used.
+    ///
+    /// Changing the remote root forgets all synchronization state;
+    /// the next sync matches local and remote files from scratch.
+    pub async fn set_remote_root(&self, remote_root: Option<&str>) -> Result<()> {
+        let drive = self.registered_drive().await?;
+        self.scheduler.set_sync(None).await;
+        self.local.reset_sync(remote_root).await?;
+        let sync = RemoteSync::load(self.local.clone(), &drive).await?;
+        self.scheduler.set_sync(Some(sync)).await;
+        Ok(())
+    }
+
+    /// Disconnect and forget the registered drive.
+    pub async fn disconnect_drive(&self) -> Result<()> {
+        self.scheduler.set_sync(None).await;
+        self.local.remove_drive().await
+    }
+
+    async fn registered_drive(&self) -> Result<Drive> {
+        self.local.get_drive().await?
+            .ok_or(Error::OAuth2("no drive has been registered".to_string()))
+    }
 }
@@ -13,6 +13,13 @@ use std::sync::Arc;
 
 mod commands;
 
+#[cfg(desktop)]
+mod scheduler;
+#[cfg(desktop)]
+pub use scheduler::RemoteSync;
+#[cfg(desktop)]
+pub use crate::sync::{SchedulerState, SyncStatus};
+
 #[cfg(desktop)]
 mod desktop;
 #[cfg(desktop)]
@@ -57,6 +64,16 @@ pub fn init<R: Runtime>(db: Arc<Database>) -> TauriPlugin<R> {
             commands::change_tag_color,
             commands::create_tag_bind,
             commands::remove_tag_bind,
+            commands::start_sync,
+            commands::pause_sync,
+            commands::stop_sync,
+            commands::sync_status,
+            commands::get_drive,
+            commands::register_drive,
+            commands::connect_drive,
+            commands::list_remote_folders,
+            commands::set_remote_root,
+            commands::disconnect_drive,
         ])
         .setup(|app, api| {
             #[cfg(mobile)]
@@ -0,0 +1,61 @@
+use crate::cloud::{googledrive, onedrive};
+use crate::local::{Client, Drive, Provider};
+use crate::sync::{Reconciled, StatusEmitter, Sync, Syncer, SyncStatus};
+use crate::core::{Error, Result};
+use crate::oauth2::Config;
+
+use tauri::{AppHandle, Emitter, Runtime};
+use std::sync::Arc;
+
+/// Event emitted whenever the scheduler's [SyncStatus] changes.
+pub const SYNC_STATUS_EVENT: &str = "helsync-sync-status";
+
+/// Event emitted by commands that change the local filesystem.
+pub const FS_CHANGE_EVENT: &str = "helsync-fs-change";
+
+/// A [Sync] between the local filesystem and a supported remote.
+pub enum RemoteSync {
+    OneDrive(Sync<onedrive::Client>),
+    GoogleDrive(Sync<googledrive::Client>),
+}
+
+impl RemoteSync {
+
+    /// Restores the [Sync] between the local filesystem and a
+    /// connected [Drive].
+    pub async fn load(local: Arc<Client>, drive: &Drive) -> Result<Self> {
+        let token = drive.token.as_ref()
+            .ok_or(Error::OAuth2("drive is not connected".to_string()))?;
+
+        let config = Config::from(drive);
+        Ok(match drive.provider {
+            Provider::OneDrive => {
+                let remote = onedrive::Client::new(token, &config)
+                    .with_token_store(local.clone())
+                    .with_upload_store(local.clone());
+                Self::OneDrive(Sync::load(local, Arc::new(remote)).await?)
+            },
+            Provider::GoogleDrive => {
+                let remote = googledrive::Client::new(token, &config)
+                    .with_token_store(local.clone())
+                    .with_upload_store(local.clone());
+                Self::GoogleDrive(Sync::load(local, Arc::new(remote)).await?)
+            },
+        })
+    }
+}
+
+impl Syncer for RemoteSync {
+    async fn sync(&mut self) -> Result<Vec<Reconciled>> {
+        match self {
+            Self::OneDrive(sync) => sync.sync().await,
+            Self::GoogleDrive(sync) => sync.sync().await,
+        }
+    }
+}
+
+impl<R: Runtime> StatusEmitter for AppHandle<R> {
+    fn emit(&self, status: &SyncStatus) {
+        let _ = Emitter::emit(self, SYNC_STATUS_EVENT, status);
+    }
+}
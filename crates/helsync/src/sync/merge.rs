@@ -0,0 +1,80 @@
+use diffy::{ConflictStyle, MergeOptions};
+
+/// The result of a three-way merge.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Merge {
+
+    /// The merged document.
+    pub content: String,
+
+    /// Whether the merged document contains conflict markers.
+    pub is_conflicted: bool,
+}
+
+/// Performs a line-based three-way merge of two markdown documents
+/// that were derived from a common `base`.
+///
+/// Changes that do not overlap are combined. Overlapping changes
+/// are wrapped in conflict markers, with `ours` listed first.
+pub fn merge(base: &str, ours: &str, theirs: &str) -> Merge {
+    let merged = MergeOptions::new()
+        .set_conflict_style(ConflictStyle::Merge)
+        .merge(base, ours, theirs);
+
+    match merged {
+        Ok(content) => Merge { content, is_conflicted: false },
+        Err(content) => Merge { content, is_conflicted: true },
+    }
+}
+
+/// Performs a three-way merge of UTF-8 encoded documents.
+///
+/// Returns `None` if any of the documents is not valid UTF-8.
+pub fn merge_bytes(base: &[u8], ours: &[u8], theirs: &[u8]) -> Option<Merge> {
+    Some(merge(
+        std::str::from_utf8(base).ok()?,
+        std::str::from_utf8(ours).ok()?,
+        std::str::from_utf8(theirs).ok()?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    const BASE: &str = "# Groceries\n\n- milk\n- eggs\n\n## Notes\n\nBuy on Friday.\n";
+
+    #[test]
+    fn test_merge_disjoint() {
+        let ours = "# Groceries\n\n- milk\n- eggs\n- bread\n\n## Notes\n\nBuy on Friday.\n";
+        let theirs = "# Groceries\n\n- milk\n- eggs\n\n## Notes\n\nBuy on Saturday.\n";
+        let merged = merge(BASE, ours, theirs);
+        assert!(!merged.is_conflicted);
+        assert_eq!(merged.content, "# Groceries\n\n- milk\n- eggs\n- bread\n\n## Notes\n\nBuy on Saturday.\n");
+    }
+
+    #[test]
+    fn test_merge_identical() {
+        let ours = "# Groceries\n\n- milk\n\n## Notes\n\nBuy on Friday.\n";
+        let merged = merge(BASE, ours, ours);
+        assert!(!merged.is_conflicted);
+        assert_eq!(merged.content, ours);
+    }
+
+    #[test]
+    fn test_merge_overlapping() {
+        let ours = "# Groceries\n\n- milk\n- eggs\n\n## Notes\n\nBuy on Friday morning.\n";
+        let theirs = "# Groceries\n\n- milk\n- eggs\n\n## Notes\n\nBuy on Friday evening.\n";
+        let merged = merge(BASE, ours, theirs);
+        assert!(merged.is_conflicted);
+        assert!(merged.content.starts_with("# Groceries\n\n- milk\n- eggs\n\n## Notes\n\n<<<<<<< "));
+        assert!(merged.content.contains("Buy on Friday morning.\n=======\nBuy on Friday evening.\n>>>>>>> "));
+    }
+
+    #[test]
+    fn test_merge_bytes() {
+        assert!(merge_bytes(b"a\n", b"b\n", &[0xff, 0xfe]).is_none());
+        assert_eq!(merge_bytes(b"a\n", b"b\n", b"a\n").unwrap().content, "b\n");
+    }
+}
@@ -1,2 +1,13 @@
 mod sync;
 pub use sync::*;
+
+mod rules;
+pub use rules::{Matcher, Rule, RuleKind, SyncRules};
+
+mod plan;
+pub use plan::{plan, ActionKind, SyncAction};
+
+pub mod merge;
+
+mod scheduler;
+pub use scheduler::{Scheduler, SchedulerState, StatusEmitter, Syncer, SyncStatus, SYNC_DEBOUNCE, SYNC_INTERVAL};
@@ -0,0 +1,462 @@
+use crate::core::File;
+use crate::local::LocalFile;
+use super::Unreconciled;
+
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+
+/// The kind of a [SyncAction].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ActionKind {
+
+    /// Create a remote copy of a new local file, or upload a local
+    /// file again after its remote copy was deleted.
+    CreateRemote,
+
+    /// Create a local copy of a new remote file, or restore a local
+    /// file after a remote edit.
+    CreateLocal,
+
+    /// Apply a local change (content, name or parent) to the remote
+    /// copy of a file.
+    Upload,
+
+    /// Apply a remote change (content, name or parent) to the local
+    /// copy of a file.
+    Download,
+
+    /// Move or rename the local copy of a remote file whose content
+    /// hasn't changed.
+    Move,
+
+    /// Delete the remote copy of a file that was deleted locally.
+    DeleteRemote,
+
+    /// Delete the local copy of a file that was deleted remotely.
+    DeleteLocal,
+
+    /// Reconcile concurrent local and remote changes to a file.
+    Conflict,
+}
+
+/// An operation planned by [plan].
+///
+/// `local` and `remote` are the versions of the file that changed
+/// on either side. Remote changes that were made by the last sync
+/// are left out.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncAction {
+
+    /// ID of the change, as in [Unreconciled::id].
+    pub id: String,
+
+    pub kind: ActionKind,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub local: Option<LocalFile>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote: Option<File>,
+}
+
+impl SyncAction {
+
+    /// Name of the file that the action applies to.
+    pub fn name(&self) -> &str {
+        match (&self.local, &self.remote) {
+            (Some(local_file), _) => &local_file.name,
+            (None, Some(remote_file)) => &remote_file.name,
+            (None, None) => "",
+        }
+    }
+}
+
+/// Plans how to reconcile a set of changes, without accessing either
+/// file system.
+///
+/// Repeated changes to the same file are coalesced into a single
+/// action, using the latest local and remote versions of the file.
+/// Actions are ordered such that parents are handled before their
+/// children. Changes that need no action (e.g. remote changes that
+/// were made by the last sync) are left out.
+///
+/// # Examples
+/// ```
+/// use helsync::core::File;
+/// use helsync::sync::{plan, ActionKind, Unreconciled};
+///
+/// let remote = File {
+///     id: "remote-id".to_string(),
+///     name: "todo.md".to_string(),
+///     modified_at: 0,
+///     created_at: 0,
+///     parent_id: None,
+///     is_folder: false,
+///     is_deleted: false,
+///     sha1: None,
+///     sha256: None,
+///     size: None,
+/// };
+///
+/// let actions = plan(vec![Unreconciled {
+///     id: remote.id.clone(),
+///     local: None,
+///     remote: Some(remote),
+///     counterpart: None,
+/// }]);
+///
+/// assert_eq!(actions[0].kind, ActionKind::CreateLocal);
+/// ```
+pub fn plan(deltas: Vec<Unreconciled>) -> Vec<SyncAction> {
+    sort_deltas(coalesce(deltas)).into_iter()
+        .filter_map(plan_one)
+        .collect()
+}
+
+/// Lists, for each action, the indices of the actions on its parents,
+/// which must complete before the action is applied.
+///
+/// Expects actions to be ordered as by [plan], so an action only
+/// depends on earlier actions.
+pub(crate) fn dependencies(actions: &[SyncAction]) -> Vec<Vec<usize>> {
+    let local_index: HashMap<i64, usize> = actions.iter().enumerate()
+        .filter_map(|(i, action)| action.local.as_ref().map(|file| (file.id, i)))
+        .collect();
+
+    let remote_index: HashMap<&str, usize> = actions.iter().enumerate()
+        .map(|(i, action)| (action.id.as_str(), i))
+        .collect();
+
+    actions.iter().enumerate().map(|(i, action)| {
+        let local_parent = action.local.as_ref()
+            .and_then(|file| file.parent)
+            .and_then(|id| local_index.get(&id));
+
+        let remote_parent = action.remote.as_ref()
+            .and_then(|file| file.parent_id.as_deref())
+            .and_then(|id| remote_index.get(id));
+
+        let mut parents: Vec<usize> = local_parent.into_iter()
+            .chain(remote_parent)
+            .copied()
+            .filter(|&parent| parent < i)
+            .collect();
+
+        parents.dedup();
+        parents
+    }).collect()
+}
+
+/// Merges deltas with the same ID, keeping the most recently
+/// modified local and remote versions of each file.
+fn coalesce(deltas: Vec<Unreconciled>) -> Vec<Unreconciled> {
+    let mut index: HashMap<String, usize> = HashMap::new();
+    let mut coalesced: Vec<Unreconciled> = Vec::with_capacity(deltas.len());
+    for delta in deltas {
+        let Some(&i) = index.get(&delta.id) else {
+            index.insert(delta.id.clone(), coalesced.len());
+            coalesced.push(delta);
+            continue;
+        };
+
+        let existing = &mut coalesced[i];
+        if let Some(local_file) = delta.local {
+            if existing.local.as_ref().map_or(true, |file| file.modified_at <= local_file.modified_at) {
+                existing.local = Some(local_file);
+            }
+        }
+
+        if let Some(remote_file) = delta.remote {
+            if existing.remote.as_ref().map_or(true, |file| file.modified_at <= remote_file.modified_at) {
+                existing.remote = Some(remote_file);
+            }
+        }
+
+        if delta.counterpart.is_some() {
+            existing.counterpart = delta.counterpart;
+        }
+    }
+
+    coalesced
+}
+
+/// Plans how to reconcile a single change.
+fn plan_one(delta: Unreconciled) -> Option<SyncAction> {
+    let Unreconciled { id, local, mut remote, counterpart } = delta;
+
+    // Remote changes made before the file was last synced have
+    // already been reconciled (e.g. they were made by us).
+    if let (Some(local_file), Some(remote_file)) = (&local, &remote) {
+        let is_echo = remote_file.is_deleted == local_file.is_deleted &&
+            local_file.synced_at.is_some_and(|ts| remote_file.modified_at <= ts);
+
+        if is_echo {
+            remote = None;
+        }
+    }
+
+    let kind = match (&local, &remote) {
+        (Some(local_file), Some(remote_file)) => match (local_file.is_deleted, remote_file.is_deleted) {
+            (true, true) => return None,
+
+            // Edits to files take precedence over deletions, while
+            // folder deletions are kept.
+            (true, false) if remote_file.is_folder => ActionKind::DeleteRemote,
+            (true, false) => ActionKind::CreateLocal,
+            (false, true) if local_file.is_folder => ActionKind::DeleteLocal,
+            (false, true) => ActionKind::CreateRemote,
+            (false, false) => ActionKind::Conflict,
+        },
+        (Some(local_file), None) => match (local_file.is_deleted, &local_file.remote_id) {
+            (true, _) => ActionKind::DeleteRemote,
+            (false, Some(_)) => ActionKind::Upload,
+            (false, None) => ActionKind::CreateRemote,
+        },
+        (None, Some(remote_file)) => match &counterpart {
+
+            // The file has been deleted locally. The deletion is
+            // propagated by its local change.
+            Some(local_file) if local_file.is_deleted => return None,
+            Some(_) if remote_file.is_deleted => ActionKind::DeleteLocal,
+            None if remote_file.is_deleted => return None,
+            None => ActionKind::CreateLocal,
+            Some(local_file) if remote_file.is_folder || is_same_hash(local_file, remote_file) => ActionKind::Move,
+            Some(_) => ActionKind::Download,
+        },
+        (None, None) => return None,
+    };
+
+    Some(SyncAction { id, kind, local, remote })
+}
+
+/// Whether the content of a local file is known to match the content
+/// of a remote file, i.e. whether both have the same SHA256 hash.
+fn is_same_hash(local_file: &LocalFile, remote_file: &File) -> bool {
+    match (&local_file.hash, &remote_file.sha256) {
+        (Some(ours), Some(theirs)) => ours.eq_ignore_ascii_case(theirs),
+        _ => false,
+    }
+}
+
+/// Orders deltas such that parents are reconciled before their
+/// children.
+fn sort_deltas(deltas: Vec<Unreconciled>) -> Vec<Unreconciled> {
+    let local_parents: HashMap<i64, Option<i64>> = deltas.iter()
+        .filter_map(|delta| delta.local.as_ref())
+        .map(|file| (file.id, file.parent))
+        .collect();
+
+    let remote_parents: HashMap<&str, Option<&str>> = deltas.iter()
+        .filter_map(|delta| delta.remote.as_ref())
+        .map(|file| (file.id.as_str(), file.parent_id.as_deref()))
+        .collect();
+
+    // The depth of a delta is its number of ancestors that are also
+    // deltas. Depth is bounded by the number of deltas in case of
+    // cyclic parent references.
+    let depths: Vec<usize> = deltas.iter().map(|delta| {
+        let mut local_depth = 0;
+        let mut parent = delta.local.as_ref().and_then(|file| file.parent);
+        while let Some(Some(next)) = parent.map(|id| local_parents.get(&id)) {
+            if local_depth > deltas.len() { break; }
+            local_depth += 1;
+            parent = *next;
+        }
+
+        let mut remote_depth = 0;
+        let mut parent = delta.remote.as_ref().and_then(|file| file.parent_id.as_deref());
+        while let Some(Some(next)) = parent.map(|id| remote_parents.get(id)) {
+            if remote_depth > deltas.len() { break; }
+            remote_depth += 1;
+            parent = *next;
+        }
+
+        std::cmp::max(local_depth, remote_depth)
+    }).collect();
+
+    let mut sorted: Vec<(usize, Unreconciled)> = depths.into_iter().zip(deltas).collect();
+    sorted.sort_by_key(|(depth, _)| *depth);
+    sorted.into_iter().map(|(_, delta)| delta).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(id: i64, parent: Option<i64>, remote_id: Option<&str>) -> LocalFile {
+        LocalFile {
+            id,
+            name: format!("local-{id}"),
+            parent,
+            remote_id: remote_id.map(str::to_string),
+            is_deleted: false,
+            created_at: 0,
+            modified_at: 10,
+            synced_at: Some(5),
+            is_folder: false,
+            is_bookmarked: false,
+            hash: None,
+        }
+    }
+
+    fn remote(id: &str, parent_id: Option<&str>) -> File {
+        File {
+            id: id.to_string(),
+            name: format!("remote-{id}"),
+            modified_at: 10,
+            created_at: 0,
+            parent_id: parent_id.map(str::to_string),
+            is_folder: false,
+            is_deleted: false,
+            sha1: None,
+            sha256: None,
+            size: None,
+        }
+    }
+
+    fn delta(local: Option<LocalFile>, remote: Option<File>, counterpart: Option<LocalFile>) -> Unreconciled {
+        let id = match (&local, &remote) {
+            (Some(file), _) => file.id.to_string(),
+            (None, Some(file)) => file.id.clone(),
+            (None, None) => String::new(),
+        };
+        Unreconciled { id, local, remote, counterpart }
+    }
+
+    fn with<T>(mut file: T, edit: impl FnOnce(&mut T)) -> T {
+        edit(&mut file);
+        file
+    }
+
+    #[test]
+    fn test_plan_kinds() {
+        let deleted_local = |file: LocalFile| with(file, |f| f.is_deleted = true);
+        let deleted_remote = |file: File| with(file, |f| f.is_deleted = true);
+        let folder = |file: File| with(file, |f| f.is_folder = true);
+        let hashed_local = |file: LocalFile| with(file, |f| f.hash = Some("ABC".to_string()));
+        let hashed_remote = |file: File| with(file, |f| f.sha256 = Some("abc".to_string()));
+
+        let cases: Vec<(&str, Unreconciled, Option<ActionKind>)> = vec![
+            ("new local file",
+             delta(Some(local(1, None, None)), None, None),
+             Some(ActionKind::CreateRemote)),
+            ("edited local file",
+             delta(Some(local(1, None, Some("a"))), None, None),
+             Some(ActionKind::Upload)),
+            ("deleted local file",
+             delta(Some(deleted_local(local(1, None, Some("a")))), None, None),
+             Some(ActionKind::DeleteRemote)),
+            ("new remote file",
+             delta(None, Some(remote("a", None)), None),
+             Some(ActionKind::CreateLocal)),
+            ("edited remote file",
+             delta(None, Some(remote("a", None)), Some(local(1, None, Some("a")))),
+             Some(ActionKind::Download)),
+            ("moved remote file",
+             delta(None, Some(hashed_remote(remote("a", None))), Some(hashed_local(local(1, None, Some("a"))))),
+             Some(ActionKind::Move)),
+            ("moved remote folder",
+             delta(None, Some(folder(remote("a", None))), Some(local(1, None, Some("a")))),
+             Some(ActionKind::Move)),
+            ("deleted remote file",
+             delta(None, Some(deleted_remote(remote("a", None))), Some(local(1, None, Some("a")))),
+             Some(ActionKind::DeleteLocal)),
+            ("deleted unknown remote file",
+             delta(None, Some(deleted_remote(remote("a", None))), None),
+             None),
+            ("remote edit of locally deleted file",
+             delta(None, Some(remote("a", None)), Some(deleted_local(local(1, None, Some("a"))))),
+             None),
+            ("concurrent edits",
+             delta(Some(local(1, None, Some("a"))), Some(remote("a", None)), None),
+             Some(ActionKind::Conflict)),
+            ("local deletion, remote edit",
+             delta(Some(deleted_local(local(1, None, Some("a")))), Some(remote("a", None)), None),
+             Some(ActionKind::CreateLocal)),
+            ("local deletion, remote folder edit",
+             delta(Some(deleted_local(local(1, None, Some("a")))), Some(folder(remote("a", None))), None),
+             Some(ActionKind::DeleteRemote)),
+            ("local edit, remote deletion",
+             delta(Some(local(1, None, Some("a"))), Some(deleted_remote(remote("a", None))), None),
+             Some(ActionKind::CreateRemote)),
+            ("concurrent deletions",
+             delta(Some(deleted_local(local(1, None, Some("a")))), Some(deleted_remote(remote("a", None))), None),
+             None),
+            ("echo of the last sync",
+             delta(Some(local(1, None, Some("a"))), Some(with(remote("a", None), |f| f.modified_at = 5)), None),
+             Some(ActionKind::Upload)),
+        ];
+
+        for (name, delta, expected) in cases {
+            let kind = plan(vec![delta]).pop().map(|action| action.kind);
+            assert_eq!(kind, expected, "{name}");
+        }
+    }
+
+    #[test]
+    fn test_plan_order() {
+        // Children are listed before their parents.
+        let deltas = vec![
+            delta(Some(local(3, Some(2), None)), None, None),
+            delta(None, Some(remote("c", Some("b"))), None),
+            delta(Some(local(2, Some(1), None)), None, None),
+            delta(None, Some(remote("b", Some("a"))), None),
+            delta(Some(local(1, None, None)), None, None),
+            delta(None, Some(remote("a", None)), None),
+        ];
+
+        let ids: Vec<String> = plan(deltas).into_iter()
+            .map(|action| action.id)
+            .collect();
+
+        let position = |id: &str| ids.iter().position(|other| other == id).unwrap();
+        assert!(position("1") < position("2"));
+        assert!(position("2") < position("3"));
+        assert!(position("a") < position("b"));
+        assert!(position("b") < position("c"));
+    }
+
+    #[test]
+    fn test_plan_coalesce() {
+        let older = with(local(1, None, Some("a")), |f| f.modified_at = 10);
+        let newer = with(local(1, None, Some("a")), |f| {
+            f.modified_at = 20;
+            f.name = "renamed".to_string();
+        });
+
+        let deltas = vec![
+            delta(Some(newer), None, None),
+            delta(None, Some(remote("b", None)), None),
+            delta(Some(older), None, None),
+        ];
+
+        let actions = plan(deltas);
+        assert_eq!(actions.len(), 2);
+
+        let action = actions.iter().find(|action| action.id == "1").unwrap();
+        assert_eq!(action.kind, ActionKind::Upload);
+        assert_eq!(action.name(), "renamed");
+    }
+
+    #[test]
+    fn test_dependencies() {
+        let folder = with(remote("a", None), |f| f.is_folder = true);
+        let actions = plan(vec![
+            delta(Some(local(2, Some(1), None)), None, None),
+            delta(None, Some(remote("b", Some("a"))), None),
+            delta(Some(local(1, None, None)), None, None),
+            delta(None, Some(folder), None),
+            delta(Some(local(3, None, None)), None, None),
+        ]);
+
+        let position = |id: &str| actions.iter().position(|action| action.id == id).unwrap();
+        let dependencies = dependencies(&actions);
+        assert_eq!(dependencies[position("2")], vec![position("1")]);
+        assert_eq!(dependencies[position("b")], vec![position("a")]);
+        assert!(dependencies[position("1")].is_empty());
+        assert!(dependencies[position("a")].is_empty());
+        assert!(dependencies[position("3")].is_empty());
+    }
+}
@@ -0,0 +1,330 @@
+use serde::{Serialize, Deserialize};
+use std::collections::HashSet;
+
+/// Whether a [Rule] includes or excludes the files it matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RuleKind {
+    Include,
+    Exclude,
+}
+
+/// The files matched by a [Rule].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type", content = "value")]
+pub enum Matcher {
+
+    /// The remote folder with the given ID, along with its
+    /// descendants.
+    Folder(String),
+
+    /// Files whose path relative to the remote root (e.g.
+    /// "journal/today.md") or one of whose ancestors' paths matches
+    /// a glob. `*` matches any characters within a path segment,
+    /// `?` matches a single character, and `**` matches any number
+    /// of segments.
+    Path(String),
+
+    /// Files (but not folders) larger than the given number of
+    /// bytes.
+    LargerThan(u64),
+}
+
+/// A selective sync rule.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Rule {
+    pub kind: RuleKind,
+    pub matcher: Matcher,
+}
+
+impl Rule {
+
+    /// Converts the rule to the `(kind, matcher, value)` strings it
+    /// is stored as, e.g. `("exclude", "largerThan", "1024")`.
+    pub fn to_columns(&self) -> (&'static str, &'static str, String) {
+        let kind = match self.kind {
+            RuleKind::Include => "include",
+            RuleKind::Exclude => "exclude",
+        };
+
+        match &self.matcher {
+            Matcher::Folder(id) => (kind, "folder", id.clone()),
+            Matcher::Path(glob) => (kind, "path", glob.clone()),
+            Matcher::LargerThan(size) => (kind, "largerThan", size.to_string()),
+        }
+    }
+
+    /// Parses a rule stored by [Rule::to_columns], returning `None`
+    /// if the columns are invalid.
+    pub fn from_columns(kind: &str, matcher: &str, value: &str) -> Option<Self> {
+        let kind = match kind {
+            "include" => RuleKind::Include,
+            "exclude" => RuleKind::Exclude,
+            _ => return None,
+        };
+
+        let matcher = match matcher {
+            "folder" => Matcher::Folder(value.to_string()),
+            "path" => Matcher::Path(value.to_string()),
+            "largerThan" => Matcher::LargerThan(value.parse().ok()?),
+            _ => return None,
+        };
+
+        Some(Self { kind, matcher })
+    }
+}
+
+/// Include and exclude rules that select which files are synced.
+///
+/// A file is synced unless an exclude rule matches it. If there are
+/// include rules, then a file must also be matched by one of them,
+/// or be a folder that leads to an included file (so that included
+/// subtrees can be reached).
+///
+/// Files that stop being synced (e.g. because they were moved into
+/// an excluded folder) are left as they are on both sides.
+///
+/// # Examples
+/// ```
+/// use helsync::sync::{Matcher, SyncRules};
+///
+/// // Sync only the journal, without attachments over 10MB.
+/// let rules = SyncRules::default()
+///     .include(Matcher::Path("journal".to_string()))
+///     .exclude(Matcher::LargerThan(10 * 1024 * 1024));
+///
+/// assert_eq!(rules.rules().len(), 2);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncRules(Vec<Rule>);
+
+impl SyncRules {
+
+    /// Adds a rule that includes the files matched by `matcher`.
+    pub fn include(mut self, matcher: Matcher) -> Self {
+        self.0.push(Rule { kind: RuleKind::Include, matcher });
+        self
+    }
+
+    /// Adds a rule that excludes the files matched by `matcher`.
+    pub fn exclude(mut self, matcher: Matcher) -> Self {
+        self.0.push(Rule { kind: RuleKind::Exclude, matcher });
+        self
+    }
+
+    /// The rules, in the order in which they were added.
+    pub fn rules(&self) -> &[Rule] {
+        &self.0
+    }
+
+    /// Whether there are no rules, i.e. whether every file is synced.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Whether any rule matches files by size.
+    pub(crate) fn has_size_rules(&self) -> bool {
+        self.0.iter().any(|rule| matches!(rule.matcher, Matcher::LargerThan(_)))
+    }
+
+    /// IDs of the folders included by folder rules.
+    pub(crate) fn included_folders(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().filter_map(|rule| match (&rule.kind, &rule.matcher) {
+            (RuleKind::Include, Matcher::Folder(id)) => Some(id.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Whether `file` is synced. `ancestors` are the remote IDs of
+    /// the ancestors of the folders included by folder rules.
+    pub(crate) fn is_included(&self, file: &Candidate, ancestors: &HashSet<String>) -> bool {
+        let is_match = |kind: RuleKind| self.0.iter()
+            .filter(|rule| rule.kind == kind)
+            .any(|rule| file.matches(&rule.matcher));
+
+        if is_match(RuleKind::Exclude) {
+            return false;
+        }
+
+        let mut includes = self.0.iter()
+            .filter(|rule| rule.kind == RuleKind::Include)
+            .peekable();
+
+        if includes.peek().is_none() || is_match(RuleKind::Include) {
+            return true;
+        }
+
+        // Folders that lead to included files.
+        file.is_folder && includes.any(|rule| match &rule.matcher {
+            Matcher::Folder(_) => file.remote_ids.last().is_some_and(|id| ancestors.contains(id)),
+            Matcher::Path(glob) => glob_prefix_match(glob, file.path),
+            Matcher::LargerThan(_) => false,
+        })
+    }
+}
+
+impl From<Vec<Rule>> for SyncRules {
+    fn from(rules: Vec<Rule>) -> Self {
+        Self(rules)
+    }
+}
+
+/// A file that is matched against [SyncRules].
+pub(crate) struct Candidate<'a> {
+
+    /// Path relative to the remote root, without a leading slash.
+    pub path: &'a str,
+
+    /// Remote IDs of the file's ancestors, from the root down, and
+    /// of the file itself (if it has one).
+    pub remote_ids: &'a [String],
+
+    pub is_folder: bool,
+
+    /// Size of the file's content, if known.
+    pub size: Option<u64>,
+}
+
+impl Candidate<'_> {
+    fn matches(&self, matcher: &Matcher) -> bool {
+        match matcher {
+            Matcher::Folder(id) => self.remote_ids.contains(id),
+            Matcher::Path(glob) => {
+                let mut path = self.path;
+                loop {
+                    if glob_match(glob, path) {
+                        return true;
+                    }
+                    match path.rsplit_once('/') {
+                        Some((parent, _)) => path = parent,
+                        None => return false,
+                    }
+                }
+            },
+            Matcher::LargerThan(limit) => !self.is_folder && self.size.is_some_and(|size| size > *limit),
+        }
+    }
+}
+
+/// Whether `path` matches the glob `pattern`.
+fn glob_match(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<&str> = segments(pattern).collect();
+    let path: Vec<&str> = segments(path).collect();
+    match_segments(&pattern, &path)
+}
+
+/// Whether a descendant of the folder at `path` could match the glob
+/// `pattern`.
+fn glob_prefix_match(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<&str> = segments(pattern).collect();
+    let mut pattern = pattern.as_slice();
+    for segment in segments(path) {
+        match pattern.split_first() {
+            Some((&"**", _)) => return true,
+            Some((first, rest)) if match_segment(first.as_bytes(), segment.as_bytes()) => pattern = rest,
+            _ => return false,
+        }
+    }
+
+    !pattern.is_empty()
+}
+
+fn segments(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|segment| !segment.is_empty())
+}
+
+fn match_segments(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|i| match_segments(rest, &path[i..])),
+        Some((first, rest)) => path.split_first().is_some_and(|(segment, path)| {
+            match_segment(first.as_bytes(), segment.as_bytes()) && match_segments(rest, path)
+        }),
+    }
+}
+
+fn match_segment(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((b'*', rest)) => (0..=name.len()).any(|i| match_segment(rest, &name[i..])),
+        Some((b'?', rest)) => !name.is_empty() && match_segment(rest, &name[1..]),
+        Some((c, rest)) => name.first() == Some(c) && match_segment(rest, &name[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        let cases = [
+            ("journal", "journal", true),
+            ("journal", "journal/today.md", false),
+            ("*.md", "today.md", true),
+            ("*.md", "journal/today.md", false),
+            ("**/*.md", "journal/today.md", true),
+            ("**/*.md", "today.md", true),
+            ("journal/**", "journal/2026/today.md", true),
+            ("j?urnal/*", "journal/today.md", true),
+            ("journal/*.txt", "journal/today.md", false),
+        ];
+
+        for (pattern, path, expected) in cases {
+            assert_eq!(glob_match(pattern, path), expected, "{pattern} {path}");
+        }
+
+        assert!(glob_prefix_match("journal/2026/*.md", "journal"));
+        assert!(glob_prefix_match("journal/2026/*.md", "journal/2026"));
+        assert!(!glob_prefix_match("journal/2026/*.md", "journal/2025"));
+        assert!(glob_prefix_match("**/*.md", "anything"));
+        assert!(!glob_prefix_match("journal", "journal"));
+    }
+
+    #[test]
+    fn test_rules() {
+        // Cases of (path, remote IDs, is_folder, size, is_included).
+        type Case<'a> = (&'a str, &'a [&'a str], bool, Option<u64>, bool);
+        let check = |rules: &SyncRules, cases: &[Case]| {
+            let ancestors = HashSet::from(["parent".to_string()]);
+            for (path, remote_ids, is_folder, size, expected) in cases {
+                let remote_ids: Vec<String> = remote_ids.iter().map(|id| id.to_string()).collect();
+                let file = Candidate { path, remote_ids: &remote_ids, is_folder: *is_folder, size: *size };
+                assert_eq!(rules.is_included(&file, &ancestors), *expected, "{path}");
+            }
+        };
+
+        // Everything is synced by default.
+        check(&SyncRules::default(), &[("a.md", &[], false, None, true)]);
+
+        let rules = SyncRules::default()
+            .exclude(Matcher::Path("private".to_string()))
+            .exclude(Matcher::Folder("archive".to_string()))
+            .exclude(Matcher::LargerThan(100));
+
+        check(&rules, &[
+            ("notes/a.md", &[], false, Some(10), true),
+            ("private", &[], true, None, false),
+            ("private/a.md", &[], false, None, false),
+            ("old/a.md", &["archive", "a"], false, None, false),
+            ("big.pdf", &[], false, Some(101), false),
+            ("big", &[], true, Some(101), true),
+        ]);
+
+        let rules = SyncRules::default()
+            .include(Matcher::Path("journal/2026".to_string()))
+            .include(Matcher::Folder("work".to_string()))
+            .exclude(Matcher::Path("**/*.tmp".to_string()));
+
+        check(&rules, &[
+            ("journal/2026/a.md", &[], false, None, true),
+            ("journal/2026/a.tmp", &[], false, None, false),
+            ("journal", &[], true, None, true),
+            ("journal/a.md", &[], false, None, false),
+            ("docs/work/a.md", &["parent", "work"], false, None, true),
+            ("docs", &["parent"], true, None, true),
+            ("other", &["other"], true, None, false),
+        ]);
+    }
+}
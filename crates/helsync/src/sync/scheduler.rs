@@ -0,0 +1,368 @@
+use crate::core::Result;
+use super::Reconciled;
+
+use serde::Serialize;
+use tokio::runtime::Handle;
+use tokio::sync::{mpsc, Mutex, Notify};
+use tokio::task::JoinHandle;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::sync::Arc;
+
+/// Default interval between scheduled syncs.
+pub const SYNC_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Default time without local changes to wait for before syncing
+/// them.
+pub const SYNC_DEBOUNCE: Duration = Duration::from_secs(2);
+
+/// A sync that can be run by a [Scheduler].
+pub trait Syncer: Send + 'static {
+
+    /// Synchronizes the local and remote filesystems, as by
+    /// [Sync::sync](super::Sync::sync).
+    fn sync(&mut self) -> impl Future<Output = Result<Vec<Reconciled>>> + Send;
+}
+
+/// Receives the [SyncStatus] of a [Scheduler] whenever it changes.
+pub trait StatusEmitter: Send + std::marker::Sync + 'static {
+    fn emit(&self, status: &SyncStatus);
+}
+
+impl StatusEmitter for mpsc::UnboundedSender<SyncStatus> {
+    fn emit(&self, status: &SyncStatus) {
+        let _ = self.send(status.clone());
+    }
+}
+
+/// The state of the sync scheduler.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SchedulerState {
+    #[default]
+    Stopped,
+    Paused,
+    Idle,
+    Syncing,
+    Error,
+}
+
+/// Describes the status of the sync scheduler.
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncStatus {
+    pub state: SchedulerState,
+
+    /// The error that caused the last sync to fail.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+
+    /// Unix timestamp of the last successful sync.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_synced_at: Option<i64>,
+
+    /// Changes reconciled by the last successful sync.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub reconciled: Vec<Reconciled>,
+}
+
+/// Runs a [Syncer] in the background, on an interval and after local
+/// changes, and emits its [SyncStatus] through a [StatusEmitter].
+pub struct Scheduler<S: Syncer, E: StatusEmitter> {
+    sync: Arc<Mutex<Option<S>>>,
+    status: Arc<std::sync::Mutex<SyncStatus>>,
+    emitter: Arc<E>,
+    changed: Arc<Notify>,
+    is_paused: Arc<AtomicBool>,
+    interval: Duration,
+    debounce: Duration,
+    runtime: Handle,
+    task: std::sync::Mutex<Option<JoinHandle<()>>>,
+}
+
+impl<S: Syncer, E: StatusEmitter> Scheduler<S, E> {
+
+    /// Create a new, stopped, [Scheduler] that runs its syncs on
+    /// `runtime`.
+    pub fn new(runtime: Handle, emitter: E) -> Self {
+        Self {
+            sync: Arc::new(Mutex::new(None)),
+            status: Arc::new(std::sync::Mutex::new(SyncStatus::default())),
+            emitter: Arc::new(emitter),
+            changed: Arc::new(Notify::new()),
+            is_paused: Arc::new(AtomicBool::new(false)),
+            interval: SYNC_INTERVAL,
+            debounce: SYNC_DEBOUNCE,
+            runtime,
+            task: std::sync::Mutex::new(None),
+        }
+    }
+
+    /// Sets the interval between scheduled syncs. Takes effect the
+    /// next time the scheduler is started.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Sets the time without local changes to wait for before
+    /// syncing them. Takes effect the next time the scheduler is
+    /// started.
+    pub fn with_debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    /// Notify the scheduler of a local change. Bursts of changes
+    /// trigger a single sync.
+    pub fn notify_change(&self) {
+        self.changed.notify_one();
+    }
+
+    /// Sets the [Syncer] run by the scheduler. If `None`, then
+    /// scheduled syncs are skipped.
+    ///
+    /// Waits for any ongoing sync to complete.
+    pub async fn set_sync(&self, sync: Option<S>) {
+        *self.sync.lock().await = sync;
+    }
+
+    /// Returns the scheduler's current status.
+    pub fn status(&self) -> SyncStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    /// Starts (or resumes) the scheduler. A sync is performed
+    /// immediately.
+    pub fn start(&self) {
+        let was_paused = self.is_paused.swap(false, Ordering::SeqCst);
+        let mut task = self.task.lock().unwrap();
+        if task.is_some() {
+            if was_paused {
+                set_status(self.emitter.as_ref(), &self.status, |status| status.state = SchedulerState::Idle);
+                self.notify_change();
+            }
+            return;
+        }
+
+        set_status(self.emitter.as_ref(), &self.status, |status| status.state = SchedulerState::Idle);
+        *task = Some(self.runtime.spawn(run(
+            self.sync.clone(),
+            self.status.clone(),
+            self.emitter.clone(),
+            self.changed.clone(),
+            self.is_paused.clone(),
+            (self.interval, self.debounce),
+        )));
+    }
+
+    /// Pauses the scheduler. Scheduled syncs are skipped until the
+    /// scheduler is started again.
+    pub fn pause(&self) {
+        if self.task.lock().unwrap().is_none() {
+            return;
+        }
+
+        self.is_paused.store(true, Ordering::SeqCst);
+        set_status(self.emitter.as_ref(), &self.status, |status| status.state = SchedulerState::Paused);
+    }
+
+    /// Stops the scheduler, cancelling any ongoing sync.
+    pub fn stop(&self) {
+        if let Some(task) = self.task.lock().unwrap().take() {
+            task.abort();
+        }
+
+        self.is_paused.store(false, Ordering::SeqCst);
+        set_status(self.emitter.as_ref(), &self.status, |status| status.state = SchedulerState::Stopped);
+    }
+}
+
+/// The scheduler's background task.
+async fn run<S: Syncer, E: StatusEmitter>(
+    sync: Arc<Mutex<Option<S>>>,
+    status: Arc<std::sync::Mutex<SyncStatus>>,
+    emitter: Arc<E>,
+    changed: Arc<Notify>,
+    is_paused: Arc<AtomicBool>,
+    (interval, debounce): (Duration, Duration),
+) {
+    loop {
+        if !is_paused.load(Ordering::SeqCst) {
+            if let Some(remote) = sync.lock().await.as_mut() {
+                set_status(emitter.as_ref(), &status, |status| status.state = SchedulerState::Syncing);
+                let res = remote.sync().await;
+
+                // The scheduler may have been paused while syncing.
+                let state = match is_paused.load(Ordering::SeqCst) {
+                    true => SchedulerState::Paused,
+                    false => SchedulerState::Idle,
+                };
+
+                set_status(emitter.as_ref(), &status, |status| match res {
+                    Ok(reconciled) => {
+                        status.state = state;
+                        status.error = None;
+                        status.last_synced_at = Some(SystemTime::now()
+                            .duration_since(UNIX_EPOCH)
+                            .unwrap_or_default()
+                            .as_secs() as i64);
+                        status.reconciled = reconciled;
+                    },
+                    Err(err) => {
+                        status.state = SchedulerState::Error;
+                        status.error = Some(err.to_string());
+                        status.reconciled = Vec::new();
+                    }
+                });
+            }
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {},
+            _ = changed.notified() => {
+                // Wait for a burst of changes to settle.
+                while tokio::time::timeout(debounce, changed.notified()).await.is_ok() {}
+            },
+        }
+    }
+}
+
+/// Updates the scheduler's status and emits it.
+fn set_status<E: StatusEmitter, F>(emitter: &E, status: &std::sync::Mutex<SyncStatus>, f: F)
+where F: FnOnce(&mut SyncStatus) {
+    let mut status = status.lock().unwrap();
+    f(&mut status);
+    emitter.emit(&status);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Scheduler, SchedulerState, Syncer, SyncStatus};
+    use crate::core::{FileSystem, Result};
+    use crate::local::Client;
+    use crate::memory::MemoryFs;
+    use crate::sync::{Reconciled, Sync};
+    use tokio::runtime::Handle;
+    use tokio::sync::mpsc::{self, UnboundedReceiver};
+    use std::future::Future;
+    use std::time::Duration;
+    use std::sync::Arc;
+
+    impl Syncer for Sync<MemoryFs> {
+        fn sync(&mut self) -> impl Future<Output = Result<Vec<Reconciled>>> + Send {
+            Sync::sync(self)
+        }
+    }
+
+    async fn get_client(name: &str) -> Arc<Client> {
+        let db_name = format!("./hs-scheduler-test-{name}.sqlite");
+        let _ = std::fs::remove_file(&db_name);
+        let _ = std::fs::remove_file(format!("{db_name}-shm"));
+        let _ = std::fs::remove_file(format!("{db_name}-wal"));
+
+        let db = database::Database::new(&database::Config {
+            max_connections: 1,
+            local_path: db_name,
+            migrations: crate::local::migrations(),
+        }).await.unwrap();
+
+        Arc::new(Client::new(Arc::new(db)))
+    }
+
+    /// Creates a scheduler that only syncs after local changes, along
+    /// with the receiver of its status events.
+    async fn get_scheduler(local: Arc<Client>, remote: Arc<MemoryFs>) -> (Scheduler<Sync<MemoryFs>, mpsc::UnboundedSender<SyncStatus>>, UnboundedReceiver<SyncStatus>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let scheduler = Scheduler::new(Handle::current(), tx)
+            .with_interval(Duration::from_secs(60 * 60))
+            .with_debounce(Duration::from_millis(100));
+
+        scheduler.set_sync(Some(Sync::new(local, remote))).await;
+        (scheduler, rx)
+    }
+
+    async fn next_state(rx: &mut UnboundedReceiver<SyncStatus>) -> SyncStatus {
+        tokio::time::timeout(Duration::from_secs(10), rx.recv()).await
+            .expect("no status was emitted")
+            .unwrap()
+    }
+
+    async fn has_local(local: &Client, name: &str) -> bool {
+        local.list_files(None).await.unwrap()
+            .iter()
+            .any(|file| file.name == name)
+    }
+
+    #[tokio::test]
+    async fn test_scheduler() {
+        let local = get_client("start").await;
+        let remote = Arc::new(MemoryFs::new());
+        remote.create_file(None, "todo.md").await.unwrap();
+        let (scheduler, mut rx) = get_scheduler(local.clone(), remote.clone()).await;
+
+        // Starting the scheduler syncs immediately, and every status
+        // change is emitted.
+        scheduler.start();
+        assert_eq!(next_state(&mut rx).await.state, SchedulerState::Idle);
+        assert_eq!(next_state(&mut rx).await.state, SchedulerState::Syncing);
+        let status = next_state(&mut rx).await;
+        assert_eq!(status.state, SchedulerState::Idle);
+        assert!(status.error.is_none());
+        assert!(status.last_synced_at.is_some());
+        assert!(has_local(&local, "todo.md").await);
+
+        // Changes are not synced while paused.
+        scheduler.pause();
+        assert_eq!(next_state(&mut rx).await.state, SchedulerState::Paused);
+        remote.create_file(None, "notes.md").await.unwrap();
+        scheduler.notify_change();
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert!(rx.try_recv().is_err());
+        assert!(!has_local(&local, "notes.md").await);
+
+        // Resuming the scheduler syncs the pending changes.
+        scheduler.start();
+        assert_eq!(next_state(&mut rx).await.state, SchedulerState::Idle);
+        assert_eq!(next_state(&mut rx).await.state, SchedulerState::Syncing);
+        assert_eq!(next_state(&mut rx).await.state, SchedulerState::Idle);
+        assert!(has_local(&local, "notes.md").await);
+
+        // Changes are not synced once stopped.
+        scheduler.stop();
+        assert_eq!(next_state(&mut rx).await.state, SchedulerState::Stopped);
+        assert_eq!(scheduler.status().state, SchedulerState::Stopped);
+        scheduler.notify_change();
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_scheduler_debounce() {
+        let local = get_client("debounce").await;
+        let remote = Arc::new(MemoryFs::new());
+        let (scheduler, mut rx) = get_scheduler(local.clone(), remote.clone()).await;
+        scheduler.start();
+        for _ in 0..3 {
+            next_state(&mut rx).await;
+        }
+
+        // A burst of changes triggers a single sync once it settles.
+        for name in ["a.md", "b.md", "c.md", "d.md", "e.md"] {
+            remote.create_file(None, name).await.unwrap();
+            scheduler.notify_change();
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        assert_eq!(next_state(&mut rx).await.state, SchedulerState::Syncing);
+        assert_eq!(next_state(&mut rx).await.state, SchedulerState::Idle);
+        for name in ["a.md", "b.md", "c.md", "d.md", "e.md"] {
+            assert!(has_local(&local, name).await);
+        }
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert!(rx.try_recv().is_err());
+        scheduler.stop();
+    }
+}
@@ -1,8 +1,21 @@
 use crate::core::{FileSystem, File, Result, Delta};
-use crate::local::{Client, LocalFile};
+use crate::local::{Client, LocalFile, SyncState};
+use super::merge;
+use super::rules::{Candidate, SyncRules};
+use super::plan::{dependencies, plan, ActionKind, SyncAction};
 
-use std::collections::HashMap;
+use chrono::Local;
+use futures_util::stream::{FuturesUnordered, StreamExt};
+use serde::{Serialize, Deserialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::Arc;
+use tokio::sync::{Mutex, OnceCell};
+
+/// Default maximum number of changes that are applied at once.
+pub const DEFAULT_CONCURRENCY: usize = 4;
 
 pub struct Sync<R: FileSystem + Delta> {
     local: Arc<Client>,
@@ -10,6 +23,18 @@ pub struct Sync<R: FileSystem + Delta> {
     local_token: Option<String>,
     remote_token: Option<String>,
     remote_root: Option<String>,
+    rules: SyncRules,
+
+    /// Remote IDs of the ancestors of the folders included by
+    /// [rules](Self::rules), fetched when first needed.
+    rule_ancestors: OnceCell<HashSet<String>>,
+
+    /// Maximum number of changes that are applied at once.
+    concurrency: usize,
+
+    /// Held while a local and a remote file are linked by name, so
+    /// that the actions on either copy don't link them twice.
+    linking: Mutex<()>,
 }
 
 impl<R: FileSystem<Error = crate::core::Error> + Delta> Sync<R> {
@@ -20,72 +45,322 @@ impl<R: FileSystem<Error = crate::core::Error> + Delta> Sync<R> {
             local, remote,
             local_token: None,
             remote_token: None,
-            remote_root: None
+            remote_root: None,
+            rules: SyncRules::default(),
+            rule_ancestors: OnceCell::new(),
+            concurrency: DEFAULT_CONCURRENCY,
+            linking: Mutex::new(()),
+        }
+    }
+
+    /// Create a new instance of [Sync], restoring the delta tokens
+    /// and remote root that were persisted by a previous run, along
+    /// with the persisted [SyncRules].
+    pub async fn load(local: Arc<Client>, remote: Arc<R>) -> Result<Self> {
+        let state = local.get_sync_state().await?.unwrap_or_default();
+        let rules = local.get_sync_rules().await?;
+        Ok(Self {
+            local, remote, rules,
+            local_token: state.local_token,
+            remote_token: state.remote_token,
+            remote_root: state.remote_root,
+            rule_ancestors: OnceCell::new(),
+            concurrency: DEFAULT_CONCURRENCY,
+            linking: Mutex::new(()),
+        })
+    }
+
+    /// Sets the remote folder that mirrors the local root directory.
+    ///
+    /// If `remote_root` is `None`, the remote filesystem's root
+    /// directory is used, and its ID is resolved by the next sync.
+    pub fn set_remote_root(&mut self, remote_root: Option<&str>) {
+        self.remote_root = remote_root.map(|id| id.to_string());
+        self.rule_ancestors = OnceCell::new();
+    }
+
+    /// ID of the remote folder that mirrors the local root directory,
+    /// if set.
+    pub fn remote_root(&self) -> Option<&str> {
+        self.remote_root.as_deref()
+    }
+
+    /// Resolves the ID of the remote filesystem's root directory if
+    /// no remote root is set, so that remote files at the root can be
+    /// matched to the local root directory.
+    ///
+    /// Called by [sync_full](Self::sync_full) and
+    /// [sync_changes](Self::sync_changes). The resolved ID is only
+    /// persisted by the next sync.
+    pub async fn resolve_remote_root(&mut self) -> Result<()> {
+        if self.remote_root.is_none() {
+            self.remote_root = self.remote.get_root_id().await?;
+            self.rule_ancestors = OnceCell::new();
         }
+
+        Ok(())
+    }
+
+    /// Sets the rules that select which files are synced, replacing
+    /// the rules that were loaded from the local database.
+    ///
+    /// Changes to files that are not selected are ignored. Files
+    /// that were excluded by earlier rules are only picked up by the
+    /// next [sync_full](Self::sync_full).
+    pub fn set_rules(&mut self, rules: SyncRules) {
+        self.rules = rules;
+        self.rule_ancestors = OnceCell::new();
+    }
+
+    /// The rules that select which files are synced.
+    pub fn rules(&self) -> &SyncRules {
+        &self.rules
+    }
+
+    /// Sets the maximum number of changes that
+    /// [sync_changes](Self::sync_changes) and
+    /// [sync_full](Self::sync_full) apply at once. Defaults to
+    /// [DEFAULT_CONCURRENCY]. A limit of 0 is treated as 1.
+    pub fn set_concurrency(&mut self, concurrency: usize) {
+        self.concurrency = concurrency.max(1);
+    }
+
+    /// The maximum number of changes that are applied at once.
+    pub fn concurrency(&self) -> usize {
+        self.concurrency
+    }
+
+    /// Whether the two file systems have been synced before, i.e.
+    /// whether [sync](Self::sync) would only sync the latest changes.
+    pub fn has_synced(&self) -> bool {
+        self.local_token.is_some() && self.remote_token.is_some()
+    }
+
+    /// Persist the delta tokens and remote root to the local
+    /// database.
+    async fn save_state(&self) -> Result<()> {
+        self.local.set_sync_state(&SyncState {
+            local_token: self.local_token.clone(),
+            remote_token: self.remote_token.clone(),
+            remote_root: self.remote_root.clone(),
+        }).await
     }
 
     /// Fetch combined local and remote deltas.
     ///
     /// Calls [list_deltas](crate::core::FileSystem::list_deltas] for
     /// the local and remote file systems, grouping deltas by their
-    /// respective file IDs.
+    /// respective file IDs. Changes to files that are excluded by the
+    /// [rules](Self::rules) are left out.
     ///
     /// If `step` is set to true, then the internal delta tokens are
     /// advanced.
     pub async fn list_deltas(&mut self, step: bool) -> Result<Vec<Unreconciled>> {
+        let (deltas, new_local_token, new_remote_token) = self.fetch_deltas().await?;
+        if step {
+            self.local_token = Some(new_local_token);
+            self.remote_token = Some(new_remote_token);
+        }
+
+        Ok(deltas)
+    }
+
+    /// Plans how the latest local and remote changes would be
+    /// reconciled, without applying them or advancing the delta
+    /// tokens (e.g. for a dry-run).
+    ///
+    /// See [plan] for how changes are planned.
+    pub async fn plan_changes(&self) -> Result<Vec<SyncAction>> {
+        let (deltas, _, _) = self.fetch_deltas().await?;
+        Ok(plan(deltas))
+    }
+
+    /// Plans how [sync_full](Self::sync_full) would reconcile every
+    /// local and remote file, without applying changes or advancing
+    /// the delta tokens (e.g. for a dry-run).
+    ///
+    /// Files are matched as by [sync_full](Self::sync_full), and the
+    /// contents of folders that only exist on one side are planned to
+    /// be created along with them. Matched files whose content
+    /// differs are compared with the content that was last synced, so
+    /// their content may be read.
+    pub async fn plan_full(&mut self) -> Result<Vec<SyncAction>> {
+        self.resolve_remote_root().await?;
+        let mut remote_tree = self.list_remote_tree().await?;
+        let remote_ids: HashSet<String> = remote_tree.values().flatten()
+            .map(|file| file.id.clone())
+            .collect();
+
+        let ancestors = self.rule_ancestors().await.clone();
+        let mut actions: Vec<SyncAction> = Vec::new();
+
+        // Folders are visited as by [sync_full]. A folder that only
+        // exists on one side has no ID on the other.
+        type Folder = (Option<Option<i64>>, Option<Option<String>>, String, Vec<String>);
+        let mut stack: Vec<Folder> = vec![(Some(None), Some(self.remote_root.clone()), String::new(), Vec::new())];
+
+        while let Some((local_parent_id, remote_parent_id, parent_path, parent_ids)) = stack.pop() {
+            let is_included = self.rule_filter(&ancestors, &parent_path, &parent_ids);
+            let local_files = match local_parent_id {
+                Some(id) => self.local.list_files(id.map(|id| id.to_string()).as_deref()).await?,
+                None => Vec::new(),
+            };
+
+            let remote_files = match &remote_parent_id {
+                Some(id) => remote_tree.remove(id).unwrap_or_default(),
+                None => Vec::new(),
+            };
+
+            let path = |name: &str| match parent_path.is_empty() {
+                true => name.to_string(),
+                false => format!("{parent_path}/{name}"),
+            };
+
+            let (operations, pairs) = self.match_files(local_files, remote_files, &remote_ids, &is_included).await?;
+            for operation in operations {
+                match &operation {
+                    Operation::CreateRemote(local_file) if local_file.is_folder => {
+                        stack.push((Some(Some(local_file.id)), None, path(&local_file.name), parent_ids.clone()));
+                    },
+                    Operation::CreateLocal(remote_file) if remote_file.is_folder => {
+                        let mut remote_ids = parent_ids.clone();
+                        remote_ids.push(remote_file.id.clone());
+                        stack.push((None, Some(Some(remote_file.id.clone())), path(&remote_file.name), remote_ids));
+                    },
+                    _ => {},
+                }
+
+                actions.push(operation.action());
+            }
+
+            for (local_file, remote_file) in pairs {
+                if !is_included(&remote_file.name, Some(&remote_file.id), remote_file.is_folder, remote_file.size) {
+                    continue;
+                }
+
+                if local_file.is_folder {
+                    let mut remote_ids = parent_ids.clone();
+                    remote_ids.push(remote_file.id.clone());
+                    stack.push((Some(Some(local_file.id)), Some(Some(remote_file.id.clone())), path(&remote_file.name), remote_ids));
+                }
+
+                let is_moved = local_parent_id != Some(local_file.parent);
+                actions.extend(self.plan_pair(local_file, remote_file, is_moved).await?);
+            }
+        }
+
+        Ok(actions)
+    }
+
+    /// Plans how [sync_pair](Self::sync_pair) would reconcile a
+    /// matched local and remote file, or returns `None` if they are
+    /// already in sync.
+    async fn plan_pair(&self, local_file: LocalFile, remote_file: File, is_moved: bool) -> Result<Option<SyncAction>> {
+        let id = remote_file.id.clone();
+        let is_renamed = local_file.name != remote_file.name;
+        let kind = match local_file.is_folder {
+            true => None,
+            false => self.plan_transfer(&local_file, &remote_file).await?,
+        };
+
+        let kind = match kind {
+            Some(kind) => kind,
+            None if is_moved => ActionKind::Move,
+            None if is_renamed && local_file.modified_at > remote_file.modified_at => ActionKind::Upload,
+            None if is_renamed => ActionKind::Move,
+            None => return Ok(None),
+        };
+
+        let (local, remote) = match kind {
+            ActionKind::Upload => (Some(local_file), None),
+            ActionKind::Conflict => (Some(local_file), Some(remote_file)),
+            _ => (None, Some(remote_file)),
+        };
+
+        Ok(Some(SyncAction { id, kind, local, remote }))
+    }
+
+    /// Plans how [transfer](Self::transfer) would reconcile the
+    /// content of a local file and its remote counterpart, or returns
+    /// `None` if both sides have the same content.
+    async fn plan_transfer(&self, local_file: &LocalFile, remote_file: &File) -> Result<Option<ActionKind>> {
+        if self.is_same_content(local_file, remote_file).await? == Some(true) {
+            return Ok(None);
+        }
+
+        let local_content = self.local.read_from_file(&local_file.id.to_string()).await?;
+        let remote_content = self.remote.read_from_file(&remote_file.id).await?;
+        if local_content == remote_content {
+            return Ok(None);
+        }
+
+        let snapshot = self.local.get_snapshot(local_file.id).await?;
+        Ok(Some(match snapshot {
+            Some(snapshot) if snapshot == local_content => ActionKind::Download,
+            Some(snapshot) if snapshot == remote_content => ActionKind::Upload,
+            _ => ActionKind::Conflict,
+        }))
+    }
+
+    /// Fetch combined local and remote deltas, along with the next
+    /// local and remote delta tokens.
+    async fn fetch_deltas(&self) -> Result<(Vec<Unreconciled>, String, String)> {
         let (local_deltas, new_local_token) = self.local
             .list_deltas(self.local_token.as_deref()).await?;
 
         let (remote_deltas, new_remote_token) = self.remote
             .list_deltas(self.remote_token.as_deref()).await?;
 
+        // Local deltas are keyed by their remote ID so that they can
+        // be grouped with their remote counterparts. Files without a
+        // remote copy are keyed by their local ID, in a namespace of
+        // their own so that they can't be mistaken for remote files.
         let mut deltas_map = HashMap::<String, Unreconciled>::new();
         for delta in local_deltas {
-            // If the key is already registered, keep the delta with
-            // the latest modified_at.
-            if let Some(mapped) = deltas_map.get(&delta.id.to_string()) {
-                if delta.modified_at > mapped.local.clone().unwrap().modified_at {
-                    deltas_map.insert(delta.id.to_string(), Unreconciled {
-                        id: delta.clone().remote_id.unwrap_or(delta.id.to_string()),
-                        local: Some(delta),
-                        remote: None,
-                    });
-                    continue;
-                }
-                continue;
-            }
-            deltas_map.insert(delta.id.to_string(), Unreconciled {
-                id: delta.clone().remote_id.unwrap_or(delta.id.to_string()),
+            let id = match &delta.remote_id {
+                Some(remote_id) => remote_id.clone(),
+                None => format!("local:{}", delta.id),
+            };
+            deltas_map.insert(id.clone(), Unreconciled {
+                id,
                 local: Some(delta),
                 remote: None,
+                counterpart: None,
             });
         }
 
-        for delta in remote_deltas.into_iter().map(|d| Into::<File>::into(d)) {
-            if let Some(mapped) = deltas_map.get(&delta.id) {
-                let mut mapped: Unreconciled = mapped.clone();
-                if let Some(remote) = mapped.remote {
-                    if delta.modified_at <= remote.modified_at {
-                        continue;
-                    }
-                }
-                mapped.remote = Some(delta);
-                deltas_map.insert(mapped.id.clone(), mapped);
+        for delta in remote_deltas.into_iter().map(Into::<File>::into) {
+            let mapped = deltas_map.entry(delta.id.clone())
+                .or_insert(Unreconciled {
+                    id: delta.id.clone(),
+                    local: None,
+                    remote: None,
+                    counterpart: None,
+                });
+
+            // If the file has changed more than once, keep the delta
+            // with the latest modified_at.
+            if mapped.remote.as_ref().is_some_and(|remote| delta.modified_at < remote.modified_at) {
                 continue;
             }
-            deltas_map.insert(delta.id.clone(), Unreconciled {
-                id: delta.id.clone(),
-                local: None,
-                remote: Some(delta),
-            });
+
+            mapped.remote = Some(delta);
         }
 
-        if step {
-            self.local_token = Some(new_local_token);
-            self.remote_token = Some(new_remote_token);
+        let mut deltas = Vec::with_capacity(deltas_map.len());
+        for mut delta in deltas_map.into_values() {
+            if !self.is_delta_included(&delta).await? {
+                continue;
+            }
+
+            if let (None, Some(remote_file)) = (&delta.local, &delta.remote) {
+                delta.counterpart = self.local.get_remote_file(&remote_file.id).await?;
+            }
+
+            deltas.push(delta);
         }
 
-        Ok(deltas_map.into_iter().map(|(_, v)| v).collect())
+        Ok((deltas, new_local_token, new_remote_token))
     }
 
     /// Performs a full synchronization across two file systems.
@@ -99,8 +374,421 @@ impl<R: FileSystem<Error = crate::core::Error> + Delta> Sync<R> {
     /// synchronized to a consistent state. Subsequent calls to
     /// [sync_changes] will have a higher probability of being
     /// accepted.
-    pub async fn sync_full(&self) -> Result<()> {
-        unimplemented!();
+    ///
+    /// Files are matched by their remote ID and then by name. Files
+    /// that are missing on either side are created (along with their
+    /// content). Content of matched files that changed on one side
+    /// since the last sync is copied to the other, and content that
+    /// changed on both sides is merged or saved to a conflict copy,
+    /// as by [sync_changes](Self::sync_changes). Files that are
+    /// excluded by the [rules](Self::rules) are left as they are on
+    /// both sides.
+    ///
+    /// Folders are visited one at a time, and the files in each
+    /// folder are reconciled concurrently, up to the
+    /// [concurrency](Self::concurrency) limit.
+    ///
+    /// A file that can't be reconciled is reported as
+    /// [Resolution::Failed] without aborting the walk, and the
+    /// contents of a folder that failed are not visited. The delta
+    /// tokens are only advanced (and persisted) once every file has
+    /// been reconciled.
+    ///
+    /// Returns a report of every file that was changed on either
+    /// side, or that failed.
+    pub async fn sync_full(&mut self) -> Result<Vec<Reconciled>> {
+
+        // Fetch the delta tokens before walking the trees so that
+        // changes made during the walk are picked up by the next
+        // call to [sync_changes]. The tokens are only advanced once
+        // the walk succeeds, so failures are retried by the next
+        // call to [sync](Self::sync).
+        self.resolve_remote_root().await?;
+        let (_, new_local_token, new_remote_token) = self.fetch_deltas().await?;
+
+        let mut remote_tree = self.list_remote_tree().await?;
+        let remote_ids: HashSet<String> = remote_tree.values().flatten()
+            .map(|file| file.id.clone())
+            .collect();
+
+        let ancestors = self.rule_ancestors().await.clone();
+        let mut report: Vec<Reconciled> = Vec::new();
+
+        // Walk both trees using a stack for DFS traversal. Folders are
+        // visited along with their path and the remote IDs of their
+        // ancestors, against which the rules are matched.
+        type Folder = (Option<i64>, Option<String>, String, Vec<String>);
+        type Pair = (LocalFile, File);
+        let mut stack: Vec<Folder> = vec![(None, self.remote_root.clone(), String::new(), Vec::new())];
+
+        while let Some((local_parent_id, remote_parent_id, parent_path, parent_ids)) = stack.pop() {
+            let is_included = self.rule_filter(&ancestors, &parent_path, &parent_ids);
+            let local_parent_id = local_parent_id.map(|id| id.to_string());
+            let local_files = self.local.list_files(local_parent_id.as_deref()).await?;
+            let remote_files = remote_tree.remove(&remote_parent_id).unwrap_or_default();
+            let (operations, matched) = self.match_files(local_files, remote_files, &remote_ids, &is_included).await?;
+
+            // Matched pairs, and whether their content is up to date.
+            let mut pairs: Vec<(LocalFile, File, bool)> = matched.into_iter()
+                .map(|(local_file, remote_file)| (local_file, remote_file, false))
+                .collect();
+
+            // Shared by the concurrent operations, which only read the
+            // sync's state.
+            let this = &*self;
+            let parents = (local_parent_id.as_deref(), remote_parent_id.as_deref());
+            let applied: Vec<(Reconciled, Option<Pair>)> = futures_util::stream::iter(operations)
+                .map(|operation| async move {
+                    match this.apply(&operation, parents.0, parents.1).await {
+                        Ok(applied) => applied,
+                        Err(err) => (operation.reconciled(Resolution::Failed { error: err.to_string() }), None),
+                    }
+                })
+                .buffer_unordered(this.concurrency)
+                .collect().await;
+
+            for (reconciled, pair) in applied {
+                report.push(reconciled);
+                pairs.extend(pair.map(|(local_file, remote_file)| (local_file, remote_file, true)));
+            }
+
+            pairs.retain(|(_, remote_file, _)| {
+                is_included(&remote_file.name, Some(&remote_file.id), remote_file.is_folder, remote_file.size)
+            });
+
+            let parents = (local_parent_id.as_deref(), remote_parent_id.as_deref());
+            let synced: Vec<(Option<Pair>, Option<Reconciled>)> = futures_util::stream::iter(pairs)
+                .map(|(local_file, remote_file, is_transferred)| async move {
+                    let (id, name) = (remote_file.id.clone(), local_file.name.clone());
+                    match this.sync_pair(local_file, remote_file, is_transferred, parents.0, parents.1).await {
+                        Ok((local_file, remote_file, reconciled)) => (Some((local_file, remote_file)), reconciled),
+                        Err(err) => (None, Some(Reconciled::new(id, &name, Resolution::Failed { error: err.to_string() }))),
+                    }
+                })
+                .buffer_unordered(this.concurrency)
+                .collect().await;
+
+            let mut folders = Vec::new();
+            for (pair, reconciled) in synced {
+                report.extend(reconciled);
+                folders.extend(pair.filter(|(local_file, _)| local_file.is_folder));
+            }
+
+            for (local_file, remote_file) in folders {
+                let path = match parent_path.is_empty() {
+                    true => local_file.name.clone(),
+                    false => format!("{parent_path}/{}", local_file.name),
+                };
+
+                let mut remote_ids = parent_ids.clone();
+                remote_ids.push(remote_file.id.clone());
+                stack.push((Some(local_file.id), Some(remote_file.id), path, remote_ids));
+            }
+        }
+
+        let is_failed = report.iter()
+            .any(|reconciled| matches!(reconciled.resolution, Resolution::Failed { .. }));
+
+        if is_failed {
+            return Ok(report);
+        }
+
+        // Every local deletion has either been propagated during the
+        // walk or no longer has a remote counterpart.
+        let synced_at: i64 = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64;
+
+        self.local.set_deleted_synced_at(synced_at).await?;
+        self.local_token = Some(new_local_token);
+        self.remote_token = Some(new_remote_token);
+        self.save_state().await?;
+        Ok(report)
+    }
+
+    /// Matches the local and remote files in a folder that
+    /// [sync_full](Self::sync_full) is visiting, first by remote ID
+    /// and then by name. Files that are excluded by `is_included` are
+    /// left out.
+    ///
+    /// Returns the operations on unmatched files, along with the
+    /// matched pairs.
+    async fn match_files<F>(&self, local_files: Vec<LocalFile>, mut remote_files: Vec<File>, remote_ids: &HashSet<String>, is_included: &F) -> Result<(Vec<Operation>, Vec<(LocalFile, File)>)>
+    where F: Fn(&str, Option<&String>, bool, Option<u64>) -> bool {
+        let mut pairs: Vec<(LocalFile, File)> = Vec::new();
+
+        // Match by remote ID.
+        let mut unmatched: Vec<LocalFile> = Vec::new();
+        for local_file in local_files {
+            let position = remote_files.iter()
+                .position(|file| local_file.remote_id.as_ref() == Some(&file.id));
+
+            match position {
+                Some(i) => pairs.push((local_file, remote_files.swap_remove(i))),
+                None => unmatched.push(local_file),
+            }
+        }
+
+        // Operations on unmatched files.
+        let mut operations: Vec<Operation> = Vec::new();
+        for local_file in unmatched {
+            let size = match self.rules.has_size_rules() && !local_file.is_folder {
+                true => Some(self.local.get_file_size(local_file.id).await?),
+                false => None,
+            };
+
+            if !is_included(&local_file.name, local_file.remote_id.as_ref(), local_file.is_folder, size) {
+                continue;
+            }
+
+            if let Some(remote_id) = &local_file.remote_id {
+
+                // The remote file has been moved to a different
+                // folder. It is reconciled once its new parent is
+                // visited.
+                if remote_ids.contains(remote_id) {
+                    continue;
+                }
+
+                // The remote file has been deleted and there are
+                // no local changes since the last sync.
+                if local_file.synced_at.is_some_and(|ts| local_file.modified_at <= ts) {
+                    operations.push(Operation::RemoveLocal(local_file));
+                    continue;
+                }
+            }
+
+            // Match by name.
+            let position = remote_files.iter().position(|file| {
+                file.name == local_file.name && file.is_folder == local_file.is_folder
+            });
+
+            match position {
+                Some(i) => pairs.push((local_file, remote_files.swap_remove(i))),
+                None => operations.push(Operation::CreateRemote(local_file)),
+            }
+        }
+
+        for remote_file in remote_files {
+            if !is_included(&remote_file.name, Some(&remote_file.id), remote_file.is_folder, remote_file.size) {
+                continue;
+            }
+
+            match self.local.get_remote_file(&remote_file.id).await? {
+
+                // The file has been deleted locally since the last sync.
+                Some(local_file) if local_file.is_deleted => {
+                    operations.push(Operation::RemoveRemote(remote_file));
+                },
+
+                // The file has been moved to a different local
+                // folder. Remote layout takes precedence, and the
+                // local file is moved back when the pair is synced.
+                Some(local_file) => pairs.push((local_file, remote_file)),
+                None => operations.push(Operation::CreateLocal(remote_file)),
+            }
+        }
+
+        Ok((operations, pairs))
+    }
+
+    /// Returns whether a file in the folder at `parent_path`, whose
+    /// ancestors have the remote IDs `parent_ids`, is included by the
+    /// [rules](Self::rules), given its name, remote ID, kind and size.
+    fn rule_filter<'a>(&'a self, ancestors: &'a HashSet<String>, parent_path: &'a str, parent_ids: &'a [String]) -> impl Fn(&str, Option<&String>, bool, Option<u64>) -> bool + 'a {
+        move |name, remote_id, is_folder, size| {
+            let path = match parent_path.is_empty() {
+                true => name.to_string(),
+                false => format!("{parent_path}/{name}"),
+            };
+
+            let remote_ids: Vec<String> = parent_ids.iter().chain(remote_id).cloned().collect();
+            let file = Candidate { path: &path, remote_ids: &remote_ids, is_folder, size };
+            self.rules.is_included(&file, ancestors)
+        }
+    }
+
+    /// Applies an [Operation] on an unmatched file in the folder that
+    /// [sync_full](Self::sync_full) is visiting.
+    ///
+    /// Returns how the file was reconciled, along with the matched
+    /// local and remote copies of the file if it wasn't deleted.
+    async fn apply(&self, operation: &Operation, local_parent_id: Option<&str>, remote_parent_id: Option<&str>) -> Result<(Reconciled, Option<(LocalFile, File)>)> {
+        match operation {
+            Operation::RemoveLocal(local_file) => {
+                self.local.remove_file(&local_file.id.to_string()).await?;
+                Ok((operation.reconciled(Resolution::Remote), None))
+            },
+            Operation::RemoveRemote(remote_file) => {
+                self.remote.remove_file(&remote_file.id).await?;
+                Ok((operation.reconciled(Resolution::Local), None))
+            },
+            Operation::CreateRemote(local_file) => {
+                let remote_file = self.create_remote(local_file, remote_parent_id).await?;
+                let reconciled = Reconciled::new(remote_file.id.clone(), &local_file.name, Resolution::Local);
+                Ok((reconciled, Some((local_file.clone(), remote_file))))
+            },
+            Operation::CreateLocal(remote_file) => {
+                let local_file = self.create_local(remote_file, local_parent_id).await?;
+                Ok((operation.reconciled(Resolution::Remote), Some((local_file, remote_file.clone()))))
+            },
+        }
+    }
+
+    /// Reconciles the location, name and content of a matched local
+    /// and remote file, and marks the local file as synced.
+    ///
+    /// Returns the local and remote file once they are reconciled,
+    /// along with how they were reconciled if either was changed.
+    async fn sync_pair(&self, mut local_file: LocalFile, mut remote_file: File, is_transferred: bool, local_parent_id: Option<&str>, remote_parent_id: Option<&str>) -> Result<(LocalFile, File, Option<Reconciled>)> {
+        if local_file.remote_id.as_ref() != Some(&remote_file.id) {
+            self.local.set_remote_id(local_file.id, &remote_file.id).await?;
+        }
+
+        // The file has been moved or renamed on either side. Moves
+        // are resolved in favour of the remote layout, and names by
+        // last-writer-wins, with ties resolved in favour of the
+        // remote.
+        let mut resolution = None;
+        let is_moved = local_file.parent.map(|id| id.to_string()).as_deref() != local_parent_id;
+        if is_moved || local_file.name != remote_file.name {
+            if !is_moved && local_file.modified_at > remote_file.modified_at {
+                remote_file.id = self.move_remote(&local_file, &remote_file.id, remote_parent_id).await?;
+                remote_file.name = local_file.name.clone();
+                resolution = Some(Resolution::Local);
+            } else {
+                local_file = self.local.move_file(
+                    &local_file.id.to_string(),
+                    local_parent_id,
+                    Some(&remote_file.name)
+                ).await?;
+                resolution = Some(Resolution::Remote);
+            }
+        }
+
+        if !local_file.is_folder && !is_transferred {
+            if let Some(transferred) = self.transfer(&local_file, &remote_file).await? {
+                resolution = Some(transferred);
+            }
+        }
+
+        let synced_at: i64 = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64;
+
+        self.local.set_synced_at(local_file.id, synced_at).await?;
+        let reconciled = resolution.map(|resolution| {
+            Reconciled::new(remote_file.id.clone(), &local_file.name, resolution)
+        });
+
+        Ok((local_file, remote_file, reconciled))
+    }
+
+    /// Lists every (non-deleted) file below the remote root, grouped
+    /// by the ID of the parent that was used to list them.
+    async fn list_remote_tree(&self) -> Result<HashMap<Option<String>, Vec<File>>> {
+        let mut tree = HashMap::new();
+        let mut stack: Vec<Option<String>> = vec![self.remote_root.clone()];
+        while let Some(parent_id) = stack.pop() {
+            let mut files: Vec<File> = Vec::new();
+            let mut pages = self.remote.list_files_paged(parent_id.as_deref());
+            while let Some(page) = pages.next().await? {
+                files.extend(page.into_iter()
+                    .map(Into::into)
+                    .filter(|file: &File| !file.is_deleted));
+            }
+
+            stack.extend(files.iter()
+                .filter(|file| file.is_folder)
+                .map(|file| Some(file.id.clone())));
+
+            tree.insert(parent_id, files);
+        }
+
+        Ok(tree)
+    }
+
+    /// Creates a remote copy of a local file, including its content.
+    async fn create_remote(&self, local_file: &LocalFile, parent_id: Option<&str>) -> Result<File> {
+        if local_file.is_folder {
+            return Ok(self.remote.create_folder(parent_id, &local_file.name).await?.into());
+        }
+
+        let remote_file: File = self.remote.create_file(parent_id, &local_file.name).await?.into();
+        let content = self.local.read_from_file(&local_file.id.to_string()).await?;
+        self.local.set_snapshot(local_file.id, &content).await?;
+        if content.is_empty() {
+            return Ok(remote_file);
+        }
+
+        Ok(self.remote.write_to_file(&remote_file.id, &content).await?.into())
+    }
+
+    /// Creates a local copy of a remote file, including its content.
+    async fn create_local(&self, remote_file: &File, parent_id: Option<&str>) -> Result<LocalFile> {
+        if remote_file.is_folder {
+            return self.local.create_folder(parent_id, &remote_file.name).await;
+        }
+
+        let local_file = self.local.create_file(parent_id, &remote_file.name).await?;
+        let content = self.remote.read_from_file(&remote_file.id).await?;
+        self.local.set_snapshot(local_file.id, &content).await?;
+        if content.is_empty() {
+            return Ok(local_file);
+        }
+
+        self.local.write_to_file(&local_file.id.to_string(), &content).await
+    }
+
+    /// Reconciles the content of a local file and its remote
+    /// counterpart. Content that only changed on one side since the
+    /// last sync is copied to the other, and content that changed on
+    /// both sides (or that was never synced) is reconciled by
+    /// [reconcile_content](Self::reconcile_content).
+    ///
+    /// Returns how the content was reconciled, or `None` if both
+    /// sides already had the same content.
+    async fn transfer(&self, local_file: &LocalFile, remote_file: &File) -> Result<Option<Resolution>> {
+        let local_content = self.local.read_from_file(&local_file.id.to_string()).await?;
+        if self.is_same_content(local_file, remote_file).await? == Some(true) {
+            self.local.set_snapshot(local_file.id, &local_content).await?;
+            return Ok(None);
+        }
+
+        let remote_content = self.remote.read_from_file(&remote_file.id).await?;
+        if local_content == remote_content {
+            self.local.set_snapshot(local_file.id, &local_content).await?;
+            return Ok(None);
+        }
+
+        let snapshot = self.local.get_snapshot(local_file.id).await?;
+        if snapshot.as_ref() == Some(&local_content) {
+            self.local.write_to_file(&local_file.id.to_string(), &remote_content).await?;
+            self.local.set_snapshot(local_file.id, &remote_content).await?;
+            return Ok(Some(Resolution::Remote));
+        }
+
+        if snapshot.as_ref() == Some(&remote_content) {
+            self.remote.write_to_file(&remote_file.id, &local_content).await?;
+            self.local.set_snapshot(local_file.id, &local_content).await?;
+            return Ok(Some(Resolution::Local));
+        }
+
+        let resolution = self.reconcile_content(local_file, remote_file, &local_file.name, local_content, remote_content).await?;
+        Ok(Some(resolution))
+    }
+
+    /// Synchronizes the two file systems, performing a full sync if
+    /// they have never been synced and syncing the latest changes
+    /// otherwise.
+    pub async fn sync(&mut self) -> Result<Vec<Reconciled>> {
+        if !self.has_synced() {
+            return self.sync_full().await;
+        }
+
+        self.sync_changes().await
     }
 
     /// Synchronizes only the latest local & remote changes.
@@ -108,178 +796,2097 @@ impl<R: FileSystem<Error = crate::core::Error> + Delta> Sync<R> {
     /// It is recommended to sync by tracking changes only after
     /// calling [sync_full] at least once. That is, by calling
     /// [sync_full] and then periodically calling [sync_changes].
-    pub async fn sync_changes(&self) -> Result<()> {
-        unimplemented!();
+    ///
+    /// Independent changes are applied concurrently, up to the
+    /// [concurrency](Self::concurrency) limit, while changes to a
+    /// folder are applied before changes to its children. A change
+    /// that fails is reported as [Resolution::Failed] without
+    /// aborting the sync, and changes to its children are reported
+    /// as [Resolution::Skipped].
+    ///
+    /// The delta tokens are only advanced (and persisted) once every
+    /// change has been reconciled, so failed, skipped and interrupted
+    /// changes are retried by the next call.
+    ///
+    /// Returns a report of every reconciled or failed change.
+    pub async fn sync_changes(&mut self) -> Result<Vec<Reconciled>> {
+        self.sync_changes_with(|_, _, _| {}).await
     }
 
-    pub async fn sync_one(&self, delta: Unreconciled) -> Result<()> {
-        if delta.local.is_some() && delta.remote.is_some() {
+    /// Like [sync_changes](Self::sync_changes), but reports progress
+    /// by calling `progress` after each planned action with the
+    /// number of processed actions, the total number of actions, and
+    /// how the change was reconciled (if it wasn't ignored).
+    pub async fn sync_changes_with<F>(&mut self, progress: F) -> Result<Vec<Reconciled>>
+    where F: FnMut(usize, usize, Option<&Reconciled>) {
+        self.resolve_remote_root().await?;
+        let (deltas, new_local_token, new_remote_token) = self.fetch_deltas().await?;
+        let (report, is_complete) = self.execute_all(plan(deltas), progress).await;
+        if is_complete {
+            self.local_token = Some(new_local_token);
+            self.remote_token = Some(new_remote_token);
+            self.save_state().await?;
         }
 
-        if delta.local.is_some() && delta.remote.is_none() {
-            let local_file = delta.local.unwrap();
-            let remote_parent_id = match local_file.parent {
-                Some(parent_id) => self.local
-                    .get_remote_file(&parent_id.to_string())
-                    .await?.map(|file| file.id.to_string()),
-                _ => None,
-            };
-            match local_file.remote_id {
-                Some(remote_id) => {
-                    let remote_file = self.remote.get_file(&remote_id).await;
-                    if local_file.is_deleted && remote_file.is_ok() {
-                        self.remote.remove_file(&remote_id).await?;
-                        return Ok(());
-                    }
-                    let remote_file: File = remote_file?.into();
-                    if local_file.name != remote_file.name || remote_parent_id != remote_file.parent_id {
-                        self.remote.move_file(&remote_id, remote_parent_id.as_deref(), Some(&local_file.name)).await?;
-                    }
-                    return Ok(());
-                },
-                None => {
-                    let uploaded: File = self.remote.create_file(remote_parent_id.as_deref(), &local_file.name).await?.into();
-                    self.local.set_remote_id(local_file.id, &uploaded.id).await?;
-                    return Ok(());
-                }
+        Ok(report)
+    }
+
+    /// Applies actions planned by [plan], running up to
+    /// [concurrency](Self::concurrency) actions at once. An action
+    /// starts once the actions on its parents have completed, and is
+    /// skipped if any of them failed.
+    ///
+    /// Returns the report, along with whether every action was
+    /// applied successfully.
+    async fn execute_all<F>(&self, actions: Vec<SyncAction>, mut progress: F) -> (Vec<Reconciled>, bool)
+    where F: FnMut(usize, usize, Option<&Reconciled>) {
+        let total = actions.len();
+        let mut children: Vec<Vec<usize>> = vec![Vec::new(); total];
+        let mut waiting: Vec<usize> = vec![0; total];
+        for (i, parents) in dependencies(&actions).into_iter().enumerate() {
+            waiting[i] = parents.len();
+            for parent in parents {
+                children[parent].push(i);
             }
         }
 
-        if delta.local.is_none() && delta.remote.is_some() {
-            let remote_file = delta.remote.unwrap();
-            let mut remote_parent_id: Option<String> = None;
-            if let Some(parent_id) = remote_file.parent_id {
-                if self.remote_root.clone().is_some_and(|id| id == parent_id) {
-                    remote_parent_id = None;
-                } else {
-                    match self.local.get_remote_file(&parent_id).await? {
-                        Some(parent_file) => {
-                            remote_parent_id = Some(parent_file.id.to_string());
-                        },
-                        None => return Ok(()),
+        let mut actions: Vec<Option<SyncAction>> = actions.into_iter().map(Some).collect();
+        let mut ready: VecDeque<usize> = (0..total).filter(|&i| waiting[i] == 0).collect();
+        let mut is_skipped = vec![false; total];
+        let mut running = FuturesUnordered::new();
+        let mut report = Vec::new();
+        let mut is_complete = true;
+        let mut done = 0;
+
+        loop {
+            while running.len() < self.concurrency {
+                let Some(i) = ready.pop_front() else { break };
+                let Some(action) = actions[i].take() else { continue };
+                running.push(async move {
+                    let (id, name) = (action.id.clone(), action.name().to_string());
+                    (i, id, name, self.execute(action).await)
+                });
+            }
+
+            let Some((i, id, name, result)) = running.next().await else {
+                break;
+            };
+
+            let (reconciled, is_failed) = match result {
+                Ok(reconciled) => (reconciled, false),
+                Err(err) => (Some(Reconciled::new(id, &name, Resolution::Failed { error: err.to_string() })), true),
+            };
+
+            done += 1;
+            progress(done, total, reconciled.as_ref());
+            report.extend(reconciled);
+
+            if !is_failed {
+                for &child in &children[i] {
+                    waiting[child] -= 1;
+                    if waiting[child] == 0 && !is_skipped[child] {
+                        ready.push_back(child);
                     }
                 }
+                continue;
             }
 
-            if self.remote_root.is_some() && remote_parent_id.is_none() {
-                return Ok(());
-            }
+            // Skip every descendant of the failed action.
+            is_complete = false;
+            let mut skipped: Vec<usize> = children[i].clone();
+            while let Some(child) = skipped.pop() {
+                if is_skipped[child] {
+                    continue;
+                }
 
-            let local_file = self.local.get_remote_file(&remote_file.id).await?;
-            match local_file {
-                Some(local_file) => {
-                    let local_parent_id = match local_file.parent {
-                        Some(parent_id) => self.local.get_file(&parent_id.to_string()).await?.remote_id,
-                        None => None,
-                    };
-                    if remote_file.is_deleted && !local_file.is_deleted {
-                        self.local.remove_file(&local_file.id.to_string()).await?;
-                        return Ok(());
-                    }
-                    if local_file.name != remote_file.name || local_parent_id != remote_parent_id {
-                        self.local.move_file(&local_file.id.to_string(), remote_parent_id.as_deref(), Some(&remote_file.name)).await?;
-                    }
-                    return Ok(());
-                },
-                None => {
-                    let new_file = self.local.create_file(remote_parent_id.as_deref(), &remote_file.name).await?;
-                    self.local.set_remote_id(new_file.id, &remote_file.id).await?;
-                    return Ok(());
+                is_skipped[child] = true;
+                skipped.extend(&children[child]);
+                done += 1;
+                match actions[child].take() {
+                    Some(action) => {
+                        let reconciled = Reconciled::new(action.id.clone(), action.name(), Resolution::Skipped);
+                        progress(done, total, Some(&reconciled));
+                        report.push(reconciled);
+                    },
+                    None => progress(done, total, None),
                 }
             }
         }
 
-        Ok(())
+        (report, is_complete)
     }
-}
-
-/// Defines an unreconciled change in the local or remote filesystems.
-#[derive(Clone, Debug)]
-pub struct Unreconciled {
-    pub id: String,
-    pub local: Option<LocalFile>,
-    pub remote: Option<File>,
-}
+
+    /// Reconciles a single local and/or remote change.
+    ///
+    /// Returns `None` if the change was ignored, e.g. because the
+    /// file is outside of the remote root or is excluded by the
+    /// [rules](Self::rules).
+    pub async fn sync_one(&self, mut delta: Unreconciled) -> Result<Option<Reconciled>> {
+        if !self.is_delta_included(&delta).await? {
+            return Ok(None);
+        }
+
+        if let (None, Some(remote_file), None) = (&delta.local, &delta.remote, &delta.counterpart) {
+            delta.counterpart = self.local.get_remote_file(&remote_file.id).await?;
+        }
+
+        match plan(vec![delta]).pop() {
+            Some(action) => self.execute(action).await,
+            None => Ok(None),
+        }
+    }
+
+    /// Applies an action planned by [plan].
+    ///
+    /// The local file is read again before the action is applied,
+    /// since it may have been changed by an earlier action (e.g.
+    /// deleted along with its parent). Actions on files that no
+    /// longer exist are ignored.
+    async fn execute(&self, action: SyncAction) -> Result<Option<Reconciled>> {
+        let synced_at: i64 = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64;
+
+        let SyncAction { id, kind, local, remote } = action;
+        let local_file = match local {
+            Some(local_file) => self.local.find_file(local_file.id).await?,
+            None => None,
+        };
+
+        match (kind, local_file, remote) {
+            (ActionKind::CreateRemote, Some(local_file), remote_file) => self.create_remote_copy(id, local_file, remote_file, synced_at).await,
+            (ActionKind::CreateLocal, local_file, Some(remote_file)) => self.create_local_copy(id, local_file, remote_file, synced_at).await,
+            (ActionKind::Upload, Some(local_file), _) => self.upload(id, local_file, synced_at).await,
+            (ActionKind::Download, _, Some(remote_file)) => self.download(id, remote_file, synced_at).await,
+            (ActionKind::Move, _, Some(remote_file)) => self.move_local(id, remote_file, synced_at).await,
+            (ActionKind::DeleteRemote, Some(local_file), _) => self.delete_remote(id, local_file, synced_at).await,
+            (ActionKind::DeleteLocal, local_file, Some(remote_file)) => self.delete_local(id, local_file, remote_file, synced_at).await,
+            (ActionKind::Conflict, Some(local_file), Some(remote_file)) => self.reconcile(id, local_file, remote_file, synced_at).await,
+            _ => Ok(None),
+        }
+    }
+
+    /// Uploads a local file that has no remote copy, either because
+    /// it is new or because its remote copy was deleted while it was
+    /// edited locally.
+    async fn create_remote_copy(&self, id: String, local_file: LocalFile, remote_file: Option<File>, synced_at: i64) -> Result<Option<Reconciled>> {
+        if local_file.is_deleted {
+            return Ok(None);
+        }
+
+        // The parent has not been synced yet, or its remote copy has
+        // been deleted along with the file, in which case the remote
+        // deletion is kept.
+        let Some(parent_id) = self.remote_parent(&local_file).await? else {
+            return match remote_file {
+                Some(remote_file) => self.delete_local(id, Some(local_file), remote_file, synced_at).await,
+                None => Ok(None),
+            };
+        };
+
+        if let Some(orphan) = self.find_remote_orphan(&local_file, parent_id.as_deref()).await? {
+            return self.link(id, local_file, orphan, Resolution::Local, synced_at).await;
+        }
+
+        let uploaded = self.create_remote(&local_file, parent_id.as_deref()).await?;
+        self.local.set_remote_id(local_file.id, &uploaded.id).await?;
+        self.local.set_synced_at(local_file.id, synced_at).await?;
+        Ok(Some(Reconciled::new(id, &local_file.name, Resolution::Local)))
+    }
+
+    /// Downloads a remote file that has no local copy, either because
+    /// it is new or because its local copy was deleted while it was
+    /// edited remotely.
+    async fn create_local_copy(&self, id: String, local_file: Option<LocalFile>, remote_file: File, synced_at: i64) -> Result<Option<Reconciled>> {
+        if remote_file.is_deleted {
+            return Ok(None);
+        }
+
+        // Files outside of the remote root are ignored. A file whose
+        // local parent has been deleted along with it stays deleted.
+        let Some(parent_id) = self.local_parent(&remote_file).await? else {
+            return match local_file {
+                Some(local_file) => self.delete_remote(id, local_file, synced_at).await,
+                None => Ok(None),
+            };
+        };
+
+        let reconciled = match self.find_local_orphan(&remote_file, parent_id.as_deref()).await? {
+            Some(orphan) => self.link(id, orphan, remote_file, Resolution::Remote, synced_at).await?,
+            None => {
+                let created = self.create_local(&remote_file, parent_id.as_deref()).await?;
+                self.local.set_remote_id(created.id, &remote_file.id).await?;
+                self.local.set_synced_at(created.id, synced_at).await?;
+                Some(Reconciled::new(id, &remote_file.name, Resolution::Remote))
+            },
+        };
+
+        // The deleted local copy is marked as synced once it has been
+        // replaced, so that a failure is retried by the next sync.
+        if let Some(local_file) = &local_file {
+            self.local.set_synced_at(local_file.id, synced_at).await?;
+        }
+
+        Ok(reconciled)
+    }
+
+    /// Finds a remote file in the folder `parent_id` with the same
+    /// name and kind as `local_file`, that has no local copy.
+    async fn find_remote_orphan(&self, local_file: &LocalFile, parent_id: Option<&str>) -> Result<Option<File>> {
+        for remote_file in self.remote.list_files(parent_id).await? {
+            let remote_file: File = remote_file.into();
+            if remote_file.name != local_file.name || remote_file.is_folder != local_file.is_folder || remote_file.is_deleted {
+                continue;
+            }
+
+            if self.local.get_remote_file(&remote_file.id).await?.is_none() {
+                return Ok(Some(remote_file));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Finds a local file in the folder `parent_id` with the same
+    /// name and kind as `remote_file`, that has no remote copy.
+    async fn find_local_orphan(&self, remote_file: &File, parent_id: Option<&str>) -> Result<Option<LocalFile>> {
+        Ok(self.local.list_files(parent_id).await?.into_iter().find(|local_file| {
+            local_file.name == remote_file.name
+                && local_file.is_folder == remote_file.is_folder
+                && local_file.remote_id.is_none()
+        }))
+    }
+
+    /// Links a local and a remote file with the same name in the same
+    /// folder, neither of which has a copy on the other side, and
+    /// reconciles their content.
+    ///
+    /// Such files are left behind by an earlier attempt to create
+    /// either copy that failed after the file was created but before
+    /// it was linked, or were created independently on both sides.
+    /// Empty content (e.g. of a file whose content was never written)
+    /// is replaced by the other side's, and content that differs
+    /// otherwise is reconciled by
+    /// [reconcile_content](Self::reconcile_content). The pair is
+    /// reported as reconciled by `resolution` if the content is the
+    /// same.
+    async fn link(&self, id: String, local_file: LocalFile, remote_file: File, mut resolution: Resolution, synced_at: i64) -> Result<Option<Reconciled>> {
+        let _guard = self.linking.lock().await;
+
+        // The pair has been linked by the action on the other copy.
+        let linked = self.local.get_remote_file(&remote_file.id).await?;
+        if linked.is_some_and(|linked| !linked.is_deleted) {
+            return Ok(None);
+        }
+
+        // The content is reconciled before the files are linked, so
+        // that a failure is retried by matching them again.
+        if !local_file.is_folder {
+            let local_id = local_file.id.to_string();
+            let local_content = self.local.read_from_file(&local_id).await?;
+            let remote_content = self.remote.read_from_file(&remote_file.id).await?;
+            if local_content != remote_content {
+                if remote_content.is_empty() {
+                    self.remote.write_to_file(&remote_file.id, &local_content).await?;
+                    self.local.set_snapshot(local_file.id, &local_content).await?;
+                    resolution = Resolution::Local;
+                } else if local_content.is_empty() {
+                    self.local.write_to_file(&local_id, &remote_content).await?;
+                    self.local.set_snapshot(local_file.id, &remote_content).await?;
+                    resolution = Resolution::Remote;
+                } else {
+                    resolution = self.reconcile_content(&local_file, &remote_file, &local_file.name, local_content, remote_content).await?;
+                }
+            } else {
+                self.local.set_snapshot(local_file.id, &local_content).await?;
+            }
+        }
+
+        self.local.set_remote_id(local_file.id, &remote_file.id).await?;
+        self.local.set_synced_at(local_file.id, synced_at).await?;
+        Ok(Some(Reconciled::new(id, &local_file.name, resolution)))
+    }
+
+    /// Applies a local change (content, name or parent) to the remote
+    /// copy of a file.
+    async fn upload(&self, id: String, local_file: LocalFile, synced_at: i64) -> Result<Option<Reconciled>> {
+        let Some(remote_id) = local_file.remote_id.as_ref().filter(|_| !local_file.is_deleted) else {
+            return Ok(None);
+        };
+
+        // The parent has not been synced yet.
+        let Some(remote_parent_id) = self.remote_parent(&local_file).await? else {
+            return Ok(None);
+        };
+
+        let mut remote_file: File = self.remote.get_file(remote_id).await?.into();
+        let is_moved = remote_parent_id != remote_file.parent_id;
+        if is_moved && self.is_remote_within(remote_parent_id.as_deref(), remote_id).await? {
+
+            // The folder that the file was moved into has been moved
+            // into the file remotely. The remote layout takes
+            // precedence.
+            self.relocate_local(&remote_file).await?;
+        } else if local_file.name != remote_file.name || is_moved {
+            remote_file.id = self.move_remote(&local_file, remote_id, remote_parent_id.as_deref()).await?;
+        }
+
+        // Upload the content if it changed since the last sync.
+        if !local_file.is_folder {
+            let content = self.local.read_from_file(&local_file.id.to_string()).await?;
+            if self.local.get_snapshot(local_file.id).await?.as_ref() != Some(&content) {
+                if self.is_same_content(&local_file, &remote_file).await? != Some(true) {
+                    self.remote.write_to_file(&remote_file.id, &content).await?;
+                }
+                self.local.set_snapshot(local_file.id, &content).await?;
+            }
+        }
+
+        self.local.set_synced_at(local_file.id, synced_at).await?;
+        Ok(Some(Reconciled::new(id, &local_file.name, Resolution::Local)))
+    }
+
+    /// Applies a remote change (content, name or parent) to the local
+    /// copy of a file.
+    async fn download(&self, id: String, remote_file: File, synced_at: i64) -> Result<Option<Reconciled>> {
+        let Some(local_file) = self.relocate_local(&remote_file).await? else {
+            return Ok(None);
+        };
+
+        // Download the content if it differs from ours.
+        if !remote_file.is_folder && self.is_same_content(&local_file, &remote_file).await? != Some(true) {
+            let local_id = local_file.id.to_string();
+            let content = self.remote.read_from_file(&remote_file.id).await?;
+            if self.local.read_from_file(&local_id).await? != content {
+                self.local.write_to_file(&local_id, &content).await?;
+            }
+            self.local.set_snapshot(local_file.id, &content).await?;
+        }
+
+        self.local.set_synced_at(local_file.id, synced_at).await?;
+        Ok(Some(Reconciled::new(id, &remote_file.name, Resolution::Remote)))
+    }
+
+    /// Moves or renames the local copy of a remote file whose content
+    /// hasn't changed.
+    async fn move_local(&self, id: String, remote_file: File, synced_at: i64) -> Result<Option<Reconciled>> {
+        let Some(local_file) = self.relocate_local(&remote_file).await? else {
+            return Ok(None);
+        };
+
+        self.local.set_synced_at(local_file.id, synced_at).await?;
+        Ok(Some(Reconciled::new(id, &remote_file.name, Resolution::Remote)))
+    }
+
+    /// Moves the local copy of a remote file to the file's remote
+    /// name and parent, and returns the local copy.
+    ///
+    /// Returns `None` if the file has no local copy (e.g. it was
+    /// deleted locally, in which case the deletion is propagated by
+    /// its local change) or is outside of the remote root.
+    async fn relocate_local(&self, remote_file: &File) -> Result<Option<LocalFile>> {
+        if remote_file.is_deleted {
+            return Ok(None);
+        }
+
+        let local_file = self.local.get_remote_file(&remote_file.id).await?
+            .filter(|file| !file.is_deleted);
+
+        let Some(local_file) = local_file else {
+            return Ok(None);
+        };
+
+        let Some(local_parent_id) = self.local_parent(remote_file).await? else {
+            return Ok(None);
+        };
+
+        let parent_id = local_file.parent.map(|id| id.to_string());
+        if local_file.name != remote_file.name || parent_id != local_parent_id {
+            self.local.move_file(&local_file.id.to_string(), local_parent_id.as_deref(), Some(&remote_file.name)).await?;
+        }
+
+        Ok(Some(local_file))
+    }
+
+    /// Deletes the remote copy of a file that was deleted locally.
+    async fn delete_remote(&self, id: String, local_file: LocalFile, synced_at: i64) -> Result<Option<Reconciled>> {
+        // The remote file has already been removed along with its
+        // parent, whose deletion is applied first.
+        let is_removed = match local_file.parent {
+            Some(parent_id) => self.local.find_file(parent_id).await?
+                .is_some_and(|parent| parent.is_deleted),
+            None => false,
+        };
+
+        if let Some(remote_id) = local_file.remote_id.as_ref().filter(|_| !is_removed) {
+            // The remote file may have already been removed, e.g. by
+            // an earlier sync that failed before its tokens were
+            // advanced.
+            if let Err(err) = self.remote.remove_file(remote_id).await {
+                if self.is_remote_listed(&local_file, remote_id).await? {
+                    return Err(err);
+                }
+            }
+        }
+
+        self.local.set_synced_at(local_file.id, synced_at).await?;
+        Ok(Some(Reconciled::new(id, &local_file.name, Resolution::Local)))
+    }
+
+    /// Whether the remote file `remote_id` is listed in the remote
+    /// copy of the parent of `local_file`.
+    async fn is_remote_listed(&self, local_file: &LocalFile, remote_id: &str) -> Result<bool> {
+        let Some(parent_id) = self.remote_parent(local_file).await? else {
+            return Ok(false);
+        };
+
+        let mut pages = self.remote.list_files_paged(parent_id.as_deref());
+        while let Some(page) = pages.next().await? {
+            if page.into_iter().map(Into::<File>::into).any(|file| file.id == remote_id && !file.is_deleted) {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Deletes the local copy of a file that was deleted remotely.
+    async fn delete_local(&self, id: String, local_file: Option<LocalFile>, remote_file: File, synced_at: i64) -> Result<Option<Reconciled>> {
+        let local_file = match local_file {
+            Some(local_file) => Some(local_file),
+            None => self.local.get_remote_file(&remote_file.id).await?,
+        };
+
+        let Some(local_file) = local_file.filter(|file| !file.is_deleted) else {
+            return Ok(None);
+        };
+
+        self.local.remove_file(&local_file.id.to_string()).await?;
+        self.local.set_synced_at(local_file.id, synced_at).await?;
+        Ok(Some(Reconciled::new(id, &local_file.name, Resolution::Remote)))
+    }
+
+    /// Reconciles concurrent local and remote edits to the same file.
+    ///
+    /// Metadata (name and parent) is resolved by last-writer-wins,
+    /// and diverging content is reconciled by [reconcile_content].
+    async fn reconcile(&self, id: String, local_file: LocalFile, mut remote_file: File, synced_at: i64) -> Result<Option<Reconciled>> {
+        if local_file.is_deleted || remote_file.is_deleted {
+            return Ok(None);
+        }
+
+        let local_id = local_file.id.to_string();
+        let remote_parent_id = self.remote_parent(&local_file).await?;
+        let is_cyclic = match &remote_parent_id {
+            Some(parent_id) if *parent_id != remote_file.parent_id => {
+                self.is_remote_within(parent_id.as_deref(), &remote_file.id).await?
+            },
+            _ => false,
+        };
+
+        // Metadata is resolved by last-writer-wins. Ties, and local
+        // moves into a folder that has been moved into the file
+        // remotely, are resolved in favour of the remote.
+        let (name, mut resolution) = match local_file.modified_at > remote_file.modified_at && !is_cyclic {
+            true => {
+                if let Some(parent_id) = remote_parent_id {
+                    let is_moved = parent_id != remote_file.parent_id;
+                    if local_file.name != remote_file.name || is_moved {
+                        remote_file.id = self.move_remote(&local_file, &remote_file.id, parent_id.as_deref()).await?;
+                    }
+                }
+                (local_file.name.clone(), Resolution::Local)
+            },
+            false => {
+                if let Some(parent_id) = self.local_parent(&remote_file).await? {
+                    let is_moved = parent_id != local_file.parent.map(|id| id.to_string());
+                    if local_file.name != remote_file.name || is_moved {
+                        self.local.move_file(&local_id, parent_id.as_deref(), Some(&remote_file.name)).await?;
+                    }
+                }
+                (remote_file.name.clone(), Resolution::Remote)
+            },
+        };
+
+        if !local_file.is_folder && !remote_file.is_folder && self.is_same_content(&local_file, &remote_file).await? != Some(true) {
+            let local_content = self.local.read_from_file(&local_id).await?;
+            let remote_content = self.remote.read_from_file(&remote_file.id).await?;
+            if local_content != remote_content {
+                resolution = self.reconcile_content(&local_file, &remote_file, &name, local_content, remote_content).await?;
+            }
+        }
+
+        self.local.set_synced_at(local_file.id, synced_at).await?;
+        Ok(Some(Reconciled::new(id, &name, resolution)))
+    }
+
+    /// Moves a remote file to the remote folder `parent_id`, naming
+    /// it after its local counterpart, and returns its ID.
+    ///
+    /// Remote filesystems that identify files by their path (e.g.
+    /// WebDAV) assign the moved file a new ID, in which case the
+    /// local file and its descendants are linked to their new
+    /// remote IDs.
+    async fn move_remote(&self, local_file: &LocalFile, remote_id: &str, parent_id: Option<&str>) -> Result<String> {
+        let moved: File = self.remote.move_file(remote_id, parent_id, Some(&local_file.name)).await?.into();
+        if moved.id == remote_id {
+            return Ok(moved.id);
+        }
+
+        self.local.set_remote_id(local_file.id, &moved.id).await?;
+        let mut stack = vec![(local_file.id, moved.id.clone())];
+        while let Some((local_id, remote_id)) = stack.pop().filter(|_| local_file.is_folder) {
+            let remote_files: Vec<File> = self.remote.list_files(Some(&remote_id)).await?
+                .into_iter()
+                .map(Into::into)
+                .collect();
+
+            for local_child in self.local.list_files(Some(&local_id.to_string())).await? {
+                let remote_child = remote_files.iter().find(|file| {
+                    file.name == local_child.name && file.is_folder == local_child.is_folder
+                });
+
+                let Some(remote_child) = remote_child else {
+                    continue;
+                };
+
+                if local_child.remote_id.as_ref() != Some(&remote_child.id) {
+                    self.local.set_remote_id(local_child.id, &remote_child.id).await?;
+                }
+
+                if local_child.is_folder {
+                    stack.push((local_child.id, remote_child.id.clone()));
+                }
+            }
+        }
+
+        Ok(moved.id)
+    }
+
+    /// Reconciles diverging local and remote content.
+    ///
+    /// The two versions are merged against the content that was last
+    /// synced, with conflict markers around overlapping changes. If
+    /// there is no common base (or the content is not text), the local
+    /// content is saved to a conflict copy and the remote content is
+    /// kept.
+    async fn reconcile_content(
+        &self,
+        local_file: &LocalFile,
+        remote_file: &File,
+        name: &str,
+        local_content: Vec<u8>,
+        remote_content: Vec<u8>,
+    ) -> Result<Resolution> {
+        let local_id = local_file.id.to_string();
+        let merged = self.local.get_snapshot(local_file.id).await?
+            .and_then(|base| merge::merge_bytes(&base, &local_content, &remote_content));
+
+        if let Some(merged) = merged {
+            let content = merged.content.into_bytes();
+            if content != local_content {
+                self.local.write_to_file(&local_id, &content).await?;
+            }
+            if content != remote_content {
+                self.remote.write_to_file(&remote_file.id, &content).await?;
+            }
+
+            self.local.set_snapshot(local_file.id, &content).await?;
+            return Ok(match (content == local_content, content == remote_content) {
+                (_, true) => Resolution::Remote,
+                (true, false) => Resolution::Local,
+                (false, false) => Resolution::Merged {
+                    is_conflicted: merged.is_conflicted,
+                },
+            });
+        }
+
+        let parent_id = self.local.get_file(&local_id).await?.parent
+            .map(|id| id.to_string());
+
+        // Avoid clashing with earlier conflict copies.
+        let siblings: HashSet<String> = self.local.list_files(parent_id.as_deref()).await?
+            .into_iter()
+            .map(|file| file.name)
+            .collect();
+
+        let copy_name = (1..)
+            .map(|n| conflict_name(name, n))
+            .find(|copy_name| !siblings.contains(copy_name))
+            .unwrap();
+
+        let copy = self.local.create_file(parent_id.as_deref(), &copy_name).await?;
+        self.local.write_to_file(&copy.id.to_string(), &local_content).await?;
+        self.local.write_to_file(&local_id, &remote_content).await?;
+        self.local.set_snapshot(local_file.id, &remote_content).await?;
+        Ok(Resolution::Conflict {
+            copy_id: copy.id,
+            copy_name: copy.name,
+        })
+    }
+
+    /// Compares the content of a local file with the content hashes
+    /// reported by the remote, without downloading the remote file.
+    ///
+    /// Returns `None` if the remote file has no SHA1 or SHA256 hash.
+    async fn is_same_content(&self, local_file: &LocalFile, remote_file: &File) -> Result<Option<bool>> {
+        if let (Some(ours), Some(theirs)) = (&local_file.hash, &remote_file.sha256) {
+            return Ok(Some(ours.eq_ignore_ascii_case(theirs)));
+        }
+
+        if remote_file.sha256.is_none() && remote_file.sha1.is_none() {
+            return Ok(None);
+        }
+
+        // The local hash is missing or isn't comparable, so it is
+        // computed from the content.
+        let content = self.local.read_from_file(&local_file.id.to_string()).await?;
+        let is_same = match (&remote_file.sha256, &remote_file.sha1) {
+            (Some(theirs), _) => format!("{:x}", Sha256::digest(&content)).eq_ignore_ascii_case(theirs),
+            (None, Some(theirs)) => format!("{:x}", Sha1::digest(&content)).eq_ignore_ascii_case(theirs),
+            (None, None) => false,
+        };
+
+        Ok(Some(is_same))
+    }
+
+    /// Whether neither side of a change is excluded by the rules.
+    ///
+    /// Deleted remote files are only matched through their local
+    /// counterpart, since their parent may no longer be known.
+    async fn is_delta_included(&self, delta: &Unreconciled) -> Result<bool> {
+        if self.rules.is_empty() {
+            return Ok(true);
+        }
+
+        if let Some(local_file) = &delta.local {
+            if !self.is_local_included(local_file).await? {
+                return Ok(false);
+            }
+        }
+
+        match delta.remote.as_ref().filter(|file| !file.is_deleted) {
+            Some(remote_file) => self.is_remote_included(remote_file).await,
+            None => Ok(true),
+        }
+    }
+
+    /// Whether a local file is selected by the rules.
+    async fn is_local_included(&self, local_file: &LocalFile) -> Result<bool> {
+        let (names, remote_ids) = self.local_path(local_file).await?;
+        let size = match self.rules.has_size_rules() && !local_file.is_folder && !local_file.is_deleted {
+            true => Some(self.local.get_file_size(local_file.id).await?),
+            false => None,
+        };
+
+        let path = names.join("/");
+        let file = Candidate { path: &path, remote_ids: &remote_ids, is_folder: local_file.is_folder, size };
+        Ok(self.rules.is_included(&file, self.rule_ancestors().await))
+    }
+
+    /// Whether a remote file is selected by the rules.
+    ///
+    /// The file's ancestors are resolved through their local copies
+    /// where possible, and fetched from the remote otherwise.
+    async fn is_remote_included(&self, remote_file: &File) -> Result<bool> {
+        let mut names = vec![remote_file.name.clone()];
+        let mut remote_ids = vec![remote_file.id.clone()];
+        let mut parent_id = remote_file.parent_id.clone();
+        while let Some(id) = parent_id.take().filter(|id| self.remote_root.as_ref() != Some(id)) {
+            if let Some(parent) = self.local.get_remote_file(&id).await?.filter(|file| !file.is_deleted) {
+                let (parent_names, parent_ids) = self.local_path(&parent).await?;
+                names.extend(parent_names.into_iter().rev());
+                remote_ids.extend(parent_ids.into_iter().rev());
+                break;
+            }
+
+            let parent: File = self.remote.get_file(&id).await?.into();
+            names.push(parent.name);
+            remote_ids.push(parent.id);
+            parent_id = parent.parent_id;
+        }
+
+        names.reverse();
+        remote_ids.reverse();
+        let path = names.join("/");
+        let file = Candidate { path: &path, remote_ids: &remote_ids, is_folder: remote_file.is_folder, size: remote_file.size };
+        Ok(self.rules.is_included(&file, self.rule_ancestors().await))
+    }
+
+    /// Resolves the names and remote IDs of a local file and its
+    /// ancestors, from the root down.
+    async fn local_path(&self, local_file: &LocalFile) -> Result<(Vec<String>, Vec<String>)> {
+        let mut names = vec![local_file.name.clone()];
+        let mut remote_ids: Vec<String> = local_file.remote_id.iter().cloned().collect();
+        let mut parent = local_file.parent;
+        while let Some(parent_id) = parent {
+            let Some(file) = self.local.find_file(parent_id).await? else {
+                break;
+            };
+
+            names.push(file.name);
+            remote_ids.extend(file.remote_id);
+            parent = file.parent;
+        }
+
+        names.reverse();
+        remote_ids.reverse();
+        Ok((names, remote_ids))
+    }
+
+    /// Fetches the remote IDs of the ancestors of the folders that
+    /// are included by folder rules, so that the folders leading to
+    /// them are synced. Folders that cannot be fetched are skipped.
+    async fn rule_ancestors(&self) -> &HashSet<String> {
+        self.rule_ancestors.get_or_init(|| async {
+            let mut ancestors = HashSet::new();
+            for id in self.rules.included_folders() {
+                let mut parent_id = match self.remote.get_file(id).await {
+                    Ok(folder) => Into::<File>::into(folder).parent_id,
+                    Err(_) => None,
+                };
+
+                while let Some(id) = parent_id.take() {
+                    if self.remote_root.as_ref() == Some(&id) || !ancestors.insert(id.clone()) {
+                        break;
+                    }
+
+                    if let Ok(parent) = self.remote.get_file(&id).await {
+                        parent_id = Into::<File>::into(parent).parent_id;
+                    }
+                }
+            }
+
+            ancestors
+        }).await
+    }
+
+    /// Whether the remote folder `id` is `ancestor_id` or one of its
+    /// descendants, in which case `ancestor_id` can't be moved into
+    /// it.
+    async fn is_remote_within(&self, id: Option<&str>, ancestor_id: &str) -> Result<bool> {
+        let mut id = id.map(|id| id.to_string());
+        while let Some(current) = id.take().filter(|id| self.remote_root.as_ref() != Some(id)) {
+            if current == ancestor_id {
+                return Ok(true);
+            }
+
+            let parent: File = self.remote.get_file(&current).await?.into();
+            id = parent.parent_id;
+        }
+
+        Ok(false)
+    }
+
+    /// Resolves the remote parent ID of a local file.
+    ///
+    /// Returns `None` if the parent has not been synced yet.
+    async fn remote_parent(&self, local_file: &LocalFile) -> Result<Option<Option<String>>> {
+        Ok(match local_file.parent {
+            Some(parent_id) => self.local.get_file(&parent_id.to_string()).await?
+                .remote_id.map(Some),
+            None => Some(self.remote_root.clone()),
+        })
+    }
+
+    /// Resolves the local parent ID of a remote file.
+    ///
+    /// Returns `None` if the file is outside of the remote root.
+    async fn local_parent(&self, remote_file: &File) -> Result<Option<Option<String>>> {
+        Ok(match &remote_file.parent_id {
+            Some(parent_id) if self.remote_root.as_ref() == Some(parent_id) => Some(None),
+            Some(parent_id) => match self.local.get_remote_file(parent_id).await? {
+                Some(parent) if !parent.is_deleted => Some(Some(parent.id.to_string())),
+                _ => None,
+            },
+            None if self.remote_root.is_none() => Some(None),
+            None => None,
+        })
+    }
+}
+
+/// Returns the name of the n-th conflict copy of a file, e.g.
+/// "note (conflict 2026-10-17).md" or "note (conflict 2026-10-17
+/// 2).md".
+fn conflict_name(name: &str, n: usize) -> String {
+    let date = Local::now().format("%Y-%m-%d");
+    let suffix = match n {
+        1 => format!("conflict {date}"),
+        _ => format!("conflict {date} {n}"),
+    };
+
+    match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => format!("{stem} ({suffix}).{ext}"),
+        _ => format!("{name} ({suffix})"),
+    }
+}
+
+/// An operation that [Sync::sync_full] applies to a file that has no
+/// counterpart in the folder being visited.
+enum Operation {
+
+    /// Delete a local file whose remote copy has been deleted.
+    RemoveLocal(LocalFile),
+
+    /// Delete a remote file whose local copy has been deleted.
+    RemoveRemote(File),
+
+    /// Create a remote copy of a local file.
+    CreateRemote(LocalFile),
+
+    /// Create a local copy of a remote file.
+    CreateLocal(File),
+}
+
+impl Operation {
+
+    /// The [SyncAction] that the operation would apply, as planned by
+    /// [plan_full](Sync::plan_full).
+    fn action(self) -> SyncAction {
+        let id = self.reconciled(Resolution::Local).id;
+        let (kind, local, remote) = match self {
+            Self::RemoveLocal(local_file) => (ActionKind::DeleteLocal, Some(local_file), None),
+            Self::RemoveRemote(remote_file) => (ActionKind::DeleteRemote, None, Some(remote_file)),
+            Self::CreateRemote(local_file) => (ActionKind::CreateRemote, Some(local_file), None),
+            Self::CreateLocal(remote_file) => (ActionKind::CreateLocal, None, Some(remote_file)),
+        };
+
+        SyncAction { id, kind, local, remote }
+    }
+
+    /// Reports the file that the operation applies to as reconciled
+    /// by `resolution`. Local files without a remote copy are
+    /// identified as `local:<id>`.
+    fn reconciled(&self, resolution: Resolution) -> Reconciled {
+        match self {
+            Self::RemoveLocal(local_file) | Self::CreateRemote(local_file) => {
+                let id = local_file.remote_id.clone()
+                    .unwrap_or_else(|| format!("local:{}", local_file.id));
+                Reconciled::new(id, &local_file.name, resolution)
+            },
+            Self::RemoveRemote(remote_file) | Self::CreateLocal(remote_file) => {
+                Reconciled::new(remote_file.id.clone(), &remote_file.name, resolution)
+            },
+        }
+    }
+}
+
+/// Defines an unreconciled change in the local or remote filesystems.
+#[derive(Clone, Debug)]
+pub struct Unreconciled {
+
+    /// Remote ID of the changed file, or `local:<id>` for local files
+    /// that have no remote copy.
+    pub id: String,
+
+    pub local: Option<LocalFile>,
+    pub remote: Option<File>,
+
+    /// The local copy of a remote file that has no local change, if
+    /// any.
+    pub counterpart: Option<LocalFile>,
+}
+
+/// Describes how a change was reconciled.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data")]
+pub enum Resolution {
+    /// The local version was kept.
+    Local,
+
+    /// The remote version was kept.
+    Remote,
+
+    /// Both versions changed the file's content. The remote content
+    /// was kept and the local content was saved to a conflict copy.
+    #[serde(rename_all = "camelCase")]
+    Conflict { copy_id: i64, copy_name: String },
+
+    /// Both versions changed the file's content and were merged. If
+    /// the changes overlapped, the merged content contains conflict
+    /// markers.
+    #[serde(rename_all = "camelCase")]
+    Merged { is_conflicted: bool },
+
+    /// The change could not be applied, and will be retried by the
+    /// next sync.
+    Failed { error: String },
+
+    /// The change was not applied because a change to one of the
+    /// file's parents failed, and will be retried by the next sync.
+    Skipped,
+}
+
+/// Reports how a change was reconciled.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Reconciled {
+    pub id: String,
+    pub name: String,
+    pub resolution: Resolution,
+}
+
+impl Reconciled {
+    fn new(id: String, name: &str, resolution: Resolution) -> Self {
+        Self { id, name: name.to_string(), resolution }
+    }
+}
 
 #[cfg(test)]
 mod tests {
+    use super::{ActionKind, Resolution, Sync, SyncAction};
+    use crate::sync::{Matcher, SyncRules};
     use crate::local::Client as LClient;
-    use crate::cloud::onedrive::Client as RClient;
-
-    use crate::oauth2;
-    use crate::core::FileSystem as _;
+    use crate::cloud::fake::FakeOneDrive;
+    use crate::core::{Delta, Error, File, FileSystem};
+    use crate::encrypted::EncryptedFs;
+    use crate::memory::{Faults, MemoryFs};
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
     use std::sync::Arc;
-    use tokio::sync::OnceCell;
-    use dotenv::dotenv;
-    use std::env;
-
-    static CLIENT: OnceCell<Arc<LClient>> = OnceCell::const_new();
-    static TOKEN: OnceCell<Arc<oauth2::Token>> = OnceCell::const_new();
-
-    async fn get_remote_client() -> RClient {
-        dotenv().ok();
-        let client_id = env::var("ONEDRIVE_CLIENT_ID")
-            .expect("missing ONEDRIVE_CLIENT_ID env variable");
-
-        let redirect_uri = env::var("ONEDRIVE_REDIRECT_URI")
-            .expect("missing ONEDRIVE_REDIRECT_URI env variable");
-
-        let refresh_token = env::var("ONEDRIVE_REFRESH_TOKEN")
-            .expect("missing ONEDRIVE_REFRESH_TOKEN env variable");
-
-        let app_config = oauth2::Config::onedrive(&client_id, &redirect_uri);
-        let token = TOKEN.get_or_init(|| async {
-            let token = oauth2::Token::from_refresh_token(&refresh_token, &app_config).await.unwrap();
-            Arc::new(token)
-        }).await.clone();
-
-        RClient::new(&token, &app_config)
-    }
-
-    async fn get_local_client() -> Arc<LClient> {
-        CLIENT.get_or_init(|| async {
-            let db_name = "./hs-localfs-test-db.sqlite";
-
-            // Add files for testing.
-            const TESTING_SCHEMA: &str = r#"
-INSERT INTO File VALUES
-  (0, "test.txt", NULL, NULL, FALSE, 0, 0, NULL, FALSE, FALSE),
-  (1, "test-dltd", NULL, NULL, TRUE, 0, 0, NULL, TRUE, FALSE),
-  (2, "my_folder", NULL, NULL, FALSE, 0, 0, NULL, TRUE, FALSE),
-  (3, "move_me.txt", NULL, NULL, FALSE, 0, 0, NULL, FALSE, FALSE),
-  (4, "delete_me.txt", NULL, NULL, FALSE, 0, 0, NULL, FALSE, FALSE),
-  (5, "delete_me", NULL, NULL, FALSE, 0, 0, NULL, TRUE, FALSE),
-  (6, "deleted_child.txt", 5, NULL, FALSE, 0, 0, NULL, FALSE, FALSE),
-  (7, "test-list", NULL, NULL, FALSE, 0, 0, NULL, TRUE, FALSE),
-  (8, "list-child.txt", 7, NULL, FALSE, 0, 0, NULL, FALSE, FALSE),
-  (9, "test-list-deleted", NULL, NULL, FALSE, 0, 0, NULL, TRUE, FALSE),
-  (10, "list-child.txt", 9, NULL, FALSE, 0, 0, NULL, FALSE, FALSE),
-  (11, "bookmarked.txt", NULL, NULL, FALSE, 0, 0, NULL, FALSE, TRUE);
-"#;
-
-            let db = database::Database::new(&database::Config {
-                max_connections: 1,
-                local_path: db_name.to_string(),
-                migrations: vec![
-                    database::Migration {
-                        version: 0,
-                        sql: crate::local::SCHEMA_VERSION_0.to_string(),
-                        kind: database::MigrationType::Up,
-                    },
-                    database::Migration {
-                        version: 1,
-                        sql: TESTING_SCHEMA.to_string(),
-                        kind: database::MigrationType::Up,
-                    }
-                ]
-            }).await.unwrap();
-            Arc::new(LClient::new(Arc::new(db)))
-        }).await.clone()
-    }
 
     #[tokio::test]
     async fn test_list_children() {
-        let rclient = get_remote_client().await;
-        rclient.list_files(None).await.unwrap().iter().for_each(|file| println!("{file:?}"));
+        let fake = FakeOneDrive::new().await;
+        let rclient = fake.client();
+        let folder = rclient.create_folder(None, "helsync-list-children").await.unwrap();
+        let files = rclient.list_files(None).await.unwrap();
+        assert!(files.iter().any(|file| file.id == folder.id));
+    }
+
+    /// Creates a remote file with `content`, or a folder if
+    /// `content` is `None`.
+    async fn insert(remote: &MemoryFs, parent_id: Option<&str>, name: &str, content: Option<&[u8]>) -> File {
+        match content {
+            Some(content) => {
+                let file = remote.create_file(parent_id, name).await.unwrap();
+                remote.write_to_file(&file.id, content).await.unwrap()
+            },
+            None => remote.create_folder(parent_id, name).await.unwrap(),
+        }
+    }
+
+    async fn find_remote(remote: &MemoryFs, parent_id: Option<&str>, name: &str) -> Option<File> {
+        remote.list_files(parent_id).await.unwrap()
+            .into_iter()
+            .find(|file| file.name == name)
+    }
+
+    fn now() -> i64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64
+    }
+
+    async fn get_sync_client(name: &str) -> Arc<LClient> {
+        let db_name = format!("./hs-sync-test-{name}.sqlite");
+        let _ = std::fs::remove_file(&db_name);
+        let _ = std::fs::remove_file(format!("{db_name}-shm"));
+        let _ = std::fs::remove_file(format!("{db_name}-wal"));
+
+        let db = database::Database::new(&database::Config {
+            max_connections: 1,
+            local_path: db_name,
+            migrations: crate::local::migrations(),
+        }).await.unwrap();
+
+        Arc::new(LClient::new(Arc::new(db)))
+    }
+
+    async fn find_local(local: &LClient, parent_id: Option<i64>, name: &str) -> Option<crate::local::LocalFile> {
+        let parent_id = parent_id.map(|id| id.to_string());
+        local.list_files(parent_id.as_deref()).await.unwrap()
+            .into_iter()
+            .find(|file| file.name == name)
+    }
+
+    #[tokio::test]
+    async fn test_sync_full() {
+        let local = get_sync_client("full").await;
+        let remote = Arc::new(MemoryFs::new().with_page_size(2));
+        let notes = insert(&remote, None, "notes", None).await;
+        insert(&remote, Some(&notes.id), "todo.md", Some(b"- [ ] milk")).await;
+
+        let mut sync = Sync::new(local.clone(), remote.clone());
+        sync.sync_full().await.unwrap();
+
+        // Remote files are created locally, along with their content.
+        let local_notes = find_local(&local, None, "notes").await.unwrap();
+        assert!(local_notes.is_folder);
+        assert_eq!(local_notes.remote_id, Some(notes.id.clone()));
+
+        let local_todo = find_local(&local, Some(local_notes.id), "todo.md").await.unwrap();
+        let content = local.read_from_file(&local_todo.id.to_string()).await.unwrap();
+        assert_eq!(content, b"- [ ] milk");
+
+        // Local files are created remotely, along with their content.
+        let local_untitled = find_local(&local, None, "Untitled").await.unwrap();
+        let remote_untitled = find_remote(&remote, None, "Untitled").await.unwrap();
+        assert_eq!(local_untitled.remote_id, Some(remote_untitled.id.clone()));
+        assert_eq!(
+            remote.read_from_file(&remote_untitled.id).await.unwrap(),
+            local.read_from_file(&local_untitled.id.to_string()).await.unwrap()
+        );
+
+        // All files are marked as synced.
+        for file in [local_notes, local_todo, local_untitled] {
+            assert!(local.get_file(&file.id.to_string()).await.unwrap().synced_at.is_some());
+        }
+
+        // Subsequent syncs do not duplicate files.
+        let count = remote.len();
+        sync.sync_full().await.unwrap();
+        assert_eq!(remote.len(), count);
+        assert_eq!(local.list_files(None).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_sync_full_match_by_name() {
+        let local = get_sync_client("full-match").await;
+        let remote = Arc::new(MemoryFs::new().with_page_size(2));
+
+        // Files with the same content are matched.
+        let same = local.create_file(None, "same.md").await.unwrap();
+        local.write_to_file(&same.id.to_string(), b"same").await.unwrap();
+        let remote_same = insert(&remote, None, "same.md", Some(b"same")).await;
+
+        // Files with different content have never been synced, so the
+        // remote content is kept and the local content is saved to a
+        // conflict copy, regardless of which is newer.
+        let ours = local.create_file(None, "ours.md").await.unwrap();
+        local.write_to_file(&ours.id.to_string(), b"ours").await.unwrap();
+        let remote_ours = insert(&remote, None, "ours.md", Some(b"theirs")).await;
+        remote.set_modified_at(&remote_ours.id, 0).unwrap();
+
+        let mut sync = Sync::new(local.clone(), remote.clone());
+        let report = sync.sync_full().await.unwrap();
+
+        let same = local.get_file(&same.id.to_string()).await.unwrap();
+        assert_eq!(same.remote_id, Some(remote_same.id.clone()));
+        assert!(!report.iter().any(|reconciled| reconciled.id == remote_same.id));
+
+        let ours = local.get_file(&ours.id.to_string()).await.unwrap();
+        assert_eq!(ours.remote_id, Some(remote_ours.id.clone()));
+        assert_eq!(local.read_from_file(&ours.id.to_string()).await.unwrap(), b"theirs");
+        assert_eq!(remote.read_from_file(&remote_ours.id).await.unwrap(), b"theirs");
+
+        let reconciled = report.iter().find(|reconciled| reconciled.id == remote_ours.id).unwrap();
+        let Resolution::Conflict { copy_id, .. } = &reconciled.resolution else {
+            panic!("expected a conflict, got {:?}", reconciled.resolution);
+        };
+        assert_eq!(local.read_from_file(&copy_id.to_string()).await.unwrap(), b"ours");
+
+        // Untitled, same.md and ours.md. The conflict copy is
+        // uploaded by the next sync.
+        assert_eq!(remote.len(), 3);
+        sync.sync_changes().await.unwrap();
+        assert_eq!(remote.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_sync_full_reconcile() {
+        let local = get_sync_client("full-reconcile").await;
+        let remote = Arc::new(MemoryFs::new().with_page_size(2));
+        let base = "# Groceries\n\n- milk\n- eggs\n\n## Notes\n\nBuy on Friday.\n";
+        let note = insert(&remote, None, "note.md", Some(base.as_bytes())).await;
+        let todo = insert(&remote, None, "todo.md", Some(b"- [ ] milk")).await;
+
+        let mut sync = Sync::new(local.clone(), remote.clone());
+        sync.sync_full().await.unwrap();
+        let local_note = find_local(&local, None, "note.md").await.unwrap().id.to_string();
+        let local_todo = find_local(&local, None, "todo.md").await.unwrap().id.to_string();
+
+        // Edits on both sides are merged against the last synced
+        // content, and edits on one side are kept, even if the other
+        // side is newer.
+        let ours = "# Groceries\n\n- milk\n- eggs\n- bread\n\n## Notes\n\nBuy on Friday.\n";
+        let theirs = "# Groceries\n\n- milk\n- eggs\n\n## Notes\n\nBuy on Saturday.\n";
+        local.write_to_file(&local_note, ours.as_bytes()).await.unwrap();
+        remote.write_to_file(&note.id, theirs.as_bytes()).await.unwrap();
+        local.write_to_file(&local_todo, b"- [x] milk").await.unwrap();
+        remote.set_modified_at(&todo.id, now() + 60).unwrap();
+
+        let report = sync.sync_full().await.unwrap();
+        let merged = "# Groceries\n\n- milk\n- eggs\n- bread\n\n## Notes\n\nBuy on Saturday.\n";
+        assert_eq!(local.read_from_file(&local_note).await.unwrap(), merged.as_bytes());
+        assert_eq!(remote.read_from_file(&note.id).await.unwrap(), merged.as_bytes());
+        assert_eq!(remote.read_from_file(&todo.id).await.unwrap(), b"- [x] milk");
+
+        let resolution = |id: &str| report.iter()
+            .find(|reconciled| reconciled.id == id)
+            .map(|reconciled| reconciled.resolution.clone());
+        assert_eq!(resolution(&note.id), Some(Resolution::Merged { is_conflicted: false }));
+        assert_eq!(resolution(&todo.id), Some(Resolution::Local));
+    }
+
+    #[tokio::test]
+    async fn test_sync_full_deletions() {
+        let local = get_sync_client("full-deletions").await;
+        let remote = Arc::new(MemoryFs::new().with_page_size(2));
+        let local_file = local.create_file(None, "local.md").await.unwrap();
+        let remote_file = insert(&remote, None, "remote.md", Some(b"")).await;
+
+        let mut sync = Sync::new(local.clone(), remote.clone());
+        sync.sync_full().await.unwrap();
+
+        // Local deletions are propagated.
+        local.remove_file(&local_file.id.to_string()).await.unwrap();
+        sync.sync_full().await.unwrap();
+        assert!(find_remote(&remote, None, "local.md").await.is_none());
+
+        // Remote deletions are propagated to unchanged local files.
+        remote.remove_file(&remote_file.id).await.unwrap();
+        sync.sync_full().await.unwrap();
+        assert!(find_local(&local, None, "remote.md").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_sync_full_renames() {
+        let local = get_sync_client("full-renames").await;
+        let remote = Arc::new(MemoryFs::new().with_page_size(2));
+        let local_file = local.create_file(None, "local.md").await.unwrap();
+        let remote_file = insert(&remote, None, "remote.md", Some(b"")).await;
+
+        let mut sync = Sync::new(local.clone(), remote.clone());
+        sync.sync_full().await.unwrap();
+
+        // Remote renames are applied locally.
+        remote.move_file(&remote_file.id, None, Some("theirs.md")).await.unwrap();
+        remote.set_modified_at(&remote_file.id, now() + 60).unwrap();
+        sync.sync_full().await.unwrap();
+        assert!(find_local(&local, None, "remote.md").await.is_none());
+        assert!(find_local(&local, None, "theirs.md").await.is_some());
+
+        // Newer local renames are applied remotely.
+        local.move_file(&local_file.id.to_string(), None, Some("ours.md")).await.unwrap();
+        let remote_id = local.get_file(&local_file.id.to_string()).await.unwrap().remote_id.unwrap();
+        remote.set_modified_at(&remote_id, 0).unwrap();
+        sync.sync_full().await.unwrap();
+        assert!(find_remote(&remote, None, "local.md").await.is_none());
+        assert!(find_remote(&remote, None, "ours.md").await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_plan_full() {
+        let local = get_sync_client("plan-full").await;
+        let remote = Arc::new(MemoryFs::new().with_page_size(2));
+        let notes = insert(&remote, None, "notes", None).await;
+        let todo = insert(&remote, Some(&notes.id), "todo.md", Some(b"- [ ] milk")).await;
+        let draft = local.create_file(None, "draft.md").await.unwrap();
+        local.write_to_file(&draft.id.to_string(), b"draft").await.unwrap();
+
+        // Every file is planned to be created on the other side,
+        // including the contents of new folders, without applying
+        // anything.
+        let mut sync = Sync::new(local.clone(), remote.clone());
+        let kinds = |actions: Vec<SyncAction>| {
+            let mut kinds: Vec<(String, ActionKind)> = actions.into_iter()
+                .map(|action| (action.name().to_string(), action.kind))
+                .collect();
+            kinds.sort_by(|a, b| a.0.cmp(&b.0));
+            kinds
+        };
+
+        let actions = sync.plan_full().await.unwrap();
+        assert_eq!(kinds(actions), vec![
+            ("Untitled".to_string(), ActionKind::CreateRemote),
+            ("draft.md".to_string(), ActionKind::CreateRemote),
+            ("notes".to_string(), ActionKind::CreateLocal),
+            ("todo.md".to_string(), ActionKind::CreateLocal),
+        ]);
+        assert_eq!(remote.len(), 2);
+        assert!(find_local(&local, None, "notes").await.is_none());
+        assert!(!sync.has_synced());
+
+        sync.sync_full().await.unwrap();
+        assert!(sync.plan_full().await.unwrap().is_empty());
+
+        // Content changes are planned against the last synced
+        // content, and remote renames are planned as moves.
+        local.write_to_file(&draft.id.to_string(), b"draft 2").await.unwrap();
+        remote.write_to_file(&todo.id, b"- [x] milk").await.unwrap();
+        remote.move_file(&notes.id, None, Some("notes 2")).await.unwrap();
+        remote.set_modified_at(&notes.id, now() + 60).unwrap();
+
+        let actions = sync.plan_full().await.unwrap();
+        assert_eq!(kinds(actions), vec![
+            ("draft.md".to_string(), ActionKind::Upload),
+            ("notes 2".to_string(), ActionKind::Move),
+            ("todo.md".to_string(), ActionKind::Download),
+        ]);
+        assert_eq!(remote.read_from_file(&todo.id).await.unwrap(), b"- [x] milk");
+        assert!(find_local(&local, None, "notes").await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_sync_changes() {
+        let local = get_sync_client("changes").await;
+        let remote = Arc::new(MemoryFs::new().with_page_size(2));
+        let notes = insert(&remote, None, "notes", None).await;
+
+        let mut sync = Sync::new(local.clone(), remote.clone());
+        assert!(!sync.has_synced());
+        sync.sync_full().await.unwrap();
+        assert!(sync.has_synced());
+
+        // Remote changes.
+        let remote_todo = insert(&remote, Some(&notes.id), "todo.md", Some(b"")).await;
+
+        // Local changes.
+        let untitled = find_local(&local, None, "Untitled").await.unwrap();
+        local.move_file(&untitled.id.to_string(), None, Some("intro.md")).await.unwrap();
+        let folder = local.create_folder(None, "journal").await.unwrap();
+        local.create_file(Some(&folder.id.to_string()), "today.md").await.unwrap();
+
+        sync.sync_changes().await.unwrap();
+
+        let local_notes = find_local(&local, None, "notes").await.unwrap();
+        let local_todo = find_local(&local, Some(local_notes.id), "todo.md").await.unwrap();
+        assert_eq!(local_todo.remote_id, Some(remote_todo.id.clone()));
+
+        assert!(find_remote(&remote, None, "Untitled").await.is_none());
+        assert!(find_remote(&remote, None, "intro.md").await.is_some());
+        let remote_journal = find_remote(&remote, None, "journal").await.unwrap();
+        assert!(remote_journal.is_folder);
+        assert!(find_remote(&remote, Some(&remote_journal.id), "today.md").await.is_some());
+
+        // Remote deletions are propagated, and progress is reported
+        // for each change.
+        remote.remove_file(&notes.id).await.unwrap();
+        let mut progress = Vec::new();
+        let report = sync.sync_changes_with(|i, total, _| progress.push((i, total))).await.unwrap();
+        assert!(find_local(&local, None, "notes").await.is_none());
+        assert_eq!(progress.last(), Some(&(progress.len(), progress.len())));
+        assert!(report.len() <= progress.len());
+
+        // Syncing without changes is a no-op.
+        let count = remote.len();
+        sync.sync_changes().await.unwrap();
+        assert_eq!(remote.len(), count);
+        assert_eq!(local.list_files(None).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_sync_changes_ids() {
+        let local = get_sync_client("ids").await;
+        let remote = get_sync_client("ids-remote").await;
+        let mut sync = Sync::new(local.clone(), remote.clone());
+        sync.sync_full().await.unwrap();
+
+        // A new remote file and an unrelated new local file share the
+        // same ID.
+        let mut remote_file = remote.create_file(None, "remote-0.md").await.unwrap();
+        for i in 1..10 {
+            remote_file = remote.create_file(None, &format!("remote-{i}.md")).await.unwrap();
+        }
+
+        let mut i = 0;
+        let local_file = loop {
+            let file = local.create_file(None, &format!("local-{i}.md")).await.unwrap();
+            if file.id >= remote_file.id {
+                break file;
+            }
+            i += 1;
+        };
+
+        assert_eq!(local_file.id, remote_file.id);
+        sync.sync_changes().await.unwrap();
+        assert!(find_local(&local, None, &remote_file.name).await.is_some());
+        assert!(find_local(&remote, None, &local_file.name).await.is_some());
+        assert_eq!(local_tree(&local).await, local_tree(&remote).await);
+    }
+
+    #[tokio::test]
+    async fn test_sync_changes_orphans() {
+        let local = get_sync_client("orphans").await;
+        let remote = Arc::new(MemoryFs::new());
+        let mut sync = Sync::new(local.clone(), remote.clone());
+        sync.sync_full().await.unwrap();
+
+        // An upload that failed after the remote file was created, and
+        // a download that failed after the local file was created.
+        let ours = local.create_file(None, "ours.md").await.unwrap();
+        local.write_to_file(&ours.id.to_string(), b"ours").await.unwrap();
+        let remote_ours = insert(&remote, None, "ours.md", Some(b"")).await;
+        let theirs = insert(&remote, None, "theirs.md", Some(b"theirs")).await;
+        let local_theirs = local.create_file(None, "theirs.md").await.unwrap();
+
+        // The files are linked instead of colliding with their copies.
+        let report = sync.sync_changes().await.unwrap();
+        assert!(report.iter().all(|reconciled| !matches!(reconciled.resolution, Resolution::Failed { .. })));
+        assert_eq!(local.get_file(&ours.id.to_string()).await.unwrap().remote_id, Some(remote_ours.id.clone()));
+        assert_eq!(local.get_file(&local_theirs.id.to_string()).await.unwrap().remote_id, Some(theirs.id.clone()));
+        assert_eq!(remote.read_from_file(&remote_ours.id).await.unwrap(), b"ours");
+        assert_eq!(local.read_from_file(&local_theirs.id.to_string()).await.unwrap(), b"theirs");
+
+        sync.sync_changes().await.unwrap();
+        assert_eq!(local_tree(&local).await, remote_tree(remote.as_ref()).await);
+    }
+
+    #[tokio::test]
+    async fn test_sync_root_id() {
+        let local = get_sync_client("root-id").await;
+        let fake = FakeOneDrive::new().await;
+        let remote = Arc::new(fake.client());
+
+        // The root's ID is resolved even though both trees are empty.
+        let mut sync = Sync::new(local.clone(), remote.clone());
+        sync.sync_full().await.unwrap();
+        assert_eq!(sync.remote_root(), Some("root"));
+
+        // Remote files at the root are reported with the root's ID as
+        // their parent.
+        let note = remote.create_file(None, "note.md").await.unwrap();
+        remote.write_to_file(&note.id, b"remote").await.unwrap();
+        let folder = local.create_folder(None, "journal").await.unwrap();
+        sync.sync_changes().await.unwrap();
+        let local_note = find_local(&local, None, "note.md").await.unwrap();
+        assert_eq!(local.read_from_file(&local_note.id.to_string()).await.unwrap(), b"remote");
+
+        // A local file moved back to the root is moved remotely.
+        let local_id = local_note.id.to_string();
+        local.move_file(&local_id, Some(&folder.id.to_string()), None).await.unwrap();
+        sync.sync_changes().await.unwrap();
+        let remote_folder = local.get_file(&folder.id.to_string()).await.unwrap().remote_id.unwrap();
+        assert_eq!(remote.get_file(&note.id).await.unwrap().parent_reference.unwrap().id, Some(remote_folder));
+
+        local.move_file(&local_id, None, None).await.unwrap();
+        sync.sync_changes().await.unwrap();
+        let parent = remote.get_file(&note.id).await.unwrap().parent_reference.unwrap();
+        assert_eq!(parent.id.as_deref(), Some("root"));
+    }
+
+    #[tokio::test]
+    async fn test_sync_changes_concurrent() {
+        let local = get_sync_client("changes-concurrent").await;
+        let remote = Arc::new(MemoryFs::new().with_page_size(2));
+
+        let mut sync = Sync::new(local.clone(), remote.clone());
+        sync.set_concurrency(8);
+        sync.sync_full().await.unwrap();
+
+        // Nested folders are created before their children.
+        let mut parent_id = None;
+        for depth in 0..4 {
+            let folder = local.create_folder(parent_id.as_deref(), &format!("folder-{depth}")).await.unwrap();
+            for i in 0..3 {
+                local.create_file(Some(&folder.id.to_string()), &format!("note-{i}.md")).await.unwrap();
+            }
+            parent_id = Some(folder.id.to_string());
+        }
+
+        let report = sync.sync_changes().await.unwrap();
+        let pushed = report.iter()
+            .filter(|reconciled| reconciled.resolution == Resolution::Local)
+            .count();
+
+        assert_eq!(pushed, 16);
+
+        let mut remote_parent = None;
+        for depth in 0..4 {
+            let folder = find_remote(&remote, remote_parent.as_deref(), &format!("folder-{depth}")).await.unwrap();
+            for i in 0..3 {
+                assert!(find_remote(&remote, Some(&folder.id), &format!("note-{i}.md")).await.is_some());
+            }
+            remote_parent = Some(folder.id);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sync_changes_failures() {
+        let local = get_sync_client("changes-failures").await;
+        let remote = Arc::new(MemoryFs::new().with_page_size(2));
+
+        let mut sync = Sync::new(local.clone(), remote.clone());
+        sync.sync_full().await.unwrap();
+        let (local_token, remote_token) = (sync.local_token.clone(), sync.remote_token.clone());
+
+        // A remote file that isn't listed by the next sync collides
+        // with a new local folder.
+        insert(&remote, None, "journal", Some(b"")).await;
+        sync.remote_token = Some(remote.list_deltas(None).await.unwrap().1);
+
+        let folder = local.create_folder(None, "journal").await.unwrap();
+        local.create_file(Some(&folder.id.to_string()), "today.md").await.unwrap();
+        local.create_file(None, "todo.md").await.unwrap();
+
+        // The failure is reported, the folder's children are skipped,
+        // and other changes are still applied.
+        let report = sync.sync_changes().await.unwrap();
+        assert_eq!(report.len(), 3);
+        let failed = report.iter().find(|reconciled| reconciled.name == "journal").unwrap();
+        assert!(matches!(failed.resolution, Resolution::Failed { .. }));
+        assert!(find_remote(&remote, None, "todo.md").await.is_some());
+        let skipped = report.iter().find(|reconciled| reconciled.name == "today.md").unwrap();
+        assert_eq!(skipped.resolution, Resolution::Skipped);
+
+        // The delta tokens aren't advanced, so the failed changes are
+        // retried by the next sync.
+        assert_eq!(sync.local_token, local_token);
+        assert_ne!(sync.remote_token, remote_token);
+        let deltas = sync.list_deltas(false).await.unwrap();
+        assert!(deltas.iter().any(|delta| delta.local.as_ref().is_some_and(|file| file.name == "today.md")));
+    }
+
+    #[tokio::test]
+    async fn test_sync_state() {
+        let local = get_sync_client("state").await;
+        let remote = Arc::new(MemoryFs::new().with_page_size(2));
+
+        let root = insert(&remote, None, "root", None).await;
+
+        let mut sync = Sync::new(local.clone(), remote.clone());
+        sync.set_remote_root(Some(&root.id));
+        sync.sync_full().await.unwrap();
+
+        // Tokens and the remote root are restored from the database.
+        let mut restored = Sync::load(local.clone(), remote.clone()).await.unwrap();
+        assert_eq!(restored.local_token, sync.local_token);
+        assert_eq!(restored.remote_token, sync.remote_token);
+        assert_eq!(restored.remote_root, Some(root.id));
+
+        // Deltas are listed from the same point.
+        assert_eq!(
+            restored.list_deltas(false).await.unwrap().len(),
+            sync.list_deltas(false).await.unwrap().len(),
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sync_conflict() {
+        let local = get_sync_client("conflict").await;
+        let remote = Arc::new(MemoryFs::new().with_page_size(2));
+        let remote_note = insert(&remote, None, "note.md", Some(b"base")).await;
+
+        let mut sync = Sync::new(local.clone(), remote.clone());
+        sync.sync_full().await.unwrap();
+
+        // Both sides change the content. Binary content can't be
+        // merged.
+        let note = find_local(&local, None, "note.md").await.unwrap();
+        local.write_to_file(&note.id.to_string(), &[0xff, 0xfe]).await.unwrap();
+        remote.write_to_file(&remote_note.id, b"theirs").await.unwrap();
+        remote.set_modified_at(&remote_note.id, now() + 60).unwrap();
+
+        let report = sync.sync_changes().await.unwrap();
+        let reconciled = report.iter().find(|r| r.id == remote_note.id).unwrap();
+        let Resolution::Conflict { copy_id, copy_name } = &reconciled.resolution else {
+            panic!("expected a conflict, found {:?}", reconciled.resolution);
+        };
+
+        let date = chrono::Local::now().format("%Y-%m-%d");
+        assert_eq!(copy_name, &format!("note (conflict {date}).md"));
+        assert_eq!(local.read_from_file(&copy_id.to_string()).await.unwrap(), [0xff, 0xfe]);
+        assert_eq!(local.read_from_file(&note.id.to_string()).await.unwrap(), b"theirs");
+
+        // The conflict copy is uploaded by the next sync.
+        sync.sync_changes().await.unwrap();
+        let remote_copy = find_remote(&remote, None, copy_name).await.unwrap();
+        assert_eq!(remote.read_from_file(&remote_copy.id).await.unwrap(), [0xff, 0xfe]);
+    }
+
+    #[tokio::test]
+    async fn test_sync_concurrent_metadata() {
+        let local = get_sync_client("concurrent-metadata").await;
+        let remote = Arc::new(MemoryFs::new().with_page_size(2));
+        let remote_note = insert(&remote, None, "note.md", Some(b"")).await;
+        let remote_todo = insert(&remote, None, "todo.md", Some(b"")).await;
+
+        let mut sync = Sync::new(local.clone(), remote.clone());
+        sync.sync_full().await.unwrap();
+
+        // The most recent rename wins.
+        let note = find_local(&local, None, "note.md").await.unwrap();
+        local.move_file(&note.id.to_string(), None, Some("ours.md")).await.unwrap();
+        remote.move_file(&remote_note.id, None, Some("theirs.md")).await.unwrap();
+        remote.set_modified_at(&remote_note.id, now() + 60).unwrap();
+
+        // Edits take precedence over deletions.
+        let todo = find_local(&local, None, "todo.md").await.unwrap();
+        local.remove_file(&todo.id.to_string()).await.unwrap();
+        remote.write_to_file(&remote_todo.id, b"- [ ] milk").await.unwrap();
+        remote.set_modified_at(&remote_todo.id, now() + 60).unwrap();
+
+        let report = sync.sync_changes().await.unwrap();
+        assert!(report.iter().all(|r| r.resolution == Resolution::Remote));
+
+        let note = local.get_file(&note.id.to_string()).await.unwrap();
+        assert_eq!(note.name, "theirs.md");
+
+        let todo = find_local(&local, None, "todo.md").await.unwrap();
+        assert_eq!(todo.remote_id, Some(remote_todo.id.clone()));
+        assert_eq!(local.read_from_file(&todo.id.to_string()).await.unwrap(), b"- [ ] milk");
+    }
+
+    #[tokio::test]
+    async fn test_sync_merge() {
+        let local = get_sync_client("merge").await;
+        let remote = Arc::new(MemoryFs::new().with_page_size(2));
+        let base = "# Groceries\n\n- milk\n\n## Notes\n\nBuy on Friday.\n";
+        let remote_note = insert(&remote, None, "note.md", Some(base.as_bytes())).await;
+
+        let mut sync = Sync::new(local.clone(), remote.clone());
+        sync.sync_full().await.unwrap();
+
+        // Changes to different paragraphs are merged.
+        let note = find_local(&local, None, "note.md").await.unwrap();
+        let ours = "# Groceries\n\n- milk\n- eggs\n\n## Notes\n\nBuy on Friday.\n";
+        let theirs = "# Groceries\n\n- milk\n\n## Notes\n\nBuy on Saturday.\n";
+        local.write_to_file(&note.id.to_string(), ours.as_bytes()).await.unwrap();
+        remote.write_to_file(&remote_note.id, theirs.as_bytes()).await.unwrap();
+        remote.set_modified_at(&remote_note.id, now() + 60).unwrap();
+
+        let report = sync.sync_changes().await.unwrap();
+        let reconciled = report.iter().find(|r| r.id == remote_note.id).unwrap();
+        assert_eq!(reconciled.resolution, Resolution::Merged { is_conflicted: false });
+
+        let merged = "# Groceries\n\n- milk\n- eggs\n\n## Notes\n\nBuy on Saturday.\n";
+        assert_eq!(local.read_from_file(&note.id.to_string()).await.unwrap(), merged.as_bytes());
+        assert_eq!(remote.read_from_file(&remote_note.id).await.unwrap(), merged.as_bytes());
+
+        // Overlapping changes are marked.
+        local.write_to_file(&note.id.to_string(), merged.replace("Saturday", "Sunday").as_bytes()).await.unwrap();
+        remote.write_to_file(&remote_note.id, merged.replace("Saturday", "Monday").as_bytes()).await.unwrap();
+        remote.set_modified_at(&remote_note.id, now() + 120).unwrap();
+
+        let report = sync.sync_changes().await.unwrap();
+        let reconciled = report.iter().find(|r| r.id == remote_note.id).unwrap();
+        assert_eq!(reconciled.resolution, Resolution::Merged { is_conflicted: true });
+
+        let content = String::from_utf8(remote.read_from_file(&remote_note.id).await.unwrap()).unwrap();
+        assert!(content.starts_with("# Groceries\n\n- milk\n- eggs\n\n## Notes\n\n<<<<<<< "));
+        assert!(content.contains("Buy on Sunday.\n=======\nBuy on Monday.\n>>>>>>> "));
+        assert_eq!(local.read_from_file(&note.id.to_string()).await.unwrap(), content.as_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_sync_content() {
+        let local = get_sync_client("content").await;
+        let remote = Arc::new(MemoryFs::new().with_page_size(2));
+        let remote_note = insert(&remote, None, "note.md", Some(b"v1")).await;
+
+        let mut sync = Sync::new(local.clone(), remote.clone());
+        sync.sync_full().await.unwrap();
+
+        // Local edits are uploaded.
+        let note = find_local(&local, None, "note.md").await.unwrap();
+        local.write_to_file(&note.id.to_string(), b"v2").await.unwrap();
+        sync.sync_changes().await.unwrap();
+        assert_eq!(remote.read_from_file(&remote_note.id).await.unwrap(), b"v2");
+
+        // Remote edits are downloaded.
+        remote.write_to_file(&remote_note.id, b"v3").await.unwrap();
+        remote.set_modified_at(&remote_note.id, now() + 60).unwrap();
+        sync.sync_changes().await.unwrap();
+        assert_eq!(local.read_from_file(&note.id.to_string()).await.unwrap(), b"v3");
+
+        // New files are created along with their content.
+        let todo = local.create_file(None, "todo.md").await.unwrap();
+        local.write_to_file(&todo.id.to_string(), b"- [ ] milk").await.unwrap();
+        let remote_idea = insert(&remote, None, "idea.md", Some(b"flying cars")).await;
+        sync.sync_changes().await.unwrap();
+
+        let remote_todo = find_remote(&remote, None, "todo.md").await.unwrap();
+        assert_eq!(remote.read_from_file(&remote_todo.id).await.unwrap(), b"- [ ] milk");
+        let idea = find_local(&local, None, "idea.md").await.unwrap();
+        assert_eq!(idea.remote_id, Some(remote_idea.id));
+        assert_eq!(local.read_from_file(&idea.id.to_string()).await.unwrap(), b"flying cars");
+    }
+
+    #[tokio::test]
+    async fn test_sync_skip_transfer() {
+        let local = get_sync_client("skip-transfer").await;
+        let remote = Arc::new(MemoryFs::new().with_page_size(2));
+        let remote_note = insert(&remote, None, "note.md", Some(b"hello")).await;
+
+        let mut sync = Sync::new(local.clone(), remote.clone());
+        sync.sync_full().await.unwrap();
+
+        // Remote metadata changes do not download content.
+        let reads = remote.reads();
+        remote.move_file(&remote_note.id, None, Some("renamed.md")).await.unwrap();
+        remote.set_modified_at(&remote_note.id, now() + 60).unwrap();
+        sync.sync_changes().await.unwrap();
+        assert!(find_local(&local, None, "renamed.md").await.is_some());
+        assert_eq!(remote.reads(), reads);
+
+        // Files with matching hashes are not transferred.
+        sync.sync_full().await.unwrap();
+        assert_eq!(remote.reads(), reads);
+    }
+
+    #[tokio::test]
+    async fn test_sync() {
+        let local = get_sync_client("sync").await;
+        let remote = Arc::new(MemoryFs::new().with_page_size(2));
+        insert(&remote, None, "note.md", Some(b"")).await;
+
+        // The first sync is a full sync.
+        let mut sync = Sync::load(local.clone(), remote.clone()).await.unwrap();
+        let report = sync.sync().await.unwrap();
+        assert!(report.iter().any(|r| r.name == "note.md" && r.resolution == Resolution::Remote));
+        assert!(find_local(&local, None, "note.md").await.is_some());
+
+        // Subsequent syncs only reconcile changes.
+        let mut sync = Sync::load(local.clone(), remote.clone()).await.unwrap();
+        insert(&remote, None, "todo.md", Some(b"")).await;
+        let report = sync.sync().await.unwrap();
+        assert!(report.iter().any(|r| r.name == "todo.md"));
+        assert!(find_local(&local, None, "todo.md").await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_sync_directory() {
+        let local = get_sync_client("directory").await;
+        let root = std::env::temp_dir()
+            .join(format!("hs-sync-directory-{}", std::process::id()));
+
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        let remote = Arc::new(crate::directory::Client::new(&root));
+
+        let journal = local.create_folder(None, "journal").await.unwrap();
+        let today = local.create_file(Some(&journal.id.to_string()), "today").await.unwrap();
+        local.write_to_file(&today.id.to_string(), b"hello").await.unwrap();
+
+        // The database is mirrored to the directory.
+        let mut sync = Sync::new(local.clone(), remote.clone());
+        sync.sync_full().await.unwrap();
+        assert_eq!(std::fs::read(root.join("journal").join("today.md")).unwrap(), b"hello");
+        assert!(root.join("Untitled.md").is_file());
+
+        // Notes edited by other applications are synced back.
+        std::fs::write(root.join("journal").join("today.md"), b"hello, world").unwrap();
+        std::fs::File::options().write(true)
+            .open(root.join("journal").join("today.md")).unwrap()
+            .set_modified(SystemTime::now() + std::time::Duration::from_secs(60)).unwrap();
+
+        std::fs::write(root.join("ideas.md"), b"").unwrap();
+        sync.sync_changes().await.unwrap();
+        assert_eq!(local.read_from_file(&today.id.to_string()).await.unwrap(), b"hello, world");
+        assert!(find_local(&local, None, "ideas").await.is_some());
+
+        // Moving a folder changes the IDs of its descendants, which
+        // are linked to their new path.
+        local.move_file(&journal.id.to_string(), None, Some("diary")).await.unwrap();
+        sync.sync_changes().await.unwrap();
+        assert!(root.join("diary").join("today.md").is_file());
+        assert!(!root.join("journal").exists());
+
+        let today = local.get_file(&today.id.to_string()).await.unwrap();
+        assert_eq!(today.remote_id.as_deref(), Some("diary/today.md"));
+
+        // The move is echoed by the directory's next delta, which
+        // doesn't delete the moved notes.
+        sync.sync_changes().await.unwrap();
+        let today = local.get_file(&today.id.to_string()).await.unwrap();
+        assert!(!today.is_deleted);
+        assert_eq!(local.read_from_file(&today.id.to_string()).await.unwrap(), b"hello, world");
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    /// Maps the path of every file to its content, or `None` for
+    /// folders.
+    type Tree = std::collections::BTreeMap<String, Option<Vec<u8>>>;
+
+    async fn local_files(local: &LClient) -> Vec<(String, crate::local::LocalFile)> {
+        let mut files = Vec::new();
+        let mut stack: Vec<(Option<i64>, String)> = vec![(None, String::new())];
+        while let Some((parent_id, path)) = stack.pop() {
+            let parent_id = parent_id.map(|id| id.to_string());
+            for file in local.list_files(parent_id.as_deref()).await.unwrap() {
+                let path = format!("{path}/{}", file.name);
+                if file.is_folder {
+                    stack.push((Some(file.id), path.clone()));
+                }
+                files.push((path, file));
+            }
+        }
+        files
+    }
+
+    async fn remote_files<F: FileSystem<File = File, Error = Error>>(remote: &F) -> Vec<(String, File)> {
+        let mut files = Vec::new();
+        let mut stack: Vec<(Option<String>, String)> = vec![(None, String::new())];
+        while let Some((parent_id, path)) = stack.pop() {
+            for file in remote.list_files(parent_id.as_deref()).await.unwrap() {
+                let path = format!("{path}/{}", file.name);
+                if file.is_folder {
+                    stack.push((Some(file.id.clone()), path.clone()));
+                }
+                files.push((path, file));
+            }
+        }
+        files
+    }
+
+    async fn local_tree(local: &LClient) -> Tree {
+        let mut tree = Tree::new();
+        for (path, file) in local_files(local).await {
+            let content = match file.is_folder {
+                true => None,
+                false => Some(local.read_from_file(&file.id.to_string()).await.unwrap()),
+            };
+            tree.insert(path, content);
+        }
+        tree
+    }
+
+    async fn remote_tree<F: FileSystem<File = File, Error = Error>>(remote: &F) -> Tree {
+        let mut tree = Tree::new();
+        for (path, file) in remote_files(remote).await {
+            let content = match file.is_folder {
+                true => None,
+                false => Some(remote.read_from_file(&file.id).await.unwrap()),
+            };
+            tree.insert(path, content);
+        }
+        tree
+    }
+
+    /// Prefix of notes that random changes only edit, so that random
+    /// deletions can't empty the tree.
+    const KEPT: &str = "kept-";
+
+    /// Makes a random change to the local database. Names are made
+    /// unique by `n` so that changes on either side don't collide.
+    async fn change_local(local: &LClient, rng: &mut StdRng, n: usize) {
+        let files: Vec<_> = local_files(local).await.into_iter().map(|(_, file)| file).collect();
+        let mut folders: Vec<Option<String>> = vec![None];
+        folders.extend(files.iter().filter(|file| file.is_folder).map(|file| Some(file.id.to_string())));
+        let folder = folders[rng.random_range(0..folders.len())].clone();
+        let notes: Vec<_> = files.iter().filter(|file| !file.is_folder).collect();
+        let movable: Vec<_> = files.iter().filter(|file| !file.name.starts_with(KEPT)).collect();
+        if movable.is_empty() || notes.is_empty() {
+            local.create_file(folder.as_deref(), &format!("note-{n}")).await.unwrap();
+            return;
+        }
+
+        let file = movable[rng.random_range(0..movable.len())];
+        let note = notes[rng.random_range(0..notes.len())];
+        let name = format!("{}-{n}", if file.is_folder { "folder" } else { "note" });
+
+        // Changes that are invalid (e.g. moving a folder into itself)
+        // are skipped.
+        let _ = match rng.random_range(0..6) {
+            0 => local.create_file(folder.as_deref(), &name).await.map(|_| ()),
+            1 => local.create_folder(folder.as_deref(), &format!("folder-{n}")).await.map(|_| ()),
+            2 => {
+                let mut content = local.read_from_file(&note.id.to_string()).await.unwrap();
+                content.extend(format!("local {n}\n").as_bytes());
+                local.write_to_file(&note.id.to_string(), &content).await.map(|_| ())
+            },
+            3 => {
+                let parent_id = file.parent.map(|id| id.to_string());
+                local.move_file(&file.id.to_string(), parent_id.as_deref(), Some(&name)).await.map(|_| ())
+            },
+            4 => local.move_file(&file.id.to_string(), folder.as_deref(), None).await.map(|_| ()),
+            _ => local.remove_file(&file.id.to_string()).await,
+        };
+    }
+
+    /// Makes a random change to the remote, as another client would.
+    /// Changes are dated in the future so that they aren't mistaken
+    /// for echoes of our own.
+    async fn change_remote(remote: &MemoryFs, rng: &mut StdRng, n: usize) {
+        let files: Vec<_> = remote_files(remote).await.into_iter().map(|(_, file)| file).collect();
+        let mut folders: Vec<Option<String>> = vec![None];
+        folders.extend(files.iter().filter(|file| file.is_folder).map(|file| Some(file.id.clone())));
+        let folder = folders[rng.random_range(0..folders.len())].clone();
+        let notes: Vec<_> = files.iter().filter(|file| !file.is_folder).collect();
+        let movable: Vec<_> = files.iter().filter(|file| !file.name.starts_with(KEPT)).collect();
+        if movable.is_empty() || notes.is_empty() {
+            remote.create_file(folder.as_deref(), &format!("note-{n}")).await.unwrap();
+            return;
+        }
+
+        let file = movable[rng.random_range(0..movable.len())];
+        let note = notes[rng.random_range(0..notes.len())];
+        let name = format!("{}-{n}", if file.is_folder { "folder" } else { "note" });
+
+        let changed = match rng.random_range(0..6) {
+            0 => remote.create_file(folder.as_deref(), &name).await,
+            1 => remote.create_folder(folder.as_deref(), &format!("folder-{n}")).await,
+            2 => {
+                let mut content = remote.read_from_file(&note.id).await.unwrap();
+                content.extend(format!("remote {n}\n").as_bytes());
+                remote.write_to_file(&note.id, &content).await
+            },
+            3 => remote.move_file(&file.id, file.parent_id.as_deref(), Some(&name)).await,
+            4 => remote.move_file(&file.id, folder.as_deref(), None).await,
+            _ => {
+                let _ = remote.remove_file(&file.id).await;
+                return;
+            },
+        };
+
+        if let Ok(file) = changed {
+            remote.set_modified_at(&file.id, now() + 60).unwrap();
+        }
+    }
+
+    /// Syncs the latest changes until a sync succeeds despite
+    /// injected faults. Changes that failed half-way (e.g. a remote
+    /// file that was created but never linked) are recovered by the
+    /// next sync.
+    async fn sync_until_ok(sync: &mut Sync<MemoryFs>) {
+        for _ in 0..100 {
+            let is_ok = sync.sync_changes().await.is_ok_and(|report| report.iter()
+                .all(|reconciled| !matches!(reconciled.resolution, Resolution::Failed { .. } | Resolution::Skipped)));
+
+            if is_ok {
+                return;
+            }
+        }
+
+        panic!("sync kept failing");
+    }
+
+    #[tokio::test]
+    async fn test_sync_convergence() {
+        let faults = Faults::default()
+            .with_latency(std::time::Duration::from_millis(1))
+            .with_error_rate(0.05)
+            .with_collision_rate(0.05);
+
+        for seed in 0..8 {
+            let local = get_sync_client(&format!("convergence-{seed}")).await;
+            let remote = Arc::new(MemoryFs::new().with_seed(seed).with_page_size(3));
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut sync = Sync::new(local.clone(), remote.clone());
+            local.create_file(None, &format!("{KEPT}local")).await.unwrap();
+            remote.create_file(None, &format!("{KEPT}remote")).await.unwrap();
+            sync.sync_full().await.unwrap();
+
+            for round in 0..12 {
+                // Other clients' changes aren't subject to our faults.
+                remote.set_faults(Faults::default());
+                for i in 0..rng.random_range(0..4) {
+                    change_local(&local, &mut rng, round * 100 + i).await;
+                }
+                for i in 0..rng.random_range(0..4) {
+                    change_remote(&remote, &mut rng, round * 100 + 50 + i).await;
+                }
+
+                remote.set_faults(faults.clone());
+                sync_until_ok(&mut sync).await;
+            }
+
+            // Conflict copies are uploaded by the next sync.
+            remote.set_faults(Faults::default());
+            sync.sync_changes().await.unwrap();
+            sync.sync_changes().await.unwrap();
+
+            let local_tree = local_tree(&local).await;
+            assert_eq!(local_tree, remote_tree(remote.as_ref()).await, "seed {seed}");
+            assert!(local_tree.len() > 1, "seed {seed}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sync_full_failure() {
+        let local = get_sync_client("full-failure").await;
+        let remote = Arc::new(MemoryFs::new());
+        let mut sync = Sync::new(local.clone(), remote.clone());
+
+        let remote_note = insert(&remote, None, "note.md", Some(b"remote")).await;
+
+        // Local files can't be uploaded. The failures are reported
+        // without aborting the walk, so remote files are still
+        // downloaded, but the delta tokens aren't advanced.
+        remote.set_faults(Faults::default().with_collision_rate(1.0));
+        let report = sync.sync_full().await.unwrap();
+        let failed = report.iter().find(|reconciled| reconciled.name == "Untitled").unwrap();
+        assert_eq!(failed.id, format!("local:{}", find_local(&local, None, "Untitled").await.unwrap().id));
+        assert!(matches!(failed.resolution, Resolution::Failed { .. }));
+        assert!(report.iter().any(|reconciled| reconciled.id == remote_note.id));
+        assert!(find_local(&local, None, "note.md").await.is_some());
+        assert!(!sync.has_synced());
+
+        // The next sync performs the full sync again.
+        remote.set_faults(Faults::default());
+        sync.sync().await.unwrap();
+        assert!(sync.has_synced());
+
+        let local_tree = local_tree(&local).await;
+        assert!(local_tree.contains_key("/Untitled"));
+        assert_eq!(local_tree, remote_tree(remote.as_ref()).await);
+    }
+
+    #[tokio::test]
+    async fn test_sync_full_concurrency() {
+        let local = get_sync_client("full-concurrency").await;
+        let remote = Arc::new(MemoryFs::new());
+        for i in 0..8 {
+            let file = remote.create_file(None, &format!("remote-{i}.md")).await.unwrap();
+            remote.write_to_file(&file.id, b"remote").await.unwrap();
+            local.create_file(None, &format!("local-{i}.md")).await.unwrap();
+        }
+
+        // Downloads and uploads overlap, but never exceed the limit.
+        remote.set_faults(Faults::default().with_latency(Duration::from_millis(10)));
+        let mut sync = Sync::new(local.clone(), remote.clone());
+        sync.set_concurrency(3);
+        sync.sync_full().await.unwrap();
+        assert!((2..=3).contains(&remote.max_in_flight()));
+
+        remote.set_faults(Faults::default());
+        assert_eq!(local_tree(&local).await, remote_tree(remote.as_ref()).await);
+    }
+
+    #[tokio::test]
+    async fn test_sync_encrypted() {
+        let local = get_sync_client("encrypted").await;
+        let inner = Arc::new(MemoryFs::new());
+        let remote = EncryptedFs::open(inner.clone(), "passphrase").await.unwrap()
+            .with_encrypted_names(true);
+
+        let remote = Arc::new(remote);
+        let mut sync = Sync::new(local.clone(), remote.clone());
+        sync.sync_full().await.unwrap();
+
+        // Local changes are uploaded encrypted.
+        let file = local.create_file(None, "secret.md").await.unwrap();
+        local.write_to_file(&file.id.to_string(), b"top secret").await.unwrap();
+        sync.sync_changes().await.unwrap();
+        assert_eq!(local_tree(&local).await, remote_tree(remote.as_ref()).await);
+
+        for (path, file) in remote_files(inner.as_ref()).await {
+            assert!(!path.contains("secret.md"));
+            let content = inner.read_from_file(&file.id).await.unwrap();
+            assert!(!content.windows(10).any(|w| w == b"top secret"));
+        }
+
+        // Remote changes are downloaded decrypted.
+        let remote_file = remote.list_files(None).await.unwrap()
+            .into_iter().find(|file| file.name == "secret.md").unwrap();
+
+        remote.write_to_file(&remote_file.id, b"declassified").await.unwrap();
+        inner.set_modified_at(&remote_file.id, now() + 60).unwrap();
+        sync.sync_changes().await.unwrap();
+        assert_eq!(local.read_from_file(&file.id.to_string()).await.unwrap(), b"declassified");
+        assert_eq!(local_tree(&local).await, remote_tree(remote.as_ref()).await);
+    }
+
+    #[tokio::test]
+    async fn test_sync_rules() {
+        let local = get_sync_client("rules").await;
+        let remote = Arc::new(MemoryFs::new());
+
+        let notes = local.create_folder(None, "notes").await.unwrap();
+        local.create_file(Some(&notes.id.to_string()), "a.md").await.unwrap();
+        let private = local.create_folder(None, "private").await.unwrap();
+        local.create_file(Some(&private.id.to_string()), "secret.md").await.unwrap();
+        let big = local.create_file(None, "big.bin").await.unwrap();
+        local.write_to_file(&big.id.to_string(), &[0; 200]).await.unwrap();
+
+        let remote_private = remote.create_folder(None, "private").await.unwrap();
+        let remote_secret = remote.create_file(Some(&remote_private.id), "remote.md").await.unwrap();
+
+        let mut sync = Sync::new(local.clone(), remote.clone());
+        sync.set_rules(SyncRules::default()
+            .exclude(Matcher::Path("private".to_string()))
+            .exclude(Matcher::LargerThan(100)));
+
+        // Excluded files are left as they are on both sides.
+        sync.sync_full().await.unwrap();
+        let paths: Vec<String> = remote_files(remote.as_ref()).await
+            .into_iter().map(|(path, _)| path).collect();
+
+        assert!(paths.contains(&"/notes/a.md".to_string()));
+        assert!(!paths.contains(&"/big.bin".to_string()));
+        assert!(!paths.contains(&"/private/secret.md".to_string()));
+        assert!(find_local(&local, Some(private.id), "remote.md").await.is_none());
+
+        // Changes to excluded files are ignored.
+        local.create_file(Some(&private.id.to_string()), "new.md").await.unwrap();
+        local.create_file(Some(&notes.id.to_string()), "b.md").await.unwrap();
+        remote.write_to_file(&remote_secret.id, b"remote").await.unwrap();
+        let names: Vec<String> = sync.list_deltas(false).await.unwrap()
+            .into_iter()
+            .flat_map(|delta| [delta.local.map(|file| file.name), delta.remote.map(|file| file.name)])
+            .flatten()
+            .collect();
+
+        assert!(names.contains(&"b.md".to_string()));
+        assert!(!names.contains(&"new.md".to_string()));
+        assert!(!names.contains(&"remote.md".to_string()));
+
+        sync.sync_changes().await.unwrap();
+        let paths: Vec<String> = remote_files(remote.as_ref()).await
+            .into_iter().map(|(path, _)| path).collect();
+
+        assert!(paths.contains(&"/notes/b.md".to_string()));
+        assert!(!paths.contains(&"/private/new.md".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_sync_rules_encrypted() {
+        let local = get_sync_client("rules-encrypted").await;
+        let inner = Arc::new(MemoryFs::new());
+        let remote = Arc::new(EncryptedFs::open(inner, "passphrase").await.unwrap());
+
+        // Sizes are compared without the encryption overhead, so
+        // files at the threshold are synced in both directions.
+        let file = local.create_file(None, "local.bin").await.unwrap();
+        local.write_to_file(&file.id.to_string(), &[0; 100]).await.unwrap();
+        let file = local.create_file(None, "local-big.bin").await.unwrap();
+        local.write_to_file(&file.id.to_string(), &[0; 101]).await.unwrap();
+
+        let file = remote.create_file(None, "remote.bin").await.unwrap();
+        let file = remote.write_to_file(&file.id, &[0; 100]).await.unwrap();
+        assert_eq!(file.size, Some(100));
+        let file = remote.create_file(None, "remote-big.bin").await.unwrap();
+        remote.write_to_file(&file.id, &[0; 101]).await.unwrap();
+
+        let mut sync = Sync::new(local.clone(), remote.clone());
+        sync.set_rules(SyncRules::default().exclude(Matcher::LargerThan(100)));
+        sync.sync_full().await.unwrap();
+
+        let local_paths: Vec<String> = local_files(&local).await
+            .into_iter().map(|(path, _)| path).collect();
+
+        let remote_paths: Vec<String> = remote_files(remote.as_ref()).await
+            .into_iter().map(|(path, _)| path).collect();
+
+        assert!(local_paths.contains(&"/remote.bin".to_string()));
+        assert!(remote_paths.contains(&"/local.bin".to_string()));
+        assert!(!local_paths.contains(&"/remote-big.bin".to_string()));
+        assert!(!remote_paths.contains(&"/local-big.bin".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_sync_include_folder() {
+        let local = get_sync_client("include-folder").await;
+        let remote = Arc::new(MemoryFs::new());
+        let docs = remote.create_folder(None, "docs").await.unwrap();
+        let work = remote.create_folder(Some(&docs.id), "work").await.unwrap();
+        remote.create_file(Some(&work.id), "todo.md").await.unwrap();
+        remote.create_file(Some(&docs.id), "other.md").await.unwrap();
+        remote.create_file(None, "root.md").await.unwrap();
+
+        // Only the included folder and the folders leading to it are
+        // synced.
+        let mut sync = Sync::new(local.clone(), remote.clone());
+        sync.set_rules(SyncRules::default().include(Matcher::Folder(work.id.clone())));
+        sync.sync_full().await.unwrap();
+        let paths: Vec<String> = local_files(&local).await
+            .into_iter().map(|(path, _)| path).collect();
+
+        assert!(paths.contains(&"/docs/work/todo.md".to_string()));
+        assert!(!paths.contains(&"/docs/other.md".to_string()));
+        assert!(!paths.contains(&"/root.md".to_string()));
+
+        // New files are only pulled into the included folder.
+        remote.create_file(Some(&work.id), "done.md").await.unwrap();
+        remote.create_file(Some(&docs.id), "ignored.md").await.unwrap();
+        sync.sync_changes().await.unwrap();
+        let paths: Vec<String> = local_files(&local).await
+            .into_iter().map(|(path, _)| path).collect();
+
+        assert!(paths.contains(&"/docs/work/done.md".to_string()));
+        assert!(!paths.contains(&"/docs/ignored.md".to_string()));
     }
 }
@@ -1,5 +1,11 @@
 use agent::agent::SCHEMA_VERSION_0 as AGENT_SCHEMA_V0;
 use helsync::local::SCHEMA_VERSION_0 as HELSYNC_SCHEMA_V0;
+use helsync::local::SCHEMA_VERSION_1 as HELSYNC_SCHEMA_V1;
+use helsync::local::SCHEMA_VERSION_2 as HELSYNC_SCHEMA_V2;
+use helsync::local::SCHEMA_VERSION_3 as HELSYNC_SCHEMA_V3;
+use helsync::local::SCHEMA_VERSION_4 as HELSYNC_SCHEMA_V4;
+use helsync::local::SCHEMA_VERSION_5 as HELSYNC_SCHEMA_V5;
+use helsync::local::SCHEMA_VERSION_6 as HELSYNC_SCHEMA_V6;
 use std::sync::Arc;
 
 fn app_db_dir() -> std::path::PathBuf {
@@ -20,6 +26,36 @@ pub async fn run() {
                 sql: vec![HELSYNC_SCHEMA_V0, AGENT_SCHEMA_V0].concat(),
                 kind: database::MigrationType::Up,
             },
+            database::Migration {
+                version: 1,
+                sql: HELSYNC_SCHEMA_V1.to_string(),
+                kind: database::MigrationType::Up,
+            },
+            database::Migration {
+                version: 2,
+                sql: HELSYNC_SCHEMA_V2.to_string(),
+                kind: database::MigrationType::Up,
+            },
+            database::Migration {
+                version: 3,
+                sql: HELSYNC_SCHEMA_V3.to_string(),
+                kind: database::MigrationType::Up,
+            },
+            database::Migration {
+                version: 4,
+                sql: HELSYNC_SCHEMA_V4.to_string(),
+                kind: database::MigrationType::Up,
+            },
+            database::Migration {
+                version: 5,
+                sql: HELSYNC_SCHEMA_V5.to_string(),
+                kind: database::MigrationType::Up,
+            },
+            database::Migration {
+                version: 6,
+                sql: HELSYNC_SCHEMA_V6.to_string(),
+                kind: database::MigrationType::Up,
+            },
         ],
     }).await.expect("could not initialize database"));
 